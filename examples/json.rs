@@ -7,7 +7,12 @@ fn main() -> Result<(), String> {
     let mut input = String::new();
     #[cfg(feature = "json")]
     loop {
-        std::io::stdin().read_line(&mut input);
+        if std::io::stdin().read_line(&mut input).unwrap_or(0) == 0 {
+            return Ok(());
+        }
+        if parser::is_complete(&input) == parser::Completeness::Incomplete {
+            continue;
+        }
         let value_res = parse(input.trim().chars());
         match value_res {
             Ok(value) => {
@@ -1,16 +1,17 @@
+use gon::repl::Repl;
 use gon::*;
 
-fn main() -> Result<(), String> {
-    let mut input = String::new();
-    loop {
-        std::io::stdin().read_line(&mut input);
-        let value_res = parse(input.trim().chars());
-        match value_res {
-            Ok(value) => {
-                dbg!(value);
-            }
-            Err(e) => eprintln!("{e}"),
-        }
-        input.clear();
+/// Starts a repl like `gon repl`, with the files given as `name=file` loaded into variables:
+/// `cargo run --example repl --features repl -- enemies=enemies.gon`
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut repl = Repl::new(SpellConfig::default());
+    for arg in std::env::args().skip(1) {
+        let Some((name, file)) = arg.split_once('=') else {
+            return Err(format!("expected name=file, found '{arg}'").into());
+        };
+        let value = parse_str(&std::fs::read_to_string(file)?)?;
+        repl.set_var(name, value)?;
     }
+    repl.run()?;
+    Ok(())
 }
@@ -0,0 +1,280 @@
+//! `#[derive(GonSchema)]`, use it through the `derive` feature of gon.
+//!
+//! The struct is read straight from the token stream instead of with `syn`, which keeps
+//! this crate free of dependencies. That's enough for what can be derived: structs with
+//! named fields and newtypes, without generics. Types that contain themselves, like trees,
+//! have no schema: it would never end.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, schema::GonSchema};
+//! #[derive(GonSchema)]
+//! struct Server {
+//!     /// Where to listen
+//!     host: String,
+//!     #[gon(rename = "port_number")]
+//!     port: Option<u16>,
+//! }
+//! let schema = Server::schema();
+//! assert!(schema.validate(&parse_str(r#"{host: "localhost"}"#).unwrap()).is_ok());
+//! assert!(schema.validate(&parse_str(r#"{host: 1, port_number: 80}"#).unwrap()).is_err());
+//! ```
+
+use proc_macro::{Delimiter, Group, Spacing, TokenStream, TokenTree};
+
+/// Implements `gon::schema::GonSchema`, see the [crate docs](crate)
+#[proc_macro_derive(GonSchema, attributes(gon))]
+pub fn derive_gon_schema(input: TokenStream) -> TokenStream {
+    let generated = match derive(input) {
+        Ok(generated) => generated,
+        Err(message) => format!("::core::compile_error!({message:?});"),
+    };
+    generated.parse().expect("generated code is valid Rust")
+}
+
+/// A field of a struct with named fields
+struct Field {
+    key: String,
+    ty: String,
+    doc: Option<String>,
+}
+
+fn derive(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let mut name = None;
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident.to_string() == "struct" => {
+                name = tokens.next().map(|t| t.to_string());
+                break;
+            }
+            TokenTree::Ident(ident) if ident.to_string() == "enum" => {
+                return Err("GonSchema can't be derived for enums".into());
+            }
+            _ => {}
+        }
+    }
+    let name = name.ok_or("GonSchema can only be derived for structs")?;
+    let schema = match tokens.next() {
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Brace => {
+            obj_schema(&fields(group)?)
+        }
+        Some(TokenTree::Group(group)) if group.delimiter() == Delimiter::Parenthesis => {
+            let fields = split_commas(group.stream());
+            let [field] = &fields[..] else {
+                return Err(
+                    "GonSchema can only be derived for tuple structs with one field".into(),
+                );
+            };
+            let (_, ty) = strip_attrs(field);
+            let ty = strip_visibility(&ty);
+            format!(
+                "fn schema() -> ::gon::schema::Schema {{ <{ty} as ::gon::schema::GonSchema>::schema() }}
+                fn optional() -> bool {{ <{ty} as ::gon::schema::GonSchema>::optional() }}",
+                ty = to_string(ty),
+            )
+        }
+        Some(TokenTree::Punct(punct)) if punct.as_char() == '<' => {
+            return Err("GonSchema can't be derived for generic structs".into());
+        }
+        _ => return Err("GonSchema can't be derived for unit structs".into()),
+    };
+    Ok(format!(
+        "impl ::gon::schema::GonSchema for {name} {{ {schema} }}"
+    ))
+}
+
+fn obj_schema(fields: &[Field]) -> String {
+    let fields: String = fields
+        .iter()
+        .map(|Field { key, ty, doc }| {
+            let doc = match doc {
+                Some(doc) => {
+                    format!("::core::option::Option::Some(::std::string::String::from({doc:?}))")
+                }
+                None => "::core::option::Option::None".to_string(),
+            };
+            format!(
+                "::gon::schema::Field {{
+                    key: ::std::string::String::from({key:?}),
+                    schema: <{ty} as ::gon::schema::GonSchema>::schema(),
+                    optional: <{ty} as ::gon::schema::GonSchema>::optional(),
+                    annotations: ::gon::schema::Annotations {{
+                        doc: {doc},
+                        ..::core::default::Default::default()
+                    }},
+                }},"
+            )
+        })
+        .collect();
+    format!(
+        "fn schema() -> ::gon::schema::Schema {{ ::gon::schema::Schema::Obj(::std::vec![{fields}]) }}"
+    )
+}
+
+/// The named fields in the braces of a struct
+fn fields(group: Group) -> Result<Vec<Field>, String> {
+    split_commas(group.stream())
+        .iter()
+        .map(|tokens| {
+            let (attrs, rest) = strip_attrs(tokens);
+            let rest = strip_visibility(&rest);
+            let (Some(TokenTree::Ident(name)), Some(TokenTree::Punct(colon))) =
+                (rest.first(), rest.get(1))
+            else {
+                return Err("expected a named field".to_string());
+            };
+            if colon.as_char() != ':' {
+                return Err(format!("expected ':' after the field {name}"));
+            }
+            let mut field = Field {
+                key: name.to_string().trim_start_matches("r#").to_string(),
+                ty: to_string(&rest[2..]),
+                doc: None,
+            };
+            let mut doc = Vec::new();
+            for attr in attrs {
+                apply_attr(attr, &mut field, &mut doc)?;
+            }
+            field.doc = (!doc.is_empty()).then(|| doc.join(" "));
+            Ok(field)
+        })
+        .collect()
+}
+
+/// Reads `doc = "..."` and `gon(rename = "...")`, ignores all other attributes
+fn apply_attr(attr: Group, field: &mut Field, doc: &mut Vec<String>) -> Result<(), String> {
+    let tokens: Vec<TokenTree> = attr.stream().into_iter().collect();
+    match &tokens[..] {
+        [
+            TokenTree::Ident(ident),
+            TokenTree::Punct(eq),
+            TokenTree::Literal(lit),
+        ] if ident.to_string() == "doc" && eq.as_char() == '=' => {
+            let line = unquote(&lit.to_string())?;
+            let line = line.trim();
+            if !line.is_empty() {
+                doc.push(line.to_string());
+            }
+        }
+        [TokenTree::Ident(ident), TokenTree::Group(args)] if ident.to_string() == "gon" => {
+            let args: Vec<TokenTree> = args.stream().into_iter().collect();
+            match &args[..] {
+                [
+                    TokenTree::Ident(arg),
+                    TokenTree::Punct(eq),
+                    TokenTree::Literal(lit),
+                ] if arg.to_string() == "rename" && eq.as_char() == '=' => {
+                    field.key = unquote(&lit.to_string())?;
+                }
+                _ => return Err(format!("unknown gon attribute `{}`", attr.stream())),
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Splits at the commas that aren't inside of brackets or `<>`
+fn split_commas(stream: TokenStream) -> Vec<Vec<TokenTree>> {
+    let mut parts = vec![Vec::new()];
+    let mut angles = 0usize;
+    let mut after_dash = false;
+    for token in stream {
+        if let TokenTree::Punct(punct) = &token {
+            match punct.as_char() {
+                ',' if angles == 0 => {
+                    parts.push(Vec::new());
+                    after_dash = false;
+                    continue;
+                }
+                '<' => angles += 1,
+                // the `>` of `->` doesn't close anything
+                '>' if !after_dash => angles = angles.saturating_sub(1),
+                _ => {}
+            }
+            after_dash = punct.as_char() == '-' && punct.spacing() == Spacing::Joint;
+        } else {
+            after_dash = false;
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push(token);
+        }
+    }
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// The contents of the leading `#[...]` attributes and the tokens after them
+fn strip_attrs(tokens: &[TokenTree]) -> (Vec<Group>, Vec<TokenTree>) {
+    let mut attrs = Vec::new();
+    let mut rest = tokens;
+    while let [TokenTree::Punct(hash), TokenTree::Group(group), after @ ..] = rest {
+        if hash.as_char() != '#' || group.delimiter() != Delimiter::Bracket {
+            break;
+        }
+        attrs.push(group.clone());
+        rest = after;
+    }
+    (attrs, rest.to_vec())
+}
+
+/// `tokens` without a leading `pub`, `pub(crate)` or the like
+fn strip_visibility(tokens: &[TokenTree]) -> &[TokenTree] {
+    match tokens {
+        [TokenTree::Ident(ident), TokenTree::Group(group), rest @ ..]
+            if ident.to_string() == "pub" && group.delimiter() == Delimiter::Parenthesis =>
+        {
+            rest
+        }
+        [TokenTree::Ident(ident), rest @ ..] if ident.to_string() == "pub" => rest,
+        _ => tokens,
+    }
+}
+
+fn to_string(tokens: &[TokenTree]) -> String {
+    tokens.iter().cloned().collect::<TokenStream>().to_string()
+}
+
+/// The value of a string literal, plain or raw
+fn unquote(lit: &str) -> Result<String, String> {
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        let inner = &raw[hashes..raw.len() - hashes];
+        return Ok(inner[1..inner.len() - 1].to_string());
+    }
+    let inner = lit
+        .strip_prefix('"')
+        .and_then(|lit| lit.strip_suffix('"'))
+        .ok_or_else(|| format!("expected a string literal, found {lit}"))?;
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('0') => out.push('\0'),
+            Some('u') => {
+                let code: String = chars.by_ref().skip(1).take_while(|c| *c != '}').collect();
+                let c = u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| format!("invalid unicode escape in {lit}"))?;
+                out.push(c);
+            }
+            // a line break after a backslash is skipped together with the indentation after it
+            Some('\n') => {
+                let rest = chars.as_str().trim_start();
+                chars = rest.chars();
+            }
+            Some(c) => out.push(c),
+            None => return Err(format!("unterminated escape in {lit}")),
+        }
+    }
+    Ok(out)
+}
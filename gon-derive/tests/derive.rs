@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use gon::parse_str;
+use gon::schema::{Annotations, Field, GonSchema, Schema};
+
+#[derive(GonSchema)]
+#[allow(dead_code)]
+pub struct Meters(pub f64);
+
+#[derive(GonSchema)]
+#[allow(dead_code)]
+struct Enemy {
+    /// What the enemy is called,
+    /// shown above its head
+    pub name: String,
+    pub(crate) hp: u32,
+    #[gon(rename = "loot_table")]
+    loot: Vec<Option<String>>,
+    reach: Meters,
+    stats: HashMap<String, i64>,
+    weakness: Option<Box<Meters>>,
+    r#type: char,
+}
+
+fn field(key: &str, schema: Schema, optional: bool) -> Field {
+    Field {
+        key: key.to_string(),
+        schema,
+        optional,
+        annotations: Annotations::default(),
+    }
+}
+
+#[test]
+fn derives_object_schemas() {
+    let Schema::Obj(fields) = Enemy::schema() else {
+        panic!("expected an object schema");
+    };
+    assert_eq!(
+        fields[0].annotations.doc.as_deref(),
+        Some("What the enemy is called, shown above its head")
+    );
+    let expected = [
+        field("name", Schema::Str, false),
+        field("hp", Schema::Num, false),
+        field(
+            "loot_table",
+            Schema::List(Box::new(Schema::Union(vec![Schema::Str, Schema::None]))),
+            false,
+        ),
+        field("reach", Schema::Num, false),
+        field("stats", Schema::AnyObj, false),
+        field(
+            "weakness",
+            Schema::Union(vec![Schema::Num, Schema::None]),
+            true,
+        ),
+        field("type", Schema::Str, false),
+    ];
+    for (field, expected) in fields[1..].iter().zip(&expected[1..]) {
+        assert_eq!(field, expected);
+    }
+    assert_eq!(fields.len(), expected.len());
+}
+
+#[test]
+fn derived_schemas_validate() {
+    let schema = Enemy::schema();
+    let goblin = parse_str(
+        r#"{
+            name: "Goblin",
+            hp: 10,
+            loot_table: ["club", None],
+            reach: 1.5,
+            stats: {str: 3},
+            weakness: 0.5,
+            type: "g",
+        }"#,
+    )
+    .unwrap();
+    assert!(schema.validate(&goblin).is_ok());
+    let errors = schema
+        .validate(
+            &parse_str(
+                r#"{name: "Slime", hp: "ten", loot_table: [], reach: 1, stats: {}, type: "s"}"#,
+            )
+            .unwrap(),
+        )
+        .unwrap_err();
+    assert_eq!(errors.0.len(), 1);
+    assert_eq!(errors.0[0].path.to_string(), "hp");
+}
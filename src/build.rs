@@ -0,0 +1,205 @@
+//! Checking configs at compile time, from build scripts.
+//!
+//! An [`Embed`] parses a config file, validates it against a schema and writes it as a Rust
+//! constant to `OUT_DIR`, where the crate [`include!`]s it. A broken config fails the build
+//! instead of the first launch of the program, and the program doesn't read any files.
+//! Cargo is told to run the build script again when the config, the schema or one of the
+//! schema files it refers to changes.
+//!
+//! The constant is the [minified](crate::Value::min_spell) config, a `&str` that
+//! [`parse_str`](crate::parse_str) can't fail on.
+//!
+//! # Usage example
+//! In `build.rs`, with gon as a build dependency with the `build` feature:
+//! ```rust,no_run
+//! gon::build::Embed::new("config/game.gon")
+//!     .schema("config/game.schema.gon")
+//!     .run(); // writes GAME to $OUT_DIR/game.rs
+//! ```
+//! And in the crate:
+//! ```rust,ignore
+//! include!(concat!(env!("OUT_DIR"), "/game.rs"));
+//!
+//! let game = gon::parse_str(GAME).unwrap();
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::schema::{Schema, SchemaError, ValidationErrors};
+use crate::{GonError, parse_str};
+
+/// Embeds a config file in a crate, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Embed {
+    config: PathBuf,
+    schema: Option<PathBuf>,
+    name: Option<String>,
+    out_dir: Option<PathBuf>,
+}
+
+/// Why a config couldn't be embedded
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("couldn't read '{0}': {1}")]
+    Io(String, io::Error),
+    #[error("couldn't parse '{0}': {1}")]
+    Parse(String, GonError),
+    #[error("invalid schema '{0}': {1}")]
+    Schema(String, SchemaError),
+    #[error("'{0}' doesn't match its schema:\n{1}")]
+    Invalid(String, ValidationErrors),
+    /// The name of the constant isn't a Rust identifier
+    #[error("'{0}' can't be the name of a constant")]
+    Name(String),
+    #[error("OUT_DIR isn't set, embedding only works in build scripts or with an out_dir")]
+    OutDir,
+    #[error("couldn't write '{0}': {1}")]
+    Write(String, io::Error),
+}
+
+impl Embed {
+    /// Embeds the config file at `path`, relative to the package like every path in build
+    /// scripts
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            config: path.into(),
+            schema: None,
+            name: None,
+            out_dir: None,
+        }
+    }
+
+    /// Validates the config against the schema file at `path`
+    pub fn schema(mut self, path: impl Into<PathBuf>) -> Self {
+        self.schema = Some(path.into());
+        self
+    }
+
+    /// The name of the constant, by default the name of the config file in upper case,
+    /// like `GAME` for `game.gon`. The constant is written to `name.rs` in lower case.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Where to write the constant instead of `OUT_DIR`
+    pub fn out_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.out_dir = Some(dir.into());
+        self
+    }
+
+    /// Checks the config and writes the constant, returning the path of the file written
+    pub fn write(self) -> Result<PathBuf, BuildError> {
+        let name = self.name.unwrap_or_else(|| default_name(&self.config));
+        if !is_identifier(&name) {
+            return Err(BuildError::Name(name));
+        }
+        let out_dir = match self.out_dir {
+            Some(dir) => dir,
+            None => std::env::var_os("OUT_DIR")
+                .map(PathBuf::from)
+                .ok_or(BuildError::OutDir)?,
+        };
+
+        let display = self.config.display().to_string();
+        rerun_if_changed(&self.config);
+        let src = std::fs::read_to_string(&self.config)
+            .map_err(|e| BuildError::Io(display.clone(), e))?;
+        let value = parse_str(&src).map_err(|e| BuildError::Parse(display.clone(), e))?;
+        if let Some(path) = &self.schema {
+            rerun_if_changed(path);
+            let (schema, dependencies) = Schema::from_file_with_dependencies(path)
+                .map_err(|e| BuildError::Schema(path.display().to_string(), e))?;
+            dependencies.iter().for_each(|path| rerun_if_changed(path));
+            schema
+                .validate(&value)
+                .map_err(|e| BuildError::Invalid(display.clone(), e))?;
+        }
+
+        let code = format!(
+            "/// `{display}`, checked and minified by `gon::build`\npub const {name}: &str = {:?};\n",
+            value.min_spell(),
+        );
+        let out = out_dir.join(format!("{}.rs", name.to_lowercase()));
+        std::fs::write(&out, code).map_err(|e| BuildError::Write(out.display().to_string(), e))?;
+        Ok(out)
+    }
+
+    /// Like [`write`](Self::write), but panics with the problem, which fails the build
+    pub fn run(self) -> PathBuf {
+        self.write().unwrap_or_else(|e| panic!("{e}"))
+    }
+}
+
+fn rerun_if_changed(path: &Path) {
+    println!("cargo:rerun-if-changed={}", path.display());
+}
+
+/// `GAME_TUNING` for `config/game-tuning.gon`
+fn default_name(path: &Path) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name: String = (stem.chars())
+        .map(|c| match c {
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{name}")
+    } else {
+        name
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+        && name != "_"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn embeds_valid_configs() {
+        let dir = TempDir::new("build");
+        let config = dir.join("game-tuning.gon");
+        let schema = dir.join("tuning.schema.gon");
+        std::fs::write(
+            &config,
+            "{\n    // per second\n    speed: 2.5,\n    name: \"a \\\"b\\\"\",\n}",
+        )
+        .unwrap();
+        std::fs::write(&schema, "{speed: \"num\", name: \"str\"}").unwrap();
+
+        let out = Embed::new(&config)
+            .schema(&schema)
+            .out_dir(&dir)
+            .write()
+            .unwrap();
+        assert_eq!(out, dir.join("game_tuning.rs"));
+        let code = std::fs::read_to_string(&out).unwrap();
+        let min = parse_str(&std::fs::read_to_string(&config).unwrap())
+            .unwrap()
+            .min_spell();
+        assert!(code.ends_with(&format!("pub const GAME_TUNING: &str = {min:?};\n")));
+
+        std::fs::write(&config, "{speed: \"fast\", name: \"b\"}").unwrap();
+        let embed = Embed::new(&config).schema(&schema).out_dir(&dir);
+        assert!(matches!(embed.write(), Err(BuildError::Invalid(..))));
+        let embed = Embed::new(&config).name("2fast").out_dir(&dir);
+        assert!(matches!(embed.write(), Err(BuildError::Name(_))));
+        std::fs::write(&config, "{speed: }").unwrap();
+        let embed = Embed::new(&config).out_dir(&dir);
+        assert!(matches!(embed.write(), Err(BuildError::Parse(..))));
+        assert_eq!(default_name(Path::new("3d.gon")), "_3D");
+    }
+}
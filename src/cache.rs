@@ -0,0 +1,185 @@
+//! Parsing every distinct document only once.
+//!
+//! A [`ParseCache`] keeps the values it parsed by the hash of their source, so parsing a
+//! source it has seen before hands out the same [`Arc<Value>`] again instead of parsing it
+//! anew. [`ParseCache::parse_file`] also remembers the modification time and length of
+//! every file it read and doesn't even read a file again while they stay the same. A file
+//! that's saved without changes only costs reading it.
+//!
+//! Share one cache between everything that reads the same files, like the
+//! [`Workspace`](crate::workspace::Workspace::cache) checks and config
+//! [`Loader`](crate::config::Loader::cache)s of an editor session. Errors aren't cached.
+//!
+//! # Usage example
+//! ```rust
+//! use std::sync::Arc;
+//! use gon::cache::ParseCache;
+//! let cache = ParseCache::new();
+//! let a = cache.parse("{hp: 10}").unwrap();
+//! let b = cache.parse("{hp: 10}").unwrap();
+//! assert!(Arc::ptr_eq(&a, &b));
+//! assert_eq!(cache.len(), 1);
+//! ```
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use crate::encoding::ReadError;
+use crate::{GonError, Value, parse_str};
+
+/// Parsed documents by their source, see the [module docs](self). Share it between
+/// threads with an [`Arc`] or by reference.
+#[derive(Debug, Default)]
+pub struct ParseCache {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// By the hash of their source, which is kept to tell apart sources with equal hashes
+    values: HashMap<u64, Vec<Cached>>,
+    /// The stamp of every file read, and what it held then
+    files: HashMap<PathBuf, (Stamp, Arc<Value>)>,
+}
+
+/// A source and its value
+type Cached = (Box<str>, Arc<Value>);
+
+/// What's used to detect changes of a file: its modification time and length
+pub(crate) type Stamp = (SystemTime, u64);
+
+impl ParseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses `src` like [`parse_str`], unless it was parsed before
+    pub fn parse(&self, src: &str) -> Result<Arc<Value>, GonError> {
+        let hash = hash(src);
+        if let Some(value) = self.state().find(hash, src) {
+            return Ok(value);
+        }
+        // parse without holding the lock, so that threads parse different documents at once
+        let value = Arc::new(parse_str(src)?);
+        let mut state = self.state();
+        // another thread may have parsed the same source in the meantime
+        if let Some(value) = state.find(hash, src) {
+            return Ok(value);
+        }
+        let entries = state.values.entry(hash).or_default();
+        entries.push((src.into(), Arc::clone(&value)));
+        Ok(value)
+    }
+
+    /// Reads and parses the file at `path` like [`parse`](Self::parse), unless its
+    /// modification time and length are the same as when it was read last. A file changed
+    /// twice within the resolution of the file system's timestamps to the same length
+    /// isn't noticed.
+    pub fn parse_file(&self, path: impl AsRef<Path>) -> Result<Arc<Value>, ReadError> {
+        let path = path.as_ref();
+        let stamp = file_stamp(path);
+        let unchanged = (self.state().files.get(path))
+            .filter(|(seen, _)| Some(*seen) == stamp)
+            .map(|(_, value)| Arc::clone(value));
+        if let Some(value) = unchanged {
+            return Ok(value);
+        }
+        let value = self.parse(&std::fs::read_to_string(path)?)?;
+        if let Some(stamp) = stamp {
+            let file = (stamp, Arc::clone(&value));
+            self.state().files.insert(path.to_path_buf(), file);
+        }
+        Ok(value)
+    }
+
+    /// How many distinct documents are cached
+    pub fn len(&self) -> usize {
+        self.state().values.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets the documents that neither a file read last nor anyone outside of the cache
+    /// holds on to, like the old versions of edited files
+    pub fn prune(&self) {
+        let mut state = self.state();
+        for entries in state.values.values_mut() {
+            entries.retain(|(_, value)| Arc::strong_count(value) > 1);
+        }
+        state.values.retain(|_, entries| !entries.is_empty());
+    }
+
+    /// Forgets everything
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.values.clear();
+        state.files.clear();
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // every change of the state is a single insertion or removal, so it's consistent
+        // even if another thread panicked while holding the lock
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl State {
+    fn find(&self, hash: u64, src: &str) -> Option<Arc<Value>> {
+        let entries = self.values.get(&hash)?;
+        let (_, value) = entries.iter().find(|(cached, _)| **cached == *src)?;
+        Some(Arc::clone(value))
+    }
+}
+
+fn hash(src: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    src.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The stamp of the file at `path`, `None` if it can't be read
+pub(crate) fn file_stamp(path: &Path) -> Option<Stamp> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn parses_documents_once() {
+        let cache = ParseCache::new();
+        let a = cache.parse("[1, 2]").unwrap();
+        assert!(Arc::ptr_eq(&a, &cache.parse("[1, 2]").unwrap()));
+        assert!(!Arc::ptr_eq(&a, &cache.parse("[1,2]").unwrap()));
+        assert!(cache.parse("[1, 2").is_err());
+        assert_eq!(cache.len(), 2);
+        drop(a);
+        cache.prune();
+        assert!(cache.is_empty());
+
+        let dir = TempDir::new("cache");
+        let file = dir.join("enemy.gon");
+        std::fs::write(&file, "{hp: 10}").unwrap();
+        let first = cache.parse_file(&file).unwrap();
+        assert!(Arc::ptr_eq(&first, &cache.parse_file(&file).unwrap()));
+        std::fs::write(&file, "{hp: 12, id: 2}").unwrap();
+        let second = cache.parse_file(&file).unwrap();
+        assert_eq!(*second, parse_str("{hp: 12, id: 2}").unwrap());
+        assert!(matches!(
+            cache.parse_file(dir.join("missing.gon")),
+            Err(ReadError::Io(_))
+        ));
+        // the old version is only held by `first`
+        drop(first);
+        cache.prune();
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,103 @@
+//! Stopping long operations from another thread.
+//!
+//! A [`CancelToken`] is shared between the thread that runs an operation and the thread
+//! that decides to stop it, like the UI thread of an editor. The operation checks the
+//! token as it goes and fails soon after the token is cancelled:
+//! - parsing with [`ParseOptions::cancel`](crate::parser::ParseOptions::cancel) fails with
+//!   [`ErrorKind::Cancelled`](crate::ErrorKind::Cancelled)
+//! - [`Schema::validate_cancellable`](crate::schema::Schema::validate_cancellable) fails
+//!   with [`Cancelled`]
+//! - with the `json` feature, `json::write_json_cancellable` fails with an
+//!   [`io::Error`](std::io::Error) that wraps [`Cancelled`]
+//!
+//! A cancelled token stays cancelled, every operation needs a new one.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::cancel::CancelToken;
+//! use gon::parser::{parse_str_with, ParseOptions};
+//! let token = CancelToken::new();
+//! let options = ParseOptions::new().cancel(token.clone());
+//! assert!(parse_str_with("[1, 2]", &options).is_ok());
+//! // for example when the user clicks "Cancel"
+//! std::thread::spawn(move || token.cancel()).join().unwrap();
+//! let err = parse_str_with("[1, 2]", &options).unwrap_err();
+//! assert_eq!(err.kind(), &gon::ErrorKind::Cancelled);
+//! ```
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use thiserror::Error;
+
+/// Tells operations to stop, see the [module docs](self). Clones share their state.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+/// An operation stopped because its [`CancelToken`] was cancelled
+#[derive(Copy, Clone, Debug, Error, PartialEq, Eq)]
+#[error("cancelled")]
+pub struct Cancelled;
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes the operations that check this token, or a clone of it, fail
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Fails if the token is cancelled
+    pub fn check(&self) -> Result<(), Cancelled> {
+        if self.is_cancelled() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Shares a flag the application already has, cancelled when it's `true`
+impl From<Arc<AtomicBool>> for CancelToken {
+    fn from(flag: Arc<AtomicBool>) -> Self {
+        Self(flag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use crate::schema::Schema;
+
+    #[test]
+    fn stops_validating() {
+        let schema = Schema::from_value(&parse_str(r#"["num"]"#).unwrap()).unwrap();
+        let value = parse_str(r#"[1, "a"]"#).unwrap();
+        let token = CancelToken::new();
+        let validated = schema.validate_cancellable(&value, &token).unwrap();
+        assert_eq!(validated.unwrap_err().0.len(), 1);
+        token.cancel();
+        assert_eq!(schema.validate_cancellable(&value, &token), Err(Cancelled));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn stops_writing_json() {
+        use crate::json::write_json_cancellable;
+        use crate::value::Strictness;
+        let value = parse_str("[1, 2]").unwrap();
+        let token = CancelToken::from(Arc::new(AtomicBool::new(true)));
+        let mut out = Vec::new();
+        let err = write_json_cancellable(&value, &mut out, false, Strictness::Lenient, &token)
+            .unwrap_err();
+        assert!(err.into_inner().unwrap().is::<Cancelled>());
+        assert_eq!(out, b"[");
+    }
+}
@@ -0,0 +1,267 @@
+//! Lenient loading of values whose scalars have the wrong kind, like numbers written as
+//! strings.
+//!
+//! [`Value::coerce`] converts a value into another [`Kind`] where a [`CoercionPolicy`]
+//! allows it: `"42"` to `42`, `"true"` to `true`, `1` to `true` or `42` to `"42"`. The
+//! default policy allows nothing. A [`Coercer`] has typed getters like the ones of
+//! [`Object`](crate::Object) that coerce when the value can't be converted as it is, and
+//! remembers every [`Coercion`] with its path, to tell which parts of a legacy file should
+//! be cleaned up.
+//! # Usage example
+//! ```rust
+//! use gon::{coerce::{Coercer, CoercionPolicy}, parse_str, Kind, Value};
+//! let value = parse_str(r#"{port: "8080", tls: 1, name: "x"}"#).unwrap();
+//! let mut coercer = Coercer::new(CoercionPolicy::LENIENT);
+//! let port: u16 = coercer.require(&value, &"port".parse().unwrap()).unwrap();
+//! let tls: bool = coercer.require(&value, &"tls".parse().unwrap()).unwrap();
+//! assert_eq!((port, tls), (8080, true));
+//! assert!(coercer.require::<u16>(&value, &"name".parse().unwrap()).is_err());
+//! let log: Vec<_> = coercer.coercions().iter().map(ToString::to_string).collect();
+//! assert_eq!(log, ["port: coerced str to num", "tls: coerced num to bool"]);
+//!
+//! assert_eq!(Value::Num("3".into()).coerce(Kind::Str, CoercionPolicy::default()), None);
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::parser::is_number_literal;
+use crate::path::{Path, get, untagged};
+use crate::value::{FieldError, TypeError, expected_type, normalize_number};
+use crate::{Kind, Value};
+
+/// Which conversions [`Value::coerce`] may make
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CoercionPolicy {
+    /// Strings spelled like numbers become numbers, surrounding whitespace is ignored
+    pub str_to_num: bool,
+    /// `"true"` and `"false"` in any case become booleans
+    pub str_to_bool: bool,
+    /// `1` and `0` become booleans
+    pub num_to_bool: bool,
+    /// Numbers and booleans become strings as they're spelled
+    pub to_str: bool,
+}
+
+impl CoercionPolicy {
+    /// Every conversion is allowed
+    pub const LENIENT: Self = Self {
+        str_to_num: true,
+        str_to_bool: true,
+        num_to_bool: true,
+        to_str: true,
+    };
+}
+
+/// A value was converted from one kind into another, see [`Coercer`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Coercion {
+    pub path: Path,
+    pub from: Kind,
+    pub to: Kind,
+}
+
+impl fmt::Display for Coercion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: coerced {} to {}", self.path, self.from, self.to)
+    }
+}
+
+impl Value {
+    /// This value as a value of `kind`, converted if it's of another kind and `policy`
+    /// allows it. `None` if it can't be. Tags are looked through.
+    pub fn coerce(&self, kind: Kind, policy: CoercionPolicy) -> Option<Cow<'_, Value>> {
+        let value = untagged(self);
+        if value.kind() == kind {
+            return Some(Cow::Borrowed(value));
+        }
+        let coerced = match (value, kind) {
+            (Self::Str { s, .. }, Kind::Num) if policy.str_to_num => {
+                let s = s.trim();
+                is_number_literal(s.strip_prefix('-').unwrap_or(s))
+                    .then(|| Self::Num(normalize_number(s)))
+            }
+            (Self::Str { s, .. }, Kind::Bool) if policy.str_to_bool => {
+                match s.trim().to_ascii_lowercase().as_str() {
+                    "true" => Some(Self::Bool(true)),
+                    "false" => Some(Self::Bool(false)),
+                    _ => None,
+                }
+            }
+            (Self::Num(_), Kind::Bool) if policy.num_to_bool => match value.as_f64() {
+                Some(1.0) => Some(Self::Bool(true)),
+                Some(0.0) => Some(Self::Bool(false)),
+                _ => None,
+            },
+            (Self::Num(n), Kind::Str) if policy.to_str => Some(Self::Str {
+                s: n.clone(),
+                raw: false,
+            }),
+            (Self::Bool(b), Kind::Str) if policy.to_str => Some(Self::Str {
+                s: b.to_string(),
+                raw: false,
+            }),
+            _ => None,
+        };
+        coerced.map(Cow::Owned)
+    }
+}
+
+/// Typed getters that coerce with a [`CoercionPolicy`] and log what they coerced, see
+/// the [module docs](self)
+#[derive(Clone, Debug, Default)]
+pub struct Coercer {
+    pub policy: CoercionPolicy,
+    coercions: Vec<Coercion>,
+}
+
+impl Coercer {
+    pub fn new(policy: CoercionPolicy) -> Self {
+        Self {
+            policy,
+            coercions: Vec::new(),
+        }
+    }
+
+    /// Converts the value at `path` in `value` with `T::try_from`. If that fails, the
+    /// value is coerced into a number, a boolean and a string in that order, until one
+    /// converts. Fails like [`Object::require`](crate::Object::require), with the path as
+    /// the key.
+    pub fn require<T>(&mut self, value: &Value, path: &Path) -> Result<T, FieldError>
+    where
+        T: for<'v> TryFrom<&'v Value, Error = TypeError>,
+    {
+        let Some(found) = get(value, path) else {
+            return Err(FieldError {
+                key: path.to_string(),
+                expected: expected_type::<T>(),
+                found: "nothing",
+            });
+        };
+        let error = match T::try_from(untagged(found)) {
+            Ok(t) => return Ok(t),
+            Err(e) => e,
+        };
+        for kind in [Kind::Num, Kind::Bool, Kind::Str] {
+            let Some(Cow::Owned(coerced)) = found.coerce(kind, self.policy) else {
+                continue;
+            };
+            if let Ok(t) = T::try_from(&coerced) {
+                self.coercions.push(Coercion {
+                    path: path.clone(),
+                    from: untagged(found).kind(),
+                    to: kind,
+                });
+                return Ok(t);
+            }
+        }
+        Err(FieldError {
+            key: path.to_string(),
+            expected: error.expected,
+            found: error.found,
+        })
+    }
+
+    /// Like [`require`](Self::require), but nothing at `path` or `None` is `Ok(None)`
+    pub fn optional<T>(&mut self, value: &Value, path: &Path) -> Result<Option<T>, FieldError>
+    where
+        T: for<'v> TryFrom<&'v Value, Error = TypeError>,
+    {
+        match get(value, path).map(untagged) {
+            None | Some(Value::None) => Ok(None),
+            Some(_) => self.require(value, path).map(Some),
+        }
+    }
+
+    /// Everything that was coerced so far, in order
+    pub fn coercions(&self) -> &[Coercion] {
+        &self.coercions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn coerces_what_the_policy_allows() {
+        let coerce = |src: &str, kind, policy| {
+            parse_str(src)
+                .unwrap()
+                .coerce(kind, policy)
+                .map(Cow::into_owned)
+        };
+        let lenient = CoercionPolicy::LENIENT;
+        let num = |n: &str| Some(Value::Num(n.into()));
+        assert_eq!(
+            coerce("\" -1_000.50 \"", Kind::Num, lenient),
+            num("-1000.5")
+        );
+        assert_eq!(coerce("\"1.2.3\"", Kind::Num, lenient), None);
+        assert_eq!(
+            coerce("\"TRUE\"", Kind::Bool, lenient),
+            Some(Value::Bool(true))
+        );
+        assert_eq!(coerce("0.0", Kind::Bool, lenient), Some(Value::Bool(false)));
+        assert_eq!(coerce("2", Kind::Bool, lenient), None);
+        assert_eq!(coerce("1_0", Kind::Str, lenient), parse_str("\"1_0\"").ok());
+        assert_eq!(coerce("[]", Kind::Str, lenient), None);
+        let tagged = Value::tagged("t", Value::Num("5".into()));
+        assert_eq!(
+            tagged.coerce(Kind::Num, CoercionPolicy::default()),
+            Some(Cow::Borrowed(&Value::Num("5".into())))
+        );
+        let only_bools = CoercionPolicy {
+            str_to_bool: true,
+            ..Default::default()
+        };
+        assert_eq!(coerce("\"5\"", Kind::Num, only_bools), None);
+    }
+
+    #[test]
+    fn getters_log_coercions() {
+        let value = parse_str(r#"{a: {b: ["7", "x", None]}, c: true}"#).unwrap();
+        let path = |p: &str| p.parse::<Path>().unwrap();
+        let mut coercer = Coercer::new(CoercionPolicy::LENIENT);
+        assert_eq!(coercer.require::<i64>(&value, &path("a.b[0]")), Ok(7));
+        assert_eq!(
+            coercer.require::<String>(&value, &path("c")),
+            Ok("true".into())
+        );
+        assert_eq!(coercer.optional::<i64>(&value, &path("a.b[2]")), Ok(None));
+        assert_eq!(coercer.optional::<i64>(&value, &path("a.z")), Ok(None));
+        assert_eq!(
+            coercer
+                .require::<i64>(&value, &path("a.b[1]"))
+                .unwrap_err()
+                .to_string(),
+            "field 'a.b[1]': expected i64, found str"
+        );
+        assert_eq!(
+            coercer
+                .require::<bool>(&value, &path("a.z"))
+                .unwrap_err()
+                .to_string(),
+            "field 'a.z': expected bool, found nothing"
+        );
+        assert_eq!(
+            coercer.coercions(),
+            [
+                Coercion {
+                    path: path("a.b[0]"),
+                    from: Kind::Str,
+                    to: Kind::Num,
+                },
+                Coercion {
+                    path: path("c"),
+                    from: Kind::Bool,
+                    to: Kind::Str,
+                },
+            ]
+        );
+        let mut strict = Coercer::default();
+        assert!(strict.require::<i64>(&value, &path("a.b[0]")).is_err());
+        assert!(strict.coercions().is_empty());
+    }
+}
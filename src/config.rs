@@ -0,0 +1,741 @@
+//! Layered loading of configuration files.
+//!
+//! A [`Loader`] reads a base file, overlays environment specific files and environment
+//! variables on top of it (using [`Value::merge`]), optionally validates the result against
+//! a [`Schema`] and finally converts it into a user type.
+//!
+//! # Usage example
+//! ```rust,no_run
+//! use gon::config::Loader;
+//! let config = Loader::new("config/app.gon")
+//!     .environment("production") // overlays config/app.production.gon if it exists
+//!     .env_prefix("APP") // APP__SERVER__PORT=1 sets server.port to 1
+//!     .load()
+//!     .unwrap();
+//! ```
+//!
+//! [`Loader::load_explained`] also remembers which layer set every value, for when it isn't
+//! obvious where a setting comes from:
+//! ```rust,no_run
+//! use gon::config::Loader;
+//! let explained = Loader::new("config/app.gon")
+//!     .environment("production")
+//!     .load_explained()
+//!     .unwrap();
+//! if let Some(provenance) = explained.provenance(&"server.port".parse().unwrap()) {
+//!     println!("server.port was set by the {}", provenance.layer);
+//! }
+//! ```
+//!
+//! A [`Watcher`] reloads a config whenever one of its files changes:
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use gon::config::{Loader, Watcher};
+//! let (_watcher, updates) = Watcher::watch(Loader::new("tuning.gon"), Duration::from_millis(250));
+//! for update in updates {
+//!     match update {
+//!         Ok(value) => println!("new tuning: {}", value.min_spell()),
+//!         Err(e) => eprintln!("ignoring broken tuning: {e}"),
+//!     }
+//! }
+//! ```
+//! With the `async` feature, `Watcher::watch_async` sends the updates through a tokio
+//! channel instead, to `.recv().await` them.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::cache::{ParseCache, file_stamp};
+use crate::document::{Document, DocumentError};
+use crate::encoding::ReadError;
+use crate::path::{self, Segment};
+use crate::schema::{Schema, ValidationErrors};
+use crate::span::Span;
+use crate::{GonError, MapT, Value, parse_str};
+
+/// The separator between the prefix and the keys of environment variable overrides
+const ENV_SEPARATOR: &str = "__";
+
+/// Loads layered configuration. See the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Loader {
+    base: PathBuf,
+    overlays: Vec<Overlay>,
+    env_prefix: Option<String>,
+    schema: Option<Schema>,
+    cache: Option<Arc<ParseCache>>,
+}
+
+#[derive(Debug, Clone)]
+struct Overlay {
+    path: PathBuf,
+    required: bool,
+}
+
+/// A loaded config together with where each of its values comes from, see
+/// [`Loader::load_explained`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explained {
+    pub value: Value,
+    provenance: HashMap<path::Path, Provenance>,
+}
+
+/// Which layer of a config set a value, see [`Explained`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// The file the value is from, `None` for environment variables
+    pub file: Option<PathBuf>,
+    /// Where the value is spelled in `file`, `None` if it couldn't be located (for example
+    /// because the file uses `let` bindings)
+    pub span: Option<Span>,
+    pub layer: Layer,
+}
+
+/// The kinds of layers a [`Loader`] merges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layer {
+    /// The base file
+    Base,
+    /// An overlay file, including environment specific files
+    Overlay,
+    /// The environment variable with this name
+    EnvVar(String),
+}
+
+/// One layer of a config and where it's from
+struct LayerValue {
+    value: Arc<Value>,
+    layer: Layer,
+    file: Option<PathBuf>,
+    /// The source text of `file` if it was read without a cache, kept for locating values
+    /// in it
+    src: Option<String>,
+}
+
+/// Watches the files of a [`Loader`] and sends the reloaded config through a channel whenever
+/// one of them changes, a std one or, with the `async` feature, a tokio one. The files are
+/// polled in a background thread which stops when the watcher is dropped or the receiving end
+/// of the channel hangs up.
+#[derive(Debug)]
+pub struct Watcher {
+    /// Dropped to stop the thread, which wakes up right away instead of after the interval
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+/// Something went wrong while loading a config
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    /// A config file couldn't be read
+    #[error("couldn't read config file '{0}': {1}")]
+    Io(String, std::io::Error),
+    /// A config file isn't valid gon
+    #[error("couldn't parse config file '{0}': {1}")]
+    Parse(String, GonError),
+    /// The merged config doesn't match the schema
+    #[error("config doesn't match the schema:\n{0}")]
+    Invalid(ValidationErrors),
+    /// The merged config couldn't be converted into the target type
+    #[error("couldn't convert config: {0}")]
+    Convert(String),
+    /// A config file couldn't be edited in place
+    #[error("couldn't edit config file '{0}': {1}")]
+    Edit(String, DocumentError),
+    /// A config file couldn't be written
+    #[error("couldn't write config file '{0}': {1}")]
+    Write(String, std::io::Error),
+}
+
+impl Loader {
+    /// A loader that starts with the given base file
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            overlays: Vec::new(),
+            env_prefix: None,
+            schema: None,
+            cache: None,
+        }
+    }
+
+    /// Merges the given file on top of the previous layers. The file must exist.
+    pub fn overlay(mut self, path: impl Into<PathBuf>) -> Self {
+        self.overlays.push(Overlay {
+            path: path.into(),
+            required: true,
+        });
+        self
+    }
+
+    /// Merges the environment specific file next to the base file on top of the previous
+    /// layers, if it exists. For a base file `app.gon` and the environment `production`
+    /// that is `app.production.gon`.
+    pub fn environment(mut self, name: &str) -> Self {
+        self.overlays.push(Overlay {
+            path: self.base.with_extension(format!("{name}.gon")),
+            required: false,
+        });
+        self
+    }
+
+    /// Merges environment variables starting with `<prefix>__` on top of all files.
+    /// The rest of the variable name is split at `__` and lowercased to get the key path,
+    /// so `APP__SERVER__PORT=1` sets `server.port` to `1`. Values are parsed as gon and
+    /// fall back to strings if that fails.
+    pub fn env_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.env_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Validates the merged config against the given schema
+    pub fn schema(mut self, schema: Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Parses the files through `cache`, so loading again only parses the files that
+    /// changed since, see the docs of [`cache`](crate::cache). A [`Watcher`] uses a cache
+    /// of its own if the loader has none.
+    pub fn cache(mut self, cache: Arc<ParseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Loads, merges and validates all layers
+    pub fn load(&self) -> Result<Value, ConfigError> {
+        self.load_with_vars(std::env::vars())
+    }
+
+    /// Like [`Loader::load`] but also records which layer set each value. Objects that
+    /// several layers contributed to are attributed to the last of them.
+    pub fn load_explained(&self) -> Result<Explained, ConfigError> {
+        self.load_explained_with_vars(std::env::vars())
+    }
+
+    /// Like [`Loader::load`] but converts the config into `T` afterwards
+    pub fn load_into<T>(&self) -> Result<T, ConfigError>
+    where
+        T: TryFrom<Value>,
+        T::Error: Display,
+    {
+        T::try_from(self.load()?).map_err(|e| ConfigError::Convert(e.to_string()))
+    }
+
+    /// All files this loader may read, including optional ones that don't exist (yet)
+    fn files(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.base.as_path()).chain(self.overlays.iter().map(|o| o.path.as_path()))
+    }
+
+    fn load_with_vars(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Value, ConfigError> {
+        self.merge_layers(vars, self.cache.as_deref(), |_, _| {})
+    }
+
+    fn load_explained_with_vars(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Explained, ConfigError> {
+        let mut provenance = HashMap::new();
+        // the cache doesn't keep the sources, which are needed to locate the values
+        let value = self.merge_layers(vars, None, |merged, layer| {
+            let doc = layer
+                .src
+                .as_deref()
+                .and_then(|src| Document::parse(src).ok());
+            let mut source = |path: &path::Path| Provenance {
+                file: layer.file.clone(),
+                span: doc.as_ref().and_then(|doc| doc.span(path)),
+                layer: layer.layer.clone(),
+            };
+            record(
+                &mut provenance,
+                &mut path::Path::default(),
+                Some(merged),
+                &layer.value,
+                &mut source,
+            );
+        })?;
+        Ok(Explained { value, provenance })
+    }
+
+    /// Merges all layers in order, calling `on_layer` with the value so far before merging
+    /// each layer on top of it, and validates the result
+    fn merge_layers(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+        cache: Option<&ParseCache>,
+        mut on_layer: impl FnMut(&Value, &LayerValue),
+    ) -> Result<Value, ConfigError> {
+        let mut value = Value::Obj(MapT::new());
+        for (i, layer) in self.layers(vars, cache)?.into_iter().enumerate() {
+            on_layer(&value, &layer);
+            // cached layers are shared with the cache, so they're cloned to be merged
+            if i == 0 {
+                value = Arc::unwrap_or_clone(layer.value);
+            } else {
+                value.merge(Arc::unwrap_or_clone(layer.value));
+            }
+        }
+        if let Some(schema) = &self.schema {
+            schema.validate(&value).map_err(ConfigError::Invalid)?;
+        }
+        Ok(value)
+    }
+
+    /// The base file, the overlays that exist and the environment variables, in the order
+    /// they're merged
+    fn layers(
+        &self,
+        vars: impl IntoIterator<Item = (String, String)>,
+        cache: Option<&ParseCache>,
+    ) -> Result<Vec<LayerValue>, ConfigError> {
+        let mut layers = vec![read_layer(&self.base, Layer::Base, cache)?];
+        for overlay in &self.overlays {
+            if overlay.required || overlay.path.exists() {
+                layers.push(read_layer(&overlay.path, Layer::Overlay, cache)?);
+            }
+        }
+        if let Some(prefix) = &self.env_prefix {
+            for (name, value) in env_overrides(prefix, vars) {
+                layers.push(LayerValue {
+                    value: Arc::new(value),
+                    layer: Layer::EnvVar(name),
+                    file: None,
+                    src: None,
+                });
+            }
+        }
+        Ok(layers)
+    }
+}
+
+impl Explained {
+    /// Which layer set the value at `path`, `None` if there is no value there
+    pub fn provenance(&self, path: &path::Path) -> Option<&Provenance> {
+        self.provenance.get(path)
+    }
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Base => write!(f, "base file"),
+            Self::Overlay => write!(f, "overlay"),
+            Self::EnvVar(name) => write!(f, "environment variable {name}"),
+        }
+    }
+}
+
+/// Records where the values `layer` sets at `path` come from, the way [`Value::merge`]
+/// merges `layer` into `merged`: objects key by key, everything else replaced as a whole
+fn record(
+    provenance: &mut HashMap<path::Path, Provenance>,
+    path: &mut path::Path,
+    merged: Option<&Value>,
+    layer: &Value,
+    source: &mut impl FnMut(&path::Path) -> Provenance,
+) {
+    provenance.insert(path.clone(), source(path));
+    match (merged, layer) {
+        (Some(Value::Obj(merged)), Value::Obj(obj)) => {
+            for (k, v) in obj {
+                path.0.push(Segment::Key(k.to_string()));
+                record(provenance, path, merged.get(&**k), v, source);
+                path.0.pop();
+            }
+        }
+        _ => {
+            // everything below was replaced, including what the new value doesn't have
+            if merged.is_some_and(|m| !children(m).is_empty()) {
+                provenance.retain(|p, _| p.0.len() <= path.0.len() || !p.0.starts_with(&path.0));
+            }
+            for (segment, child) in children(layer) {
+                path.0.push(segment);
+                record(provenance, path, None, child, source);
+                path.0.pop();
+            }
+        }
+    }
+}
+
+/// The direct children of `value` and where they are in it
+fn children(value: &Value) -> Vec<(Segment, &Value)> {
+    match value {
+        Value::Obj(obj) => obj
+            .iter()
+            .map(|(k, v)| (Segment::Key(k.to_string()), v))
+            .collect(),
+        Value::List(xs) => xs
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (Segment::Index(i), x))
+            .collect(),
+        Value::Tagged { value, .. } => children(value),
+        _ => Vec::new(),
+    }
+}
+
+impl Watcher {
+    /// Starts watching all files of `loader`, checking them for changes every `interval`.
+    /// The first message is the initial config, every following one is sent after a change.
+    /// Broken intermediate states (parse or validation errors) are sent as errors, the
+    /// watcher keeps going.
+    pub fn watch(
+        loader: Loader,
+        interval: Duration,
+    ) -> (Self, Receiver<Result<Value, ConfigError>>) {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = Self::spawn(loader, interval, Ok, move |update| {
+            sender.send(update).is_ok()
+        });
+        (watcher, receiver)
+    }
+
+    /// Like [`Watcher::watch`] but converts every reloaded config into `T`
+    pub fn watch_into<T>(
+        loader: Loader,
+        interval: Duration,
+    ) -> (Self, Receiver<Result<T, ConfigError>>)
+    where
+        T: TryFrom<Value> + Send + 'static,
+        T::Error: Display,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let watcher = Self::spawn(loader, interval, convert_into::<T>, move |update| {
+            sender.send(update).is_ok()
+        });
+        (watcher, receiver)
+    }
+
+    /// Like [`Watcher::watch`] but sends through a tokio channel, so async code can await
+    /// the updates. The watcher still polls in a thread of its own.
+    #[cfg(feature = "async")]
+    pub fn watch_async(
+        loader: Loader,
+        interval: Duration,
+    ) -> (
+        Self,
+        tokio::sync::mpsc::UnboundedReceiver<Result<Value, ConfigError>>,
+    ) {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = Self::spawn(loader, interval, Ok, move |update| {
+            sender.send(update).is_ok()
+        });
+        (watcher, receiver)
+    }
+
+    /// Like [`Watcher::watch_into`] but sends through a tokio channel, see
+    /// [`Watcher::watch_async`]
+    #[cfg(feature = "async")]
+    pub fn watch_into_async<T>(
+        loader: Loader,
+        interval: Duration,
+    ) -> (
+        Self,
+        tokio::sync::mpsc::UnboundedReceiver<Result<T, ConfigError>>,
+    )
+    where
+        T: TryFrom<Value> + Send + 'static,
+        T::Error: Display,
+    {
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = Self::spawn(loader, interval, convert_into::<T>, move |update| {
+            sender.send(update).is_ok()
+        });
+        (watcher, receiver)
+    }
+
+    /// Polls the files of `loader` in a thread that passes every reloaded config to `send`,
+    /// until it returns false because nobody receives them anymore
+    fn spawn<T: Send + 'static>(
+        loader: Loader,
+        interval: Duration,
+        convert: fn(Value) -> Result<T, ConfigError>,
+        send: impl Fn(Result<T, ConfigError>) -> bool + Send + 'static,
+    ) -> Self {
+        // reloading parses only the files that changed. A cache of the watcher's own only
+        // needs the current version of every file, the older ones are pruned after reloads.
+        let own_cache = loader.cache.is_none();
+        let loader = match loader.cache {
+            Some(_) => loader,
+            None => loader.cache(Arc::default()),
+        };
+        let (stop, stopped) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut last_seen = None;
+            loop {
+                let stamps: Vec<_> = loader.files().map(file_stamp).collect();
+                if last_seen.as_ref() != Some(&stamps) {
+                    last_seen = Some(stamps);
+                    let update = loader.load().and_then(convert);
+                    if own_cache {
+                        loader.cache.iter().for_each(|cache| cache.prune());
+                    }
+                    if !send(update) {
+                        break;
+                    }
+                }
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => {}
+                    _ => break,
+                }
+            }
+        });
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+/// Converts a reloaded config for [`Watcher::watch_into`]
+fn convert_into<T>(value: Value) -> Result<T, ConfigError>
+where
+    T: TryFrom<Value>,
+    T::Error: Display,
+{
+    T::try_from(value).map_err(|e| ConfigError::Convert(e.to_string()))
+}
+
+impl Watcher {
+    /// Writes `value` back into the file at `path`, editing the existing text as little as
+    /// possible so comments, key order and formatting of unchanged parts are kept (see
+    /// [`Document::update`]). Files that don't exist yet are created. A running watcher of
+    /// the same file picks the change up like any other edit.
+    ///
+    /// Note that `value` replaces the content of this one file: when persisting changes to a
+    /// layered config, pass the layer's own value rather than the merged one.
+    pub fn persist(path: impl AsRef<Path>, value: &Value) -> Result<(), ConfigError> {
+        let path = path.as_ref();
+        let name = || path.display().to_string();
+        let src = match std::fs::read_to_string(path) {
+            Ok(src) => {
+                let mut doc = Document::parse(src).map_err(|e| ConfigError::Edit(name(), e))?;
+                doc.update(value)
+                    .map_err(|e| ConfigError::Edit(name(), e))?;
+                doc.to_string()
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => value
+                .spell(Default::default())
+                .unwrap_or_else(|_| value.min_spell()),
+            Err(e) => return Err(ConfigError::Io(name(), e)),
+        };
+        std::fs::write(path, src).map_err(|e| ConfigError::Write(name(), e))
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn read_layer(
+    path: &Path,
+    layer: Layer,
+    cache: Option<&ParseCache>,
+) -> Result<LayerValue, ConfigError> {
+    let name = || path.display().to_string();
+    let (value, src) = match cache {
+        // by path, so that the cache keeps the version of the file read last
+        Some(cache) => match cache.parse_file(path) {
+            Ok(value) => (value, None),
+            Err(ReadError::Parse(e)) => return Err(ConfigError::Parse(name(), e)),
+            Err(ReadError::Io(e)) => return Err(ConfigError::Io(name(), e)),
+            Err(e) => return Err(ConfigError::Io(name(), std::io::Error::other(e))),
+        },
+        None => {
+            let src = std::fs::read_to_string(path).map_err(|e| ConfigError::Io(name(), e))?;
+            let value = parse_str(&src).map_err(|e| ConfigError::Parse(name(), e))?;
+            (Arc::new(value), Some(src))
+        }
+    };
+    Ok(LayerValue {
+        value,
+        layer,
+        file: Some(path.to_path_buf()),
+        src,
+    })
+}
+
+/// Turns every variable that starts with `<prefix>__` into a nested object
+fn env_overrides(
+    prefix: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> Vec<(String, Value)> {
+    let mut overrides = Vec::new();
+    for (name, raw) in vars {
+        let Some(keys) = name
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_prefix(ENV_SEPARATOR))
+        else {
+            continue;
+        };
+        let value = parse_str(&raw).unwrap_or(Value::Str { s: raw, raw: false });
+        let nested = keys.rsplit(ENV_SEPARATOR).fold(value, |inner, key| {
+            Value::Obj(MapT::from_iter([(
+                key.to_lowercase().as_str().into(),
+                inner,
+            )]))
+        });
+        overrides.push((name, nested));
+    }
+    overrides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn layers_files_and_env_vars() {
+        let dir = TempDir::new("config");
+        let base = dir.join("app.gon");
+        std::fs::write(
+            &base,
+            r#"{server: {host: "localhost", port: 80}, debug: false}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("app.production.gon"), "{server: {port: 443}}").unwrap();
+        let schema = Schema::from_value(&parse_str(r#"{server: {port: "num"}}"#).unwrap()).unwrap();
+        let loader = Loader::new(&base)
+            .environment("production")
+            .environment("missing")
+            .env_prefix("APP")
+            .schema(schema);
+        let value = loader
+            .load_with_vars([
+                ("APP__SERVER__HOST".to_string(), "example.com".to_string()),
+                ("APP__DEBUG".to_string(), "true".to_string()),
+                ("OTHER__DEBUG".to_string(), "1".to_string()),
+            ])
+            .unwrap();
+        assert_eq!(
+            value,
+            parse_str(r#"{server: {host: "example.com", port: 443}, debug: true}"#).unwrap()
+        );
+        let invalid = loader.load_with_vars([("APP__SERVER__PORT".to_string(), "x".to_string())]);
+        assert!(matches!(invalid, Err(ConfigError::Invalid(_))));
+    }
+
+    #[test]
+    fn caches_layers_by_file() {
+        let dir = TempDir::new("config-cache");
+        let base = dir.join("app.gon");
+        std::fs::write(&base, "{port: 80}").unwrap();
+        let cache = Arc::new(ParseCache::new());
+        let loader = Loader::new(&base).cache(Arc::clone(&cache));
+        assert_eq!(loader.load().unwrap(), parse_str("{port: 80}").unwrap());
+        std::fs::write(&base, "{port: 8080}").unwrap();
+        assert_eq!(loader.load().unwrap(), parse_str("{port: 8080}").unwrap());
+        // only the file's current version is left once the old one is pruned
+        cache.prune();
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn explains_where_values_come_from() {
+        let dir = TempDir::new("explain");
+        let base = dir.join("app.gon");
+        let overlay = dir.join("app.production.gon");
+        std::fs::write(
+            &base,
+            "{server: {host: \"a\", port: 80}, tags: [1, 2], debug: true}",
+        )
+        .unwrap();
+        std::fs::write(&overlay, "{server: {port: 443}, tags: [3]}").unwrap();
+        let explained = Loader::new(&base)
+            .environment("production")
+            .env_prefix("APP")
+            .load_explained_with_vars([("APP__SERVER__HOST".to_string(), "b".to_string())])
+            .unwrap();
+        let provenance = |path: &str| explained.provenance(&path.parse().unwrap());
+        let port = provenance("server.port").unwrap();
+        assert_eq!(
+            (&port.layer, port.file.as_ref(), port.span),
+            (&Layer::Overlay, Some(&overlay), Some(Span::new(16, 3)))
+        );
+        assert_eq!(
+            provenance("server.host").unwrap().layer,
+            Layer::EnvVar("APP__SERVER__HOST".into())
+        );
+        assert_eq!(provenance("debug").unwrap().layer, Layer::Base);
+        assert_eq!(provenance("tags[0]").unwrap().layer, Layer::Overlay);
+        assert_eq!(provenance("tags[1]"), None);
+    }
+
+    #[test]
+    fn watcher_sends_reloaded_values() {
+        let dir = TempDir::new("watch");
+        let file = dir.join("tuning.gon");
+        std::fs::write(&file, "{speed: 1}").unwrap();
+        let (watcher, updates) = Watcher::watch(Loader::new(&file), Duration::from_millis(10));
+        let timeout = Duration::from_secs(5);
+        assert_eq!(
+            updates.recv_timeout(timeout).unwrap().unwrap(),
+            parse_str("{speed: 1}").unwrap()
+        );
+        std::fs::write(&file, "{speed: 2.5}").unwrap();
+        assert_eq!(
+            updates.recv_timeout(timeout).unwrap().unwrap(),
+            parse_str("{speed: 2.5}").unwrap()
+        );
+        std::fs::write(&file, "{speed: }").unwrap();
+        assert!(matches!(
+            updates.recv_timeout(timeout).unwrap(),
+            Err(ConfigError::Parse(..))
+        ));
+        drop(watcher);
+        // the thread stops right away instead of after the interval
+        let (watcher, _updates) = Watcher::watch(Loader::new(&file), Duration::from_secs(60));
+        let start = std::time::Instant::now();
+        drop(watcher);
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_watcher_sends_through_tokio() {
+        let dir = TempDir::new("watch_async");
+        let file = dir.join("tuning.gon");
+        std::fs::write(&file, "{speed: 1}").unwrap();
+        let (watcher, mut updates) =
+            Watcher::watch_async(Loader::new(&file), Duration::from_millis(10));
+        assert_eq!(
+            updates.blocking_recv().unwrap().unwrap(),
+            parse_str("{speed: 1}").unwrap()
+        );
+        std::fs::write(&file, "{speed: 2.5}").unwrap();
+        assert_eq!(
+            updates.blocking_recv().unwrap().unwrap(),
+            parse_str("{speed: 2.5}").unwrap()
+        );
+        drop(watcher);
+        assert!(updates.blocking_recv().is_none());
+    }
+
+    #[test]
+    fn persist_keeps_comments() {
+        let dir = TempDir::new("persist");
+        let file = dir.join("tuning.gon");
+        std::fs::write(&file, "{\n    // slider: 0..10\n    speed: 1,\n}\n").unwrap();
+        Watcher::persist(&file, &parse_str("{speed: 7}").unwrap()).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "{\n    // slider: 0..10\n    speed: 7,\n}\n"
+        );
+    }
+}
@@ -0,0 +1,469 @@
+//! Pull-style reading of gon source without building a [`Value`](crate::Value).
+//!
+//! A [`Cursor`] walks the source token by token and hands out keys, strings and numbers
+//! borrowed from it, so decoding into your own types doesn't allocate anything but what
+//! those types need. Strings are only copied if they contain escape sequences or are
+//! joined with `+`. The cursor reads plain data: `let` bindings, variables, conditional
+//! sections and tags are reported as unexpected.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::cursor::Cursor;
+//! struct Enemy {
+//!     name: String,
+//!     hp: i64,
+//! }
+//! let src = r#"[{name: "Goblin", hp: 10}, {name: "Slime", hp: 3, loot: ["gel"]}]"#;
+//! let mut cursor = Cursor::new(src);
+//! let mut enemies = Vec::new();
+//! cursor.enter_list()?;
+//! while cursor.next_element()? {
+//!     let mut enemy = Enemy { name: String::new(), hp: 0 };
+//!     cursor.enter_obj()?;
+//!     while let Some(key) = cursor.key()? {
+//!         match &*key {
+//!             "name" => enemy.name = cursor.next_str()?.into_owned(),
+//!             "hp" => enemy.hp = cursor.next_i64()?,
+//!             _ => cursor.skip_value()?,
+//!         }
+//!     }
+//!     enemies.push(enemy);
+//! }
+//! cursor.finish()?;
+//! assert_eq!(enemies[1].name, "Slime");
+//! assert_eq!(enemies[1].hp, 3);
+//! # Ok::<(), gon::cursor::CursorError>(())
+//! ```
+
+use std::borrow::Cow;
+
+use thiserror::Error;
+
+use crate::Kind;
+use crate::parser::{SpannedToken, TokenKind, is_number_literal, next_token};
+
+/// The source didn't hold what the [`Cursor`] was asked to read
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum CursorError {
+    /// Something else came first
+    #[error("expected {expected}, found '{found}' at byte {at}")]
+    Unexpected {
+        expected: &'static str,
+        found: String,
+        at: usize,
+    },
+    /// The source ended early
+    #[error("expected {0}, found the end of the input")]
+    Eof(&'static str),
+    /// A number or string that can't be read as what was asked for
+    #[error("malformed {expected} '{found}' at byte {at}")]
+    Malformed {
+        expected: &'static str,
+        found: String,
+        at: usize,
+    },
+}
+
+/// Reads gon source front to back, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    src: &'a str,
+    pos: usize,
+    peeked: Option<SpannedToken>,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(src: &'a str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            peeked: None,
+        }
+    }
+
+    /// The byte offset the cursor has read up to
+    pub fn offset(&self) -> usize {
+        self.peeked.map_or(self.pos, |t| t.span.start)
+    }
+
+    /// What kind of value comes next, without reading it
+    pub fn peek_kind(&mut self) -> Result<Kind, CursorError> {
+        const EXPECTED: &str = "a value";
+        let token = self.peek().ok_or(CursorError::Eof(EXPECTED))?;
+        let text = token.text(self.src);
+        Ok(match token.kind {
+            TokenKind::LBrace => Kind::Obj,
+            TokenKind::LBrack => Kind::List,
+            TokenKind::Str | TokenKind::RawStr => Kind::Str,
+            TokenKind::Num | TokenKind::Dash => Kind::Num,
+            TokenKind::Keyword if is_none(text) => Kind::None,
+            TokenKind::Keyword if parse_bool(text).is_some() => Kind::Bool,
+            _ => return Err(self.unexpected(token, EXPECTED)),
+        })
+    }
+
+    /// Reads the `{` of an object. Read its entries with [`Cursor::key`].
+    pub fn enter_obj(&mut self) -> Result<(), CursorError> {
+        self.expect(TokenKind::LBrace, "'{'").map(drop)
+    }
+
+    /// Reads the next key of the object the cursor is in and the colon after it, or the
+    /// closing `}` and `None` if there are no more entries. Read or
+    /// [skip](Cursor::skip_value) the value before asking for the next key.
+    pub fn key(&mut self) -> Result<Option<Cow<'a, str>>, CursorError> {
+        const EXPECTED: &str = "a key or '}'";
+        self.skip_comma();
+        let token = self.bump().ok_or(CursorError::Eof(EXPECTED))?;
+        let key = match token.kind {
+            TokenKind::RBrace => return Ok(None),
+            TokenKind::Sym | TokenKind::Keyword | TokenKind::Num => {
+                Cow::Borrowed(token.text(self.src))
+            }
+            TokenKind::Str => self.unescape(token)?,
+            _ => return Err(self.unexpected(token, EXPECTED)),
+        };
+        self.expect(TokenKind::Colon, "':'")?;
+        Ok(Some(key))
+    }
+
+    /// Reads the `[` of a list. Ask for its elements with [`Cursor::next_element`].
+    pub fn enter_list(&mut self) -> Result<(), CursorError> {
+        self.expect(TokenKind::LBrack, "'['").map(drop)
+    }
+
+    /// Whether the list the cursor is in has another element, which is read next.
+    /// Reads the closing `]` if it doesn't.
+    pub fn next_element(&mut self) -> Result<bool, CursorError> {
+        self.skip_comma();
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::RBrack => {
+                self.bump();
+                Ok(false)
+            }
+            Some(_) => Ok(true),
+            None => Err(CursorError::Eof("an element or ']'")),
+        }
+    }
+
+    /// Reads a string, joining literals that are concatenated with `+`
+    pub fn next_str(&mut self) -> Result<Cow<'a, str>, CursorError> {
+        const EXPECTED: &str = "a str";
+        let token = self.bump().ok_or(CursorError::Eof(EXPECTED))?;
+        if !matches!(token.kind, TokenKind::Str | TokenKind::RawStr) {
+            return Err(self.unexpected(token, EXPECTED));
+        }
+        let mut s = self.unescape(token)?;
+        while self.peek().is_some_and(|t| t.kind == TokenKind::Plus) {
+            self.bump();
+            let token = self.bump().ok_or(CursorError::Eof(EXPECTED))?;
+            if !matches!(token.kind, TokenKind::Str | TokenKind::RawStr) {
+                return Err(self.unexpected(token, EXPECTED));
+            }
+            s.to_mut().push_str(&self.unescape(token)?);
+        }
+        Ok(s)
+    }
+
+    /// Reads a number as it's spelled, like `-1_000.5`
+    pub fn next_num(&mut self) -> Result<Cow<'a, str>, CursorError> {
+        const EXPECTED: &str = "a num";
+        let first = self.bump().ok_or(CursorError::Eof(EXPECTED))?;
+        let num = match first.kind {
+            TokenKind::Num => first,
+            TokenKind::Dash => match self.bump() {
+                Some(num) if num.kind == TokenKind::Num => num,
+                Some(token) => return Err(self.unexpected(token, EXPECTED)),
+                None => return Err(CursorError::Eof(EXPECTED)),
+            },
+            _ => return Err(self.unexpected(first, EXPECTED)),
+        };
+        let digits = num.text(self.src);
+        if !is_number_literal(digits) {
+            return Err(CursorError::Malformed {
+                expected: EXPECTED,
+                found: digits.to_string(),
+                at: num.span.start,
+            });
+        }
+        Ok(match first.kind {
+            // `- 1` is fine, but has to be put together
+            TokenKind::Dash if first.span.end() != num.span.start => {
+                Cow::Owned(format!("-{digits}"))
+            }
+            _ => Cow::Borrowed(&self.src[first.span.start..num.span.end()]),
+        })
+    }
+
+    /// Reads a number that fits into an `i64`
+    pub fn next_i64(&mut self) -> Result<i64, CursorError> {
+        self.next_parsed("an i64")
+    }
+
+    /// Reads a number as an `f64`
+    pub fn next_f64(&mut self) -> Result<f64, CursorError> {
+        self.next_parsed("an f64")
+    }
+
+    /// Reads `true` or `false`
+    pub fn next_bool(&mut self) -> Result<bool, CursorError> {
+        const EXPECTED: &str = "a bool";
+        let token = self.bump().ok_or(CursorError::Eof(EXPECTED))?;
+        match token.kind {
+            TokenKind::Keyword => parse_bool(token.text(self.src)),
+            _ => None,
+        }
+        .ok_or_else(|| self.unexpected(token, EXPECTED))
+    }
+
+    /// Reads `None` if it comes next and tells whether it did, for optional values
+    pub fn next_none(&mut self) -> bool {
+        let none = self
+            .peek()
+            .is_some_and(|t| t.kind == TokenKind::Keyword && is_none(t.text(self.src)));
+        if none {
+            self.bump();
+        }
+        none
+    }
+
+    /// Reads the next value, whatever it is, and throws it away
+    pub fn skip_value(&mut self) -> Result<(), CursorError> {
+        match self.peek_kind()? {
+            Kind::None => {
+                self.bump();
+            }
+            Kind::Str => {
+                self.next_str()?;
+            }
+            Kind::Num => {
+                self.next_num()?;
+            }
+            Kind::Bool => {
+                self.next_bool()?;
+            }
+            Kind::Obj => {
+                self.enter_obj()?;
+                while self.key()?.is_some() {
+                    self.skip_value()?;
+                }
+            }
+            Kind::List => {
+                self.enter_list()?;
+                while self.next_element()? {
+                    self.skip_value()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if there is anything but comments left
+    pub fn finish(mut self) -> Result<(), CursorError> {
+        match self.peek() {
+            Some(token) => Err(self.unexpected(token, "the end of the input")),
+            None => Ok(()),
+        }
+    }
+
+    /// The next token that isn't a comment
+    fn peek(&mut self) -> Option<SpannedToken> {
+        if self.peeked.is_none() {
+            self.peeked = std::iter::from_fn(|| next_token(self.src, &mut self.pos))
+                .find(|t| t.kind != TokenKind::Comment);
+        }
+        self.peeked
+    }
+
+    fn bump(&mut self) -> Option<SpannedToken> {
+        self.peek();
+        self.peeked.take()
+    }
+
+    fn expect(
+        &mut self,
+        kind: TokenKind,
+        expected: &'static str,
+    ) -> Result<SpannedToken, CursorError> {
+        match self.bump() {
+            Some(token) if token.kind == kind => Ok(token),
+            Some(token) => Err(self.unexpected(token, expected)),
+            None => Err(CursorError::Eof(expected)),
+        }
+    }
+
+    /// Commas between entries and elements are optional
+    fn skip_comma(&mut self) {
+        if self.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+            self.bump();
+        }
+    }
+
+    fn next_parsed<T: std::str::FromStr>(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<T, CursorError> {
+        let at = self.offset();
+        let num = self.next_num()?;
+        let parsed = if num.contains('_') {
+            num.replace('_', "").parse()
+        } else {
+            num.parse()
+        };
+        parsed.map_err(|_| CursorError::Malformed {
+            expected,
+            found: num.into_owned(),
+            at,
+        })
+    }
+
+    /// The contents of a string literal token, only copied if it has escape sequences
+    fn unescape(&self, token: SpannedToken) -> Result<Cow<'a, str>, CursorError> {
+        let text = token.text(self.src);
+        let literal = &text[text.find('"').unwrap_or(0)..];
+        if !literal.contains('\\') {
+            return Ok(Cow::Borrowed(&literal[1..literal.len() - 1]));
+        }
+        // klex has the final say on escape sequences, just like when parsing
+        match klex::Lexer::new(literal, 0).lex().as_deref() {
+            Ok(
+                [
+                    klex::RichToken {
+                        inner: klex::Token::Str(s),
+                        ..
+                    },
+                ],
+            ) => Ok(Cow::Owned(s.clone())),
+            _ => Err(CursorError::Malformed {
+                expected: "str",
+                found: text.to_string(),
+                at: token.span.start,
+            }),
+        }
+    }
+
+    fn unexpected(&self, token: SpannedToken, expected: &'static str) -> CursorError {
+        CursorError::Unexpected {
+            expected,
+            found: token.text(self.src).to_string(),
+            at: token.span.start,
+        }
+    }
+}
+
+fn is_none(keyword: &str) -> bool {
+    keyword.eq_ignore_ascii_case("none") || keyword.eq_ignore_ascii_case("null")
+}
+
+fn parse_bool(keyword: &str) -> Option<bool> {
+    if keyword.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if keyword.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Value, parse_str};
+
+    /// Builds a value with nothing but the cursor, to compare it to what the parser builds
+    fn read(cursor: &mut Cursor) -> Result<Value, CursorError> {
+        Ok(match cursor.peek_kind()? {
+            Kind::None => {
+                cursor.next_none();
+                Value::None
+            }
+            Kind::Str => Value::Str {
+                s: cursor.next_str()?.into_owned(),
+                raw: false,
+            },
+            Kind::Num => Value::Num(cursor.next_num()?.into_owned()),
+            Kind::Bool => Value::Bool(cursor.next_bool()?),
+            Kind::Obj => {
+                let mut obj = crate::MapT::new();
+                cursor.enter_obj()?;
+                while let Some(key) = cursor.key()? {
+                    let key = crate::KeyT::from(key.into_owned());
+                    obj.insert(key, read(cursor)?);
+                }
+                Value::Obj(obj)
+            }
+            Kind::List => {
+                let mut xs = Vec::new();
+                cursor.enter_list()?;
+                while cursor.next_element()? {
+                    xs.push(read(cursor)?);
+                }
+                Value::List(xs)
+            }
+        })
+    }
+
+    #[test]
+    fn reads_like_the_parser() {
+        for src in [
+            "None",
+            "[1, -2, - 3, 4.5, true, FALSE, null]",
+            r#"{a: "x\ty" + "z", "b c": [{}, []] // comment
+                7: "\"quoted\"", nested: {deeper: [None,],},}"#,
+            "{a: 1 b: 2}",
+            include_str!("../example.gon"),
+        ] {
+            let mut cursor = Cursor::new(src);
+            let value = read(&mut cursor).unwrap();
+            cursor.finish().unwrap();
+            assert_eq!(Ok(value), parse_str(src), "{src}");
+        }
+    }
+
+    #[test]
+    fn borrows_unless_it_has_to_copy() {
+        let mut cursor = Cursor::new(r#"["plain", "esc\n", "a" + "b", 1_000, -5]"#);
+        cursor.enter_list().unwrap();
+        assert!(cursor.next_element().unwrap());
+        assert!(matches!(cursor.next_str(), Ok(Cow::Borrowed("plain"))));
+        assert!(cursor.next_element().unwrap());
+        assert_eq!(cursor.next_str().unwrap(), "esc\n");
+        assert!(cursor.next_element().unwrap());
+        assert!(matches!(cursor.next_str(), Ok(Cow::Owned(s)) if s == "ab"));
+        assert!(cursor.next_element().unwrap());
+        assert_eq!(cursor.next_i64(), Ok(1000));
+        assert!(cursor.next_element().unwrap());
+        assert!(matches!(cursor.next_num(), Ok(Cow::Borrowed("-5"))));
+        assert_eq!(cursor.next_element(), Ok(false));
+        cursor.finish().unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let mut cursor = Cursor::new("{hp: \"ten\"}");
+        cursor.enter_obj().unwrap();
+        assert_eq!(cursor.key().unwrap().as_deref(), Some("hp"));
+        assert_eq!(
+            cursor.next_i64(),
+            Err(CursorError::Unexpected {
+                expected: "a num",
+                found: "\"ten\"".to_string(),
+                at: 5,
+            })
+        );
+        let mut cursor = Cursor::new("[1.5");
+        cursor.enter_list().unwrap();
+        assert!(cursor.next_element().unwrap());
+        assert!(matches!(
+            cursor.next_i64(),
+            Err(CursorError::Malformed { at: 1, .. })
+        ));
+        assert_eq!(
+            cursor.next_element(),
+            Err(CursorError::Eof("an element or ']'"))
+        );
+        assert!(Cursor::new("1 2").skip_value().is_ok());
+        let mut cursor = Cursor::new("1 2");
+        cursor.skip_value().unwrap();
+        assert!(cursor.finish().is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Named presets of [`ParseOptions`] and [`SpellConfig`] for how strict gon is.
+//!
+//! | Dialect | Parsing | Spelling |
+//! |---------|---------|----------|
+//! | [`Standard`](Dialect::Standard) | like [`parse`](crate::parse) | like [`SpellConfig::default`] |
+//! | [`Strict`](Dialect::Strict) | no trailing commas | canonical numbers |
+//! | [`Relaxed`](Dialect::Relaxed) | bare strings, dotted keys | trailing commas |
+//! | [`JsonCompat`](Dialect::JsonCompat) | no comments or trailing commas, quoted keys | JSON |
+//!
+//! A dialect only turns on what it's about: [`Dialect::parse_options`] and
+//! [`Dialect::spell_config`] keep everything else of the options they're given, like
+//! `@if` variables or the indentation of a format profile.
+//! # Usage example
+//! ```rust
+//! use gon::{dialect::Dialect, parser::{parse_str_with, ParseOptions}, SpellConfig};
+//! let src = r#"{"name": "gon", "tags": [],}"#;
+//! let strict = ParseOptions::from(Dialect::JsonCompat);
+//! assert!(parse_str_with(src, &strict).is_err());
+//! let value = parse_str_with(src, &ParseOptions::from(Dialect::Relaxed)).unwrap();
+//! assert_eq!(
+//!     value.spell(SpellConfig::from(Dialect::JsonCompat)).unwrap(),
+//!     "{\n    \"name\": \"gon\",\n    \"tags\": []\n}"
+//! );
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::SpellConfig;
+use crate::parser::ParseOptions;
+
+/// A preset of how strict gon is parsed and spelled, see the [module docs](self)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Dialect {
+    /// Gon as it is without any options
+    #[default]
+    Standard,
+    /// Gon without its leniencies, spelled canonically
+    Strict,
+    /// Gon that forgives what people write by hand, like `{mode: fast}`
+    Relaxed,
+    /// Only the syntax JSON has too, spelled as JSON
+    JsonCompat,
+}
+
+/// A [`Dialect`] was spelled wrong
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("unknown dialect '{0}'\n\tExpected one of: standard, strict, relaxed, json")]
+pub struct UnknownDialect(pub String);
+
+impl FromStr for Dialect {
+    type Err = UnknownDialect;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "standard" => Ok(Self::Standard),
+            "strict" => Ok(Self::Strict),
+            "relaxed" => Ok(Self::Relaxed),
+            "json" | "json-compat" | "json_compat" => Ok(Self::JsonCompat),
+            _ => Err(UnknownDialect(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Dialect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Standard => "standard",
+            Self::Strict => "strict",
+            Self::Relaxed => "relaxed",
+            Self::JsonCompat => "json",
+        })
+    }
+}
+
+impl Dialect {
+    /// `options` with the parsing rules of this dialect turned on
+    pub fn parse_options(self, options: ParseOptions) -> ParseOptions {
+        match self {
+            Self::Standard => options,
+            Self::Strict => options.forbid_trailing_commas(true),
+            Self::Relaxed => options.bare_strings(true).dotted_keys(true),
+            Self::JsonCompat => options
+                .forbid_comments(true)
+                .forbid_trailing_commas(true)
+                .require_quoted_keys(true),
+        }
+    }
+
+    /// `config` with the spelling rules of this dialect turned on
+    pub fn spell_config(self, mut config: SpellConfig) -> SpellConfig {
+        match self {
+            Self::Standard => {}
+            Self::Strict => {
+                config.trailing_commas = false;
+                config.normalize_numbers = true;
+            }
+            Self::Relaxed => config.trailing_commas = true,
+            Self::JsonCompat => {
+                config.trailing_commas = false;
+                config.normalize_numbers = true;
+                config.json_compat = true;
+                config.dotted_keys = false;
+                config.max_width = 0;
+            }
+        }
+        config
+    }
+}
+
+impl From<Dialect> for ParseOptions {
+    fn from(dialect: Dialect) -> Self {
+        dialect.parse_options(Self::new())
+    }
+}
+
+impl From<Dialect> for SpellConfig {
+    fn from(dialect: Dialect) -> Self {
+        dialect.spell_config(Self::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use crate::parser::parse_str_with;
+    use crate::{KeyT, MapT, Value};
+
+    #[test]
+    fn presets() {
+        let parse = |src: &str, dialect: Dialect| parse_str_with(src, &dialect.into());
+        let disallowed = |src: &str, dialect: Dialect| {
+            parse(src, dialect)
+                .map_err(|e| e.kind().clone())
+                .unwrap_err()
+        };
+        use crate::ErrorKind::Disallowed;
+        assert_eq!(
+            disallowed("[1, 2,]", Dialect::Strict),
+            Disallowed("trailing commas")
+        );
+        assert_eq!(
+            disallowed("{a: {b: 1,}}", Dialect::JsonCompat),
+            Disallowed("unquoted keys")
+        );
+        assert_eq!(
+            disallowed("{\"a\": [1,],}", Dialect::JsonCompat),
+            Disallowed("trailing commas")
+        );
+        assert_eq!(
+            disallowed("{\"a\": 1} // no", Dialect::JsonCompat),
+            Disallowed("comments")
+        );
+        let err = parse("{\"a\": \"//\"} // no", Dialect::JsonCompat).unwrap_err();
+        assert_eq!(err.span().map(|span| span.start), Some(12));
+        assert_eq!(parse("[1, 2] // yes", Dialect::Strict), parse_str("[1, 2]"));
+        assert_eq!(
+            parse("{a.b: on, c: r}", Dialect::Relaxed),
+            parse_str(r#"{a: {b: "on"}, c: "r"}"#)
+        );
+        assert_eq!(
+            parse("{\"a\": [1, null]}", Dialect::JsonCompat),
+            parse_str("{a: [1, None]}")
+        );
+
+        let raw = Value::Str {
+            s: r"\d".into(),
+            raw: true,
+        };
+        let value = Value::Obj(MapT::from([
+            (KeyT::from("a"), parse_str("[1_000, None]").unwrap()),
+            (KeyT::from("b"), raw),
+        ]));
+        assert_eq!(value.min_spell(), r#"{a:[1_000,None],b:r"\\d"}"#);
+        assert_eq!(
+            value.spell(Dialect::JsonCompat.into()).unwrap(),
+            "{\n    \"a\": [1000, null],\n    \"b\": \"\\\\d\"\n}"
+        );
+        assert_eq!("JSON".parse(), Ok(Dialect::JsonCompat));
+        assert!("lenient".parse::<Dialect>().is_err());
+    }
+}
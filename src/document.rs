@@ -0,0 +1,1364 @@
+//! Format preserving editing of gon source text.
+//!
+//! A [`Document`] keeps the original source next to the parsed [`Value`] and knows where
+//! every value lives in the text. [`Document::update`] applies a new value by rewriting only
+//! the parts of the text that actually changed, so comments, key order and hand-made
+//! formatting of everything else survive.
+//!
+//! [`Document::format`] pretty spells a document like [`Value::spell`], but honors
+//! directives in its comments, so hand-aligned tables survive formatting:
+//! - `// gon-fmt: off` keeps the following entries or elements of the same object or list
+//!   exactly as they are written, up to a `// gon-fmt: on` or the end of the object or list.
+//! - `// gon-fmt: inline` spells the following entry or element on a single line.
+//!
+//! Other comments belong to the entry or element they're written before, or after on the
+//! same line, and are kept with it, also when the keys are reordered.
+//! [`SpellConfig::comment_placement`], [`SpellConfig::wrap_comments`] and
+//! [`SpellConfig::align_comments`] tell where they go.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::{document::Document, parse_str};
+//! let mut doc = Document::parse("{\n    // units per second\n    speed: 1,\n    jump: 2,\n}").unwrap();
+//! doc.update(&parse_str("{speed: 1.5, jump: 2}").unwrap()).unwrap();
+//! assert_eq!(doc.as_str(), "{\n    // units per second\n    speed: 1.5,\n    jump: 2,\n}");
+//! ```
+
+use std::fmt;
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::parser::{SpannedToken, TokenKind, tokenize};
+use crate::path::untagged;
+use crate::pragma::{self, VersionError};
+use crate::span::Span;
+use crate::value::{apply_indent, spell_key, spelling_order};
+use crate::{CommentPlacement, GonError, SpellConfig, Value, parse_str};
+
+/// Gon source text together with its parsed value. See the [module docs](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+    src: String,
+    value: Value,
+    root: Node,
+}
+
+/// Something went wrong while editing a [`Document`]
+#[derive(Debug, Error, PartialEq)]
+pub enum DocumentError {
+    /// The source isn't valid gon
+    #[error(transparent)]
+    Parse(#[from] GonError),
+    /// The source uses syntax that can't be edited in place
+    #[error("unsupported syntax for format preserving editing at byte {0}")]
+    Unsupported(usize),
+    /// An edit range isn't within the source or doesn't start and end at characters
+    #[error("invalid edit range {0:?}")]
+    InvalidRange(Range<usize>),
+    /// There is no object or list to insert into at the path
+    #[error("nothing to insert '{0}' into")]
+    NotFound(crate::path::Path),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Node {
+    span: Range<usize>,
+    kind: NodeKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum NodeKind {
+    Scalar,
+    Obj(Vec<Entry>),
+    List(Vec<Node>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    key: String,
+    key_span: Range<usize>,
+    value: Node,
+    /// The span of the comma after the value, if there is one
+    comma: Option<Range<usize>>,
+}
+
+/// A `// gon-fmt: ...` comment, see the [module docs](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    Off,
+    On,
+    Inline,
+}
+
+/// How [`Document::format`] spells an entry or element
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Format,
+    Verbatim,
+    Inline,
+}
+
+/// A replacement of `range` in the source with `text`
+struct Edit {
+    range: Range<usize>,
+    text: String,
+}
+
+impl Document {
+    /// Parses the given source, remembering where every value is
+    pub fn parse(src: impl Into<String>) -> Result<Self, DocumentError> {
+        let src = src.into();
+        let value = parse_str(&src)?;
+        let tokens = lex(&src)?;
+        let mut parser = SpanParser {
+            src: &src,
+            tokens: &tokens,
+            pos: 0,
+        };
+        let root = parser.node()?;
+        if let Some(tok) = tokens.get(parser.pos) {
+            return Err(DocumentError::Unsupported(tok.span.start));
+        }
+        Ok(Self { src, value, root })
+    }
+
+    /// The current source text
+    pub fn as_str(&self) -> &str {
+        &self.src
+    }
+
+    /// The current value
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    /// The version of the format the document declares with its pragma or `@version`
+    /// entry, see the [`pragma` module](crate::pragma)
+    /// # Usage example
+    /// ```rust
+    /// use gon::document::Document;
+    /// assert_eq!(Document::parse("#gon 1\n{a: 1}").unwrap().version(), Ok(Some(1)));
+    /// assert_eq!(Document::parse("{\"@version\": 1}").unwrap().version(), Ok(Some(1)));
+    /// ```
+    pub fn version(&self) -> Result<Option<u32>, VersionError> {
+        pragma::declared_version(&self.src, &self.value)
+    }
+
+    /// Where the value at `path` is spelled in the source, `None` if there is nothing.
+    /// # Usage example
+    /// ```rust
+    /// use gon::document::Document;
+    /// let doc = Document::parse("{server: {port: 80}}").unwrap();
+    /// let span = doc.span(&"server.port".parse().unwrap()).unwrap();
+    /// assert_eq!(&doc.as_str()[span.range()], "80");
+    /// ```
+    pub fn span(&self, path: &crate::path::Path) -> Option<Span> {
+        Some(self.node(&path.0)?.span.clone().into())
+    }
+
+    fn node(&self, path: &[crate::path::Segment]) -> Option<&Node> {
+        use crate::path::Segment;
+        path.iter()
+            .try_fold(&self.root, |node, segment| match (&node.kind, segment) {
+                // the last of duplicate keys is the one that counts
+                (NodeKind::Obj(entries), Segment::Key(key)) => entries
+                    .iter()
+                    .rev()
+                    .find(|e| e.key == *key)
+                    .map(|e| &e.value),
+                (NodeKind::List(items), Segment::Index(i)) => items.get(*i),
+                _ => None,
+            })
+    }
+
+    /// Splices the gon text `fragment` into the source at `path`, keeping its comments and
+    /// formatting and all of the source around it. The last segment of `path` is a key of
+    /// an object, whose value is replaced or which is added after the other entries, or an
+    /// index of a list, where the fragment is inserted before the element that is there
+    /// now or appended if it's the length of the list. An empty path replaces the whole
+    /// value. Lines after the first of the fragment are indented like the line it's put on.
+    /// # Usage example
+    /// ```rust
+    /// use gon::document::Document;
+    /// let mut doc = Document::parse("{\n    // hand written\n    goblin: {hp: 7},\n}").unwrap();
+    /// doc.insert_fragment(&"orc".parse().unwrap(), "{\n    // generated\n    hp: 12,\n}").unwrap();
+    /// assert_eq!(
+    ///     doc.as_str(),
+    ///     "{\n    // hand written\n    goblin: {hp: 7},\n    orc: {\n        // generated\n        hp: 12,\n    },\n}"
+    /// );
+    /// ```
+    pub fn insert_fragment(
+        &mut self,
+        path: &crate::path::Path,
+        fragment: &str,
+    ) -> Result<(), DocumentError> {
+        use crate::path::Segment;
+        parse_str(fragment)?;
+        let fragment = fragment.trim();
+        // a line comment at the very end would swallow what comes after the fragment
+        let last_token = tokenize(fragment).pop();
+        if let Some(comment) = last_token
+            .filter(|t| t.kind == TokenKind::Comment && t.text(fragment).starts_with("//"))
+        {
+            return Err(DocumentError::Unsupported(comment.span.start));
+        }
+        let not_found = || DocumentError::NotFound(path.clone());
+        let Some((last, parent)) = path.0.split_last() else {
+            return self.edit(self.root.span.clone(), fragment);
+        };
+        let parent = self.node(parent).ok_or_else(not_found)?;
+        let src = &self.src;
+        let edit = match (&parent.kind, last) {
+            (NodeKind::Obj(entries), Segment::Key(key)) => {
+                match entries.iter().rev().find(|e| e.key == *key) {
+                    Some(entry) => Edit {
+                        range: entry.value.span.clone(),
+                        text: reindented(fragment, line_indent(src, entry.key_span.start)),
+                    },
+                    None if entries.is_empty() => {
+                        let entry = format!("{}: {fragment}", spell_key(key));
+                        insert_into_empty(src, parent, &entry)
+                    }
+                    None => {
+                        let indent = line_indent(src, entries[entries.len() - 1].key_span.start);
+                        let entry = (spell_key(key), reindented(fragment, indent));
+                        append_entries(src, parent, entries, &[entry])
+                    }
+                }
+            }
+            (NodeKind::List(items), Segment::Index(i)) if *i < items.len() => {
+                let at = items[*i].span.start;
+                let indent = line_indent(src, at);
+                let separator = match src[parent.span.clone()].contains('\n') {
+                    true => format!("\n{indent}"),
+                    false => " ".to_string(),
+                };
+                Edit {
+                    range: at..at,
+                    text: format!("{},{separator}", reindented(fragment, indent)),
+                }
+            }
+            (NodeKind::List(items), Segment::Index(i)) if *i == items.len() => match items.last() {
+                Some(last) => {
+                    let indent = line_indent(src, last.span.start);
+                    append_element(src, parent, last, &reindented(fragment, indent))
+                }
+                None => insert_into_empty(src, parent, fragment),
+            },
+            _ => return Err(not_found()),
+        };
+        self.edit(edit.range, &edit.text)
+    }
+
+    /// Rewrites the source so that it spells `new`, touching only the parts that changed.
+    /// Changed scalars are replaced in place, removed keys are cut out (together with their
+    /// line if they were on their own), added keys are appended to their object in its
+    /// existing style and everything else is respelled at its current indentation.
+    pub fn update(&mut self, new: &Value) -> Result<(), DocumentError> {
+        let mut edits = Vec::new();
+        diff(&self.src, &self.root, &self.value, new, &mut edits);
+        if edits.is_empty() {
+            return Ok(());
+        }
+        edits.sort_by_key(|e| std::cmp::Reverse(e.range.start));
+        let mut src = self.src.clone();
+        for edit in edits {
+            src.replace_range(edit.range, &edit.text);
+        }
+        *self = Self::parse(src)?;
+        Ok(())
+    }
+
+    /// Replaces `range` of the source with `new_text`. Only the innermost value the edit
+    /// is inside of is lexed and parsed again, the spans of everything after it are moved.
+    /// If that value doesn't parse on its own anymore, the values around it are tried and
+    /// finally the whole source. Nothing changes if the new source isn't valid gon.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{document::Document, parse_str};
+    /// let mut doc = Document::parse("{speed: 1, names: [\"a\"]}").unwrap();
+    /// doc.edit(8..9, "2.5").unwrap();
+    /// assert_eq!(doc.as_str(), "{speed: 2.5, names: [\"a\"]}");
+    /// assert_eq!(doc.value(), &parse_str("{speed: 2.5, names: [\"a\"]}").unwrap());
+    /// let name = doc.span(&"names[0]".parse().unwrap()).unwrap();
+    /// assert_eq!(&doc.as_str()[name.range()], "\"a\"");
+    /// ```
+    pub fn edit(&mut self, range: Range<usize>, new_text: &str) -> Result<(), DocumentError> {
+        if range.start > range.end
+            || !self.src.is_char_boundary(range.start)
+            || !self.src.is_char_boundary(range.end)
+        {
+            return Err(DocumentError::InvalidRange(range));
+        }
+        let mut src = self.src.clone();
+        src.replace_range(range.clone(), new_text);
+        let delta = new_text.len() as isize - range.len() as isize;
+        for (address, path, span) in self.root.enclosing(&range).into_iter().rev() {
+            let new_span = span.start..span.end.saturating_add_signed(delta);
+            let Some((node, value)) = reparse(&src, new_span) else {
+                continue;
+            };
+            self.root.shift(span.end, delta);
+            *self.root.at_mut(&address) = node;
+            if let Some(old) = crate::path::get_mut(&mut self.value, &path) {
+                *old = value;
+            }
+            self.src = src;
+            return Ok(());
+        }
+        *self = Self::parse(src)?;
+        Ok(())
+    }
+
+    /// Pretty spells the value of the document like [`Value::spell`], honoring the
+    /// `gon-fmt` directives in its comments, see the [module docs](self). The directives
+    /// are kept, so formatting the result again doesn't change it, and so is the pragma.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{document::Document, SpellConfig};
+    /// let src = "{\n  // gon-fmt: off\n  m: [1, 0,\n      0, 1], n: [1,\n2]\n}";
+    /// let formatted = Document::parse(src).unwrap().format(SpellConfig::default()).unwrap();
+    /// assert_eq!(
+    ///     formatted,
+    ///     "{\n    // gon-fmt: off\n    m: [1, 0,\n      0, 1],\n    n: [1,\n2]\n}"
+    /// );
+    /// ```
+    pub fn format(&self, config: SpellConfig) -> Result<String, fmt::Error> {
+        self.format_value(&self.value, config)
+    }
+
+    /// Like [`Document::format`], but spells `value`, usually the value of the document
+    /// with its keys reordered. Parts of it the source doesn't have are spelled normally.
+    pub fn format_value(&self, value: &Value, config: SpellConfig) -> Result<String, fmt::Error> {
+        let pragma = pragma::pragma(&self.src);
+        let mut directives = Vec::new();
+        let mut comments = Vec::new();
+        for token in tokenize(&self.src) {
+            if token.kind != TokenKind::Comment || pragma.is_some_and(|p| p.span == token.span) {
+                continue;
+            }
+            match Directive::parse(token.text(&self.src)) {
+                Some(directive) => directives.push((token.span.start, directive)),
+                None => comments.push(token.span.range()),
+            }
+        }
+        let mut formatter = Formatter {
+            src: &self.src,
+            directives,
+            comments,
+            config,
+            buf: String::new(),
+        };
+        if let Some(p) = pragma {
+            formatter.buf = format!("{}\n", &self.src[p.span.range()]);
+        }
+        let root = self.root.span.clone();
+        let (before, after): (Vec<_>, Vec<_>) = formatter
+            .comments
+            .iter()
+            .filter(|c| !root.contains(&c.start))
+            .cloned()
+            .partition(|c| c.start < root.start);
+        formatter.comment_lines(&before, 0);
+        let mode = formatter.modes(0, [root.clone()])[0];
+        formatter.directive_comments(Mode::Format, mode, 0);
+        formatter.item(&self.root, value, mode, 0)?;
+        for (i, comment) in after.iter().enumerate() {
+            if i == 0 && !self.src[root.end..comment.start].contains('\n') {
+                formatter.buf.push(' ');
+                formatter.buf.push_str(self.src[comment.clone()].trim_end());
+            } else {
+                formatter.buf.push('\n');
+                formatter.comment_lines(std::slice::from_ref(comment), 0);
+                formatter.buf.pop();
+            }
+        }
+        Ok(formatter.buf)
+    }
+}
+
+impl Node {
+    /// Whether an edit of `range` is inside of this value, which for objects and lists
+    /// means between their brackets
+    fn encloses(&self, range: &Range<usize>) -> bool {
+        match self.kind {
+            NodeKind::Scalar => self.span.start <= range.start && range.end <= self.span.end,
+            _ => self.span.start < range.start && range.end < self.span.end,
+        }
+    }
+
+    /// The values an edit of `range` is inside of, outermost first, with how to get to them
+    /// through the nodes and where they are in the value
+    fn enclosing(
+        &self,
+        range: &Range<usize>,
+    ) -> Vec<(Vec<usize>, crate::path::Path, Range<usize>)> {
+        use crate::path::{Path, Segment};
+        let mut enclosing = Vec::new();
+        if !self.encloses(range) {
+            return enclosing;
+        }
+        let (mut node, mut address, mut path) = (self, Vec::new(), Path::default());
+        loop {
+            enclosing.push((address.clone(), path.clone(), node.span.clone()));
+            let child = match &node.kind {
+                NodeKind::Obj(entries) => entries
+                    .iter()
+                    .enumerate()
+                    .find(|(i, e)| {
+                        // the last of duplicate keys is the one that counts
+                        e.value.encloses(range) && !entries[i + 1..].iter().any(|o| o.key == e.key)
+                    })
+                    .map(|(i, e)| (i, &e.value, Segment::Key(e.key.clone()))),
+                NodeKind::List(items) => items
+                    .iter()
+                    .enumerate()
+                    .find(|(_, n)| n.encloses(range))
+                    .map(|(i, n)| (i, n, Segment::Index(i))),
+                NodeKind::Scalar => None,
+            };
+            let Some((i, child, segment)) = child else {
+                return enclosing;
+            };
+            address.push(i);
+            path.0.push(segment);
+            node = child;
+        }
+    }
+
+    /// The node reached by taking the entries or elements at the indices of `address`
+    fn at_mut(&mut self, address: &[usize]) -> &mut Node {
+        address.iter().fold(self, |node, &i| match &mut node.kind {
+            NodeKind::Obj(entries) => &mut entries[i].value,
+            NodeKind::List(items) => &mut items[i],
+            NodeKind::Scalar => unreachable!("addresses only lead through objects and lists"),
+        })
+    }
+
+    /// Moves every position at or after `from` by `delta` bytes
+    fn shift(&mut self, from: usize, delta: isize) {
+        if self.span.end < from {
+            return;
+        }
+        let shift = |pos: &mut usize| {
+            if *pos >= from {
+                *pos = pos.saturating_add_signed(delta);
+            }
+        };
+        shift(&mut self.span.start);
+        shift(&mut self.span.end);
+        match &mut self.kind {
+            NodeKind::Scalar => {}
+            NodeKind::Obj(entries) => {
+                for entry in entries {
+                    shift(&mut entry.key_span.start);
+                    shift(&mut entry.key_span.end);
+                    entry.value.shift(from, delta);
+                    if let Some(comma) = &mut entry.comma {
+                        shift(&mut comma.start);
+                        shift(&mut comma.end);
+                    }
+                }
+            }
+            NodeKind::List(items) => {
+                for item in items {
+                    item.shift(from, delta);
+                }
+            }
+        }
+    }
+}
+
+/// Parses the value at `span` of `src` on its own, `None` if it isn't valid gon there or
+/// its tokens would run into the ones around it
+fn reparse(src: &str, span: Range<usize>) -> Option<(Node, Value)> {
+    let text = src.get(span.clone())?;
+    let word = |c: char| c.is_alphanumeric() || "_.-\"".contains(c);
+    let before = src[..span.start].chars().next_back();
+    let after = src[span.end..].chars().next();
+    if before
+        .zip(text.chars().next())
+        .is_some_and(|(a, b)| word(a) && word(b))
+        || text
+            .chars()
+            .next_back()
+            .zip(after)
+            .is_some_and(|(a, b)| word(a) && word(b))
+    {
+        return None;
+    }
+    // a line comment at the very end would swallow what comes after the value
+    let last = tokenize(text).pop()?;
+    if last.kind == TokenKind::Comment && last.text(text).starts_with("//") {
+        return None;
+    }
+    let value = parse_str(text).ok()?;
+    let tokens = lex(text).ok()?;
+    let mut parser = SpanParser {
+        src: text,
+        tokens: &tokens,
+        pos: 0,
+    };
+    let mut node = parser.node().ok()?;
+    if parser.pos != tokens.len() {
+        return None;
+    }
+    node.shift(0, span.start as isize);
+    Some((node, value))
+}
+
+impl Directive {
+    fn parse(comment: &str) -> Option<Self> {
+        let body = comment.strip_prefix("//")?.trim();
+        match body.strip_prefix("gon-fmt:")?.trim() {
+            "off" => Some(Self::Off),
+            "on" => Some(Self::On),
+            "inline" => Some(Self::Inline),
+            _ => None,
+        }
+    }
+}
+
+struct Formatter<'a> {
+    src: &'a str,
+    /// Where the directives are in `src`
+    directives: Vec<(usize, Directive)>,
+    /// Where the other comments are in `src`
+    comments: Vec<Range<usize>>,
+    config: SpellConfig,
+    buf: String,
+}
+
+/// The comments inside of an object or list, by the entry or element they belong to
+struct Attached {
+    /// On the lines before each item
+    leading: Vec<Vec<Range<usize>>>,
+    /// After each item on the line it ends on
+    trailing: Vec<Vec<Range<usize>>>,
+    /// After the last item
+    dangling: Vec<Range<usize>>,
+}
+
+/// Trailing comments of consecutive lines to line up, see [`SpellConfig::align_comments`]
+#[derive(Default)]
+struct Aligned {
+    /// Where each comment goes and how wide the line before it is, by block of lines
+    runs: Vec<Vec<(usize, usize)>>,
+    /// Where the line after the last recorded one starts
+    end: usize,
+}
+
+impl Formatter<'_> {
+    /// How to spell the items at `spans` of a container that starts at `start`, by the
+    /// directives before each of them
+    fn modes(&self, start: usize, spans: impl IntoIterator<Item = Range<usize>>) -> Vec<Mode> {
+        let mut off = false;
+        let mut from = start;
+        let mut modes = Vec::new();
+        for span in spans {
+            let mut inline = false;
+            for (pos, directive) in &self.directives {
+                if (from..span.start).contains(pos) {
+                    match directive {
+                        Directive::Off => off = true,
+                        Directive::On => off = false,
+                        Directive::Inline => inline = true,
+                    }
+                }
+            }
+            modes.push(match (off, inline) {
+                (true, _) => Mode::Verbatim,
+                (false, true) => Mode::Inline,
+                (false, false) => Mode::Format,
+            });
+            from = span.end;
+        }
+        modes
+    }
+
+    /// The comments directly inside of the container `node`, attached to the `items` of
+    /// their spans and the spans of their values
+    fn attach(&self, node: &Node, items: &[(Range<usize>, Range<usize>)]) -> Attached {
+        let mut attached = Attached {
+            leading: vec![Vec::new(); items.len()],
+            trailing: vec![Vec::new(); items.len()],
+            dangling: Vec::new(),
+        };
+        for comment in &self.comments {
+            if !node.span.contains(&comment.start)
+                || items
+                    .iter()
+                    .any(|(_, value)| value.contains(&comment.start))
+            {
+                continue;
+            }
+            let prev = items
+                .iter()
+                .rposition(|(item, _)| item.end <= comment.start);
+            match prev {
+                Some(i) if !self.src[items[i].0.end..comment.start].contains('\n') => {
+                    attached.trailing[i].push(comment.clone());
+                }
+                _ => match items.iter().position(|(item, _)| item.end > comment.start) {
+                    Some(i) => attached.leading[i].push(comment.clone()),
+                    None => attached.dangling.push(comment.clone()),
+                },
+            }
+        }
+        attached
+    }
+
+    /// Writes `comments` on lines of their own
+    fn comment_lines(&mut self, comments: &[Range<usize>], indent: usize) {
+        for comment in comments {
+            let text = self.src[comment.clone()].trim_end().to_string();
+            for line in self.wrapped(&text, indent) {
+                apply_indent(&mut self.buf, indent, &self.config);
+                self.buf.push_str(&line);
+                self.buf.push('\n');
+            }
+        }
+    }
+
+    /// `comment` broken into lines that fit into the max width after `indent`, if it's a
+    /// line comment that doesn't and [`SpellConfig::wrap_comments`] is on
+    #[cfg(feature = "wrap")]
+    fn wrapped(&self, comment: &str, indent: usize) -> Vec<String> {
+        let max_width = self.config.max_width;
+        match comment.strip_prefix("//") {
+            Some(body)
+                if self.config.wrap_comments
+                    && max_width != 0
+                    && indent + comment.chars().count() > max_width =>
+            {
+                let width = max_width.saturating_sub(indent + 3).max(1);
+                let options = textwrap::Options::new(width).break_words(false);
+                textwrap::wrap(body.trim(), options)
+                    .iter()
+                    .map(|line| format!("// {line}"))
+                    .collect()
+            }
+            _ => vec![comment.to_string()],
+        }
+    }
+
+    #[cfg(not(feature = "wrap"))]
+    fn wrapped(&self, comment: &str, _indent: usize) -> Vec<String> {
+        vec![comment.to_string()]
+    }
+
+    /// Writes an entry or element with `spell` and its comments, on lines of its own
+    fn line(
+        &mut self,
+        leading: &[Range<usize>],
+        trailing: &[Range<usize>],
+        indent: usize,
+        comma: bool,
+        aligned: &mut Aligned,
+        spell: impl FnOnce(&mut Self) -> fmt::Result,
+    ) -> fmt::Result {
+        let (mut leading, mut trailing) = (leading.to_vec(), trailing.to_vec());
+        if self.config.comment_placement == CommentPlacement::Above {
+            leading.append(&mut trailing);
+        }
+        let start = self.buf.len();
+        apply_indent(&mut self.buf, indent, &self.config);
+        spell(self)?;
+        if comma {
+            self.buf.push(',');
+        }
+        let item = self.buf.split_off(start);
+        let single_line = !item.contains('\n');
+        if self.config.comment_placement == CommentPlacement::SameLine
+            && trailing.is_empty()
+            && single_line
+            && leading.len() == 1
+            && !self.src[leading[0].clone()].contains('\n')
+        {
+            trailing = std::mem::take(&mut leading);
+        }
+        let comment = trailing
+            .iter()
+            .map(|c| self.src[c.clone()].trim_end())
+            .collect::<Vec<_>>()
+            .join(" ");
+        let last_line = item.rsplit('\n').next().unwrap_or_default().chars().count();
+        let max_width = self.config.max_width;
+        if self.config.wrap_comments
+            && max_width != 0
+            && !trailing.is_empty()
+            && last_line + 1 + comment.chars().count() > max_width
+        {
+            leading.append(&mut trailing);
+        }
+        self.comment_lines(&leading, indent);
+        let line_start = self.buf.len();
+        self.buf.push_str(&item);
+        if !trailing.is_empty() {
+            if self.config.align_comments && single_line {
+                let at = (self.buf.len(), last_line);
+                match aligned.runs.last_mut() {
+                    Some(run) if aligned.end == line_start => run.push(at),
+                    _ => aligned.runs.push(vec![at]),
+                }
+            }
+            self.buf.push(' ');
+            self.buf.push_str(&comment);
+            self.buf.push('\n');
+            aligned.end = self.buf.len();
+        } else {
+            self.buf.push('\n');
+        }
+        Ok(())
+    }
+
+    /// Pads the lines of each run of `aligned` so their comments start in the same column
+    fn align(&mut self, aligned: Aligned) {
+        let mut pads: Vec<_> = aligned
+            .runs
+            .iter()
+            .filter(|run| run.len() > 1)
+            .flat_map(|run| {
+                let widest = run.iter().map(|(_, width)| *width).max().unwrap_or(0);
+                run.iter().map(move |(at, width)| (*at, widest - width))
+            })
+            .collect();
+        pads.sort_unstable_by_key(|(at, _)| std::cmp::Reverse(*at));
+        for (at, pad) in pads {
+            self.buf.insert_str(at, &" ".repeat(pad));
+        }
+    }
+
+    /// Writes the directives that switch from spelling an item with `prev` to `mode`
+    fn directive_comments(&mut self, prev: Mode, mode: Mode, indent: usize) {
+        let mut comment = |text: &str| {
+            apply_indent(&mut self.buf, indent, &self.config);
+            self.buf.push_str(text);
+            self.buf.push('\n');
+        };
+        if prev == Mode::Verbatim && mode != Mode::Verbatim {
+            comment("// gon-fmt: on");
+        }
+        match mode {
+            Mode::Verbatim if prev != Mode::Verbatim => comment("// gon-fmt: off"),
+            Mode::Inline => comment("// gon-fmt: inline"),
+            _ => {}
+        }
+    }
+
+    fn item(&mut self, node: &Node, value: &Value, mode: Mode, indent: usize) -> fmt::Result {
+        match mode {
+            Mode::Verbatim => self.buf.push_str(&self.src[node.span.clone()]),
+            Mode::Inline => spell_inline(&mut self.buf, value),
+            Mode::Format => self.node(node, value, indent)?,
+        }
+        Ok(())
+    }
+
+    /// Spells `value` like [`Value::spell`] unless there are directives or comments inside
+    /// of `node`
+    fn node(&mut self, node: &Node, value: &Value, indent: usize) -> fmt::Result {
+        use std::fmt::Write;
+        let has_directives = self
+            .directives
+            .iter()
+            .any(|(pos, _)| node.span.contains(pos));
+        let has_comments = self.comments.iter().any(|c| node.span.contains(&c.start));
+        let inner = indent + self.config.indent_amount;
+        match (&node.kind, untagged(value)) {
+            (NodeKind::Obj(entries), Value::Obj(obj))
+                if (has_directives && !obj.is_empty()) || has_comments =>
+            {
+                let spans: Vec<_> = entries
+                    .iter()
+                    .map(|e| (e.key_span.start..e.value.span.end, e.value.span.clone()))
+                    .collect();
+                let modes = self.modes(node.span.start, spans.iter().map(|s| s.0.clone()));
+                let attached = self.attach(node, &spans);
+                let mut aligned = Aligned::default();
+                writeln!(self.buf, "{{")?;
+                let mut prev = Mode::Format;
+                for (i, (key, v)) in spelling_order(obj).enumerate() {
+                    // the last of duplicate keys is the one that counts
+                    let entry = entries
+                        .iter()
+                        .enumerate()
+                        .zip(&modes)
+                        .rev()
+                        .find(|((_, e), _)| e.key == **key);
+                    let mode = entry.map_or(Mode::Format, |(_, mode)| *mode);
+                    self.directive_comments(prev, mode, inner);
+                    let (leading, trailing) = match entry {
+                        Some(((j, _), _)) => (&attached.leading[j][..], &attached.trailing[j][..]),
+                        None => (&[][..], &[][..]),
+                    };
+                    let comma = self.config.trailing_commas || i != obj.len() - 1;
+                    self.line(leading, trailing, inner, comma, &mut aligned, |f| {
+                        write!(f.buf, "{}: ", spell_key(key))?;
+                        match entry {
+                            Some(((_, entry), _)) => f.item(&entry.value, v, mode, inner),
+                            None => v.spell0(&mut f.buf, inner, &f.config),
+                        }
+                    })?;
+                    prev = mode;
+                }
+                self.comment_lines(&attached.dangling, inner);
+                self.align(aligned);
+                apply_indent(&mut self.buf, indent, &self.config);
+                write!(self.buf, "}}")
+            }
+            (NodeKind::List(items), Value::List(xs))
+                if (has_directives && !xs.is_empty()) || has_comments =>
+            {
+                let spans: Vec<_> = items
+                    .iter()
+                    .map(|n| (n.span.clone(), n.span.clone()))
+                    .collect();
+                let modes = self.modes(node.span.start, items.iter().map(|n| n.span.clone()));
+                let attached = self.attach(node, &spans);
+                let mut aligned = Aligned::default();
+                writeln!(self.buf, "[")?;
+                let mut prev = Mode::Format;
+                for (i, x) in xs.iter().enumerate() {
+                    let mode = modes.get(i).copied().unwrap_or(Mode::Format);
+                    self.directive_comments(prev, mode, inner);
+                    let (leading, trailing) = match items.get(i) {
+                        Some(_) => (&attached.leading[i][..], &attached.trailing[i][..]),
+                        None => (&[][..], &[][..]),
+                    };
+                    let comma = self.config.trailing_commas || i != xs.len() - 1;
+                    self.line(
+                        leading,
+                        trailing,
+                        inner,
+                        comma,
+                        &mut aligned,
+                        |f| match items.get(i) {
+                            Some(item) => f.item(item, x, mode, inner),
+                            None => x.spell0(&mut f.buf, inner, &f.config),
+                        },
+                    )?;
+                    prev = mode;
+                }
+                self.comment_lines(&attached.dangling, inner);
+                self.align(aligned);
+                apply_indent(&mut self.buf, indent, &self.config);
+                write!(self.buf, "]")
+            }
+            _ => value.spell0(&mut self.buf, indent, &self.config),
+        }
+    }
+}
+
+/// Spells `value` on a single line, with spaces after colons and commas
+fn spell_inline(buf: &mut String, value: &Value) {
+    match untagged(value) {
+        Value::Obj(obj) => {
+            buf.push('{');
+            for (i, (key, v)) in spelling_order(obj).enumerate() {
+                if i != 0 {
+                    buf.push_str(", ");
+                }
+                buf.push_str(&spell_key(key));
+                buf.push_str(": ");
+                spell_inline(buf, v);
+            }
+            buf.push('}');
+        }
+        Value::List(xs) => {
+            buf.push('[');
+            for (i, x) in xs.iter().enumerate() {
+                if i != 0 {
+                    buf.push_str(", ");
+                }
+                spell_inline(buf, x);
+            }
+            buf.push(']');
+        }
+        scalar => buf.push_str(&scalar.min_spell()),
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.src)
+    }
+}
+
+fn diff(src: &str, node: &Node, old: &Value, new: &Value, edits: &mut Vec<Edit>) {
+    if old == new {
+        return;
+    }
+    match (&node.kind, old, new) {
+        (NodeKind::Obj(entries), Value::Obj(old_obj), Value::Obj(new_obj))
+            if !entries.is_empty() =>
+        {
+            for (i, entry) in entries.iter().enumerate() {
+                let shadowed = entries[i + 1..].iter().any(|e| e.key == entry.key);
+                match new_obj.get(entry.key.as_str()) {
+                    Some(new_value) if !shadowed => diff(
+                        src,
+                        &entry.value,
+                        &old_obj[entry.key.as_str()],
+                        new_value,
+                        edits,
+                    ),
+                    _ => edits.push(Edit {
+                        range: entry_removal_range(src, entry),
+                        text: String::new(),
+                    }),
+                }
+            }
+            let added: Vec<_> = new_obj
+                .iter()
+                .filter(|(k, _)| !old_obj.contains_key(*k))
+                .collect();
+            if !added.is_empty() {
+                edits.push(insert_entries(src, node, entries, &added));
+            }
+        }
+        (NodeKind::List(items), Value::List(old_xs), Value::List(new_xs))
+            if old_xs.len() == new_xs.len() =>
+        {
+            for ((item, old), new) in items.iter().zip(old_xs).zip(new_xs) {
+                diff(src, item, old, new, edits);
+            }
+        }
+        _ => edits.push(Edit {
+            range: node.span.clone(),
+            text: spell_indented(new, line_indent(src, node.span.start)),
+        }),
+    }
+}
+
+/// The range to remove to delete `entry`. If the entry is alone on its line, the whole line goes.
+fn entry_removal_range(src: &str, entry: &Entry) -> Range<usize> {
+    let mut start = entry.key_span.start;
+    let mut end = entry.comma.as_ref().unwrap_or(&entry.value.span).end;
+    let before = src[..start].trim_end_matches([' ', '\t']);
+    let after = src[end..].trim_start_matches([' ', '\t']);
+    if (before.is_empty() || before.ends_with('\n')) && after.starts_with('\n') {
+        start = before.len();
+        end = src.len() - after.len() + 1;
+    }
+    start..end
+}
+
+/// Appends `added` to the (non-empty) object `node` in the style of its existing entries
+fn insert_entries(
+    src: &str,
+    node: &Node,
+    entries: &[Entry],
+    added: &[(&crate::KeyT, &Value)],
+) -> Edit {
+    let last = entries
+        .last()
+        .expect("insert_entries needs existing entries");
+    let indent = line_indent(src, last.key_span.start);
+    let added: Vec<_> = added
+        .iter()
+        .map(|(key, value)| (spell_key(key), spell_indented(value, indent)))
+        .collect();
+    append_entries(src, node, entries, &added)
+}
+
+/// Appends entries of spelled keys and values to the (non-empty) object `node` in the
+/// style of its existing entries
+fn append_entries(src: &str, node: &Node, entries: &[Entry], added: &[(String, String)]) -> Edit {
+    let last = entries
+        .last()
+        .expect("append_entries needs existing entries");
+    let multiline = src[node.span.clone()].contains('\n');
+    let trailing_comma = last.comma.is_some();
+    let any_comma = entries.iter().any(|e| e.comma.is_some());
+    let indent = line_indent(src, last.key_span.start);
+    let mut text = String::new();
+    for (key, value) in added {
+        if any_comma && !trailing_comma {
+            text.push(',');
+        }
+        if multiline {
+            text.push('\n');
+            text.push_str(indent);
+        } else {
+            text.push(' ');
+        }
+        text.push_str(&format!("{key}: {value}"));
+        if trailing_comma {
+            text.push(',');
+        }
+    }
+    let at = last.comma.as_ref().unwrap_or(&last.value.span).end;
+    Edit {
+        range: at..at,
+        text,
+    }
+}
+
+/// Appends the spelled element `text` to the list `node` after its `last` element, in
+/// the style of the list
+fn append_element(src: &str, node: &Node, last: &Node, text: &str) -> Edit {
+    let rest = &src[last.span.end..node.span.end];
+    let comma = rest
+        .trim_start()
+        .starts_with(',')
+        .then(|| last.span.end + rest.len() - rest.trim_start().len() + 1);
+    let separator = match src[node.span.clone()].contains('\n') {
+        true => format!("\n{}", line_indent(src, last.span.start)),
+        false => " ".to_string(),
+    };
+    match comma {
+        Some(at) => Edit {
+            range: at..at,
+            text: format!("{separator}{text},"),
+        },
+        None => Edit {
+            range: last.span.end..last.span.end,
+            text: format!(",{separator}{text}"),
+        },
+    }
+}
+
+/// Puts the spelled entry or element `text` into the empty object or list `node`, on a
+/// line of its own if either spans several lines
+fn insert_into_empty(src: &str, node: &Node, text: &str) -> Edit {
+    let at = node.span.start + 1;
+    let indent = line_indent(src, node.span.start);
+    let inner = format!("{indent}    ");
+    let multiline = src[node.span.clone()].contains('\n');
+    let text = if multiline {
+        format!("\n{inner}{}", reindented(text, &inner))
+    } else if text.contains('\n') {
+        format!("\n{inner}{}\n{indent}", reindented(text, &inner))
+    } else {
+        text.to_string()
+    };
+    Edit {
+        range: at..at,
+        text,
+    }
+}
+
+/// `text` with every line but the first indented by `indent`
+fn reindented(text: &str, indent: &str) -> String {
+    text.replace('\n', &format!("\n{indent}"))
+}
+
+/// Spells `value` so that it can be put at a position whose line is indented with `indent`
+fn spell_indented(value: &Value, indent: &str) -> String {
+    let config = SpellConfig {
+        max_width: 0,
+        ..Default::default()
+    };
+    let spelling = value.spell(config).unwrap_or_else(|_| value.min_spell());
+    spelling.replace('\n', &format!("\n{indent}"))
+}
+
+/// The leading whitespace of the line that contains `pos`
+fn line_indent(src: &str, pos: usize) -> &str {
+    let line_start = src[..pos].rfind('\n').map_or(0, |i| i + 1);
+    let line = &src[line_start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// The tokens of `src` without comments
+fn lex(src: &str) -> Result<Vec<SpannedToken>, DocumentError> {
+    let mut tokens = tokenize(src);
+    tokens.retain(|t| t.kind != TokenKind::Comment);
+    match tokens.iter().find(|t| t.kind == TokenKind::Unterminated) {
+        Some(tok) => Err(DocumentError::Unsupported(tok.span.start)),
+        None => Ok(tokens),
+    }
+}
+
+/// Rebuilds the structure of a document from its tokens. The source has already been
+/// checked by the real parser, so this only needs to handle valid input.
+struct SpanParser<'a> {
+    src: &'a str,
+    tokens: &'a [SpannedToken],
+    pos: usize,
+}
+
+impl SpanParser<'_> {
+    fn next(&mut self) -> Result<&SpannedToken, DocumentError> {
+        let tok = self
+            .tokens
+            .get(self.pos)
+            .ok_or(DocumentError::Unsupported(self.src.len()))?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    /// Skips `+ "..."` continuations of a string ending at `end`, returns where they end
+    fn continuations_end(&mut self, mut end: usize) -> usize {
+        while let [plus, s, ..] = &self.tokens[self.pos.min(self.tokens.len())..] {
+            if plus.kind != TokenKind::Plus || !matches!(s.kind, TokenKind::Str | TokenKind::RawStr)
+            {
+                break;
+            }
+            end = s.span.end();
+            self.pos += 2;
+        }
+        end
+    }
+
+    fn peek_kind(&self) -> Option<TokenKind> {
+        self.tokens.get(self.pos).map(|t| t.kind)
+    }
+
+    fn node(&mut self) -> Result<Node, DocumentError> {
+        let first = *self.next()?;
+        let start = first.span.start;
+        match first.kind {
+            TokenKind::Str | TokenKind::RawStr => Ok(Node {
+                span: start..self.continuations_end(first.span.end()),
+                kind: NodeKind::Scalar,
+            }),
+            TokenKind::Num | TokenKind::Keyword | TokenKind::Sym => Ok(Node {
+                span: first.span.range(),
+                kind: NodeKind::Scalar,
+            }),
+            TokenKind::Dash if self.peek_kind() == Some(TokenKind::Num) => {
+                let end = self.next()?.span.end();
+                Ok(Node {
+                    span: start..end,
+                    kind: NodeKind::Scalar,
+                })
+            }
+            TokenKind::LBrace => {
+                let mut entries = Vec::new();
+                while self.peek_kind() != Some(TokenKind::RBrace) {
+                    let key_tok = *self.next()?;
+                    let key = match key_tok.kind {
+                        TokenKind::Str => match parse_str(&self.src[key_tok.span.range()])? {
+                            Value::Str { s, .. } => s,
+                            _ => return Err(DocumentError::Unsupported(key_tok.span.start)),
+                        },
+                        TokenKind::Sym | TokenKind::Keyword | TokenKind::Num => {
+                            self.src[key_tok.span.range()].to_string()
+                        }
+                        _ => return Err(DocumentError::Unsupported(key_tok.span.start)),
+                    };
+                    if self.next()?.kind != TokenKind::Colon {
+                        return Err(DocumentError::Unsupported(key_tok.span.end()));
+                    }
+                    let value = self.node()?;
+                    let comma = self.comma();
+                    entries.push(Entry {
+                        key,
+                        key_span: key_tok.span.range(),
+                        value,
+                        comma,
+                    });
+                }
+                let end = self.next()?.span.end();
+                Ok(Node {
+                    span: start..end,
+                    kind: NodeKind::Obj(entries),
+                })
+            }
+            TokenKind::LBrack => {
+                let mut items = Vec::new();
+                while self.peek_kind() != Some(TokenKind::RBrack) {
+                    items.push(self.node()?);
+                    self.comma();
+                }
+                let end = self.next()?.span.end();
+                Ok(Node {
+                    span: start..end,
+                    kind: NodeKind::List(items),
+                })
+            }
+            _ => Err(DocumentError::Unsupported(start)),
+        }
+    }
+
+    fn comma(&mut self) -> Option<Range<usize>> {
+        if self.peek_kind() == Some(TokenKind::Comma) {
+            self.pos += 1;
+            Some(self.tokens[self.pos - 1].span.range())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn updated(src: &str, new: &str) -> String {
+        let mut doc = Document::parse(src).unwrap();
+        doc.update(&parse_str(new).unwrap()).unwrap();
+        assert_eq!(doc.value(), &parse_str(new).unwrap());
+        doc.to_string()
+    }
+
+    #[test]
+    fn replaces_changed_scalars_only() {
+        let src = "{\n  // tuning\n  speed:   1, // fast\n  names: [\"a\",   \"b\"]\n}";
+        assert_eq!(
+            updated(src, "{speed: -2, names: [\"a\", \"c\"]}"),
+            "{\n  // tuning\n  speed:   -2, // fast\n  names: [\"a\",   \"c\"]\n}"
+        );
+    }
+
+    #[test]
+    fn removes_and_adds_keys_in_style() {
+        let src = "{\n    a: 1,\n    b: 2,\n    c: 3,\n}";
+        assert_eq!(
+            updated(src, "{a: 1, c: 3, d: {x: true}}"),
+            "{\n    a: 1,\n    c: 3,\n    d: {\n        x: true\n    },\n}"
+        );
+        assert_eq!(
+            updated("{a: 1, b: 2}", "{a: 1, b: 2, c: 3}"),
+            "{a: 1, b: 2, c: 3}"
+        );
+        assert_eq!(updated("{a: 1 b: 2}", "{a: 1}"), "{a: 1 }");
+    }
+
+    #[test]
+    fn respells_lists_that_changed_length() {
+        assert_eq!(
+            updated("{\n  xs: [1, 2]\n}", "{xs: [1, 2, 3]}"),
+            "{\n  xs: [1, 2, 3]\n}"
+        );
+    }
+
+    #[test]
+    fn format_honors_directives() {
+        let src = "{\n// gon-fmt: inline\na: {x: 1,\n y: [2]},\n// gon-fmt: off\nb: [1,0,\n 0,1],\n\
+                   // gon-fmt: on\nc: [\n[1]]}";
+        let formatted = Document::parse(src)
+            .unwrap()
+            .format(SpellConfig::default())
+            .unwrap();
+        assert_eq!(
+            formatted,
+            "{\n    // gon-fmt: inline\n    a: {x: 1, y: [2]},\n    // gon-fmt: off\n    \
+             b: [1,0,\n 0,1],\n    // gon-fmt: on\n    c: [\n        [1]\n    ]\n}"
+        );
+        let again = Document::parse(formatted.as_str()).unwrap();
+        assert_eq!(again.format(SpellConfig::default()).unwrap(), formatted);
+    }
+
+    #[test]
+    fn format_keeps_comments() {
+        let src = "// enemies\n{\n  bat: [1, // one\n 2],\n  // the weakest\n  slime: {hp: 5}, // green\n\
+                   // nothing left\n} // end";
+        let doc = Document::parse(src).unwrap();
+        assert_eq!(
+            doc.format(SpellConfig::default()).unwrap(),
+            "// enemies\n{\n    bat: [\n        1, // one\n        2\n    ],\n    // the weakest\n    \
+             slime: {\n        hp: 5\n    } // green\n    // nothing left\n} // end"
+        );
+        let above = SpellConfig {
+            comment_placement: CommentPlacement::Above,
+            ..Default::default()
+        };
+        assert_eq!(
+            doc.format(above).unwrap(),
+            "// enemies\n{\n    bat: [\n        // one\n        1,\n        2\n    ],\n    \
+             // the weakest\n    // green\n    slime: {\n        hp: 5\n    }\n    \
+             // nothing left\n} // end"
+        );
+
+        let profile = parse_str(r#"{align_comments: true, comment_placement: "same_line"}"#);
+        let config = SpellConfig::from_value(&profile.unwrap()).unwrap();
+        let doc = Document::parse("{\n  a: 1, // one\n  bb: 22, // two\n  // three\n  ccc: 3\n}");
+        assert_eq!(
+            doc.unwrap().format(config).unwrap(),
+            "{\n    a: 1,   // one\n    bb: 22, // two\n    ccc: 3  // three\n}"
+        );
+
+        let wrap = SpellConfig {
+            max_width: 20,
+            wrap_comments: true,
+            ..Default::default()
+        };
+        let doc = Document::parse("{\n  a: 1, // a comment that is far too long\n}").unwrap();
+        let wrapped = if cfg!(feature = "wrap") {
+            "{\n    // a comment\n    // that is far\n    // too long\n    a: 1\n}"
+        } else {
+            "{\n    // a comment that is far too long\n    a: 1\n}"
+        };
+        assert_eq!(doc.format(wrap).unwrap(), wrapped);
+    }
+
+    #[test]
+    fn inserts_fragments() {
+        let insert = |src: &str, path: &str, fragment: &str| {
+            let mut doc = Document::parse(src).unwrap();
+            doc.insert_fragment(&path.parse().unwrap(), fragment)
+                .map(|_| doc.as_str().to_string())
+        };
+        let src = "{\n  drops: [\n    \"gold\", // always\n    \"gem\"\n  ],\n  hp: 5\n}";
+        assert_eq!(
+            insert(src, "drops[2]", "{id: 1} // rare").unwrap_err(),
+            DocumentError::Unsupported(8)
+        );
+        assert_eq!(
+            insert(src, "drops[2]", "{\n  id: 1, // rare\n}").unwrap(),
+            "{\n  drops: [\n    \"gold\", // always\n    \"gem\",\n    {\n      id: 1, // rare\n    }\n  ],\n  hp: 5\n}"
+        );
+        assert_eq!(
+            insert(src, "drops[0]", "\"bone\"").unwrap(),
+            "{\n  drops: [\n    \"bone\",\n    \"gold\", // always\n    \"gem\"\n  ],\n  hp: 5\n}"
+        );
+        assert_eq!(
+            insert(src, "hp", "[5, 6]").unwrap(),
+            "{\n  drops: [\n    \"gold\", // always\n    \"gem\"\n  ],\n  hp: [5, 6]\n}"
+        );
+        assert_eq!(insert("[1, 2,]", "[2]", "3").unwrap(), "[1, 2, 3,]");
+        assert_eq!(insert("{a: {}}", "a.b", "1").unwrap(), "{a: {b: 1}}");
+        assert_eq!(
+            insert("{a: []}", "a[0]", "{\n  b: 1\n}").unwrap(),
+            "{a: [\n    {\n      b: 1\n    }\n]}"
+        );
+        assert_eq!(
+            insert("{a: 1}", "b.c", "1").unwrap_err(),
+            DocumentError::NotFound("b.c".parse().unwrap())
+        );
+        assert_eq!(
+            insert("[1]", "[3]", "1").unwrap_err(),
+            DocumentError::NotFound("[3]".parse().unwrap())
+        );
+        assert!(matches!(
+            insert("[1]", "[1]", "{").unwrap_err(),
+            DocumentError::Parse(_)
+        ));
+    }
+
+    #[test]
+    fn edits_match_full_reparses() {
+        let src = "{\n  a: [1, {b: \"x\"}], // list\n  c: 2,\n  c: 3\n}";
+        for (range, text) in [
+            (8..9, "10"),
+            (15..18, "\"y\" + \"z\""),
+            (9..19, ""),
+            (4..5, "key"),
+            (43..44, "[\n    true,\n  ]"),
+            (35..36, "4"),
+        ] {
+            let mut doc = Document::parse(src).unwrap();
+            doc.edit(range.clone(), text).unwrap();
+            let mut expected = src.to_string();
+            expected.replace_range(range, text);
+            assert_eq!(doc, Document::parse(expected).unwrap());
+        }
+        let mut doc = Document::parse(src).unwrap();
+        assert!(doc.edit(8..9, "1 // x").is_err());
+        assert!(doc.edit(2..99, "").is_err());
+        assert_eq!(doc, Document::parse(src).unwrap());
+    }
+
+    #[test]
+    fn keeps_string_continuations() {
+        let src = "{\n  motd: \"hello \" +\n    \"world\",\n  n: 1\n}";
+        assert_eq!(
+            updated(src, "{motd: \"hello world\", n: 2}"),
+            "{\n  motd: \"hello \" +\n    \"world\",\n  n: 2\n}"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! Decoding gon source from bytes in other encodings than UTF-8.
+//!
+//! A byte order mark at the start of the input decides the encoding, input without one is
+//! decoded with a fallback encoding. UTF-8 always works, UTF-16 and Latin-1 need the
+//! `encoding` feature. See [`parse_reader`](crate::parser::parse_reader).
+
+use std::fmt;
+use std::io;
+
+use thiserror::Error;
+
+use crate::GonError;
+
+/// How the bytes of a source are turned into characters
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO 8859-1, every byte is the char with the same number. Never detected, only used
+    /// as a fallback.
+    Latin1,
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Latin1 => "Latin-1",
+        })
+    }
+}
+
+/// Something went wrong reading a source
+#[derive(Debug, Error)]
+pub enum ReadError {
+    #[error("couldn't read the input: {0}")]
+    Io(#[from] io::Error),
+    /// The input isn't valid in its encoding, `at` is the offset of the first bad byte
+    #[error("invalid {encoding} at byte {at}")]
+    Decode { encoding: Encoding, at: usize },
+    /// The input is in an encoding that needs the `encoding` feature
+    #[error("{0} input needs the encoding feature of gon")]
+    Unsupported(Encoding),
+    #[error("{0}")]
+    Parse(#[from] GonError),
+}
+
+/// The encoding announced by the byte order mark at the start of `bytes` and the length of
+/// the mark
+pub fn detect_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+        [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+        [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+        _ => None,
+    }
+}
+
+/// Decodes `bytes` in the encoding of their byte order mark, or in `fallback` if they
+/// don't start with one. The mark isn't part of the result.
+/// # Usage example
+/// ```rust
+/// use gon::encoding::{decode, Encoding};
+/// assert_eq!(decode(b"\xEF\xBB\xBF{a: 1}", Encoding::Utf8).unwrap(), "{a: 1}");
+/// ```
+pub fn decode(bytes: &[u8], fallback: Encoding) -> Result<String, ReadError> {
+    let (encoding, bom_len) = detect_bom(bytes).unwrap_or((fallback, 0));
+    let body = &bytes[bom_len..];
+    let decode_err = |at: usize| ReadError::Decode {
+        encoding,
+        at: bom_len + at,
+    };
+    match encoding {
+        Encoding::Utf8 => match std::str::from_utf8(body) {
+            Ok(s) => Ok(s.to_string()),
+            Err(e) => Err(decode_err(e.valid_up_to())),
+        },
+        #[cfg(feature = "encoding")]
+        Encoding::Utf16Le | Encoding::Utf16Be => {
+            let units = body.chunks(2).map(|pair| match (pair, encoding) {
+                ([a, b], Encoding::Utf16Le) => Ok(u16::from_le_bytes([*a, *b])),
+                ([a, b], _) => Ok(u16::from_be_bytes([*a, *b])),
+                _ => Err(()),
+            });
+            let mut out = String::with_capacity(body.len() / 2);
+            let mut at = 0;
+            for c in char::decode_utf16(units.map_while(Result::ok)) {
+                let c = c.map_err(|_| decode_err(at))?;
+                at += c.len_utf16() * 2;
+                out.push(c);
+            }
+            if body.len() % 2 == 1 {
+                return Err(decode_err(body.len() - 1));
+            }
+            Ok(out)
+        }
+        #[cfg(feature = "encoding")]
+        Encoding::Latin1 => Ok(body.iter().map(|&b| b as char).collect()),
+        #[cfg(not(feature = "encoding"))]
+        unsupported => Err(ReadError::Unsupported(unsupported)),
+    }
+}
+
+#[cfg(all(test, feature = "encoding"))]
+mod tests {
+    use super::*;
+
+    fn utf16(bom: &[u8], s: &str, le: bool) -> Vec<u8> {
+        let mut bytes = bom.to_vec();
+        for unit in s.encode_utf16() {
+            bytes.extend(if le {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            });
+        }
+        bytes
+    }
+
+    #[test]
+    fn decodes_by_bom_or_fallback() {
+        let s = "{name: \"Größe 𝄞\"}";
+        assert_eq!(
+            decode(&utf16(&[0xFF, 0xFE], s, true), Encoding::Utf8).unwrap(),
+            s
+        );
+        assert_eq!(
+            decode(&utf16(&[0xFE, 0xFF], s, false), Encoding::Latin1).unwrap(),
+            s
+        );
+        assert_eq!(decode(&utf16(&[], s, false), Encoding::Utf16Be).unwrap(), s);
+        assert_eq!(
+            decode(b"{a: \"\xE4\"}", Encoding::Latin1).unwrap(),
+            "{a: \"ä\"}"
+        );
+    }
+
+    #[test]
+    fn reports_byte_positions() {
+        let at = |bytes: &[u8], fallback| match decode(bytes, fallback) {
+            Err(ReadError::Decode { at, .. }) => at,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(at(b"\xEF\xBB\xBF{a\xFF}", Encoding::Utf8), 5);
+        // an unpaired surrogate after "a"
+        assert_eq!(
+            at(&[0xFF, 0xFE, b'a', 0, 0x00, 0xD8, b'b', 0], Encoding::Utf8),
+            4
+        );
+        assert_eq!(at(&[0xFF, 0xFE, b'a', 0, b'b'], Encoding::Utf8), 4);
+    }
+}
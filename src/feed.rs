@@ -0,0 +1,408 @@
+//! Parsing a stream of values as its bytes arrive.
+//!
+//! A [`FeedParser`] is fed chunks of a stream, like the reads of a TCP socket, and hands
+//! out every top-level value once it's complete, without waiting for the stream to end.
+//! Only the bytes of the value that's still coming in are kept. Values follow each other
+//! with or without whitespace and comments between them, like `{id: 1} {id: 2}\n[3]`.
+//!
+//! Objects and lists are complete at their closing bracket and numbers and other bare
+//! scalars at the whitespace or bracket after them. A string is only complete once the
+//! next value starts or the stream is [finished](FeedParser::finish), because it may
+//! still go on with `+`. Values can't be preceded by `let` bindings.
+//!
+//! A value that doesn't parse is reported and the values after it are still read, as long
+//! as its brackets are balanced.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::feed::FeedParser;
+//! let mut parser = FeedParser::new();
+//! parser.feed(b"{id: 1, pos: [0, ");
+//! assert!(parser.poll().is_none());
+//! parser.feed(b"5]}\n{id: 2");
+//! let value = parser.poll().unwrap().unwrap();
+//! assert_eq!(value, gon::parse_str("{id: 1, pos: [0, 5]}").unwrap());
+//! assert!(parser.poll().is_none());
+//! parser.feed(b"}");
+//! assert_eq!(parser.poll().unwrap().unwrap(), gon::parse_str("{id: 2}").unwrap());
+//! parser.finish();
+//! assert!(parser.poll().is_none());
+//! ```
+
+use std::collections::VecDeque;
+
+use thiserror::Error;
+
+use crate::parser::{ParseOptions, parse_str_with};
+use crate::{GonError, Value};
+
+/// Why a value of a stream couldn't be read, see [`FeedParser::poll`]
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum FeedError {
+    /// The value doesn't parse, the location is within the value
+    #[error("{0}")]
+    Parse(#[from] GonError),
+    /// The value isn't valid UTF-8, `at` is the offset of the first bad byte in the stream
+    #[error("invalid UTF-8 at byte {at} of the stream")]
+    Utf8 { at: usize },
+    /// The value is longer than [`FeedParser::max_len`], its bytes were skipped
+    #[error("a value is longer than {limit} bytes")]
+    TooLong { limit: usize },
+    /// The stream was finished within a value
+    #[error("the stream ended within a value")]
+    Incomplete,
+}
+
+/// Where in the syntax the last byte fed was
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lex {
+    /// Between tokens
+    Space,
+    /// Within a bare scalar, `r` if it's just an `r` yet, which may start a raw string
+    Scalar {
+        r: bool,
+    },
+    Str,
+    /// After a backslash in a string
+    Escape,
+    RawStr,
+    /// After a `/`, which may start a comment
+    Slash,
+    LineComment,
+    /// Within a block comment, `star` after a `*`
+    BlockComment {
+        star: bool,
+    },
+}
+
+/// Splits a stream into top-level values and parses them, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct FeedParser {
+    options: ParseOptions,
+    max_len: Option<usize>,
+    /// The bytes of the value that's coming in, and the whitespace and comments before it
+    buf: Vec<u8>,
+    /// How many bytes of the stream came before `buf`
+    offset: usize,
+    lex: Lex,
+    /// The closing brackets of the open objects, lists and parentheses, innermost last
+    closers: Vec<u8>,
+    /// Whether `buf` has anything but whitespace and comments
+    started: bool,
+    /// Where a top-level string ended in `buf`, if it isn't known yet whether it goes on
+    /// with `+`
+    string_end: Option<usize>,
+    /// Whether the value that's coming in is too long and its bytes are dropped
+    skipping: bool,
+    values: VecDeque<Result<Value, FeedError>>,
+}
+
+impl Default for FeedParser {
+    fn default() -> Self {
+        Self {
+            options: ParseOptions::default(),
+            max_len: None,
+            buf: Vec::new(),
+            offset: 0,
+            lex: Lex::Space,
+            closers: Vec::new(),
+            started: false,
+            string_end: None,
+            skipping: false,
+            values: VecDeque::new(),
+        }
+    }
+}
+
+impl FeedParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parses every value with `options`, like [`parse_str_with`]
+    pub fn options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Fails values longer than `max_len` bytes with [`FeedError::TooLong`] instead of
+    /// buffering them, so a peer can't make the parser hold arbitrarily much memory.
+    /// Unlimited by default.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self
+    }
+
+    /// Reads the next chunk of the stream. Chunks may end anywhere, even within a string
+    /// or a UTF-8 sequence.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.push(b);
+        }
+    }
+
+    /// The next complete value, or its error, in the order of the stream. `None` until
+    /// more of the stream is fed.
+    pub fn poll(&mut self) -> Option<Result<Value, FeedError>> {
+        self.values.pop_front()
+    }
+
+    /// Tells the parser that the stream ended, so a string or scalar at its end is complete.
+    /// Fails the value that's coming in, if there is one, with [`FeedError::Incomplete`].
+    /// The parser can be fed a new stream afterwards.
+    pub fn finish(&mut self) {
+        if matches!(self.lex, Lex::Scalar { .. } | Lex::Slash) && self.closers.is_empty() {
+            self.lex = Lex::Space;
+            self.emit(self.buf.len());
+        }
+        if let Some(end) = self.string_end {
+            self.emit(end);
+        }
+        let unfinished = !matches!(self.lex, Lex::Space | Lex::LineComment);
+        if self.started || unfinished {
+            self.values.push_back(Err(FeedError::Incomplete));
+        }
+        self.offset += self.buf.len();
+        self.buf.clear();
+        self.lex = Lex::Space;
+        self.closers.clear();
+        self.started = false;
+        self.skipping = false;
+    }
+
+    fn push(&mut self, b: u8) {
+        match self.lex {
+            Lex::Str => {
+                self.lex = match b {
+                    b'\\' => Lex::Escape,
+                    b'"' => Lex::Space,
+                    _ => Lex::Str,
+                };
+                self.keep(b);
+                if b == b'"' {
+                    self.string_ended();
+                }
+            }
+            Lex::Escape => {
+                self.lex = Lex::Str;
+                self.keep(b);
+            }
+            Lex::RawStr => {
+                self.keep(b);
+                if b == b'"' {
+                    self.lex = Lex::Space;
+                    self.string_ended();
+                }
+            }
+            Lex::LineComment => {
+                if b == b'\n' {
+                    self.lex = Lex::Space;
+                }
+                self.keep(b);
+            }
+            Lex::BlockComment { star } => {
+                self.lex = match b {
+                    b'/' if star => Lex::Space,
+                    b'*' => Lex::BlockComment { star: true },
+                    _ => Lex::BlockComment { star: false },
+                };
+                self.keep(b);
+            }
+            Lex::Slash => match b {
+                b'/' => {
+                    self.lex = Lex::LineComment;
+                    self.keep(b);
+                }
+                b'*' => {
+                    self.lex = Lex::BlockComment { star: false };
+                    self.keep(b);
+                }
+                // the slash was no comment, the parser reports it
+                _ => {
+                    if let Some(end) = self.string_end.take() {
+                        self.emit(end);
+                    }
+                    self.started = true;
+                    self.lex = Lex::Scalar { r: false };
+                    self.push(b);
+                }
+            },
+            Lex::Scalar { r } => {
+                if b == b'"' && r {
+                    self.lex = Lex::RawStr;
+                    self.keep(b);
+                } else if is_delimiter(b) {
+                    self.lex = Lex::Space;
+                    if self.closers.is_empty() {
+                        self.emit(self.buf.len());
+                    }
+                    self.push(b);
+                } else {
+                    self.lex = Lex::Scalar { r: false };
+                    self.keep(b);
+                }
+            }
+            Lex::Space => self.push_token(b),
+        }
+    }
+
+    /// Reads `b` between tokens
+    fn push_token(&mut self, b: u8) {
+        if b.is_ascii_whitespace() {
+            return self.keep(b);
+        }
+        if b == b'/' {
+            self.lex = Lex::Slash;
+            return self.keep(b);
+        }
+        if let Some(end) = self.string_end.take() {
+            if b == b'+' {
+                return self.keep(b);
+            }
+            self.emit(end);
+        }
+        self.started = true;
+        match b {
+            b'{' | b'[' | b'(' => {
+                self.closers.push(closer(b));
+                self.keep(b);
+            }
+            b'}' | b']' | b')' => {
+                self.keep(b);
+                // a bracket that closes nothing ends the value, the parser reports it
+                if self.closers.pop() != Some(b) {
+                    self.closers.clear();
+                }
+                if self.closers.is_empty() {
+                    self.emit(self.buf.len());
+                }
+            }
+            b'"' => {
+                self.lex = Lex::Str;
+                self.keep(b);
+            }
+            _ => {
+                self.lex = Lex::Scalar { r: b == b'r' };
+                self.keep(b);
+            }
+        }
+    }
+
+    fn string_ended(&mut self) {
+        if self.closers.is_empty() {
+            self.string_end = Some(self.buf.len());
+        }
+    }
+
+    /// Buffers `b` unless the value is too long
+    fn keep(&mut self, b: u8) {
+        if self.skipping {
+            self.offset += 1;
+            return;
+        }
+        self.buf.push(b);
+        let Some(limit) = self.max_len else {
+            return;
+        };
+        if self.buf.len() <= limit {
+            return;
+        }
+        if let Some(end) = self.string_end.take() {
+            // nothing but whitespace and comments after a complete string
+            self.emit(end);
+        } else {
+            self.offset += self.buf.len();
+            self.buf.clear();
+            self.skipping = true;
+        }
+    }
+
+    /// Parses the first `end` bytes of the buffer as a value, unless they're skipped
+    fn emit(&mut self, end: usize) {
+        let value = if self.skipping {
+            Err(FeedError::TooLong {
+                limit: self.max_len.unwrap_or_default(),
+            })
+        } else {
+            match std::str::from_utf8(&self.buf[..end]) {
+                Ok(src) => parse_str_with(src, &self.options).map_err(FeedError::from),
+                Err(e) => Err(FeedError::Utf8 {
+                    at: self.offset + e.valid_up_to(),
+                }),
+            }
+        };
+        self.values.push_back(value);
+        self.buf.drain(..end);
+        self.offset += end;
+        self.started = false;
+        self.string_end = None;
+        self.skipping = false;
+    }
+}
+
+fn closer(opener: u8) -> u8 {
+    match opener {
+        b'{' => b'}',
+        b'[' => b']',
+        _ => b')',
+    }
+}
+
+/// Whether `b` ends a bare scalar
+fn is_delimiter(b: u8) -> bool {
+    b.is_ascii_whitespace() || b"{}[](),:;\"/".contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn values(parser: &mut FeedParser) -> Vec<Result<Value, FeedError>> {
+        std::iter::from_fn(|| parser.poll()).collect()
+    }
+
+    #[test]
+    fn splits_streams_into_values() {
+        let src = "{a: 1} // one\n[\"]\", 2]// two\n3 r\"x\" \"y\" + \"z\"\n\"w\"";
+        let expected: Vec<_> = ["{a: 1}", r#"["]", 2]"#, "3", r#"r"x""#, r#""yz""#, r#""w""#]
+            .into_iter()
+            .map(|src| Ok(parse_str(src).unwrap()))
+            .collect();
+        // every way of cutting the stream into chunks reads the same values
+        for at in 0..src.len() {
+            let mut parser = FeedParser::new();
+            parser.feed(&src.as_bytes()[..at]);
+            parser.feed(&src.as_bytes()[at..]);
+            parser.finish();
+            assert_eq!(values(&mut parser), expected, "{at}");
+        }
+        let mut parser = FeedParser::new();
+        parser.feed(src.as_bytes());
+        assert_eq!(values(&mut parser), expected[..5]);
+    }
+
+    #[test]
+    fn reports_bad_values() {
+        let mut parser = FeedParser::new().max_len(8);
+        parser.feed(b"{a 1} [1, 2, 3, 4, 5] \"\xFF\" {a: 1} [");
+        parser.finish();
+        let errors: Vec<_> = values(&mut parser)
+            .into_iter()
+            .map(|v| v.map_err(|e| e.to_string()))
+            .collect();
+        assert!(
+            matches!(&errors[0], Err(e) if e.contains("missing colon")),
+            "{errors:?}"
+        );
+        assert_eq!(
+            errors[1..],
+            [
+                Err("a value is longer than 8 bytes".into()),
+                Err("invalid UTF-8 at byte 23 of the stream".into()),
+                Ok(parse_str("{a: 1}").unwrap()),
+                Err("the stream ended within a value".into()),
+            ]
+        );
+    }
+}
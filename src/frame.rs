@@ -0,0 +1,217 @@
+//! A simple wire protocol: values as length-prefixed messages.
+//!
+//! Every message is the length of its body as a big-endian `u32`, followed by the body,
+//! the value [minified](crate::Value::min_spell) as UTF-8. Both ends only need the
+//! [`Value`] model and no framing of their own, and a reader knows how much to read before
+//! it parses anything. With the `async` feature there are variants of the functions for
+//! tokio's `AsyncRead` and `AsyncWrite`.
+//!
+//! Writing a message takes one `write_all` of a buffer, so wrap `out` in a
+//! [`BufWriter`](std::io::BufWriter) only when writing many small messages in a row.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::frame::{read_frame, write_frame};
+//! let moved = gon::parse_str(r#"{op: "move", to: [3, 4]}"#).unwrap();
+//! let quit = gon::parse_str(r#"{op: "quit"}"#).unwrap();
+//! let mut wire = Vec::new();
+//! write_frame(&mut wire, &moved).unwrap();
+//! write_frame(&mut wire, &quit).unwrap();
+//! assert_eq!(&wire[..24], b"\0\0\0\x14{op:\"move\",to:[3,4]}");
+//! let mut input = &wire[..];
+//! assert_eq!(read_frame(&mut input).unwrap(), Some(moved));
+//! assert_eq!(read_frame(&mut input).unwrap(), Some(quit));
+//! // the end of the stream
+//! assert_eq!(read_frame(&mut input).unwrap(), None);
+//! ```
+
+use std::io::{self, Read, Write};
+
+use thiserror::Error;
+
+use crate::{GonError, Value, parse_str};
+
+/// The most bytes a message may have for [`read_frame`] and [`read_frame_async`], 64 MiB
+pub const MAX_LEN: usize = 64 << 20;
+
+/// Why a message couldn't be read
+#[derive(Debug, Error)]
+pub enum FrameError {
+    #[error("couldn't read the message: {0}")]
+    Io(#[from] io::Error),
+    /// The length prefix exceeds the most the reader accepts. The body wasn't read, so
+    /// the stream can't be read further.
+    #[error("the message is {len} bytes long, at most {max_len} are allowed")]
+    TooLong { len: usize, max_len: usize },
+    #[error("the message isn't valid UTF-8: {0}")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("{0}")]
+    Parse(#[from] GonError),
+}
+
+/// The body of a message with `value`, and its length prefix
+fn encode(value: &Value) -> io::Result<Vec<u8>> {
+    let body = value.min_spell();
+    let Ok(len) = u32::try_from(body.len()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("a message can't be {} bytes long", body.len()),
+        ));
+    };
+    let mut message = Vec::with_capacity(4 + body.len());
+    message.extend(len.to_be_bytes());
+    message.extend(body.as_bytes());
+    Ok(message)
+}
+
+fn decode(body: &[u8]) -> Result<Value, FrameError> {
+    Ok(parse_str(std::str::from_utf8(body)?)?)
+}
+
+fn check_len(prefix: [u8; 4], max_len: usize) -> Result<usize, FrameError> {
+    let len = u32::from_be_bytes(prefix) as usize;
+    if len > max_len {
+        return Err(FrameError::TooLong { len, max_len });
+    }
+    Ok(len)
+}
+
+/// Fails if the stream ended before all `len` bytes of the body were read
+fn check_body(body: &[u8], len: usize) -> io::Result<()> {
+    if body.len() < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+/// Writes `value` as one message, see the [module docs](self). Fails with
+/// [`io::ErrorKind::InvalidInput`] if it's 4 GiB or longer minified.
+pub fn write_frame<W: Write>(mut out: W, value: &Value) -> io::Result<()> {
+    out.write_all(&encode(value)?)?;
+    out.flush()
+}
+
+/// Reads the next message, or `None` if the stream ended before it. A stream that ends
+/// within a message fails with [`io::ErrorKind::UnexpectedEof`], a message longer than
+/// [`MAX_LEN`] with [`FrameError::TooLong`].
+pub fn read_frame<R: Read>(input: R) -> Result<Option<Value>, FrameError> {
+    read_frame_limited(input, MAX_LEN)
+}
+
+/// Like [`read_frame`], but fails with [`FrameError::TooLong`] instead of reading a message
+/// longer than `max_len` bytes, so a peer can't make the reader allocate arbitrarily much
+/// memory. The body is read as it arrives, so a length prefix alone doesn't allocate
+/// `max_len` bytes either.
+pub fn read_frame_limited<R: Read>(
+    mut input: R,
+    max_len: usize,
+) -> Result<Option<Value>, FrameError> {
+    let mut prefix = [0; 4];
+    let mut filled = 0;
+    while filled < prefix.len() {
+        match input.read(&mut prefix[filled..]) {
+            Ok(0) if filled == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let len = check_len(prefix, max_len)?;
+    let mut body = Vec::new();
+    input.take(len as u64).read_to_end(&mut body)?;
+    check_body(&body, len)?;
+    decode(&body).map(Some)
+}
+
+/// Like [`write_frame`], for tokio's `AsyncWrite`
+#[cfg(feature = "async")]
+pub async fn write_frame_async<W>(mut out: W, value: &Value) -> io::Result<()>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncWriteExt;
+    out.write_all(&encode(value)?).await?;
+    out.flush().await
+}
+
+/// Like [`read_frame`], for tokio's `AsyncRead`
+#[cfg(feature = "async")]
+pub async fn read_frame_async<R>(input: R) -> Result<Option<Value>, FrameError>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    read_frame_limited_async(input, MAX_LEN).await
+}
+
+/// Like [`read_frame_limited`], for tokio's `AsyncRead`
+#[cfg(feature = "async")]
+pub async fn read_frame_limited_async<R>(
+    mut input: R,
+    max_len: usize,
+) -> Result<Option<Value>, FrameError>
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    use tokio::io::AsyncReadExt;
+    let mut prefix = [0; 4];
+    let mut filled = 0;
+    while filled < prefix.len() {
+        match input.read(&mut prefix[filled..]).await? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            n => filled += n,
+        }
+    }
+    let len = check_len(prefix, max_len)?;
+    let mut body = Vec::new();
+    input.take(len as u64).read_to_end(&mut body).await?;
+    check_body(&body, len)?;
+    decode(&body).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_what_it_writes() {
+        let values = ["{a: [1, \"x y\"], b: None}", "\"\"", "[]"].map(|s| parse_str(s).unwrap());
+        let mut wire = Vec::new();
+        for value in &values {
+            write_frame(&mut wire, value).unwrap();
+        }
+        let mut input = &wire[..];
+        for value in &values {
+            assert_eq!(read_frame(&mut input).unwrap().as_ref(), Some(value));
+        }
+        assert!(read_frame(&mut input).unwrap().is_none());
+
+        let err = read_frame(&wire[..6]).unwrap_err();
+        assert!(matches!(err, FrameError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+        let err = read_frame(&wire[..2]).unwrap_err();
+        assert!(matches!(err, FrameError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+        let err = read_frame(&[0xFF, 0xFF, 0xFF, 0xFF, b'{'][..]).unwrap_err();
+        assert!(matches!(
+            err,
+            FrameError::TooLong {
+                max_len: MAX_LEN,
+                ..
+            }
+        ));
+        let err = read_frame_limited(&[0xFF, 0xFF, 0xFF, 0xFF, b'{'][..], usize::MAX).unwrap_err();
+        assert!(matches!(err, FrameError::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof));
+        assert!(matches!(
+            read_frame_limited(&wire[..], 10),
+            Err(FrameError::TooLong { max_len: 10, .. })
+        ));
+        assert!(matches!(
+            read_frame(&[0, 0, 0, 2, b'{', b'a'][..]),
+            Err(FrameError::Parse(_))
+        ));
+        assert!(matches!(
+            read_frame(&[0, 0, 0, 1, 0xFF][..]),
+            Err(FrameError::Utf8(_))
+        ));
+    }
+}
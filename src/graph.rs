@@ -0,0 +1,150 @@
+//! Drawing the structure of gon documents as diagrams.
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::Value;
+use crate::path::untagged;
+use crate::value::spelling_order;
+
+/// Which diagram language [`render`] writes
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GraphFormat {
+    /// Graphviz' DOT language
+    #[default]
+    Dot,
+    /// A mermaid flowchart
+    Mermaid,
+}
+
+/// A [`GraphFormat`] was spelled wrong
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("unknown graph format '{0}'\n\tExpected one of: dot, mermaid")]
+pub struct UnknownGraphFormat(pub String);
+
+impl FromStr for GraphFormat {
+    type Err = UnknownGraphFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dot" => Ok(Self::Dot),
+            "mermaid" => Ok(Self::Mermaid),
+            _ => Err(UnknownGraphFormat(s.to_string())),
+        }
+    }
+}
+
+/// An object or list in the diagram
+struct Node {
+    /// The key or index the node is stored under
+    name: String,
+    /// The keys of the scalar entries of an object
+    scalars: Vec<String>,
+}
+
+/// Renders which objects and lists contain which others. Every object and list becomes a
+/// node named after its key that also lists the keys of its scalar entries, edges point
+/// from containers to what they contain.
+/// # Usage example
+/// ```rust
+/// use gon::{graph::{render, GraphFormat}, parse_str};
+/// let value = parse_str("{server: {host: \"a\", port: 80}}").unwrap();
+/// assert_eq!(
+///     render(&value, GraphFormat::Mermaid),
+///     "flowchart LR\n    n0[\"root\"]\n    n1[\"server<br/>host, port\"]\n    n0 --> n1\n",
+/// );
+/// ```
+pub fn render(value: &Value, format: GraphFormat) -> String {
+    let mut nodes = Vec::new();
+    let mut edges = Vec::new();
+    collect(value, "root".to_string(), &mut nodes, &mut edges);
+    let mut out = String::new();
+    match format {
+        GraphFormat::Dot => {
+            out.push_str("digraph {\n    node [shape=box];\n");
+            for (i, node) in nodes.iter().enumerate() {
+                let label = label(node, "\\n", |s| {
+                    s.replace('\\', "\\\\").replace('"', "\\\"")
+                });
+                out.push_str(&format!("    n{i} [label=\"{label}\"];\n"));
+            }
+            for (from, to) in edges {
+                out.push_str(&format!("    n{from} -> n{to};\n"));
+            }
+            out.push_str("}\n");
+        }
+        GraphFormat::Mermaid => {
+            out.push_str("flowchart LR\n");
+            for (i, node) in nodes.iter().enumerate() {
+                let label = label(node, "<br/>", |s| s.replace('"', "#quot;"));
+                out.push_str(&format!("    n{i}[\"{label}\"]\n"));
+            }
+            for (from, to) in edges {
+                out.push_str(&format!("    n{from} --> n{to}\n"));
+            }
+        }
+    }
+    out
+}
+
+/// The name of `node` and the keys of its scalars below, both escaped with `escape`
+fn label(node: &Node, line_break: &str, escape: fn(&str) -> String) -> String {
+    let mut label = escape(&node.name);
+    if !node.scalars.is_empty() {
+        label.push_str(line_break);
+        label.push_str(&escape(&node.scalars.join(", ")));
+    }
+    label
+}
+
+/// Adds `value` and everything it contains as nodes
+fn collect(value: &Value, name: String, nodes: &mut Vec<Node>, edges: &mut Vec<(usize, usize)>) {
+    let id = nodes.len();
+    nodes.push(Node {
+        name,
+        scalars: Vec::new(),
+    });
+    match untagged(value) {
+        Value::Obj(obj) => {
+            for (key, v) in spelling_order(obj) {
+                if is_container(v) {
+                    edges.push((id, nodes.len()));
+                    collect(v, key.to_string(), nodes, edges);
+                } else {
+                    nodes[id].scalars.push(key.to_string());
+                }
+            }
+        }
+        Value::List(xs) => {
+            for (i, x) in xs.iter().enumerate().filter(|(_, x)| is_container(x)) {
+                edges.push((id, nodes.len()));
+                collect(x, format!("[{i}]"), nodes, edges);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_container(value: &Value) -> bool {
+    matches!(untagged(value), Value::Obj(_) | Value::List(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn dot_nodes_and_edges() {
+        let value = parse_str(r#"{"a\"b": {x: 1}, xs: [1, {y: 2}], z: None}"#).unwrap();
+        assert_eq!(
+            render(&value, GraphFormat::Dot),
+            "digraph {\n    node [shape=box];\n    n0 [label=\"root\\nz\"];\n    \
+             n1 [label=\"a\\\"b\\nx\"];\n    n2 [label=\"xs\"];\n    n3 [label=\"[1]\\ny\"];\n    \
+             n0 -> n1;\n    n0 -> n2;\n    n2 -> n3;\n}\n"
+        );
+        assert_eq!("Mermaid".parse(), Ok(GraphFormat::Mermaid));
+        assert!("svg".parse::<GraphFormat>().is_err());
+    }
+}
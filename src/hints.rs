@@ -0,0 +1,503 @@
+//! Suggestions for fixing gon source that doesn't parse.
+//!
+//! The parser stops at the first problem and reports where it noticed it, which often isn't
+//! where the mistake is. [`hints`] looks at the tokens of the whole source instead and
+//! guesses what was meant: a missing `:`, an unquoted string, a bracket that closes the
+//! wrong thing. It keeps going after mistakes it knows how to get past, like unquoted
+//! strings, and stops at the first one it doesn't.
+//! # Usage example
+//! ```rust
+//! use gon::hints::hints;
+//! let src = "{\n    name: slime,\n    hp 10,\n    tags: [\"a\"}\n}";
+//! let found: Vec<_> = hints(src).into_iter().map(|h| h.message).collect();
+//! assert_eq!(found, [
+//!     "strings have to be quoted: \"slime\"",
+//!     "missing `:` after the key 'hp'",
+//!     "`}` closes the `[` opened on line 4, did you mean `]`?",
+//! ]);
+//! ```
+
+use std::collections::HashSet;
+
+use crate::parser::{SpannedToken, TokenKind, is_number_literal, tokenize};
+use crate::span::{LineIndex, Span};
+
+/// A likely mistake in the source and how to fix it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hint {
+    /// Where the mistake is, or what it comes right after
+    pub span: Span,
+    pub message: String,
+}
+
+/// Guesses what's wrong with `src`, in the order of the source. Empty if nothing looks
+/// wrong, which doesn't mean that `src` parses.
+pub fn hints(src: &str) -> Vec<Hint> {
+    let mut analyzer = Analyzer {
+        src,
+        index: LineIndex::new(src),
+        tokens: tokenize(src)
+            .into_iter()
+            .filter(|t| t.kind != TokenKind::Comment)
+            .collect(),
+        pos: 0,
+        bindings: HashSet::new(),
+        hints: Vec::new(),
+    };
+    let _ = analyzer.document();
+    analyzer.hints
+}
+
+/// A mistake the analysis can't get past, the hint for it is already recorded
+struct Stop;
+
+struct Analyzer<'s> {
+    src: &'s str,
+    index: LineIndex,
+    tokens: Vec<SpannedToken>,
+    pos: usize,
+    /// The names bound by `let` so far
+    bindings: HashSet<&'s str>,
+    hints: Vec<Hint>,
+}
+
+impl<'s> Analyzer<'s> {
+    fn document(&mut self) -> Result<(), Stop> {
+        while self.peek_is_keyword("let") {
+            self.binding()?;
+        }
+        if self.peek().is_none() {
+            let span = Span::new(self.src.len(), 0);
+            return self.stop(span, "there is no value".into());
+        }
+        self.value()?;
+        if let Some(token) = self.peek() {
+            let line = self.line(self.tokens[self.pos - 1].span);
+            return self.stop(
+                token.span,
+                format!(
+                    "the value already ended on line {line}, wrap several values in `[...]` \
+                     or entries in `{{...}}`"
+                ),
+            );
+        }
+        Ok(())
+    }
+
+    /// `let <name> = <value>;`, after the `let`
+    fn binding(&mut self) -> Result<(), Stop> {
+        let let_span = self.next().expect("peeked").span;
+        let name = match self.next() {
+            Some(t) if t.kind == TokenKind::Sym => t,
+            Some(t) if t.kind == TokenKind::Keyword => {
+                let text = t.text(self.src);
+                return self.stop(t.span, format!("'{text}' is a keyword and can't be bound"));
+            }
+            _ => return self.stop(let_span, "`let` has to be followed by a name".into()),
+        };
+        let text = name.text(self.src);
+        match self.next() {
+            Some(t) if t.kind == TokenKind::Equals => {}
+            Some(t) if t.kind == TokenKind::Colon => {
+                self.hint(t.span, format!("bindings use `=`: `let {text} = ...;`"));
+            }
+            _ => return self.stop(name.span, format!("missing `=` after `let {text}`")),
+        }
+        self.value()?;
+        self.bindings.insert(text);
+        match self.peek() {
+            Some(t) if t.kind == TokenKind::Semicolon => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => {
+                let span = self.tokens[self.pos - 1].span;
+                self.stop(span, format!("missing `;` after the binding of '{text}'"))
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<(), Stop> {
+        let Some(token) = self.next() else {
+            let span = Span::new(self.src.len(), 0);
+            return self.stop(span, "missing value at the end".into());
+        };
+        let text = token.text(self.src);
+        match token.kind {
+            TokenKind::Str | TokenKind::RawStr => {
+                while self.peek().is_some_and(|t| t.kind == TokenKind::Plus) {
+                    let plus = self.next().expect("peeked");
+                    match self.next() {
+                        Some(t) if matches!(t.kind, TokenKind::Str | TokenKind::RawStr) => {}
+                        _ => {
+                            return self
+                                .stop(plus.span, "`+` has to be followed by a string".into());
+                        }
+                    }
+                }
+                Ok(())
+            }
+            TokenKind::Num => self.number(token),
+            TokenKind::Dash => match self.next() {
+                Some(t) if t.kind == TokenKind::Num => self.number(t),
+                Some(t) if t.kind == TokenKind::Dash => {
+                    self.stop(t.span, "numbers have a single `-`".into())
+                }
+                _ => self.stop(token.span, "`-` has to be followed by a number".into()),
+            },
+            TokenKind::Keyword if text.eq_ignore_ascii_case("let") => self.stop(
+                token.span,
+                "`let` bindings have to come before the value".into(),
+            ),
+            TokenKind::Keyword => Ok(()),
+            TokenKind::Sym if self.bindings.contains(text) => Ok(()),
+            TokenKind::Sym => {
+                self.unquoted(token);
+                Ok(())
+            }
+            TokenKind::LBrace => self.obj(token),
+            TokenKind::LBrack => self.list(token),
+            TokenKind::Unterminated if text.ends_with("*/") || text.starts_with("/*") => {
+                self.stop(token.span, "this comment is never closed, add `*/`".into())
+            }
+            TokenKind::Unterminated => self.stop(
+                token.span,
+                "this string is never closed, add a `\"` at its end".into(),
+            ),
+            TokenKind::Other if text == "'" => self.single_quoted(token),
+            _ => self.stop(token.span, format!("expected a value, found `{text}`")),
+        }
+    }
+
+    fn number(&mut self, token: SpannedToken) -> Result<(), Stop> {
+        let text = token.text(self.src);
+        if is_number_literal(text) {
+            return Ok(());
+        }
+        self.hint(
+            token.span,
+            format!("'{text}' isn't a number, if it's a string, quote it: \"{text}\""),
+        );
+        Ok(())
+    }
+
+    /// Words that were probably meant to be a string, starting with `first`
+    fn unquoted(&mut self, first: SpannedToken) {
+        let line = self.line(first.span);
+        let mut end = first.span.end();
+        // more words on the same line, unless they're the next key
+        while let Some(t) = self.peek() {
+            let is_word = matches!(t.kind, TokenKind::Sym | TokenKind::Num | TokenKind::Keyword);
+            let is_key = self
+                .tokens
+                .get(self.pos + 1)
+                .is_some_and(|next| next.kind == TokenKind::Colon);
+            if !is_word || is_key || self.line(t.span) != line {
+                break;
+            }
+            end = t.span.end();
+            self.pos += 1;
+        }
+        let span = Span::from(first.span.start..end);
+        let words = &self.src[span.range()];
+        let message = if self.bindings.is_empty() || words.contains(' ') {
+            format!("strings have to be quoted: \"{words}\"")
+        } else {
+            format!("'{words}' isn't bound by a `let`, if it's a string, quote it: \"{words}\"")
+        };
+        self.hint(span, message);
+    }
+
+    /// `'...'`, after the first `'`
+    fn single_quoted(&mut self, open: SpannedToken) -> Result<(), Stop> {
+        let line = self.line(open.span);
+        let close = self.tokens[self.pos..]
+            .iter()
+            .take_while(|t| self.line(t.span) == line)
+            .position(|t| t.kind == TokenKind::Other && t.text(self.src) == "'");
+        let Some(close) = close.map(|i| self.pos + i) else {
+            return self.stop(open.span, "strings use double quotes: `\"`".into());
+        };
+        let span = Span::from(open.span.start..self.tokens[close].span.end());
+        let inner = &self.src[open.span.end()..self.tokens[close].span.start];
+        self.hint(span, format!("strings use double quotes: \"{inner}\""));
+        self.pos = close + 1;
+        Ok(())
+    }
+
+    /// `{...}`, after the `{`
+    fn obj(&mut self, open: SpannedToken) -> Result<(), Stop> {
+        loop {
+            let Some(token) = self.peek() else {
+                return self.never_closed(open);
+            };
+            match token.kind {
+                TokenKind::RBrace => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                TokenKind::RBrack | TokenKind::RParen => return self.mismatched(open, token),
+                TokenKind::At => {
+                    self.section(TokenKind::LBrace)?;
+                    continue;
+                }
+                TokenKind::Comma => {
+                    self.pos += 1;
+                    self.hint(token.span, "`,` without an entry before it".into());
+                    continue;
+                }
+                TokenKind::Str | TokenKind::Num | TokenKind::Sym | TokenKind::Keyword => {
+                    self.pos += 1;
+                }
+                _ => {
+                    let text = token.text(self.src);
+                    return self.stop(token.span, format!("expected a key, found `{text}`"));
+                }
+            }
+            let key = token.text(self.src);
+            match self.peek() {
+                Some(t) if t.kind == TokenKind::Colon => self.pos += 1,
+                Some(t) if t.kind == TokenKind::Equals => {
+                    self.pos += 1;
+                    self.hint(t.span, "keys are followed by `:`, not `=`".into());
+                }
+                Some(_) if self.starts_value(self.pos) => {
+                    self.hint(token.span, format!("missing `:` after the key '{key}'"));
+                }
+                _ => return self.stop(token.span, format!("missing `:` after the key '{key}'")),
+            }
+            match self.peek() {
+                Some(t) if !matches!(t.kind, TokenKind::RBrace | TokenKind::Comma) => {
+                    self.value()?
+                }
+                _ => return self.stop(token.span, format!("missing value after '{key}:'")),
+            }
+            if self.peek().is_some_and(|t| t.kind == TokenKind::Comma) {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// `[...]`, after the `[`
+    fn list(&mut self, open: SpannedToken) -> Result<(), Stop> {
+        loop {
+            let Some(token) = self.peek() else {
+                return self.never_closed(open);
+            };
+            match token.kind {
+                TokenKind::RBrack => {
+                    self.pos += 1;
+                    return Ok(());
+                }
+                TokenKind::RBrace | TokenKind::RParen => return self.mismatched(open, token),
+                TokenKind::At => {
+                    self.section(TokenKind::LBrack)?;
+                    continue;
+                }
+                TokenKind::Comma => {
+                    self.pos += 1;
+                    self.hint(token.span, "`,` without an element before it".into());
+                    continue;
+                }
+                _ if self
+                    .tokens
+                    .get(self.pos + 1)
+                    .is_some_and(|t| t.kind == TokenKind::Colon) =>
+                {
+                    let colon = self.tokens[self.pos + 1];
+                    return self.stop(
+                        colon.span,
+                        "lists hold values, not `key: value` entries, use `{...}` for an object"
+                            .into(),
+                    );
+                }
+                _ => self.value()?,
+            }
+            match self.peek() {
+                Some(t) if t.kind == TokenKind::Colon => {
+                    return self.stop(
+                        t.span,
+                        "lists hold values, not `key: value` entries, use `{...}` for an object"
+                            .into(),
+                    );
+                }
+                Some(t) if t.kind == TokenKind::Comma => self.pos += 1,
+                _ => {}
+            }
+        }
+    }
+
+    /// `@if(...) <body> (@else ...)?`, whose bodies open with `open`
+    fn section(&mut self, open: TokenKind) -> Result<(), Stop> {
+        let at = self.next().expect("peeked");
+        match self.next() {
+            Some(t) if t.kind == TokenKind::Sym && t.text(self.src).eq_ignore_ascii_case("if") => {}
+            _ => return self.stop(at.span, "`@` has to be followed by `if(...)`".into()),
+        }
+        match self.next() {
+            Some(t) if t.kind == TokenKind::LParen => {
+                let close = self.tokens[self.pos..]
+                    .iter()
+                    .position(|t| t.kind == TokenKind::RParen);
+                match close {
+                    Some(i) => self.pos += i + 1,
+                    None => return self.never_closed(t),
+                }
+            }
+            _ => {
+                return self.stop(
+                    at.span,
+                    "`@if` has to be followed by `(<condition>)`".into(),
+                );
+            }
+        }
+        self.section_body(open)?;
+        while self.peek_is(TokenKind::At)
+            && self
+                .tokens
+                .get(self.pos + 1)
+                .is_some_and(|t| t.text(self.src).eq_ignore_ascii_case("else"))
+        {
+            self.pos += 2;
+            if self.peek_is(TokenKind::At) {
+                return self.section(open);
+            }
+            self.section_body(open)?;
+        }
+        Ok(())
+    }
+
+    fn section_body(&mut self, open: TokenKind) -> Result<(), Stop> {
+        if self.peek_is(open) {
+            return self.value();
+        }
+        let span = self
+            .peek()
+            .map_or(self.tokens[self.pos - 1].span, |t| t.span);
+        let (body, outer) = match open {
+            TokenKind::LBrace => ("{...}", "objects"),
+            _ => ("[...]", "lists"),
+        };
+        self.stop(
+            span,
+            format!("sections in {outer} have to be followed by `{body}`"),
+        )
+    }
+
+    fn never_closed(&mut self, open: SpannedToken) -> Result<(), Stop> {
+        let text = open.text(self.src);
+        let line = self.line(open.span);
+        self.stop(
+            open.span,
+            format!("the `{text}` on line {line} is never closed"),
+        )
+    }
+
+    fn mismatched(&mut self, open: SpannedToken, close: SpannedToken) -> Result<(), Stop> {
+        let (open_text, close_text) = (open.text(self.src), close.text(self.src));
+        let line = self.line(open.span);
+        let expected = match open.kind {
+            TokenKind::LBrace => "}",
+            _ => "]",
+        };
+        self.stop(
+            close.span,
+            format!(
+                "`{close_text}` closes the `{open_text}` opened on line {line}, \
+                 did you mean `{expected}`?"
+            ),
+        )
+    }
+
+    /// Whether the token at `pos` starts a value rather than the next key
+    fn starts_value(&self, pos: usize) -> bool {
+        let Some(token) = self.tokens.get(pos) else {
+            return false;
+        };
+        let before_colon = self
+            .tokens
+            .get(pos + 1)
+            .is_some_and(|t| t.kind == TokenKind::Colon);
+        match token.kind {
+            TokenKind::RawStr | TokenKind::Dash | TokenKind::LBrace | TokenKind::LBrack => true,
+            TokenKind::Str | TokenKind::Num | TokenKind::Keyword => !before_colon,
+            _ => false,
+        }
+    }
+
+    fn hint(&mut self, span: Span, message: String) {
+        self.hints.push(Hint { span, message });
+    }
+
+    fn stop(&mut self, span: Span, message: String) -> Result<(), Stop> {
+        self.hint(span, message);
+        Err(Stop)
+    }
+
+    fn line(&self, span: Span) -> usize {
+        self.index.line_col(span.start).line
+    }
+
+    fn peek(&self) -> Option<SpannedToken> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn peek_is(&self, kind: TokenKind) -> bool {
+        self.peek().is_some_and(|t| t.kind == kind)
+    }
+
+    fn peek_is_keyword(&self, keyword: &str) -> bool {
+        self.peek().is_some_and(|t| {
+            t.kind == TokenKind::Keyword && t.text(self.src).eq_ignore_ascii_case(keyword)
+        })
+    }
+
+    fn next(&mut self) -> Option<SpannedToken> {
+        let token = self.peek()?;
+        self.pos += 1;
+        Some(token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(src: &str) -> Vec<String> {
+        hints(src).into_iter().map(|h| h.message).collect()
+    }
+
+    #[test]
+    fn explains_common_mistakes() {
+        assert!(messages("let x = 1; {a: [x, -2], @if(debug) {b: r\"c\"}}").is_empty());
+        assert_eq!(
+            messages("{name: John Doe, age: 3}"),
+            ["strings have to be quoted: \"John Doe\""]
+        );
+        assert_eq!(
+            messages("let x = 1; {a: y}"),
+            ["'y' isn't bound by a `let`, if it's a string, quote it: \"y\""]
+        );
+        assert_eq!(
+            messages("{a = 'b', c: }"),
+            [
+                "keys are followed by `:`, not `=`",
+                "strings use double quotes: \"b\"",
+                "missing value after 'c:'"
+            ]
+        );
+        assert_eq!(
+            messages("[1, 2\n{a: 1}"),
+            ["the `[` on line 1 is never closed"]
+        );
+        assert_eq!(
+            messages("{a: 1} {b: 2}"),
+            [
+                "the value already ended on line 1, wrap several values in `[...]` or \
+                 entries in `{...}`"
+            ]
+        );
+        let hint = &hints("{a: [1, b: 2]}")[0];
+        assert_eq!(hint.span, Span::new(9, 1));
+    }
+}
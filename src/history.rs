@@ -0,0 +1,108 @@
+//! Journals of previous versions of files, so edits in place can be undone.
+//!
+//! [`record`] copies a file into the journal next to it before it's changed: the versions of
+//! `dir/app.gon` are kept in `dir/.gon.history/app.gon/`, numbered in the order they were
+//! recorded. [`undo`] puts the last recorded version back and removes it from the journal, so
+//! undoing repeatedly walks back through the versions.
+//! # Usage example
+//! ```rust,no_run
+//! use gon::history;
+//! let file = std::path::Path::new("tuning.gon");
+//! history::record(file).unwrap();
+//! std::fs::write(file, "{speed: 2}").unwrap();
+//! assert!(history::undo(file).unwrap());
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The directory next to edited files that holds their journals
+pub const DIR_NAME: &str = ".gon.history";
+
+/// The extension of recorded versions
+const EXTENSION: &str = "gon";
+
+/// Copies the current content of `file` into its journal. Returns where the version was
+/// put, `None` if `file` doesn't exist yet so there is nothing to keep.
+pub fn record(file: &Path) -> io::Result<Option<PathBuf>> {
+    let content = match std::fs::read(file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let journal = journal(file)?;
+    std::fs::create_dir_all(&journal)?;
+    let next = versions(file)?.last().map_or(1, |(n, _)| n + 1);
+    let version = journal.join(format!("{next:06}.{EXTENSION}"));
+    std::fs::write(&version, content)?;
+    Ok(Some(version))
+}
+
+/// Restores the last recorded version of `file` and removes it from the journal. Returns
+/// whether there was a version to restore.
+pub fn undo(file: &Path) -> io::Result<bool> {
+    let Some((_, last)) = versions(file)?.pop() else {
+        return Ok(false);
+    };
+    // renaming keeps `file` intact if anything goes wrong halfway
+    std::fs::rename(&last, file)?;
+    Ok(true)
+}
+
+/// The recorded versions of `file`, oldest first
+pub fn versions(file: &Path) -> io::Result<Vec<(u64, PathBuf)>> {
+    let journal = journal(file)?;
+    let entries = match std::fs::read_dir(&journal) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut versions = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let number = path
+            .file_stem()
+            .filter(|_| path.extension().is_some_and(|ext| ext == EXTENSION))
+            .and_then(|stem| stem.to_str()?.parse().ok());
+        if let Some(number) = number {
+            versions.push((number, path));
+        }
+    }
+    versions.sort();
+    Ok(versions)
+}
+
+/// Where the versions of `file` are kept
+fn journal(file: &Path) -> io::Result<PathBuf> {
+    let name = file.file_name().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("'{}' isn't a file", file.display()),
+        )
+    })?;
+    let dir = file.parent().unwrap_or(Path::new(""));
+    Ok(dir.join(DIR_NAME).join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn undo_walks_back_through_versions() {
+        let dir = TempDir::new("history");
+        let file = dir.join("tuning.gon");
+        assert_eq!(record(&file).unwrap(), None);
+        for version in ["{speed: 1}", "{speed: 2}", "{speed: 3}"] {
+            record(&file).unwrap();
+            std::fs::write(&file, version).unwrap();
+        }
+        assert_eq!(versions(&file).unwrap().len(), 2);
+        assert!(undo(&file).unwrap());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "{speed: 2}");
+        assert!(undo(&file).unwrap());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "{speed: 1}");
+        assert!(!undo(&file).unwrap());
+    }
+}
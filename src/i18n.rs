@@ -0,0 +1,114 @@
+//! Treating gon documents as catalogs of translatable strings.
+//!
+//! [`extract`] flattens every string of a document into a catalog: an object that maps the
+//! [`Path`] of each string to the string, which is what translators work with. [`merge`]
+//! writes the strings of a translated catalog back into a copy of the document.
+//! # Usage example
+//! ```rust
+//! use gon::{i18n, parse_str};
+//! let ui = parse_str(r#"{menu: {start: "Start", items: ["Load", "Quit"]}, width: 300}"#).unwrap();
+//! let catalog = i18n::extract(&ui);
+//! assert_eq!(catalog.as_obj().unwrap().require_str("menu.items[1]"), Ok("Quit"));
+//!
+//! let mut german = ui.clone();
+//! i18n::merge(&mut german, &parse_str(r#"{"menu.start": "Los"}"#).unwrap()).unwrap();
+//! assert_eq!(
+//!     german,
+//!     parse_str(r#"{menu: {start: "Los", items: ["Load", "Quit"]}, width: 300}"#).unwrap()
+//! );
+//! ```
+
+use thiserror::Error;
+
+use crate::path::{self, Path, PathError, Pattern, PatternSegment, select};
+use crate::{MapT, Value};
+
+/// A catalog couldn't be merged into a document
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum I18nError {
+    /// The catalog isn't an object
+    #[error("expected the catalog to be an obj, found {0}")]
+    NotACatalog(&'static str),
+    /// A key of the catalog isn't a path
+    #[error("invalid catalog key: {0}")]
+    InvalidKey(PathError),
+    /// The catalog has something else than a string for a key
+    #[error("'{0}': expected a str in the catalog, found {1}")]
+    NotAString(String, &'static str),
+    /// A key of the catalog isn't the path of a string in the document
+    #[error("'{0}' isn't a string in the document")]
+    UnknownPath(String),
+}
+
+/// The paths of all strings in `value` and the strings, as a flat object
+pub fn extract(value: &Value) -> Value {
+    let everything = Pattern(vec![PatternSegment::AnyDepth]);
+    Value::Obj(
+        select(value, &everything)
+            .filter(|(_, v)| matches!(v, Value::Str { .. }))
+            .map(|(path, v)| (path.to_string().into(), v.clone()))
+            .collect::<MapT>(),
+    )
+}
+
+/// Replaces the strings of `value` with the ones of `catalog`, a flat object like
+/// [`extract`] makes. Strings that aren't in the catalog stay as they are. Nothing is
+/// changed if any key of the catalog isn't the path of a string in `value`.
+pub fn merge(value: &mut Value, catalog: &Value) -> Result<(), I18nError> {
+    let Value::Obj(entries) = catalog else {
+        return Err(I18nError::NotACatalog(catalog.type_name()));
+    };
+    let mut translations = Vec::with_capacity(entries.len());
+    for (key, translation) in entries {
+        let path: Path = key.parse().map_err(I18nError::InvalidKey)?;
+        let Value::Str { s, .. } = translation else {
+            return Err(I18nError::NotAString(
+                key.to_string(),
+                translation.type_name(),
+            ));
+        };
+        if !matches!(path::get(value, &path), Some(Value::Str { .. })) {
+            return Err(I18nError::UnknownPath(key.to_string()));
+        }
+        translations.push((path, s));
+    }
+    for (path, translation) in translations {
+        if let Some(Value::Str { s, .. }) = path::get_mut(value, &path) {
+            s.clone_from(translation);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn round_trips_and_rejects_unknown_paths() {
+        let ui = parse_str(r#"{"a.b": "x", list: [1, "y"], raw: r"z"}"#).unwrap();
+        let catalog = extract(&ui);
+        assert_eq!(
+            catalog,
+            parse_str(r#"{"\"a.b\"": "x", "list[1]": "y", raw: r"z"}"#).unwrap()
+        );
+        let mut merged = ui.clone();
+        merge(&mut merged, &catalog).unwrap();
+        assert_eq!(merged, ui);
+        for (bad, error) in [
+            (
+                r#"{"list[0]": "n"}"#,
+                I18nError::UnknownPath("list[0]".into()),
+            ),
+            (
+                r#"{"list[1]": 1}"#,
+                I18nError::NotAString("list[1]".into(), "num"),
+            ),
+            ("[]", I18nError::NotACatalog("list")),
+        ] {
+            assert_eq!(merge(&mut merged, &parse_str(bad).unwrap()), Err(error));
+        }
+        assert_eq!(merged, ui);
+    }
+}
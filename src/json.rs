@@ -1,29 +1,79 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
 use serde_json::Value as JsonValue;
+use thiserror::Error;
 
-use crate::Value;
+use crate::cancel::CancelToken;
+use crate::cursor::{Cursor, CursorError};
+use crate::path::{Path, Segment};
+use crate::value::{NumberError, Strictness, json_escape, parse_raw, spelling_order};
+use crate::{Kind, Value};
 
 impl From<Value> for JsonValue {
+    /// Converts leniently, see [`to_json`]
     fn from(value: Value) -> Self {
-        match value {
-            Value::None => JsonValue::Null,
-            Value::Bool(b) => JsonValue::Bool(b),
-            // FIXME
-            Value::Num(_) => {
-                if let Some(n) = value.as_i128() {
-                    JsonValue::Number(serde_json::Number::from_i128(n).unwrap())
-                } else {
-                    JsonValue::Number(
-                        serde_json::Number::from_f64(value.as_f64().unwrap()).unwrap(),
-                    )
-                }
+        to_json(&value, Strictness::Lenient).expect("lenient conversion can't fail")
+    }
+}
+
+/// Converts `value` to a [`JsonValue`]. JSON numbers are `i64`, `u64` or finite `f64`, so
+/// leniently bigger integers become floats and numbers too big for a float become `null`,
+/// strictly they're an error that says where they are.
+/// # Usage example
+/// ```rust
+/// use gon::{json::to_json, parse_str, value::Strictness};
+/// let value = parse_str("{ids: [1, 100000000000000000000]}").unwrap();
+/// assert_eq!(
+///     to_json(&value, Strictness::Lenient).unwrap().to_string(),
+///     r#"{"ids":[1,1e20]}"#
+/// );
+/// assert_eq!(
+///     to_json(&value, Strictness::Strict).unwrap_err().to_string(),
+///     "ids[1]: 100000000000000000000 doesn't fit into a JSON integer"
+/// );
+/// ```
+pub fn to_json(value: &Value, strictness: Strictness) -> Result<JsonValue, NumberError> {
+    let mut path = Path::default();
+    to_json0(value, strictness, &mut path)
+}
+
+fn to_json0(
+    value: &Value,
+    strictness: Strictness,
+    path: &mut Path,
+) -> Result<JsonValue, NumberError> {
+    Ok(match value {
+        Value::None => JsonValue::Null,
+        Value::Bool(b) => JsonValue::Bool(*b),
+        Value::Num(_) => json_number(value, strictness)
+            .map_err(|e| NumberError {
+                path: path.clone(),
+                ..e
+            })?
+            .map_or(JsonValue::Null, JsonValue::Number),
+        Value::Str { s, raw: _ } => JsonValue::String(s.clone()),
+        Value::List(xs) => {
+            let mut array = Vec::with_capacity(xs.len());
+            for (i, x) in xs.iter().enumerate() {
+                path.0.push(Segment::Index(i));
+                array.push(to_json0(x, strictness, path)?);
+                path.0.pop();
             }
-            Value::Str { s, raw: _ } => JsonValue::String(s),
-            Value::List(xs) => JsonValue::Array(xs.into_iter().map(Value::into).collect()),
-            Value::Obj(obj) => {
-                JsonValue::Object(obj.into_iter().map(|(k, v)| (k, v.into())).collect())
+            JsonValue::Array(array)
+        }
+        Value::Obj(obj) => {
+            let mut object = serde_json::Map::new();
+            for (k, v) in spelling_order(obj) {
+                path.0.push(Segment::Key(k.to_string()));
+                object.insert(k.to_string(), to_json0(v, strictness, path)?);
+                path.0.pop();
             }
+            JsonValue::Object(object)
         }
-    }
+        Value::Tagged { value, .. } => to_json0(value, strictness, path)?,
+        Value::Raw(text) => to_json0(&parse_raw(text), strictness, path)?,
+    })
 }
 
 impl From<JsonValue> for Value {
@@ -36,8 +86,368 @@ impl From<JsonValue> for Value {
             JsonValue::String(s) => Value::Str { s, raw: true },
             JsonValue::Array(xs) => Value::List(xs.into_iter().map(JsonValue::into).collect()),
             JsonValue::Object(obj) => {
-                Value::Obj(obj.into_iter().map(|(k, v)| (k, v.into())).collect())
+                Value::Obj(obj.into_iter().map(|(k, v)| (k.into(), v.into())).collect())
+            }
+        }
+    }
+}
+
+/// Writes `value` as JSON into `out` as it goes, without building a [`JsonValue`] first,
+/// so converting huge documents takes no more memory than the [`Value`] itself.
+/// The output is the same as serde_json's for `JsonValue::from(value.clone())`, `pretty`
+/// or compact. Wrap `out` in a [`BufWriter`](io::BufWriter) if it isn't buffered.
+/// # Usage example
+/// ```rust
+/// use gon::{json::write_json, parse_str};
+/// let value = parse_str(r#"{none: None, xs: [1, 2.5, "a\nb"]}"#).unwrap();
+/// let mut out = Vec::new();
+/// write_json(&value, &mut out, false).unwrap();
+/// assert_eq!(out, br#"{"none":null,"xs":[1,2.5,"a\nb"]}"#);
+/// ```
+pub fn write_json<W: Write>(value: &Value, out: W, pretty: bool) -> io::Result<()> {
+    write_json_with(value, out, pretty, Strictness::Lenient)
+}
+
+/// Like [`write_json`], but numbers are converted like [`to_json`] does with `strictness`.
+/// A [`NumberError`] fails the write with [`io::ErrorKind::InvalidData`] and what was
+/// written before it stays in `out`.
+pub fn write_json_with<W: Write>(
+    value: &Value,
+    out: W,
+    pretty: bool,
+    strictness: Strictness,
+) -> io::Result<()> {
+    write_json0(value, out, pretty, strictness, None)
+}
+
+/// Like [`write_json_with`], but stops soon after `cancel` is cancelled, see the docs of
+/// [`cancel`](crate::cancel). Then the write fails with [`io::ErrorKind::Other`] wrapping
+/// [`Cancelled`](crate::cancel::Cancelled), and what was written before stays in `out`.
+pub fn write_json_cancellable<W: Write>(
+    value: &Value,
+    out: W,
+    pretty: bool,
+    strictness: Strictness,
+    cancel: &CancelToken,
+) -> io::Result<()> {
+    write_json0(value, out, pretty, strictness, Some(cancel))
+}
+
+fn write_json0<W: Write>(
+    value: &Value,
+    mut out: W,
+    pretty: bool,
+    strictness: Strictness,
+    cancel: Option<&CancelToken>,
+) -> io::Result<()> {
+    let mut writer = JsonWriter {
+        out: &mut out,
+        pretty,
+        strictness,
+        path: Path::default(),
+        cancel,
+    };
+    writer.write(value, 0)?;
+    out.flush()
+}
+
+struct JsonWriter<'a, W> {
+    out: &'a mut W,
+    pretty: bool,
+    strictness: Strictness,
+    /// Where the value being written is, for errors
+    path: Path,
+    /// Checked before every element of a list or object
+    cancel: Option<&'a CancelToken>,
+}
+
+impl<W: Write> JsonWriter<'_, W> {
+    fn check_cancelled(&self) -> io::Result<()> {
+        // not `Interrupted`, `write_all` retries that
+        match self.cancel.map(CancelToken::check) {
+            Some(Err(e)) => Err(io::Error::other(e)),
+            _ => Ok(()),
+        }
+    }
+
+    fn write(&mut self, value: &Value, depth: usize) -> io::Result<()> {
+        let (out, pretty) = (&mut *self.out, self.pretty);
+        match value {
+            Value::None => out.write_all(b"null"),
+            Value::Bool(b) => write!(out, "{b}"),
+            Value::Num(_) => write_json_number(value, out, self.strictness, &self.path),
+            Value::Str { s, .. } => write_json_str(s, out),
+            Value::List(xs) => {
+                out.write_all(b"[")?;
+                for (i, x) in xs.iter().enumerate() {
+                    self.check_cancelled()?;
+                    write_separator(self.out, i, pretty, depth + 1)?;
+                    self.path.0.push(Segment::Index(i));
+                    self.write(x, depth + 1)?;
+                    self.path.0.pop();
+                }
+                if pretty && !xs.is_empty() {
+                    write_separator(self.out, 0, pretty, depth)?;
+                }
+                self.out.write_all(b"]")
+            }
+            Value::Obj(obj) => {
+                out.write_all(b"{")?;
+                for (i, (k, v)) in spelling_order(obj).enumerate() {
+                    self.check_cancelled()?;
+                    write_separator(self.out, i, pretty, depth + 1)?;
+                    write_json_str(k, self.out)?;
+                    self.out.write_all(if pretty { b": " } else { b":" })?;
+                    self.path.0.push(Segment::Key(k.to_string()));
+                    self.write(v, depth + 1)?;
+                    self.path.0.pop();
+                }
+                if pretty && !obj.is_empty() {
+                    write_separator(self.out, 0, pretty, depth)?;
+                }
+                self.out.write_all(b"}")
+            }
+            Value::Tagged { value, .. } => self.write(value, depth),
+            Value::Raw(text) => self.write(&parse_raw(text), depth),
+        }
+    }
+}
+
+/// [`transcode`] couldn't write gon source as JSON
+#[derive(Debug, Error)]
+pub enum TranscodeError {
+    /// The source isn't plain data, nothing was written. Parse it and use
+    /// [`write_json_with`] instead.
+    #[error("not plain data: {0}")]
+    NotPlain(CursorError),
+    /// Writing failed, or a number doesn't fit into JSON with [`Strictness::Strict`]
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Writes the gon source `src` as JSON into `out` while reading it with a [`Cursor`],
+/// without building a [`Value`], so converting a huge document takes hardly more memory
+/// than its source. The output is the same as [`write_json_with`]'s for the parsed source
+/// with the `preserve_order` feature, without it keys aren't sorted.
+///
+/// Only plain data can be read like that: `let` bindings, variables, conditional sections
+/// and objects with a key written twice fail with [`TranscodeError::NotPlain`]. The source is
+/// read once to check that before anything is written.
+/// # Usage example
+/// ```rust
+/// use gon::{json::{transcode, TranscodeError}, value::Strictness};
+/// let mut out = Vec::new();
+/// transcode(r#"{b: [1, - 2.5], a: "x" + "y"}"#, &mut out, false, Strictness::Lenient).unwrap();
+/// assert_eq!(out, br#"{"b":[1,-2.5],"a":"xy"}"#);
+/// let dup = transcode("{a: 1, a: 2}", Vec::new(), false, Strictness::Lenient);
+/// assert!(matches!(dup, Err(TranscodeError::NotPlain(_))));
+/// ```
+pub fn transcode<W: Write>(
+    src: &str,
+    mut out: W,
+    pretty: bool,
+    strictness: Strictness,
+) -> Result<(), TranscodeError> {
+    let mut check = Cursor::new(src);
+    check_plain(&mut check).map_err(TranscodeError::NotPlain)?;
+    check.finish().map_err(TranscodeError::NotPlain)?;
+    let mut transcoder = Transcoder {
+        cursor: Cursor::new(src),
+        out: &mut out,
+        pretty,
+        strictness,
+        path: Path::default(),
+    };
+    transcoder.write(0)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Reads the next value like [`Transcoder`] does, failing on what it can't write
+fn check_plain(cursor: &mut Cursor) -> Result<(), CursorError> {
+    match cursor.peek_kind()? {
+        Kind::Obj => {
+            let mut keys = HashSet::new();
+            cursor.enter_obj()?;
+            loop {
+                let at = cursor.offset();
+                let Some(key) = cursor.key()? else {
+                    break;
+                };
+                if keys.contains(&key) {
+                    return Err(CursorError::Unexpected {
+                        expected: "a key that isn't in the object yet",
+                        found: key.into_owned(),
+                        at,
+                    });
+                }
+                keys.insert(key);
+                check_plain(cursor)?;
+            }
+        }
+        Kind::List => {
+            cursor.enter_list()?;
+            while cursor.next_element()? {
+                check_plain(cursor)?;
+            }
+        }
+        _ => cursor.skip_value()?,
+    }
+    Ok(())
+}
+
+/// Writes what a [`Cursor`] reads as JSON, for [`transcode`]
+struct Transcoder<'a, 'src, W> {
+    cursor: Cursor<'src>,
+    out: &'a mut W,
+    pretty: bool,
+    strictness: Strictness,
+    /// Where the value being written is, for errors
+    path: Path,
+}
+
+impl<W: Write> Transcoder<'_, '_, W> {
+    fn write(&mut self, depth: usize) -> io::Result<()> {
+        let pretty = self.pretty;
+        match self.cursor.peek_kind().map_err(invalid_data)? {
+            Kind::None => {
+                self.cursor.next_none();
+                self.out.write_all(b"null")
+            }
+            Kind::Bool => {
+                let b = self.cursor.next_bool().map_err(invalid_data)?;
+                write!(self.out, "{b}")
+            }
+            Kind::Num => {
+                let num = self.cursor.next_num().map_err(invalid_data)?;
+                let num = Value::Num(num.into_owned());
+                write_json_number(&num, self.out, self.strictness, &self.path)
+            }
+            Kind::Str => {
+                let s = self.cursor.next_str().map_err(invalid_data)?;
+                write_json_str(&s, self.out)
+            }
+            Kind::List => {
+                self.cursor.enter_list().map_err(invalid_data)?;
+                self.out.write_all(b"[")?;
+                let mut i = 0;
+                while self.cursor.next_element().map_err(invalid_data)? {
+                    write_separator(self.out, i, pretty, depth + 1)?;
+                    self.path.0.push(Segment::Index(i));
+                    self.write(depth + 1)?;
+                    self.path.0.pop();
+                    i += 1;
+                }
+                if pretty && i > 0 {
+                    write_separator(self.out, 0, pretty, depth)?;
+                }
+                self.out.write_all(b"]")
+            }
+            Kind::Obj => {
+                self.cursor.enter_obj().map_err(invalid_data)?;
+                self.out.write_all(b"{")?;
+                let mut i = 0;
+                while let Some(k) = self.cursor.key().map_err(invalid_data)? {
+                    write_separator(self.out, i, pretty, depth + 1)?;
+                    write_json_str(&k, self.out)?;
+                    self.out.write_all(if pretty { b": " } else { b":" })?;
+                    self.path.0.push(Segment::Key(k.into_owned()));
+                    self.write(depth + 1)?;
+                    self.path.0.pop();
+                    i += 1;
+                }
+                if pretty && i > 0 {
+                    write_separator(self.out, 0, pretty, depth)?;
+                }
+                self.out.write_all(b"}")
             }
         }
     }
 }
+
+/// The source was checked before it's transcoded, so this only happens if it's broken
+/// in a way that the check missed
+fn invalid_data(e: CursorError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Writes the number `value` like [`json_number`] converts it, `path` is where it is
+fn write_json_number<W: Write>(
+    value: &Value,
+    out: &mut W,
+    strictness: Strictness,
+    path: &Path,
+) -> io::Result<()> {
+    match json_number(value, strictness) {
+        Ok(Some(n)) => write!(out, "{n}"),
+        Ok(None) => out.write_all(b"null"),
+        Err(e) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            NumberError {
+                path: path.clone(),
+                ..e
+            },
+        )),
+    }
+}
+
+/// The comma before all but the first (`i == 0`) element, and the line break and indentation
+/// for `depth` when `pretty`
+fn write_separator<W: Write>(out: &mut W, i: usize, pretty: bool, depth: usize) -> io::Result<()> {
+    if i > 0 {
+        out.write_all(b",")?;
+    }
+    if pretty {
+        out.write_all(b"\n")?;
+        for _ in 0..depth {
+            out.write_all(b"  ")?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `s` as a JSON string literal, escaped like serde_json does
+fn write_json_str<W: Write>(s: &str, out: &mut W) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let Some(escaped) = json_escape(c) else {
+            continue;
+        };
+        out.write_all(&s.as_bytes()[start..i])?;
+        out.write_all(escaped.as_bytes())?;
+        start = i + c.len_utf8();
+    }
+    out.write_all(&s.as_bytes()[start..])?;
+    out.write_all(b"\"")
+}
+
+/// `value` as a serde_json number, `None` if it should be `null`. The error's path is
+/// left for the caller to fill in.
+fn json_number(
+    value: &Value,
+    strictness: Strictness,
+) -> Result<Option<serde_json::Number>, NumberError> {
+    let strict = strictness == Strictness::Strict;
+    if let Some(n) = value
+        .as_i128_with(strictness)
+        .map_err(|_| value.number_error(JSON_INT))?
+    {
+        match serde_json::Number::from_i128(n) {
+            Some(n) => return Ok(Some(n)),
+            None if strict => return Err(value.number_error(JSON_INT)),
+            None => {}
+        }
+    }
+    match value
+        .as_f64_with(strictness)
+        .map_err(|_| value.number_error(JSON_NUM))?
+    {
+        Some(f) => Ok(serde_json::Number::from_f64(f)),
+        None if strict => Err(value.number_error(JSON_NUM)),
+        None => Ok(None),
+    }
+}
+
+const JSON_INT: &str = "a JSON integer";
+const JSON_NUM: &str = "a JSON number";
@@ -0,0 +1,206 @@
+//! The keys of objects.
+//!
+//! A [`Key`] is used like a `&str`: it derefs to `str`, borrows as one for looking up
+//! entries and converts from `&str` and `String` with `into`. How it stores the key is up
+//! to the features and doesn't change its API, so turning them on doesn't break code that
+//! builds or reads objects:
+//! - By default a key is a `String`.
+//! - With `shared_keys`, keys are shared `Arc<str>`s and the parser gives equal keys of one
+//!   document the same allocation.
+//! - With `compact_keys` (and without `shared_keys`), keys of up to 16 bytes are stored
+//!   inline in a `SmallVec`, so small objects don't need an allocation for each of their
+//!   keys. Lists and maps can't be stored inline like that, a `Value` can't contain other
+//!   `Value`s without a pointer in between.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::{key::Key, parse_str};
+//! let value = parse_str("{hp: 10}").unwrap();
+//! let obj = value.as_obj().unwrap();
+//! assert_eq!(obj.keys().next().unwrap(), "hp");
+//! let key = Key::from("hp");
+//! assert_eq!(key.len(), 2);
+//! assert_eq!(String::from(key), "hp");
+//! ```
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+
+/// The key of an object entry, see the [module docs](self)
+#[derive(Clone)]
+pub struct Key(Repr);
+
+#[cfg(feature = "shared_keys")]
+type Repr = std::sync::Arc<str>;
+/// The UTF-8 of the key, only ever built from a `str`
+#[cfg(all(feature = "compact_keys", not(feature = "shared_keys")))]
+type Repr = smallvec::SmallVec<[u8; 16]>;
+#[cfg(not(any(feature = "shared_keys", feature = "compact_keys")))]
+type Repr = String;
+
+impl Key {
+    #[cfg(not(all(feature = "compact_keys", not(feature = "shared_keys"))))]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    #[cfg(all(feature = "compact_keys", not(feature = "shared_keys")))]
+    pub fn as_str(&self) -> &str {
+        // SAFETY: the bytes are those of the `str` the key was built from
+        unsafe { std::str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl Deref for Key {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Key {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Key {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Key {
+    #[cfg(not(all(feature = "compact_keys", not(feature = "shared_keys"))))]
+    fn from(key: &str) -> Self {
+        Self(key.into())
+    }
+
+    #[cfg(all(feature = "compact_keys", not(feature = "shared_keys")))]
+    fn from(key: &str) -> Self {
+        Self(Repr::from_slice(key.as_bytes()))
+    }
+}
+
+impl From<String> for Key {
+    // by default, the key is kept as it is
+    #[allow(clippy::useless_conversion)]
+    #[cfg(not(all(feature = "compact_keys", not(feature = "shared_keys"))))]
+    fn from(key: String) -> Self {
+        Self(key.into())
+    }
+
+    #[cfg(all(feature = "compact_keys", not(feature = "shared_keys")))]
+    fn from(key: String) -> Self {
+        // a `SmallVec` only moves a vec inline if its capacity fits
+        if key.len() <= 16 {
+            Self::from(key.as_str())
+        } else {
+            Self(Repr::from_vec(key.into_bytes()))
+        }
+    }
+}
+
+impl From<&String> for Key {
+    fn from(key: &String) -> Self {
+        Self::from(key.as_str())
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        #[cfg(not(any(feature = "shared_keys", feature = "compact_keys")))]
+        return key.0;
+        #[cfg(feature = "shared_keys")]
+        return key.as_str().to_owned();
+        #[cfg(all(feature = "compact_keys", not(feature = "shared_keys")))]
+        // SAFETY: see `as_str`
+        unsafe {
+            String::from_utf8_unchecked(key.0.into_vec())
+        }
+    }
+}
+
+// equal keys hash and compare like their `str`s, which `Borrow<str>` relies on
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Key {}
+
+impl PartialEq<str> for Key {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Key {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Key {
+    fn eq(&self, other: &String) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Key> for &str {
+    fn eq(&self, other: &Key) -> bool {
+        *self == other.as_str()
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl fmt::Debug for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self)
+    }
+}
+
+#[cfg(all(test, feature = "compact_keys", not(feature = "shared_keys")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_keys_are_inline() {
+        let value = crate::parse_str("{hp: 1, \"a key too long to fit\": 2}").unwrap();
+        let obj = value.as_obj().unwrap();
+        assert!(obj.keys().all(|k| k.0.spilled() == (k.len() > 16)));
+        let key = Key::from(String::with_capacity(64) + "hp");
+        assert!(!key.0.spilled());
+        assert_eq!(String::from(key.clone()), "hp");
+        assert_eq!(key, Key::from("hp"));
+    }
+}
@@ -0,0 +1,276 @@
+//! Policies for the order keys of objects are spelled in.
+//!
+//! A [`KeyOrderPolicy`] is a list of rules, each for the objects a [`Pattern`] matches. A
+//! rule names keys that come first, in that order, and says whether the rest of the keys
+//! are sorted alphabetically or stay as they are. If several rules match an object, the
+//! last one wins. [`KeyOrderPolicy::apply`] reorders a value, which only has an effect with
+//! the `preserve_order` feature, and [`KeyOrderPolicy::check`] reports where a value breaks
+//! the policy.
+//!
+//! In a formatting profile, the policy is the `key_order` list, see
+//! [`KeyOrderPolicy::from_value`].
+//! # Usage example
+//! ```rust
+//! use gon::{key_order::KeyOrderPolicy, parse_str};
+//! let policy = KeyOrderPolicy::from_value(
+//!     &parse_str(r#"[{path: "users[*]", first: ["id"], rest: "alphabetical"}]"#)
+//!         .unwrap(),
+//! )
+//! .unwrap();
+//! let mut users = parse_str(r#"{users: [{name: "b", id: 1, mail: "b@x"}]}"#).unwrap();
+//! # #[cfg(feature = "preserve_order")]
+//! assert_eq!(
+//!     policy.check(&users)[0].to_string(),
+//!     "users[0]: 'id' should come before 'name'"
+//! );
+//! policy.apply(&mut users);
+//! assert!(policy.check(&users).is_empty());
+//! ```
+
+use std::fmt;
+
+use crate::Value;
+use crate::path::{Path, Pattern, PatternSegment, Segment};
+use crate::value::{SpellConfigError, spelling_order};
+
+/// Rules for the order of keys, see the [module docs](self)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyOrderPolicy(pub Vec<KeyOrderRule>);
+
+/// How the keys of the objects `pattern` matches are ordered
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyOrderRule {
+    pub pattern: Pattern,
+    /// Keys that come before all others, in this order
+    pub first: Vec<String>,
+    pub rest: RestOrder,
+}
+
+/// The order of the keys of an object that aren't in [`KeyOrderRule::first`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RestOrder {
+    /// As they already are
+    #[default]
+    Unchanged,
+    /// Sorted by key
+    Alphabetical,
+}
+
+/// An object spells a key after one it should come before
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyOrderViolation {
+    /// Where the object is
+    pub path: Path,
+    pub key: String,
+    /// The key spelled where `key` should be
+    pub before: String,
+}
+
+impl fmt::Display for KeyOrderViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.0.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "'{}' should come before '{}'", self.key, self.before)
+    }
+}
+
+impl KeyOrderPolicy {
+    /// Reads the `key_order` of a formatting profile, the policy is empty if there is none.
+    /// See [`SpellConfig::from_file`](crate::SpellConfig::from_file).
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, SpellConfigError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| SpellConfigError::Io(path.display().to_string(), e))?;
+        let profile = crate::parse_str(&src)
+            .map_err(|e| SpellConfigError::Parse(path.display().to_string(), e))?;
+        match profile.as_obj().and_then(|obj| obj.get(KEY)) {
+            Some(rules) => Self::from_value(rules),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Builds a policy out of a list of objects with the keys `path`, a [`Pattern`] that
+    /// is `**` if it's missing, `first`, a list of keys, and `rest`, which is `"unchanged"`
+    /// (the default) or `"alphabetical"`.
+    pub fn from_value(value: &Value) -> Result<Self, SpellConfigError> {
+        let rules = value
+            .as_list()
+            .ok_or_else(|| invalid("expected a list of rules"))?;
+        rules.iter().map(rule).collect::<Result<_, _>>().map(Self)
+    }
+
+    /// Reorders every object in `value` a rule matches
+    pub fn apply(&self, value: &mut Value) {
+        if !self.0.is_empty() {
+            self.apply0(value, &mut Path::default());
+        }
+    }
+
+    fn apply0(&self, value: &mut Value, path: &mut Path) {
+        match value {
+            Value::Obj(obj) => {
+                if let Some(rule) = self.rule_for(path) {
+                    let mut entries: Vec<_> = std::mem::take(obj).into_iter().collect();
+                    entries.sort_by(|(a, _), (b, _)| rule.rank(a).cmp(&rule.rank(b)));
+                    *obj = entries.into_iter().collect();
+                }
+                for (key, v) in obj.iter_mut() {
+                    path.0.push(Segment::Key(key.to_string()));
+                    self.apply0(v, path);
+                    path.0.pop();
+                }
+            }
+            Value::List(xs) => {
+                for (i, x) in xs.iter_mut().enumerate() {
+                    path.0.push(Segment::Index(i));
+                    self.apply0(x, path);
+                    path.0.pop();
+                }
+            }
+            Value::Tagged { value, .. } => self.apply0(value, path),
+            _ => {}
+        }
+    }
+
+    /// The first out of place key of every object in `value` that doesn't follow its
+    /// rule, depth first and with keys in the order they're spelled in
+    pub fn check(&self, value: &Value) -> Vec<KeyOrderViolation> {
+        let mut violations = Vec::new();
+        if !self.0.is_empty() {
+            self.check0(value, &mut Path::default(), &mut violations);
+        }
+        violations
+    }
+
+    fn check0(&self, value: &Value, path: &mut Path, violations: &mut Vec<KeyOrderViolation>) {
+        match value {
+            Value::Obj(obj) => {
+                if let Some(rule) = self.rule_for(path) {
+                    let keys: Vec<&str> = spelling_order(obj).map(|(k, _)| &**k).collect();
+                    let mut expected = keys.clone();
+                    expected.sort_by(|a, b| rule.rank(a).cmp(&rule.rank(b)));
+                    if let Some((key, before)) = expected.iter().zip(&keys).find(|(e, k)| e != k) {
+                        violations.push(KeyOrderViolation {
+                            path: path.clone(),
+                            key: key.to_string(),
+                            before: before.to_string(),
+                        });
+                    }
+                }
+                for (key, v) in spelling_order(obj) {
+                    path.0.push(Segment::Key(key.to_string()));
+                    self.check0(v, path, violations);
+                    path.0.pop();
+                }
+            }
+            Value::List(xs) => {
+                for (i, x) in xs.iter().enumerate() {
+                    path.0.push(Segment::Index(i));
+                    self.check0(x, path, violations);
+                    path.0.pop();
+                }
+            }
+            Value::Tagged { value, .. } => self.check0(value, path, violations),
+            _ => {}
+        }
+    }
+
+    fn rule_for(&self, path: &Path) -> Option<&KeyOrderRule> {
+        self.0.iter().rev().find(|rule| rule.pattern.matches(path))
+    }
+}
+
+impl KeyOrderRule {
+    /// Where `key` goes, keys with equal ranks keep their order
+    fn rank<'k>(&self, key: &'k str) -> (usize, &'k str) {
+        let first = self.first.iter().position(|f| f == key);
+        match (first, self.rest) {
+            (Some(i), _) => (i, ""),
+            (None, RestOrder::Unchanged) => (self.first.len(), ""),
+            (None, RestOrder::Alphabetical) => (self.first.len(), key),
+        }
+    }
+}
+
+/// The key of a formatting profile that holds the policy,
+/// [`SpellConfig::from_value`](crate::SpellConfig::from_value) skips it
+pub(crate) const KEY: &str = "key_order";
+
+fn rule(value: &Value) -> Result<KeyOrderRule, SpellConfigError> {
+    let obj = value
+        .as_obj()
+        .ok_or_else(|| invalid("expected {path, first, rest}"))?;
+    let mut rule = KeyOrderRule {
+        pattern: Pattern(vec![PatternSegment::AnyDepth]),
+        first: Vec::new(),
+        rest: RestOrder::default(),
+    };
+    for (key, v) in obj.iter() {
+        match &**key {
+            "path" => {
+                let src: &str = v.try_into().map_err(|_| invalid("path: expected a str"))?;
+                rule.pattern = src.parse().map_err(|e| invalid(format!("path: {e}")))?;
+            }
+            "first" => {
+                rule.first = v
+                    .as_list()
+                    .and_then(|keys| {
+                        keys.iter()
+                            .map(|k| <&str>::try_from(k).ok().map(String::from))
+                            .collect()
+                    })
+                    .ok_or_else(|| invalid("first: expected a list of keys"))?;
+            }
+            "rest" => {
+                rule.rest = match v {
+                    Value::Str { s, .. } if s == "unchanged" => RestOrder::Unchanged,
+                    Value::Str { s, .. } if s == "alphabetical" => RestOrder::Alphabetical,
+                    _ => return Err(invalid("rest: expected \"unchanged\" or \"alphabetical\"")),
+                }
+            }
+            _ => return Err(invalid(format!("unknown rule option '{key}'"))),
+        }
+    }
+    Ok(rule)
+}
+
+fn invalid(message: impl Into<String>) -> SpellConfigError {
+    SpellConfigError::Invalid(KEY.into(), message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn last_matching_rule_wins() {
+        let policy = KeyOrderPolicy::from_value(
+            &parse_str(
+                r#"[{first: ["id"], rest: "alphabetical"}, {path: "raw", rest: "unchanged"}]"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let mut value = parse_str("{b: 1, id: 2, raw: {z: 1, id: 2, a: 3}}").unwrap();
+        assert_eq!(
+            policy.check(&value),
+            [KeyOrderViolation {
+                path: Path::default(),
+                key: "id".into(),
+                before: "b".into(),
+            }]
+        );
+        policy.apply(&mut value);
+        #[cfg(feature = "preserve_order")]
+        {
+            assert!(policy.check(&value).is_empty());
+            assert_eq!(value.min_spell(), "{id:2,b:1,raw:{z:1,id:2,a:3}}");
+        }
+        assert!(matches!(
+            KeyOrderPolicy::from_value(&parse_str(r#"[{rest: "random"}]"#).unwrap()),
+            Err(SpellConfigError::Invalid(..))
+        ));
+    }
+}
@@ -1,65 +1,281 @@
-///! Parser for a simple JSON-like format that doesn't require quotes around keys and
-///! allows trailing commas (but requires non at all).
+//! Parser for a simple JSON-like format that doesn't require quotes around keys and
+//! allows trailing commas (but requires non at all).
 
+/// Enters a `tracing` span named `$name` that lasts until the end of the scope and starts
+/// timing it, if the `tracing` feature is on. `$timer` names it for [`trace_done!`].
+macro_rules! trace_span {
+    ($timer:ident, $name:literal) => {
+        #[cfg(feature = "tracing")]
+        let $timer = (
+            tracing::debug_span!($name).entered(),
+            std::time::Instant::now(),
+        );
+    };
+}
+
+/// Emits a `tracing` event with the given fields and the microseconds since
+/// [`trace_span!`] started `$timer`, if the `tracing` feature is on
+macro_rules! trace_done {
+    ($timer:ident, $($field:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(elapsed_us = $timer.1.elapsed().as_micros() as u64, $($field)*);
+    };
+}
+
+#[cfg(feature = "build")]
+pub mod build;
+pub mod cache;
+pub mod cancel;
+pub mod coerce;
+pub mod config;
+pub mod cursor;
+pub mod dialect;
+pub mod document;
+pub mod encoding;
+pub mod feed;
+pub mod frame;
+pub mod graph;
+pub mod hints;
+pub mod history;
+pub mod i18n;
 #[cfg(feature = "json")]
 pub mod json;
+pub mod key;
+pub mod key_order;
+pub mod lint;
+pub mod markdown;
+pub mod matching;
+pub mod merge;
+pub mod outline;
 pub mod parser;
+pub mod path;
+pub mod pragma;
+pub mod query;
+#[cfg(feature = "repl")]
+pub mod repl;
+pub mod schema;
+pub mod script;
+#[cfg(feature = "rhai")]
+pub mod scripting;
+#[cfg(feature = "snapshots")]
+pub mod snapshot;
+pub mod sniff;
+pub mod span;
+pub mod store;
+pub mod table;
+pub mod template;
+pub mod truncate;
+pub mod usage;
 pub mod value;
+pub mod workspace;
+pub mod writer;
 
 pub use parser::{parse, parse_str};
-pub use value::{List, Object, SpellConfig, Value};
+pub use value::{
+    CommentPlacement, InlineLimits, InlineMode, Kind, List, Object, RawText, SpellConfig, Value,
+    escape_str, unescape_str,
+};
 
+#[cfg(not(feature = "preserve_order"))]
 use std::collections::HashMap;
 
-use klex::{Loc, Token};
+use klex::Loc;
 use thiserror::Error;
 
+use crate::span::{LineCol, Span};
+
+/// The key type of objects, the same with every feature. See [`key`] for what the
+/// `shared_keys` and `compact_keys` features change about it.
+pub type KeyT = key::Key;
+
 /// The map type when the `preserve_order` feature is on
 #[cfg(feature = "preserve_order")]
-pub type MapT = indexmap::IndexMap<String, Value>;
+pub type MapT = indexmap::IndexMap<KeyT, Value>;
 /// The map type when the `preserve_order` feature is off
 #[cfg(not(feature = "preserve_order"))]
-pub type MapT = HashMap<String, Value>;
-
-/// Something went wrong?!
-#[derive(Debug, Error, PartialEq)]
-pub enum GonError {
-    /// An error from the tokenizer `klex`
-    #[error("couldn't tokenize")]
-    LexerErr(#[from] klex::KlexError),
+pub type MapT = HashMap<KeyT, Value>;
+
+/// Something went wrong?! What went wrong is the [`ErrorKind`], the other getters tell
+/// where and what would have been fine instead. The kinds are `#[non_exhaustive]` and the
+/// context is only reachable through getters, so new kinds of errors and more context don't
+/// break code that inspects them.
+/// # Usage example
+/// ```rust
+/// use gon::{ErrorKind, parse_str, span::LineCol};
+/// let e = parse_str("{\n    hp 10\n}").unwrap_err();
+/// assert_eq!(e.kind(), &ErrorKind::MissingColon("hp".into()));
+/// assert_eq!(e.at(), Some(LineCol { line: 2, col: 8 }));
+/// assert_eq!(e.expected(), [":"]);
+/// assert_eq!(e.source_line(), Some("    hp 10"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GonError(Box<ErrorContext>);
+
+/// Everything a [`GonError`] knows, boxed to keep results small
+#[derive(Debug, Clone, PartialEq)]
+struct ErrorContext {
+    kind: ErrorKind,
+    span: Option<Span>,
+    at: Option<LineCol>,
+    expected: Vec<&'static str>,
+    source_line: Option<String>,
+    /// Where klex saw the token, until the parser turns it into a span
+    loc: Option<Loc>,
+}
+
+/// What kind of [`GonError`] happened
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The tokenizer gave up, usually on an unclosed string
+    #[error("couldn't tokenize: {0}")]
+    Lex(String),
     /// Attempted to parse the empty string
     #[error("no value present")]
-    NoValueErr,
-    /// An invalid value
-    #[error(
-        "invalid value: '{0}' at {1}\n\tExpected one of: None, \"...\", <number>, true/false, [values], {{key: value}}"
-    )]
-    InvalidValue(String, Loc),
-    /// An unexpected token
-    #[error("unexpected token: '{0:?}' at {1}")]
-    UnexpectedToken(Token, Loc),
+    NoValue,
+    /// A symbol where a value should be
+    #[error("invalid value '{0}'")]
+    InvalidValue(String),
+    /// A token that doesn't fit where it is, spelled like in the source
+    #[error("unexpected token '{0}'")]
+    UnexpectedToken(String),
     /// A missing : after an object key
-    #[error("missing colon : after key '{0}' at {1}")]
-    MissingColon(String, Loc),
+    #[error("missing colon : after key '{0}'")]
+    MissingColon(String),
     /// A key within an object without a value
-    #[error("missing value after '{0}:' at {1}")]
-    MissingValue(String, Loc),
-    /// A delimiter wasn't closed
-    #[error("unclosed delimiter: missing '{0:?}' which was opened at {1}")]
-    UnclosedDelimiter(char, Loc),
+    #[error("missing value after '{0}:'")]
+    MissingValue(String),
+    /// A delimiter wasn't closed, the error is where it was opened
+    #[error("unclosed delimiter: missing '{0}' for the one opened")]
+    UnclosedDelimiter(char),
     /// There are leftover tokens after parsing everything
-    #[error("leftover tokens starting with '{0:?}' at {1}")]
-    LeftoverTokens(Token, Loc),
+    #[error("leftover tokens starting with '{0}'")]
+    LeftoverTokens(String),
+    /// A symbol that isn't bound by any `let`
+    #[error("undefined variable '{0}'")]
+    UndefinedVariable(String),
+    /// A malformed or incomplete `let` binding
+    #[error("invalid binding '{0}'")]
+    InvalidBinding(String),
+    /// A malformed `@if`/`@else` section
+    #[error("invalid conditional section: {0}")]
+    InvalidSection(String),
+    /// A number literal that isn't spelled like a number, e.g. `1.2.3` or `--5`
+    #[error("malformed number '{0}'")]
+    MalformedNumber(String),
+    /// Syntax the [`ParseOptions`](parser::ParseOptions) forbid, like comments in a
+    /// [`Dialect::JsonCompat`](dialect::Dialect::JsonCompat) document
+    #[error("{0} aren't allowed in this dialect")]
+    Disallowed(&'static str),
+    /// The [`CancelToken`](cancel::CancelToken) of the
+    /// [`ParseOptions`](parser::ParseOptions) was cancelled
+    #[error("cancelled")]
+    Cancelled,
+}
+
+impl GonError {
+    pub(crate) fn new(kind: ErrorKind, loc: Option<Loc>) -> Self {
+        Self(Box::new(ErrorContext {
+            kind,
+            span: None,
+            at: None,
+            expected: Vec::new(),
+            source_line: None,
+            loc,
+        }))
+    }
+
+    pub(crate) fn expecting(mut self, expected: &[&'static str]) -> Self {
+        self.0.expected = expected.to_vec();
+        self
+    }
+
+    /// Fills in the span, line and column and source line for `span` in `src`
+    pub(crate) fn located(mut self, src: &str, span: Span) -> Self {
+        let line_start = src[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = src[span.start..]
+            .find('\n')
+            .map_or(src.len(), |i| span.start + i);
+        self.0.at = Some(LineCol {
+            line: src[..line_start].matches('\n').count() + 1,
+            col: src[line_start..span.start].chars().count() + 1,
+        });
+        self.0.source_line = Some(src[line_start..line_end].trim_end_matches('\r').into());
+        self.0.span = Some(span);
+        self
+    }
+
+    /// Where klex saw the token the error is about
+    pub(crate) fn loc(&self) -> Option<Loc> {
+        self.0.loc
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.0.kind
+    }
+
+    /// The token where the parser noticed the problem, `None` if it isn't about one place,
+    /// like for empty input
+    pub fn span(&self) -> Option<Span> {
+        self.0.span
+    }
+
+    /// Where [`span`](Self::span) starts
+    pub fn at(&self) -> Option<LineCol> {
+        self.0.at
+    }
+
+    /// What would have been fine instead, like `":"` or `"a value"`
+    pub fn expected(&self) -> &[&'static str] {
+        &self.0.expected
+    }
+
+    /// The line of the source that [`span`](Self::span) starts on
+    pub fn source_line(&self) -> Option<&str> {
+        self.0.source_line.as_deref()
+    }
+}
+
+impl From<ErrorKind> for GonError {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind, None)
+    }
+}
+
+impl std::fmt::Display for GonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.kind())?;
+        if let Some(at) = self.at() {
+            write!(f, " at {}:{}", at.line, at.col)?;
+        }
+        match self.expected() {
+            [] => {}
+            [expected] => write!(f, "\n\tExpected: {expected}")?,
+            expected => write!(f, "\n\tExpected one of: {}", expected.join(", "))?,
+        }
+        if let (Some(line), Some(at)) = (self.source_line(), self.at()) {
+            write!(f, "\n\t{line}\n\t{:1$}^", "", at.col - 1)?;
+        }
+        Ok(())
+    }
 }
 
+impl std::error::Error for GonError {}
+
+#[cfg(test)]
+mod testing;
 #[cfg(test)]
 mod tests {
     use super::parser::*;
+    use super::span::{LineCol, Span};
     use super::*;
 
     #[test]
     fn empty_string() {
-        assert_eq!(parse_str(""), Err(GonError::NoValueErr));
+        assert_eq!(
+            parse_str("").map_err(|e| e.kind().clone()),
+            Err(ErrorKind::NoValue)
+        );
     }
 
     #[test]
@@ -99,13 +315,13 @@ mod tests {
     #[test]
     fn single_value_obj() {
         assert_eq!(parse_str("{}"), Ok(Value::Obj(MapT::new())));
-        let a = Value::Obj(HashMap::from([(
-            String::from("pi"),
+        let a = Value::Obj(MapT::from([(
+            "pi".into(),
             Value::Num(String::from("3.14")),
         )]));
         assert_eq!(parse_str("{pi: 3.14}"), Ok(a));
-        let b = Value::Obj(HashMap::from([(
-            String::from("name"),
+        let b = Value::Obj(MapT::from([(
+            "name".into(),
             Value::Str {
                 s: "gon".into(),
                 raw: false,
@@ -126,56 +342,928 @@ mod tests {
         );
     }
 
+    #[test]
+    fn comments() {
+        assert_eq!(
+            parse_str("// the answer\n[42, // not 41\n]"),
+            Ok(Value::List(vec![Value::Num("42".into())]))
+        );
+    }
+
+    #[test]
+    fn let_bindings() {
+        assert_eq!(
+            parse_str("let base = 100; let hp = [base, base]; let base = 1; {hp: hp, mp: base}"),
+            parse_str("{hp: [100, 100], mp: 1}")
+        );
+        assert_eq!(
+            parse_str("let hp = 1; [mp]").unwrap_err().kind(),
+            &ErrorKind::UndefinedVariable("mp".into())
+        );
+        assert_eq!(
+            parse_str("let true = 1; 2").unwrap_err().kind(),
+            &ErrorKind::InvalidBinding("true".into())
+        );
+        assert!(parse_str("let hp = 1 [hp]").is_err());
+        assert!(parse_str("[1] let hp = 1;").is_err());
+    }
+
+    #[test]
+    fn conditional_sections() {
+        let src = r#"{
+            fps: 60,
+            @if(platform == "switch") {fps: 30, docked: false}
+            @else @if(platform != "pc") {fps: 45}
+            @else {fps: 144},
+            modes: [1, @if(debug) [2, @if(verbose) [3]], 4],
+        }"#;
+        let switch = ResolveOptions::new()
+            .define("platform", "switch")
+            .define("debug", "true");
+        assert_eq!(
+            parse_str_with(src, &switch.into()),
+            parse_str("{fps: 30, docked: false, modes: [1, 2, 4]}")
+        );
+        let pc = ResolveOptions::new()
+            .define("platform", "pc")
+            .define("debug", "false");
+        assert_eq!(
+            parse_str_with(src, &pc.into()),
+            parse_str("{fps: 144, modes: [1, 4]}")
+        );
+        assert_eq!(parse_str(src), parse_str("{fps: 45, modes: [1, 4]}"));
+        assert!(matches!(
+            parse_str("[@if(a) {b: 1}]").unwrap_err().kind(),
+            ErrorKind::InvalidSection(_)
+        ));
+        assert_eq!(
+            parse_str("{@if a {b: 1}}").unwrap_err().kind(),
+            &ErrorKind::UnexpectedToken("a".into())
+        );
+    }
+
+    #[test]
+    fn error_context() {
+        let e = parse_str("{\n    a: 1,\n    b 2,\n}").unwrap_err();
+        assert_eq!(e.kind(), &ErrorKind::MissingColon("b".into()));
+        assert_eq!(e.span(), Some(Span::new(18, 1)));
+        assert_eq!(
+            e.to_string(),
+            "missing colon : after key 'b' at 3:7\n\tExpected: :\n\t    b 2,\n\t      ^"
+        );
+        let e = parse_str("[1, 2] // done\n[3]").unwrap_err();
+        assert_eq!(e.kind(), &ErrorKind::LeftoverTokens("[".into()));
+        assert_eq!(e.at(), Some(LineCol { line: 2, col: 1 }));
+        assert_eq!(e.expected(), ["the end of the input"]);
+        let e = parse_str("{\"é\": \"ü\", // x\nb: [1, -x]}").unwrap_err();
+        assert_eq!(e.kind(), &ErrorKind::MissingValue("b".into()));
+        let e = parse_str("// x\n{\"é\": \"ü\", b: {c: 1}").unwrap_err();
+        assert_eq!(e.kind(), &ErrorKind::UnclosedDelimiter('}'));
+        assert_eq!(e.at(), Some(LineCol { line: 2, col: 1 }));
+        let e = parse_str("{\"é\": r\"ü\", b: c}").unwrap_err();
+        assert_eq!(e.span(), Some(Span::new(17, 1)));
+        assert_eq!(e.expected(), ["a value"]);
+        let e = parse_str("[\"open").unwrap_err();
+        assert!(matches!(e.kind(), &ErrorKind::Lex(_)));
+        assert_eq!(e.span(), Some(Span::new(1, 5)));
+        assert_eq!(parse_str("").unwrap_err().span(), None);
+        fn at(_: &str, span: Span) -> Option<Value> {
+            Some(Value::Num(span.start.to_string()))
+        }
+        let options = ParseOptions::new().scalar_hook(at);
+        assert_eq!(
+            parse_str_with("// x\n[a, \"ü\", bc]", &options),
+            parse_str("[6, 9, 15]")
+        );
+    }
+
+    #[test]
+    fn scalar_hooks() {
+        fn vec(src: &str, _: Span) -> Option<Value> {
+            let args = src.strip_prefix("vec(")?.strip_suffix(')')?;
+            let xs = args.split(',').map(|x| Value::Num(x.into())).collect();
+            Some(Value::tagged("vec", Value::List(xs)))
+        }
+        fn size(src: &str, _: Span) -> Option<Value> {
+            let (w, h) = src.split_once('x')?;
+            let dims = [w, h].map(|d| d.parse::<u32>().ok().map(|_| Value::Num(d.into())));
+            let [Some(w), Some(h)] = dims else {
+                return None;
+            };
+            Some(Value::tagged("size", Value::List(vec![w, h])))
+        }
+        let options = ParseOptions::new().scalar_hook(vec).scalar_hook(size);
+        let value =
+            parse_str_with("{pos: vec(1, 2, 3) grid: 2x3, n: 4, s: \"2x3\"}", &options).unwrap();
+        let obj = value.as_obj().unwrap();
+        assert_eq!(
+            obj["pos"],
+            Value::tagged("vec", parse_str("[1, 2, 3]").unwrap())
+        );
+        assert_eq!(
+            obj["grid"],
+            Value::tagged("size", parse_str("[2, 3]").unwrap())
+        );
+        assert_eq!(obj["n"], Value::Num("4".into()));
+        assert_eq!(obj["s"].type_name(), "str");
+        assert_eq!(obj["grid"].min_spell(), "[2,3]");
+    }
+
+    #[test]
+    fn custom_renderer() {
+        fn matrix(value: &Value, buf: &mut String) -> Option<()> {
+            let Value::Tagged { tag, value } = value else {
+                return None;
+            };
+            if tag != "matrix" {
+                return None;
+            }
+            let rows = value.as_list()?.as_vec_of_f64().ok()?;
+            buf.push_str(&format!("matrix{rows:?}"));
+            Some(())
+        }
+        fn declines(_: &Value, buf: &mut String) -> Option<()> {
+            buf.push_str("garbage");
+            None
+        }
+        let mut value = parse_str("{id: [1, 2]}").unwrap();
+        let Value::Obj(obj) = &mut value else {
+            unreachable!()
+        };
+        obj.insert(
+            "m".into(),
+            Value::tagged("matrix", parse_str("[1, 0.5]").unwrap()),
+        );
+        let config = SpellConfig {
+            indent_amount: 2,
+            ..Default::default()
+        };
+        let spelled = value.spell(config.custom_renderer(matrix)).unwrap();
+        assert!(spelled.contains("  m: matrix[1.0, 0.5]"));
+        assert!(spelled.contains("  id: [1, 2]"));
+        let spelled = value.spell(config.custom_renderer(declines)).unwrap();
+        assert!(!spelled.contains("garbage"));
+        assert_eq!(spelled, value.spell(config).unwrap());
+    }
+
+    #[test]
+    fn inline_small_objects() {
+        let value = parse_str(r#"{pos: {x: 1, y: 2}, big: {a: 1, b: 2, c: 3}, deep: {v: {x: 1}}}"#)
+            .unwrap();
+        let config = SpellConfig {
+            inline_small_objects: Some(InlineLimits {
+                max_entries: 2,
+                max_width: 20,
+            }),
+            ..Default::default()
+        };
+        let spelled = value.spell(config).unwrap();
+        let pos = spelled.lines().find(|l| l.contains("pos")).unwrap();
+        assert!(pos.contains("x: 1") && pos.contains("y: 2"));
+        assert!(spelled.contains("\n        c: 3"));
+        assert!(spelled.contains("        v: {x: 1}\n"));
+        assert_eq!(parse_str(&spelled), Ok(value.clone()));
+        let narrow = SpellConfig {
+            inline_small_objects: Some(InlineLimits {
+                max_entries: 2,
+                max_width: 5,
+            }),
+            ..Default::default()
+        };
+        assert!(!value.spell(narrow).unwrap().contains("{x: 1"));
+    }
+
+    #[test]
+    fn inline_lists() {
+        let value = parse_str("[1, 2, 3, 4, 5, 6]").unwrap();
+        let spell = |inline_lists| {
+            let config = SpellConfig {
+                inline_lists,
+                ..Default::default()
+            };
+            value.spell(config).unwrap()
+        };
+        assert_eq!(spell(InlineMode::default()).lines().count(), 8);
+        assert_eq!(spell(InlineMode::Always), "[1, 2, 3, 4, 5, 6]");
+        assert_eq!(spell(InlineMode::Never).lines().count(), 8);
+        let wide = InlineLimits {
+            max_entries: 10,
+            max_width: 18,
+        };
+        assert_eq!(spell(InlineMode::Limits(wide)), "[1, 2, 3, 4, 5, 6]");
+        let narrow = InlineLimits {
+            max_width: 17,
+            ..wide
+        };
+        assert_eq!(spell(InlineMode::Limits(narrow)).lines().count(), 8);
+        let nested = parse_str("[[1]]").unwrap();
+        let config = SpellConfig {
+            inline_lists: InlineMode::Always,
+            ..Default::default()
+        };
+        assert_eq!(nested.spell(config).unwrap(), "[\n    [1]\n]");
+    }
+
+    #[test]
+    fn dotted_keys() {
+        let options = parser::ParseOptions::new().dotted_keys(true);
+        let value = parser::parse_str_with(
+            r#"{
+                a.b.c: 1,
+                a.b.d: [2],
+                a: {e: 3},
+                x.y: {z: None},
+                "q.r": 4,
+                s."t u": 5,
+            }"#,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            value,
+            parse_str(
+                r#"{a: {b: {c: 1, d: [2]}, e: 3}, x: {y: {z: None}}, "q.r": 4, s: {"t u": 5}}"#
+            )
+            .unwrap()
+        );
+        let config = SpellConfig {
+            dotted_keys: true,
+            ..Default::default()
+        };
+        let spelled = value.spell(config).unwrap();
+        assert!(spelled.contains("\n    x.y.z: None"), "{spelled}");
+        assert!(spelled.contains("\n    s: {\n"), "{spelled}");
+        assert_eq!(parser::parse_str_with(&spelled, &options), Ok(value));
+        assert!(matches!(
+            parser::parse_str_with("{a.: 1}", &options)
+                .unwrap_err()
+                .kind(),
+            ErrorKind::UnexpectedToken(_)
+        ));
+    }
+
+    #[test]
+    fn skip_none() {
+        let value = parse_str("{a: None, b: {c: None, d: 1}, e: [None], f: {g: None}}").unwrap();
+        assert!(!value.is_nullish());
+        assert!(Value::tagged("t", Value::None).is_nullish());
+        let config = SpellConfig {
+            skip_none: true,
+            inline_small_objects: Some(InlineLimits {
+                max_entries: 1,
+                max_width: 80,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            value.spell(config).unwrap(),
+            "{\n    b: {d: 1},\n    e: [None],\n    f: {\n    }\n}"
+        );
+        let obj = value.as_obj().unwrap();
+        assert_eq!(obj.get_non_none("a"), None);
+        assert!(obj.get_non_none("b").is_some());
+        assert_eq!(obj.presence::<&Object>("a"), Ok(value::Presence::None));
+        assert_eq!(
+            obj.presence::<bool>("b").unwrap_err().to_string(),
+            "field 'b': expected bool, found obj"
+        );
+        assert_eq!(
+            obj.presence::<bool>("z").map(value::Presence::into_option),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn normalize_numbers() {
+        let config = SpellConfig {
+            normalize_numbers: true,
+            ..Default::default()
+        };
+        for (num, normalized) in [
+            ("1_000", "1000"),
+            ("-007", "-7"),
+            ("0", "0"),
+            ("0.500", "0.5"),
+            ("12.000", "12.0"),
+            ("00.01", "0.01"),
+            ("1_000.50E+03", "1000.5e3"),
+            ("2e-05", "2e-5"),
+            ("1E00", "1e0"),
+            ("nan", "nan"),
+        ] {
+            let spelled = Value::Num(num.into()).spell(config).unwrap();
+            assert_eq!(spelled, normalized, "{num}");
+        }
+        let value = parse_str("{a: [1_0, 02]}").unwrap();
+        assert_eq!(value.spell(config).unwrap(), "{\n    a: [10, 2]\n}");
+        assert_eq!(
+            value.spell(SpellConfig::default()).unwrap(),
+            "{\n    a: [1_0, 02]\n}"
+        );
+    }
+
+    #[test]
+    fn output_limits() {
+        use value::{LimitError, OutputLimits};
+        let mut deep = Value::Num("1".into());
+        for _ in 0..1000 {
+            deep = Value::List(vec![deep]);
+        }
+        let limits = OutputLimits {
+            max_depth: 64,
+            ..Default::default()
+        };
+        assert_eq!(deep.min_spell_limited(limits), Err(LimitError::TooDeep(64)));
+        let config = SpellConfig {
+            output_limits: limits,
+            ..Default::default()
+        };
+        assert_eq!(deep.spell_limited(config), Err(LimitError::TooDeep(64)));
+        assert!(deep.spell(config).is_err());
+
+        let long = Value::Str {
+            s: "x".repeat(100),
+            raw: false,
+        };
+        let wide = Value::List(vec![long; 1000]);
+        let limits = OutputLimits {
+            max_bytes: 1000,
+            ..Default::default()
+        };
+        assert_eq!(
+            wide.min_spell_limited(limits),
+            Err(LimitError::TooLong(1000))
+        );
+        let small = parse_str("{a: [1, 2], b: None}").unwrap();
+        assert_eq!(small.min_spell_limited(limits), Ok(small.min_spell()));
+        let config = SpellConfig {
+            output_limits: limits,
+            ..Default::default()
+        };
+        assert_eq!(wide.spell_limited(config), Err(LimitError::TooLong(1000)));
+        assert_eq!(
+            small.spell_limited(config),
+            Ok(small.spell(SpellConfig::default()).unwrap())
+        );
+    }
+
+    #[test]
+    fn spell_config_from_value() {
+        let profile = parse_str(
+            r#"{
+                indent_amount: 2,
+                indent_char: "\t",
+                trailing_commas: true,
+                inline_lists: {max_entries: 3},
+                inline_small_objects: {max_width: 40},
+                output_limits: {max_bytes: 4096},
+            }"#,
+        )
+        .unwrap();
+        let config = SpellConfig::from_value(&profile).unwrap();
+        assert_eq!(config.indent_amount, 2);
+        assert_eq!(config.indent_char, '\t');
+        assert!(config.trailing_commas);
+        assert_eq!(config.max_width, SpellConfig::default().max_width);
+        assert_eq!(
+            config.inline_lists,
+            InlineMode::Limits(InlineLimits {
+                max_entries: 3,
+                ..Default::default()
+            })
+        );
+        assert_eq!(config.inline_small_objects.unwrap().max_width, 40);
+        assert_eq!(config.output_limits.max_bytes, 4096);
+        assert_eq!(config.output_limits.max_depth, usize::MAX);
+        for bad in [
+            "{indent_amount: -1}",
+            "{indent_char: \"ab\"}",
+            "{inline_lists: \"sometimes\"}",
+            "{inline_small_objects: {max_lines: 2}}",
+            "{output_limits: 100}",
+            "{colour: true}",
+            "[]",
+        ] {
+            let err = SpellConfig::from_value(&parse_str(bad).unwrap()).unwrap_err();
+            assert!(matches!(err, value::SpellConfigError::Invalid(..)), "{bad}");
+        }
+    }
+
+    const CORPUS: &[&str] = &[
+        include_str!("../example.gon"),
+        "None",
+        r#""""#,
+        "-3.5",
+        r#"r"raw  string\n with   spaces""#,
+        "[]",
+        "{}",
+        "[[], {}, [[]], {a: {}}]",
+        "[1, 2, 3, 4, 5, 6, 7]",
+        r#"["one", -2, 3.0, true, None]"#,
+        r#"[{a: 1}, 2, [3, [4, [5]]]]"#,
+        r#"{"": 1, "a b": 2, "x\"y": 3, "{": 4, "123": 5, "-1": 6, none: 7, "é": 8}"#,
+        r#"{s: "two  spaces, a\ttab and a\nnewline", t: "  padded  "}"#,
+        r#"{long: "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.", word: "supercalifragilisticexpialidocious"}"#,
+        r#"[["Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat."]]"#,
+        r#"{pos: {x: 1, y: 2}, color: {r: 255, g: 0, b: 170, a: 1}, tags: ["a", "b"], matrix: [[1, 0], [0, 1]]}"#,
+        r#"{escapes: "quote \" backslash \\ nul \0 cr \r", list: [r"raw", "cooked"]}"#,
+    ];
+
+    fn corpus_configs() -> Vec<SpellConfig> {
+        let mut configs = Vec::new();
+        for (indent_amount, indent_char) in [(0, ' '), (2, ' '), (4, ' '), (1, '\t')] {
+            for trailing_commas in [false, true] {
+                for (max_width, preserve_strings) in
+                    [(0, true), (12, true), (40, true), (12, false), (40, false)]
+                {
+                    for inline_lists in [
+                        InlineMode::default(),
+                        InlineMode::Never,
+                        InlineMode::Always,
+                        InlineMode::Limits(InlineLimits {
+                            max_entries: 3,
+                            max_width: 20,
+                        }),
+                    ] {
+                        for inline_small_objects in [None, Some(InlineLimits::default())] {
+                            configs.push(SpellConfig {
+                                indent_amount,
+                                indent_char,
+                                trailing_commas,
+                                max_width,
+                                preserve_strings,
+                                inline_lists,
+                                inline_small_objects,
+                                ..Default::default()
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        configs
+    }
+
+    #[test]
+    fn spelling_is_a_fixpoint() {
+        let configs = corpus_configs();
+        for src in CORPUS {
+            let value = parse_str(src).unwrap_or_else(|e| panic!("{src}: {e}"));
+            assert_eq!(parse_str(&value.min_spell()).as_ref(), Ok(&value), "{src}");
+            for config in &configs {
+                let once = value.spell(*config).unwrap();
+                let reparsed = parse_str(&once).unwrap_or_else(|e| panic!("{once}: {e}"));
+                let twice = reparsed.spell(*config).unwrap();
+                assert_eq!(once, twice, "{config:?}");
+                if config.max_width == 0 || config.preserve_strings {
+                    assert_eq!(reparsed, value, "{config:?}\n{once}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn subtrees_spell_like_in_place() {
+        let configs = corpus_configs();
+        let everything: path::Pattern = "**".parse().unwrap();
+        for src in CORPUS {
+            let value = parse_str(src).unwrap();
+            for config in &configs {
+                let whole = value.spell(*config).unwrap();
+                for (path, _) in path::select(&value, &everything) {
+                    let part = value.spell_at(&path, *config).unwrap().unwrap();
+                    assert!(whole.contains(&part), "{path} of {src} with {config:?}");
+                }
+            }
+        }
+        let value = parse_str("{a: 1}").unwrap();
+        let missing = "b".parse().unwrap();
+        assert_eq!(value.spell_at(&missing, SpellConfig::default()), Ok(None));
+    }
+
+    #[test]
+    fn string_continuations() {
+        assert_eq!(
+            parse_str(r#"["a " + "b" + r"c", r"d" + "e"]"#),
+            parse_str(r#"["a bc", r"de"]"#)
+        );
+        assert!(parse_str(r#""a" + 1"#).is_err());
+        let s = "two  spaces\nand a newline, then a rather long tail";
+        let value = Value::Str {
+            s: s.into(),
+            raw: false,
+        };
+        let config = SpellConfig {
+            max_width: 16,
+            ..Default::default()
+        };
+        assert_eq!(
+            value.spell(config).unwrap(),
+            "\"two  spaces\\n\" +\n    \"and a newline, \" +\n    \"then a rather \" +\n    \"long tail\""
+        );
+    }
+
+    #[test]
+    fn escape_and_unescape() {
+        for s in [
+            "",
+            "plain",
+            "quote \" backslash \\ nul \0 cr \r",
+            "tab\tnewline\n",
+            "é ${x}",
+        ] {
+            let escaped = escape_str(s);
+            assert_eq!(unescape_str(&escaped).as_deref(), Ok(s));
+            let value = Value::Str {
+                s: s.into(),
+                raw: false,
+            };
+            assert_eq!(parse_str(&format!("\"{escaped}\"")), Ok(value));
+        }
+        assert_eq!(escape_str("a\"b"), "a\\\"b");
+        assert!(unescape_str("a\"b").is_err());
+        assert!(unescape_str("trailing \\").is_err());
+    }
+
+    #[test]
+    fn display_and_from_str() {
+        let value: Value = r#"{name: "gon", tags: ["a", "b"]}"#.parse().unwrap();
+        assert_eq!(value.to_string(), value.min_spell());
+        assert_eq!(
+            format!("{value:#}"),
+            value.spell(SpellConfig::default()).unwrap()
+        );
+        assert_eq!(value.to_string().parse(), Ok(value));
+        assert!("[1,".parse::<Value>().is_err());
+    }
+
+    #[test]
+    fn completeness() {
+        use Completeness::*;
+        for (src, expected) in [
+            ("", Incomplete),
+            ("  // just a comment", Incomplete),
+            ("[1, /* ] */", Incomplete),
+            (r#"["escaped \" quote"#, Incomplete),
+            (r#"["escaped \" quote"]"#, Complete),
+            ("{xs: [vec(1, 2)]}", Complete),
+            ("42", Complete),
+            ("[1, 2}", Invalid),
+            ("]", Invalid),
+        ] {
+            assert_eq!(is_complete(src), expected, "{src}");
+        }
+    }
+
+    #[cfg(feature = "shared_keys")]
+    #[test]
+    fn equal_keys_are_shared() {
+        let Ok(Value::List(xs)) = parse_str("[{hp: 1}, {hp: 2}]") else {
+            panic!("expected a list");
+        };
+        let keys: Vec<&KeyT> = xs
+            .iter()
+            .map(|x| match x {
+                Value::Obj(obj) => obj.keys().next().unwrap(),
+                _ => panic!("expected an object"),
+            })
+            .collect();
+        assert_eq!(keys[0].as_ptr(), keys[1].as_ptr());
+    }
+
+    #[test]
+    fn tokens_with_spans() {
+        use parser::{TokenKind::*, tokenize};
+        let src = "let ä = NONE; {\"k\\\"\": [-1.5, R \"x\" + \"y\", @if(a) [ä]]} /* end */";
+        let tokens = tokenize(src);
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                Keyword, Sym, Equals, Keyword, Semicolon, LBrace, Str, Colon, LBrack, Dash, Num,
+                Comma, RawStr, Plus, Str, Comma, At, Sym, LParen, Sym, RParen, LBrack, Sym, RBrack,
+                RBrack, RBrace, Comment,
+            ]
+        );
+        let texts: Vec<_> = tokens.iter().map(|t| t.text(src)).collect();
+        assert_eq!(texts[1], "ä");
+        assert_eq!(texts[6], "\"k\\\"\"");
+        assert_eq!(texts[10], "1.5");
+        assert_eq!(texts[12], "R \"x\"");
+        assert_eq!(texts[26], "/* end */");
+        let numbers = "[1e-5, 2E+3, 0xE-1, 3e-x]";
+        let texts: Vec<_> = tokenize(numbers).iter().map(|t| t.text(numbers)).collect();
+        let expected = [
+            "[", "1e-5", ",", "2E+3", ",", "0xE", "-", "1", ",", "3e", "-", "x", "]",
+        ];
+        assert_eq!(texts, expected);
+        assert_eq!(tokenize(numbers)[3].kind, Num);
+        let arrow = "{a: →}\u{a0}©";
+        let tokens = tokenize(arrow);
+        let kinds: Vec<_> = tokens.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, [LBrace, Sym, Colon, Other, RBrace, Other, Other]);
+        assert_eq!(tokens[3].text(arrow), "→");
+        assert_eq!(tokens[6].text(arrow), "©");
+        assert!(parse_str("{a: →}").is_err());
+        let unterminated = tokenize("[\"abc");
+        assert_eq!(unterminated[1].kind, Unterminated);
+        assert_eq!(unterminated[1].span, span::Span::new(1, 4));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn streamed_json_matches_serde_json() {
+        let value = parse_str(
+            "{a: [1, -2.5, {}, []], b: {c: \"q\\\"\\\\\\n\u{1}\", d: None}, e: true, f: r\"x\"}",
+        )
+        .unwrap();
+        let json = serde_json::Value::from(value.clone());
+        for (pretty, expected) in [
+            (false, serde_json::to_string(&json).unwrap()),
+            (true, serde_json::to_string_pretty(&json).unwrap()),
+        ] {
+            let mut out = Vec::new();
+            json::write_json(&value, &mut out, pretty).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected);
+        }
+    }
+
+    #[cfg(all(feature = "json", feature = "preserve_order"))]
+    #[test]
+    fn json_keeps_key_order() {
+        let src = "{z: 1, a: {y: [{c: 1, b: 2}], x: None}, m: true}";
+        let value = parse_str(src).unwrap();
+        let json = serde_json::Value::from(value.clone());
+        let serde_json::Value::Object(obj) = &json else {
+            panic!("{json:?} isn't an object");
+        };
+        let keys: Vec<_> = obj.keys().cloned().collect();
+        assert_eq!(keys, ["z", "a", "m"]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"z":1,"a":{"y":[{"c":1,"b":2}],"x":null},"m":true}"#
+        );
+        let mut out = Vec::new();
+        json::write_json(&value, &mut out, false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), json.to_string());
+
+        let parsed: serde_json::Value = serde_json::from_str(&json.to_string()).unwrap();
+        assert_eq!(Value::from(parsed).min_spell(), value.min_spell());
+        assert_eq!(Value::from(json).min_spell(), value.min_spell());
+    }
+
+    #[cfg(all(feature = "json", feature = "preserve_order"))]
+    #[test]
+    fn transcoding_matches_writing_the_parsed_value() {
+        let src = "{z: [1, - 2, 1e400, {}, []], a: {s: \"q\\\"\" + r\"x\", n: None}, m: TRUE}";
+        for pretty in [false, true] {
+            let (mut streamed, mut written) = (Vec::new(), Vec::new());
+            json::transcode(src, &mut streamed, pretty, value::Strictness::Lenient).unwrap();
+            json::write_json(&parse_str(src).unwrap(), &mut written, pretty).unwrap();
+            assert_eq!(String::from_utf8(streamed), String::from_utf8(written));
+        }
+        let strict =
+            json::transcode(src, Vec::new(), false, value::Strictness::Strict).unwrap_err();
+        assert!(strict.to_string().starts_with("z[2]: "), "{strict}");
+        for not_plain in ["let x = 1; [x]", "{a: 1, b: {}, a: 2}", "[1] [2]"] {
+            let mut out = Vec::new();
+            let result = json::transcode(not_plain, &mut out, false, value::Strictness::Lenient);
+            assert!(matches!(result, Err(json::TranscodeError::NotPlain(_))));
+            assert!(out.is_empty());
+        }
+    }
+
+    #[test]
+    fn raw_text_is_spelled_verbatim() {
+        let value = Value::List(vec![
+            Value::Raw("{x: 1, // kept\ny: 2}".into()),
+            Value::Raw("not gon".into()),
+        ]);
+        assert_eq!(value.min_spell(), "[{x: 1, // kept\ny: 2},not gon]");
+        let Value::List(xs) = &value else {
+            unreachable!()
+        };
+        assert_eq!(xs[0].kind(), Kind::Obj);
+        assert_eq!(xs[1].kind(), Kind::Str);
+        // the line is ended so the comment doesn't swallow what follows
+        let commented = Value::List(vec![Value::Raw("1 // one".into()), Value::Num("2".into())]);
+        assert_eq!(commented.min_spell(), "[1 // one\n,2]");
+        assert_eq!(
+            commented.spell(SpellConfig::default()).unwrap(),
+            "[\n    1 // one\n    2\n]"
+        );
+        assert_eq!(
+            parse_str(&commented.min_spell()).unwrap(),
+            parse_str("[1, 2]").unwrap()
+        );
+        #[cfg(feature = "json")]
+        assert_eq!(
+            serde_json::Value::from(value),
+            serde_json::Value::from(parse_str(r#"[{x: 1, y: 2}, "not gon"]"#).unwrap())
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn strict_numbers() {
+        use value::Strictness;
+        let value = Value::Obj(MapT::from([(
+            "a".into(),
+            Value::List(vec![
+                Value::Num("18446744073709551615".into()),
+                Value::Num("1e400".into()),
+            ]),
+        )]));
+        let mut out = Vec::new();
+        json::write_json(&value, &mut out, false).unwrap();
+        assert_eq!(out, br#"{"a":[18446744073709551615,null]}"#);
+        assert_eq!(
+            serde_json::Value::from(value.clone()).to_string(),
+            r#"{"a":[18446744073709551615,null]}"#
+        );
+        let err = json::write_json_with(&value, Vec::new(), false, Strictness::Strict).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "a[1]: 1e400 doesn't fit into a JSON number"
+        );
+        let huge = Value::Num("1".repeat(40));
+        assert_eq!(huge.as_i128_with(Strictness::Lenient), Ok(None));
+        assert_eq!(
+            huge.as_i128_with(Strictness::Strict).unwrap_err().target,
+            "i128"
+        );
+    }
+
+    #[test]
+    fn malformed_numbers() {
+        for src in ["1_000", "-0.5", "[1, -2]"] {
+            assert!(parse_str(src).is_ok(), "{src}");
+        }
+        for src in ["1.2.3", "--5", "1.", "{a: 1.2.3}"] {
+            assert!(
+                matches!(
+                    parse_str(src).map_err(|e| e.kind().clone()),
+                    Err(ErrorKind::MalformedNumber(_))
+                ),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn typed_list_extraction() {
+        let value = parse_str(r#"[{a: 1}, {b: 2}]"#).unwrap();
+        let list = value.as_list().unwrap();
+        assert_eq!(list.as_vec_of_obj().unwrap().len(), 2);
+        assert_eq!(
+            list.as_vec_of_str(),
+            Err(value::ElementError {
+                index: 0,
+                error: value::TypeError {
+                    expected: "str",
+                    found: "obj"
+                }
+            })
+        );
+        let value = parse_str("[1, 2.5, -3]").unwrap();
+        let list = value.as_list().unwrap();
+        assert_eq!(list.as_vec_of_f64(), Ok(vec![1.0, 2.5, -3.0]));
+        assert_eq!(list.try_map::<i64>().unwrap_err().index, 1);
+    }
+
+    #[test]
+    fn homogeneous_lists() {
+        let value = parse_str(r#"[[1], [2, 3], {}, None]"#).unwrap();
+        let list = value.as_list().unwrap();
+        assert_eq!(list.element_kinds(), [Kind::List, Kind::Obj, Kind::None]);
+        assert_eq!(
+            value.assert_homogeneous(Kind::List),
+            Err(value::KindError {
+                path: "[2]".parse().unwrap(),
+                expected: Kind::List,
+                found: Kind::Obj,
+            })
+        );
+        assert!(
+            Value::tagged("v", parse_str("[1, 2]").unwrap())
+                .assert_homogeneous(Kind::Num)
+                .is_ok()
+        );
+        assert!(
+            Value::List(Vec::new())
+                .assert_homogeneous(Kind::Str)
+                .is_ok()
+        );
+        let not_a_list = parse_str("{a: [1]}").unwrap().assert_homogeneous(Kind::Num);
+        assert_eq!(
+            not_a_list.unwrap_err().to_string(),
+            "expected list, found obj"
+        );
+    }
+
+    #[test]
+    fn kinds() {
+        for kind in Kind::ALL {
+            assert_eq!(kind.name().parse::<Kind>(), Ok(kind));
+        }
+        assert_eq!(
+            "int".parse::<Kind>(),
+            Err(value::UnknownKind("int".to_string()))
+        );
+        assert_eq!(Kind::from(&parse_str("[1]").unwrap()), Kind::List);
+        assert_eq!(Value::Raw("0x10".into()).type_name(), "raw");
+        assert_eq!(schema::Schema::from(Kind::Obj).kind(), Some(Kind::Obj));
+        let schema = schema::Schema::from_value(&parse_str(r#""none | num""#).unwrap()).unwrap();
+        assert_eq!(schema.kind(), None);
+    }
+
+    #[test]
+    fn take_subtrees() {
+        let mut value = parse_str("{a: [1, {b: 2}, 3], c: 4, d: 5}").unwrap();
+        let path = |src: &str| src.parse::<path::Path>().unwrap();
+        assert_eq!(value.take_path(&path("a[1].b")), parse_str("2").ok());
+        assert_eq!(value.take_path(&path("a[0]")), parse_str("1").ok());
+        assert_eq!(value.take_path(&path("a[5]")), None);
+        assert_eq!(value.take_path(&path("c.x")), None);
+        let rest = value.as_obj_mut().unwrap().split_off(&["c", "d"]);
+        assert_eq!(Value::from(rest), parse_str("{c: 4, d: 5}").unwrap());
+        assert_eq!(value, parse_str("{a: [{}, 3]}").unwrap());
+        assert_eq!(value.take_path(&path("")), parse_str("{a: [{}, 3]}").ok());
+        assert_eq!(value, Value::None);
+    }
+
+    #[test]
+    fn typed_field_getters() {
+        let value =
+            parse_str(r#"{hp: 10, speed: 1.5, drops: None, tags: ["a"], boss: 1}"#).unwrap();
+        let obj = value.as_obj().unwrap();
+        assert_eq!(obj.require_i64("hp"), Ok(10));
+        assert_eq!(obj.require_list("tags").map(|xs| xs.len()), Ok(1));
+        assert_eq!(obj.optional_f64("speed"), Ok(Some(1.5)));
+        assert_eq!(obj.optional_str("drops"), Ok(None));
+        assert_eq!(obj.optional_obj("missing"), Ok(None));
+        assert_eq!(
+            obj.optional_bool("boss"),
+            Err(value::FieldError {
+                key: "boss".into(),
+                expected: "bool",
+                found: "num"
+            })
+        );
+        assert_eq!(obj.require_u64("drops").unwrap_err().found, "none");
+        assert_eq!(obj.require_obj("missing").unwrap_err().expected, "obj");
+    }
+
     #[test]
     fn many_values() {
-        let name = Value::Obj(HashMap::from([
+        let name = Value::Obj(MapT::from([
             (
-                String::from("first"),
+                "first".into(),
                 Value::Str {
                     s: "John".into(),
                     raw: false,
                 },
             ),
             (
-                String::from("last"),
+                "last".into(),
                 Value::Str {
                     s: "Doe".into(),
                     raw: false,
                 },
             ),
         ]));
-        let address = Value::Obj(HashMap::from([
+        let address = Value::Obj(MapT::from([
             (
-                String::from("street"),
+                "street".into(),
                 Value::Str {
                     s: "Wood Way".into(),
                     raw: false,
                 },
             ),
-            (String::from("house"), Value::Num(String::from("-9_000"))),
+            ("house".into(), Value::Num(String::from("-9_000"))),
         ]));
         let friends = Value::List(vec![
-            Value::Obj(HashMap::from([(
-                String::from("name"),
+            Value::Obj(MapT::from([(
+                "name".into(),
                 Value::Str {
                     s: "Alice".into(),
                     raw: false,
                 },
             )])),
-            Value::Obj(HashMap::from([(
-                String::from("name"),
+            Value::Obj(MapT::from([(
+                "name".into(),
                 Value::Str {
                     s: "Bob".into(),
                     raw: false,
                 },
             )])),
         ]);
-        let obj = Value::Obj(HashMap::from([
-            (String::from("id"), Value::Num(String::from("456"))),
-            (String::from("name"), name),
-            (String::from("address"), address),
-            (String::from("alive"), Value::Bool(true)),
-            (String::from("friends"), friends),
+        let obj = Value::Obj(MapT::from([
+            ("id".into(), Value::Num(String::from("456"))),
+            ("name".into(), name),
+            ("address".into(), address),
+            ("alive".into(), Value::Bool(true)),
+            ("friends".into(), friends),
         ]));
         assert_eq!(
             parse_str(
@@ -201,4 +1289,47 @@ mod tests {
             Ok(obj)
         );
     }
+
+    #[test]
+    fn validates_while_parsing() {
+        let schema = schema::Schema::from_value(
+            &parse_str(r#"{name: "str", size: "num?", parts: [{id: "num"}]}"#).unwrap(),
+        )
+        .unwrap();
+        let src = r#"let part = {id: "a"};
+            {name: 1, size: None, parts: [{id: 1}, part, @if(big) [{}]], extra: [1]}"#;
+        let options = ParseOptions::new().define("big", "true");
+        let mut expected = schema
+            .validate(&parse_str_with(src, &options).unwrap())
+            .unwrap_err()
+            .0;
+        let Err(ValidatedParseError::Invalid(errors)) =
+            parse_validated_with(src, &schema, &options)
+        else {
+            panic!("expected validation errors");
+        };
+        let mut errors = errors.0;
+        let by_path = |e: &schema::ValidationError| e.path.to_string();
+        expected.sort_by_key(by_path);
+        errors.sort_by_key(by_path);
+        assert_eq!(errors, expected);
+        let Err(ValidatedParseError::Invalid(errors)) =
+            parse_validated_with(src, &schema, &options.clone().fail_fast(true))
+        else {
+            panic!("expected validation errors");
+        };
+        assert_eq!(errors.0.len(), 1);
+        assert_eq!(
+            parse_validated_with(
+                "{name: \"n\", parts: [], extra: 1}",
+                &schema,
+                &ParseOptions::new().prune_unknown(true),
+            ),
+            Ok(parse_str("{name: \"n\", parts: []}").unwrap())
+        );
+        assert!(matches!(
+            parse_validated("{name: ", &schema),
+            Err(ValidatedParseError::Parse(_))
+        ));
+    }
 }
@@ -0,0 +1,1251 @@
+//! Checks of gon source for mistakes and style issues that still parse.
+//!
+//! A [`Linter`] runs [`Rule`]s over a source and reports what they find as [`Diagnostic`]s,
+//! each with the [`Severity`] configured for its rule. The built-in rules are
+//! - `duplicate_keys`: an object spells a key twice, only the last value is kept. An error
+//!   by default, the other rules are warnings.
+//! - `empty_objects`: an object without entries, which usually stands for a missing value.
+//! - `deep_nesting`: objects and lists nested deeper than 8 levels.
+//! - `key_casing`: the keys of an object mix `snake_case`, `camelCase`, `PascalCase` and
+//!   `SCREAMING_CASE`.
+//! - `long_strings`: a string literal of more than 120 chars, which is hard to read and to
+//!   diff. Long strings can be wrapped into literals joined with `+`.
+//! - `mixed_lists`: a list with elements of different kinds, like a string among numbers.
+//!   `None` elements don't count.
+//! - `json_compat`: syntax that isn't JSON, like comments, unquoted keys, `None`, trailing
+//!   commas or escapes JSON doesn't know. Off by default, for files that are also read
+//!   by JSON parsers.
+//!
+//! Projects add their own checks by implementing [`Rule`] and passing it to
+//! [`Linter::rule`]. For the common case of objects that have to define some keys, or
+//! mustn't define others, a [`KeyRule`] does without code and can be read from a gon file
+//! with [`Linter::rules_from_value`].
+//!
+//! In a formatting profile, the `lint` object configures the rules, see
+//! [`Linter::from_value`].
+//! Schema documents are checked by [`Linter::for_schemas`], which adds the rules
+//! `invalid_schema`, `unsatisfiable_schema` and `unused_definitions` of the
+//! [`schema`](crate::schema) module.
+//! # Usage example
+//! ```rust
+//! use gon::lint::{Linter, Severity};
+//! let src = "{maxHp: 10, move_speed: 2, drops: {}, maxHp: 12}";
+//! let linter = Linter::new().severity("empty_objects", Severity::Off);
+//! let found: Vec<_> = linter.lint(src).unwrap().iter().map(|d| d.to_string()).collect();
+//! assert_eq!(found, [
+//!     "warning: keys mix camelCase ('maxHp') and snake_case ('move_speed') [key_casing]",
+//!     "error: 'maxHp' is already a key of this object [duplicate_keys]",
+//! ]);
+//! ```
+//! A rule of a project:
+//! ```rust
+//! use gon::lint::{Finding, Linter, Rule, Source};
+//! struct NoTodos;
+//! impl Rule for NoTodos {
+//!     fn name(&self) -> &str {
+//!         "no_todos"
+//!     }
+//!     fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+//!         for located in source.tokens() {
+//!             if located.token.text(source.text).contains("TODO") {
+//!                 findings.push(Finding {
+//!                     path: located.path.clone(),
+//!                     span: Some(located.token.span),
+//!                     message: "unfinished".into(),
+//!                 });
+//!             }
+//!         }
+//!     }
+//! }
+//! let linter = Linter::empty().rule(NoTodos);
+//! let found = linter.lint(r#"{name: "Goblin", loot: "TODO"}"#).unwrap();
+//! assert_eq!(found[0].to_string(), "warning: loot: unfinished [no_todos]");
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::parser::{SpannedToken, TokenKind, tokenize};
+use crate::path::{Path, Pattern, Segment};
+use crate::span::Span;
+use crate::value::{SpellConfigError, spelling_order};
+use crate::{GonError, Kind, Value, parse_str};
+
+/// How much a [`Diagnostic`] matters
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Severity {
+    /// The rule doesn't run
+    Off,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Off => "off",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// A check of gon sources, see the [module docs](self) for the built-in ones
+pub trait Rule {
+    /// The name the rule is configured by, in snake_case
+    fn name(&self) -> &str;
+
+    /// The severity of the rule unless it's configured otherwise
+    fn default_severity(&self) -> Severity {
+        Severity::Warning
+    }
+
+    /// Adds everything that's wrong with `source` to `findings`
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>);
+}
+
+/// What a [`Rule`] found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    /// Where the offending value is
+    pub path: Path,
+    /// Where the finding is in the source, [`Linter::lint`] fills it in from `path` if
+    /// it's `None`
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+/// A [`Finding`] of a rule and how much it matters
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: String,
+    pub severity: Severity,
+    pub path: Path,
+    pub span: Option<Span>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.severity)?;
+        if !self.path.0.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{} [{}]", self.message, self.rule)
+    }
+}
+
+/// A source to lint: its text, its value and its tokens
+pub struct Source<'s> {
+    pub text: &'s str,
+    pub value: Value,
+    tokens: Vec<LocatedToken>,
+}
+
+/// A token of a [`Source`] and where it is in the value
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LocatedToken {
+    pub token: SpannedToken,
+    /// The path of the value the token is part of or, for keys, of the object
+    pub path: Path,
+    /// Whether the token is the key of an entry
+    pub is_key: bool,
+    /// Tells objects and lists apart, tokens directly inside the same one have the same
+    /// number. `@else` sections count as their own.
+    pub container: usize,
+}
+
+impl<'s> Source<'s> {
+    pub fn new(text: &'s str) -> Result<Self, GonError> {
+        Ok(Self {
+            text,
+            value: parse_str(text)?,
+            tokens: locate(text),
+        })
+    }
+
+    /// The tokens of the value, without comments, `let` bindings and the `@if` sections that
+    /// aren't part of the value
+    pub fn tokens(&self) -> &[LocatedToken] {
+        &self.tokens
+    }
+
+    /// The span of the first token of the value at `path`
+    fn span(&self, path: &Path) -> Option<Span> {
+        self.tokens
+            .iter()
+            .find(|t| !t.is_key && t.path == *path)
+            .map(|t| t.token.span)
+    }
+}
+
+/// Rules with their severities, see the [module docs](self)
+pub struct Linter {
+    rules: Vec<(Box<dyn Rule>, Severity)>,
+}
+
+impl Default for Linter {
+    fn default() -> Self {
+        Self::empty()
+            .rule(DuplicateKeys)
+            .rule(EmptyObjects)
+            .rule(DeepNesting::default())
+            .rule(KeyCasing)
+            .rule(LongStrings::default())
+            .rule(MixedLists)
+            .rule(JsonCompat)
+    }
+}
+
+impl Linter {
+    /// All the built-in rules with their default severities
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// No rules at all
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// The rules for schema documents: [`InvalidSchema`](crate::schema::InvalidSchema),
+    /// [`UnsatisfiableSchema`](crate::schema::UnsatisfiableSchema) and
+    /// [`UnusedDefinitions`](crate::schema::UnusedDefinitions) besides the built-in ones
+    pub fn for_schemas() -> Self {
+        Self::new()
+            .rule(crate::schema::InvalidSchema)
+            .rule(crate::schema::UnsatisfiableSchema)
+            .rule(crate::schema::UnusedDefinitions)
+    }
+
+    /// Adds `rule` with its default severity, replacing a rule with the same name
+    pub fn rule(mut self, rule: impl Rule + 'static) -> Self {
+        let severity = rule.default_severity();
+        let rule: Box<dyn Rule> = Box::new(rule);
+        match self.rules.iter_mut().find(|(r, _)| r.name() == rule.name()) {
+            Some(entry) => entry.0 = rule,
+            None => self.rules.push((rule, severity)),
+        }
+        self
+    }
+
+    /// Sets the severity of the rule called `name`, if there is one
+    pub fn severity(mut self, name: &str, severity: Severity) -> Self {
+        if let Some(entry) = self.rules.iter_mut().find(|(r, _)| r.name() == name) {
+            entry.1 = severity;
+        }
+        self
+    }
+
+    /// Reads the `lint` object of a formatting profile, all the built-in rules with their
+    /// default severities if there is none.
+    /// See [`SpellConfig::from_file`](crate::SpellConfig::from_file).
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, SpellConfigError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| SpellConfigError::Io(path.display().to_string(), e))?;
+        let profile =
+            parse_str(&src).map_err(|e| SpellConfigError::Parse(path.display().to_string(), e))?;
+        match profile.as_obj().and_then(|obj| obj.get(KEY)) {
+            Some(lint) => Self::from_value(lint),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Builds a linter with the built-in rules out of an object of rule names and their
+    /// severity, `"off"`, `"warning"` or `"error"`. `deep_nesting` and `long_strings` also
+    /// take an object `{severity, max}`, where either may be missing. The list `custom`
+    /// adds [`KeyRule`]s, see [`Linter::rules_from_value`].
+    /// # Usage example
+    /// ```rust
+    /// use gon::{lint::Linter, parse_str};
+    /// let linter = Linter::from_value(&parse_str(
+    ///     r#"{empty_objects: "error", deep_nesting: {max: 1}}"#,
+    /// ).unwrap()).unwrap();
+    /// assert_eq!(linter.lint("{a: {b: []}}").unwrap()[0].path.to_string(), "a.b");
+    /// ```
+    pub fn from_value(value: &Value) -> Result<Self, SpellConfigError> {
+        let obj = value
+            .as_obj()
+            .ok_or_else(|| invalid("expected an obj of rules and severities"))?;
+        let mut linter = match obj.get(CUSTOM) {
+            Some(custom) => Self::default().rules_from_value(custom)?,
+            None => Self::default(),
+        };
+        for (name, v) in spelling_order(obj) {
+            let name = &**name;
+            if name == CUSTOM {
+                continue;
+            }
+            if !linter.rules.iter().any(|(r, _)| r.name() == name) {
+                return Err(invalid(format!("unknown rule '{name}'")));
+            }
+            let (severity, max) = match v {
+                Value::Obj(options) => {
+                    for key in options.keys() {
+                        if !["severity", "max"].contains(&&**key) {
+                            return Err(invalid(format!("{name}: unknown option '{key}'")));
+                        }
+                    }
+                    (options.get("severity"), options.get("max"))
+                }
+                severity => (Some(severity), None),
+            };
+            if let Some(severity) = severity {
+                linter = linter.severity(name, parse_severity(name, severity)?);
+            }
+            if let Some(max) = max {
+                let max = max
+                    .as_f64()
+                    .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+                    .map(|n| n as usize)
+                    .ok_or_else(|| invalid(format!("{name}: max has to be a whole number")))?;
+                linter = match name {
+                    "deep_nesting" => linter.rule(DeepNesting { max }),
+                    "long_strings" => linter.rule(LongStrings { max }),
+                    _ => return Err(invalid(format!("{name}: has no max"))),
+                };
+            }
+        }
+        Ok(linter)
+    }
+
+    /// Adds a [`KeyRule`] for every object in the list `value`. Each has a `name`, the
+    /// `path` of the objects it checks, a [`Pattern`] that is the outermost value if it's
+    /// missing, and lists of keys they have to
+    /// define (`require`) or mustn't define (`forbid`). Its `severity` is `"warning"` if
+    /// it's missing.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{lint::Linter, parse_str};
+    /// let rules = parse_str(r#"[
+    ///     {name: "enemy_loot", path: "enemies[*]", require: ["loot_table"], severity: "error"},
+    /// ]"#).unwrap();
+    /// let linter = Linter::empty().rules_from_value(&rules).unwrap();
+    /// let found = linter.lint(r#"{enemies: [{name: "Goblin"}]}"#).unwrap();
+    /// assert_eq!(
+    ///     found[0].to_string(),
+    ///     "error: enemies[0]: missing 'loot_table' [enemy_loot]"
+    /// );
+    /// ```
+    pub fn rules_from_value(mut self, value: &Value) -> Result<Self, SpellConfigError> {
+        let rules = value
+            .as_list()
+            .ok_or_else(|| invalid(format!("{CUSTOM}: expected a list of rules")))?;
+        for rule in rules.iter() {
+            self = self.rule(key_rule(rule)?);
+        }
+        Ok(self)
+    }
+
+    /// Adds the rules of a file of [`KeyRule`]s, see [`Linter::rules_from_value`]
+    pub fn rules_from_file(
+        self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<Self, SpellConfigError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| SpellConfigError::Io(path.display().to_string(), e))?;
+        let rules =
+            parse_str(&src).map_err(|e| SpellConfigError::Parse(path.display().to_string(), e))?;
+        self.rules_from_value(&rules)
+    }
+
+    /// Parses `src` and runs every rule that isn't off, the diagnostics are in the order
+    /// of the source
+    pub fn lint(&self, src: &str) -> Result<Vec<Diagnostic>, GonError> {
+        let source = Source::new(src)?;
+        let mut diagnostics = Vec::new();
+        for (rule, severity) in &self.rules {
+            if *severity == Severity::Off {
+                continue;
+            }
+            let mut findings = Vec::new();
+            rule.check(&source, &mut findings);
+            diagnostics.extend(findings.into_iter().map(|finding| Diagnostic {
+                rule: rule.name().to_string(),
+                severity: *severity,
+                span: finding.span.or_else(|| source.span(&finding.path)),
+                path: finding.path,
+                message: finding.message,
+            }));
+        }
+        diagnostics.sort_by_key(|d| d.span.map(|span| span.start));
+        Ok(diagnostics)
+    }
+}
+
+/// The key of a formatting profile that configures the linter,
+/// [`SpellConfig::from_value`](crate::SpellConfig::from_value) skips it
+pub(crate) const KEY: &str = "lint";
+
+/// The key of the `lint` object that holds [`KeyRule`]s
+const CUSTOM: &str = "custom";
+
+fn invalid(message: impl Into<String>) -> SpellConfigError {
+    SpellConfigError::Invalid(KEY.into(), message.into())
+}
+
+fn parse_severity(name: &str, value: &Value) -> Result<Severity, SpellConfigError> {
+    match value {
+        Value::Str { s, .. } if s == "off" => Ok(Severity::Off),
+        Value::Str { s, .. } if s == "warning" => Ok(Severity::Warning),
+        Value::Str { s, .. } if s == "error" => Ok(Severity::Error),
+        _ => Err(invalid(format!(
+            "{name}: expected \"off\", \"warning\" or \"error\""
+        ))),
+    }
+}
+
+fn key_rule(value: &Value) -> Result<KeyRule, SpellConfigError> {
+    let obj = value
+        .as_obj()
+        .ok_or_else(|| invalid("expected {name, path, require, forbid, severity}"))?;
+    let name: &str = obj
+        .get("name")
+        .and_then(|v| v.try_into().ok())
+        .ok_or_else(|| invalid(format!("{CUSTOM}: every rule needs a name")))?;
+    let mut rule = KeyRule {
+        name: name.to_string(),
+        pattern: Pattern::default(),
+        require: Vec::new(),
+        forbid: Vec::new(),
+        severity: Severity::Warning,
+    };
+    let keys = |v: &Value, option| {
+        v.as_list()
+            .and_then(|keys| {
+                keys.iter()
+                    .map(|k| <&str>::try_from(k).ok().map(String::from))
+                    .collect()
+            })
+            .ok_or_else(|| invalid(format!("{name}: {option}: expected a list of keys")))
+    };
+    for (key, v) in obj.iter() {
+        match &**key {
+            "name" => {}
+            "path" => {
+                let src: &str = v
+                    .try_into()
+                    .map_err(|_| invalid(format!("{name}: path: expected a str")))?;
+                rule.pattern = src
+                    .parse()
+                    .map_err(|e| invalid(format!("{name}: path: {e}")))?;
+            }
+            "require" => rule.require = keys(v, "require")?,
+            "forbid" => rule.forbid = keys(v, "forbid")?,
+            "severity" => rule.severity = parse_severity(name, v)?,
+            _ => return Err(invalid(format!("{name}: unknown option '{key}'"))),
+        }
+    }
+    Ok(rule)
+}
+
+/// Reports keys that an object spells more than once
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DuplicateKeys;
+
+impl Rule for DuplicateKeys {
+    fn name(&self) -> &str {
+        "duplicate_keys"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        let mut seen = HashSet::new();
+        for located in source.tokens().iter().filter(|t| t.is_key) {
+            let key = key_text(source.text, &located.token);
+            if !seen.insert((located.container, key.clone())) {
+                findings.push(Finding {
+                    path: located.path.clone(),
+                    span: Some(located.token.span),
+                    message: format!("'{key}' is already a key of this object"),
+                });
+            }
+        }
+    }
+}
+
+/// Reports objects without entries
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EmptyObjects;
+
+impl Rule for EmptyObjects {
+    fn name(&self) -> &str {
+        "empty_objects"
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        walk(&source.value, &mut Path::default(), &mut |value, path| {
+            if value.as_obj().is_some_and(|obj| obj.is_empty()) {
+                findings.push(Finding {
+                    path: path.clone(),
+                    span: None,
+                    message: "empty object".into(),
+                });
+            }
+            true
+        });
+    }
+}
+
+/// Reports objects and lists nested more than `max` levels deep, the outermost value is
+/// at level 0
+#[derive(Copy, Clone, Debug)]
+pub struct DeepNesting {
+    pub max: usize,
+}
+
+impl Default for DeepNesting {
+    fn default() -> Self {
+        Self { max: 8 }
+    }
+}
+
+impl Rule for DeepNesting {
+    fn name(&self) -> &str {
+        "deep_nesting"
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        walk(&source.value, &mut Path::default(), &mut |value, path| {
+            let nested = matches!(value, Value::Obj(_) | Value::List(_));
+            if nested && path.0.len() > self.max {
+                findings.push(Finding {
+                    path: path.clone(),
+                    span: None,
+                    message: format!(
+                        "nested {} levels deep, more than {}",
+                        path.0.len(),
+                        self.max
+                    ),
+                });
+                // everything inside is too deep as well
+                return false;
+            }
+            true
+        });
+    }
+}
+
+/// Reports objects whose keys are written in different cases
+#[derive(Copy, Clone, Debug, Default)]
+pub struct KeyCasing;
+
+impl Rule for KeyCasing {
+    fn name(&self) -> &str {
+        "key_casing"
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        walk(&source.value, &mut Path::default(), &mut |value, path| {
+            let Some(obj) = value.as_obj() else {
+                return true;
+            };
+            let mut cases: Vec<(&str, &str)> = Vec::new();
+            for (key, _) in spelling_order(obj) {
+                match casing(key) {
+                    Some(case) if !cases.iter().any(|(c, _)| *c == case) => cases.push((case, key)),
+                    _ => {}
+                }
+            }
+            if cases.len() > 1 {
+                let mixed: Vec<_> = cases
+                    .iter()
+                    .map(|(case, key)| format!("{case} ('{key}')"))
+                    .collect();
+                findings.push(Finding {
+                    path: path.clone(),
+                    span: None,
+                    message: format!("keys mix {}", mixed.join(" and ")),
+                });
+            }
+            true
+        });
+    }
+}
+
+/// The case `key` is written in, `None` for keys that fit several like `name`
+fn casing(key: &str) -> Option<&'static str> {
+    let has_lower = key.chars().any(|c| c.is_lowercase());
+    let has_upper = key.chars().any(|c| c.is_uppercase());
+    let starts_upper = key.chars().next().is_some_and(char::is_uppercase);
+    match (has_lower, has_upper, key.contains('_')) {
+        (true, false, true) => Some("snake_case"),
+        (false, true, _) if key.chars().filter(|c| c.is_alphabetic()).count() > 1 => {
+            Some("SCREAMING_CASE")
+        }
+        (true, true, false) if starts_upper => Some("PascalCase"),
+        (true, true, false) => Some("camelCase"),
+        _ => None,
+    }
+}
+
+/// Reports string literals longer than `max` chars
+#[derive(Copy, Clone, Debug)]
+pub struct LongStrings {
+    pub max: usize,
+}
+
+impl Default for LongStrings {
+    fn default() -> Self {
+        Self { max: 120 }
+    }
+}
+
+impl Rule for LongStrings {
+    fn name(&self) -> &str {
+        "long_strings"
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        for located in source.tokens().iter().filter(|t| !t.is_key) {
+            let quotes = match located.token.kind {
+                TokenKind::Str => 2,
+                TokenKind::RawStr => 3,
+                _ => continue,
+            };
+            let len = located.token.text(source.text).chars().count() - quotes;
+            if len > self.max {
+                findings.push(Finding {
+                    path: located.path.clone(),
+                    span: Some(located.token.span),
+                    message: format!(
+                        "a string literal of {len} chars, more than {}, wrap it with `+`",
+                        self.max
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// Reports lists with elements of different kinds, ignoring `None`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MixedLists;
+
+impl Rule for MixedLists {
+    fn name(&self) -> &str {
+        "mixed_lists"
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        walk(&source.value, &mut Path::default(), &mut |value, path| {
+            let Value::List(xs) = value else {
+                return true;
+            };
+            let mut kinds = xs
+                .iter()
+                .enumerate()
+                .map(|(i, x)| (i, x.kind()))
+                .filter(|(_, kind)| *kind != Kind::None);
+            let Some((_, first)) = kinds.next() else {
+                return true;
+            };
+            if let Some((i, other)) = kinds.find(|(_, kind)| *kind != first) {
+                findings.push(Finding {
+                    path: path.clone().index(i),
+                    span: None,
+                    message: format!("a {other} in a list of {first}"),
+                });
+            }
+            true
+        });
+    }
+}
+
+/// Reports syntax that JSON parsers don't read, so the source stays valid JSON
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JsonCompat;
+
+impl Rule for JsonCompat {
+    fn name(&self) -> &str {
+        "json_compat"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Off
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        let tokens = tokenize(source.text);
+        let mut report = |token: &SpannedToken, message: String| {
+            let path = source
+                .tokens()
+                .iter()
+                .find(|t| t.token.span == token.span)
+                .map(|t| match t.is_key {
+                    true => t.path.clone().key(key_text(source.text, &t.token).as_str()),
+                    false => t.path.clone(),
+                })
+                .unwrap_or_default();
+            findings.push(Finding {
+                path,
+                span: Some(token.span),
+                message,
+            });
+        };
+        let mut in_let = false;
+        // tokens before this byte were reported with the one before them: the exponent
+        // signs of numbers and the headers of `@` sections
+        let mut skip_to = 0;
+        let mut prev: Option<&SpannedToken> = None;
+        for (i, token) in tokens.iter().enumerate() {
+            let text = token.text(source.text);
+            if in_let {
+                in_let = token.kind != TokenKind::Semicolon;
+                continue;
+            }
+            if token.span.range().start < skip_to {
+                continue;
+            }
+            if token.kind == TokenKind::Comment {
+                report(token, "comments aren't JSON".into());
+                continue;
+            }
+            if token.kind == TokenKind::Keyword && text.eq_ignore_ascii_case("let") {
+                report(token, "`let` bindings aren't JSON".into());
+                in_let = true;
+                continue;
+            }
+            if prev.is_some_and(|p| ends_value(p.kind)) && starts_value(token.kind) {
+                report(
+                    token,
+                    "a missing comma, JSON needs one between values".into(),
+                );
+            }
+            match token.kind {
+                TokenKind::Keyword if !["null", "true", "false"].contains(&text) => {
+                    let json = match text.to_lowercase().as_str() {
+                        "none" => "null",
+                        other => other,
+                    }
+                    .to_string();
+                    report(token, format!("`{text}` isn't JSON, spell it `{json}`"));
+                }
+                TokenKind::Sym if kind_at(&tokens, i + 1, TokenKind::Colon) => {
+                    report(token, format!("the key '{text}' has to be quoted in JSON"));
+                }
+                TokenKind::Sym => report(token, format!("the symbol '{text}' isn't JSON")),
+                TokenKind::RawStr => report(token, "raw strings aren't JSON".into()),
+                TokenKind::Str => {
+                    if let Some(problem) = json_str_problem(text) {
+                        report(token, problem);
+                    }
+                }
+                TokenKind::Num => {
+                    skip_to = signed_exponent_end(source.text, token);
+                    let number = &source.text[token.span.range().start..skip_to];
+                    if !is_json_number(number) {
+                        report(
+                            token,
+                            format!("the number {number} isn't spelled like JSON"),
+                        );
+                    }
+                }
+                TokenKind::Comma
+                    if kind_at(&tokens, i + 1, TokenKind::RBrace)
+                        || kind_at(&tokens, i + 1, TokenKind::RBrack) =>
+                {
+                    report(token, "trailing commas aren't JSON".into());
+                }
+                TokenKind::Plus => report(token, "`+` isn't JSON".into()),
+                TokenKind::At => {
+                    report(token, "`@` sections aren't JSON".into());
+                    skip_to = section_header_end(&tokens, i);
+                }
+                TokenKind::LParen
+                | TokenKind::RParen
+                | TokenKind::Semicolon
+                | TokenKind::Equals
+                | TokenKind::Other => report(token, format!("`{text}` isn't JSON")),
+                _ => {}
+            }
+            prev = Some(token);
+        }
+    }
+}
+
+fn ends_value(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::RBrace
+            | TokenKind::RBrack
+            | TokenKind::Str
+            | TokenKind::RawStr
+            | TokenKind::Num
+            | TokenKind::Keyword
+            | TokenKind::Sym
+    )
+}
+
+fn starts_value(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::LBrace
+            | TokenKind::LBrack
+            | TokenKind::Str
+            | TokenKind::RawStr
+            | TokenKind::Num
+            | TokenKind::Keyword
+            | TokenKind::Sym
+            | TokenKind::Dash
+    )
+}
+
+/// What keeps the string literal `text` from being JSON, if anything does
+fn json_str_problem(text: &str) -> Option<String> {
+    let mut chars = text[1..text.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u') => {}
+                Some(c) => return Some(format!("the escape \\{c} isn't JSON")),
+                None => {}
+            },
+            c if (c as u32) < 0x20 => {
+                return Some("JSON strings can't hold line breaks or control chars".into());
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Where the number `token` ends, which is after its token if its exponent has a sign
+fn signed_exponent_end(src: &str, token: &SpannedToken) -> usize {
+    let end = token.span.range().end;
+    let rest = &src.as_bytes()[end..];
+    if token.text(src).ends_with(['e', 'E'])
+        && matches!(rest.first(), Some(b'+' | b'-'))
+        && rest.get(1).is_some_and(u8::is_ascii_digit)
+    {
+        end + 1
+            + rest[1..]
+                .iter()
+                .take_while(|b| b.is_ascii_digit() || **b == b'_')
+                .count()
+    } else {
+        end
+    }
+}
+
+/// Where the header of the `@` section starting at `tokens[at]` ends, after its condition
+/// if it has one
+fn section_header_end(tokens: &[SpannedToken], at: usize) -> usize {
+    let mut end = at + 1;
+    if kind_at(tokens, end + 1, TokenKind::LParen) {
+        end = tokens[end..]
+            .iter()
+            .position(|t| t.kind == TokenKind::RParen)
+            .map_or(tokens.len() - 1, |n| end + n);
+    }
+    tokens.get(end).map_or(usize::MAX, |t| t.span.range().end)
+}
+
+/// Is `text`, a number without its sign, spelled like JSON: no leading zeros, `_` or
+/// bare `.`?
+fn is_json_number(text: &str) -> bool {
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (text, None),
+    };
+    let (int, fraction) = match mantissa.split_once('.') {
+        Some((int, fraction)) => (int, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    digits(int)
+        && (int == "0" || !int.starts_with('0'))
+        && fraction.is_none_or(digits)
+        && exponent.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+}
+
+/// Reports objects at the paths `pattern` matches that lack a key of `require` or have
+/// one of `forbid`, see [`Linter::rules_from_value`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyRule {
+    pub name: String,
+    /// The objects to check, the outermost value if it's empty
+    pub pattern: Pattern,
+    pub require: Vec<String>,
+    pub forbid: Vec<String>,
+    pub severity: Severity,
+}
+
+impl Rule for KeyRule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn default_severity(&self) -> Severity {
+        self.severity
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        walk(&source.value, &mut Path::default(), &mut |value, path| {
+            let Some(obj) = value.as_obj().filter(|_| self.pattern.matches(path)) else {
+                return true;
+            };
+            for key in &self.require {
+                if !obj.contains_key(key.as_str()) {
+                    findings.push(Finding {
+                        path: path.clone(),
+                        span: None,
+                        message: format!("missing '{key}'"),
+                    });
+                }
+            }
+            for key in &self.forbid {
+                if obj.contains_key(key.as_str()) {
+                    findings.push(Finding {
+                        path: path.clone().key(key.as_str()),
+                        span: None,
+                        message: format!("'{key}' isn't allowed here"),
+                    });
+                }
+            }
+            true
+        });
+    }
+}
+
+/// Calls `visit` for `value` and everything in it, parents first. Stops going deeper
+/// where `visit` returns `false`.
+fn walk(value: &Value, path: &mut Path, visit: &mut impl FnMut(&Value, &Path) -> bool) {
+    if !visit(value, path) {
+        return;
+    }
+    match value {
+        Value::Obj(obj) => {
+            for (key, v) in spelling_order(obj) {
+                path.0.push(Segment::Key(key.to_string()));
+                walk(v, path, visit);
+                path.0.pop();
+            }
+        }
+        Value::List(xs) => {
+            for (i, x) in xs.iter().enumerate() {
+                path.0.push(Segment::Index(i));
+                walk(x, path, visit);
+                path.0.pop();
+            }
+        }
+        Value::Tagged { value, .. } => walk(value, path, visit),
+        _ => {}
+    }
+}
+
+/// The key `token` spells
+fn key_text(src: &str, token: &SpannedToken) -> String {
+    let text = token.text(src);
+    match parse_str(text) {
+        Ok(Value::Str { s, .. }) if token.kind == TokenKind::Str => s,
+        _ => text.to_string(),
+    }
+}
+
+fn kind_at(tokens: &[SpannedToken], i: usize, kind: TokenKind) -> bool {
+    tokens.get(i).is_some_and(|t| t.kind == kind)
+}
+
+/// An object or list that [`locate`] is inside of
+struct Container {
+    path: Path,
+    is_obj: bool,
+    id: usize,
+    /// The key whose value comes next, in objects
+    key: Option<String>,
+    /// The index of the next element, in lists
+    index: usize,
+    /// Whether this is the body of an `@else` section, which adds to the enclosing object
+    /// or list
+    section: bool,
+}
+
+/// The tokens of `src` with the paths of the values they're part of
+fn locate(src: &str) -> Vec<LocatedToken> {
+    let tokens: Vec<_> = tokenize(src)
+        .into_iter()
+        .filter(|t| t.kind != TokenKind::Comment)
+        .collect();
+    let mut located = Vec::new();
+    let mut stack: Vec<Container> = Vec::new();
+    let mut ids = 0..;
+    let mut in_section_header = false;
+    // the path of the last value, for tokens that continue it like `"a" + "b"` or `-1`
+    let mut continued: Option<Path> = None;
+    let mut i = 0;
+    while let Some(&token) = tokens.get(i) {
+        i += 1;
+        match token.kind {
+            // `let` bindings aren't part of the value
+            TokenKind::Keyword
+                if stack.is_empty() && token.text(src).eq_ignore_ascii_case("let") =>
+            {
+                let mut depth = 0usize;
+                while let Some(t) = tokens.get(i) {
+                    i += 1;
+                    match t.kind {
+                        TokenKind::LBrace | TokenKind::LBrack | TokenKind::LParen => depth += 1,
+                        TokenKind::RBrace | TokenKind::RBrack | TokenKind::RParen => {
+                            depth = depth.saturating_sub(1)
+                        }
+                        TokenKind::Semicolon if depth == 0 => break,
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            // without defined variables, `@if` sections are dropped and `@else` ones kept
+            TokenKind::At => {
+                let is_if = tokens
+                    .get(i)
+                    .is_some_and(|t| t.text(src).eq_ignore_ascii_case("if"));
+                i += 1;
+                if kind_at(&tokens, i, TokenKind::LParen) {
+                    while tokens.get(i).is_some_and(|t| t.kind != TokenKind::RParen) {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                if kind_at(&tokens, i, TokenKind::At) {
+                    continue;
+                }
+                if is_if {
+                    let mut depth = 0usize;
+                    while let Some(t) = tokens.get(i) {
+                        i += 1;
+                        match t.kind {
+                            TokenKind::LBrace | TokenKind::LBrack => depth += 1,
+                            TokenKind::RBrace | TokenKind::RBrack => {
+                                depth = depth.saturating_sub(1)
+                            }
+                            _ => {}
+                        }
+                        if depth == 0 {
+                            break;
+                        }
+                    }
+                } else {
+                    in_section_header = true;
+                }
+                continue;
+            }
+            TokenKind::RBrace | TokenKind::RBrack => {
+                let closed = stack.pop();
+                match (closed, stack.last_mut()) {
+                    (Some(closed), Some(parent)) if closed.section => parent.index = closed.index,
+                    _ => {}
+                }
+                continue;
+            }
+            TokenKind::Comma | TokenKind::Colon | TokenKind::Semicolon => continue,
+            _ => {}
+        }
+        let is_key = kind_at(&tokens, i, TokenKind::Colon) && !in_section_header;
+        match stack.last_mut() {
+            Some(top) if top.is_obj && is_key => {
+                top.key = Some(key_text(src, &token));
+                located.push(LocatedToken {
+                    token,
+                    path: top.path.clone(),
+                    is_key: true,
+                    container: top.id,
+                });
+                continue;
+            }
+            _ => {}
+        }
+        let path = match continued.take() {
+            Some(path) if !matches!(token.kind, TokenKind::LBrace | TokenKind::LBrack) => path,
+            _ if in_section_header => stack.last().map_or_else(Path::default, |c| c.path.clone()),
+            _ => match stack.last_mut() {
+                Some(top) if top.is_obj => {
+                    let mut path = top.path.clone();
+                    path.0
+                        .push(Segment::Key(top.key.take().unwrap_or_default()));
+                    path
+                }
+                Some(top) => {
+                    let mut path = top.path.clone();
+                    path.0.push(Segment::Index(top.index));
+                    top.index += 1;
+                    path
+                }
+                None => Path::default(),
+            },
+        };
+        located.push(LocatedToken {
+            token,
+            path: path.clone(),
+            is_key: false,
+            container: stack.last().map_or(0, |c| c.id),
+        });
+        match token.kind {
+            TokenKind::LBrace | TokenKind::LBrack => {
+                let index = match stack.last() {
+                    Some(parent) if in_section_header => parent.index,
+                    _ => 0,
+                };
+                stack.push(Container {
+                    path,
+                    is_obj: token.kind == TokenKind::LBrace,
+                    id: ids.next().expect("endless") + 1,
+                    key: None,
+                    index,
+                    section: in_section_header,
+                });
+                in_section_header = false;
+            }
+            TokenKind::Dash | TokenKind::Plus => continued = Some(path),
+            TokenKind::Str | TokenKind::RawStr if kind_at(&tokens, i, TokenKind::Plus) => {
+                continued = Some(path)
+            }
+            _ => {}
+        }
+    }
+    located
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locates_tokens_and_reports_findings() {
+        let src = r#"let x = {a: 1};
+            {
+                list: [-1, "a" + "b", @if(debug) [1] @else [{c: 1, c: 2}], {}],
+                deep: [[[1]]],
+                text: "abcdef",
+                mixed: [None, 1, 2, "3"],
+            }"#;
+        let source = Source::new(src).unwrap();
+        let path_of = |text: &str| {
+            let t = source
+                .tokens()
+                .iter()
+                .find(|t| t.token.text(src) == text)
+                .unwrap();
+            t.path.to_string()
+        };
+        assert_eq!(path_of("\"b\""), "list[1]");
+        assert_eq!(path_of("c"), "list[2]");
+        assert_eq!(path_of("\"abcdef\""), "text");
+        let linter = Linter::new()
+            .rule(DeepNesting { max: 2 })
+            .rule(LongStrings { max: 5 });
+        let found: Vec<_> = linter
+            .lint(src)
+            .unwrap()
+            .iter()
+            .map(|d| (d.rule.clone(), d.path.to_string(), d.span.is_some()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("mixed_lists".into(), "list[1]".into(), true),
+                ("duplicate_keys".into(), "list[2]".into(), true),
+                ("empty_objects".into(), "list[3]".into(), true),
+                ("deep_nesting".into(), "deep[0][0]".into(), true),
+                ("long_strings".into(), "text".into(), true),
+                ("mixed_lists".into(), "mixed[3]".into(), true),
+            ]
+        );
+        assert!(matches!(
+            Linter::from_value(&parse_str(r#"{empty_objects: "loud"}"#).unwrap()),
+            Err(SpellConfigError::Invalid(..))
+        ));
+    }
+
+    #[test]
+    fn json_compat() {
+        let linter = Linter::empty().rule(JsonCompat);
+        assert_eq!(linter.lint("{a: 1}").unwrap(), []);
+        let linter = linter.severity("json_compat", Severity::Warning);
+        let json = r#"{"a": [1, -2.5, 0, null, true], "b": {"c": "\"q\""}}"#;
+        assert_eq!(linter.lint(json).unwrap(), []);
+        let src = r#"let mode = "fast";
+        {
+            // comment
+            a: [None, 1_000, 01, r"x", "x" + "y", "\0",],
+            "b": mode,
+            @if(debug) {"c": 1} @else {"c": 2}
+            "d": 0.50,
+        }"#;
+        let found: Vec<_> = linter
+            .lint(src)
+            .unwrap()
+            .iter()
+            .map(|d| format!("{}: {}", d.path, d.message))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ": `let` bindings aren't JSON",
+                ": comments aren't JSON",
+                "a: the key 'a' has to be quoted in JSON",
+                "a[0]: `None` isn't JSON, spell it `null`",
+                "a[1]: the number 1_000 isn't spelled like JSON",
+                "a[2]: the number 01 isn't spelled like JSON",
+                "a[3]: raw strings aren't JSON",
+                "a[4]: `+` isn't JSON",
+                "a[5]: the escape \\0 isn't JSON",
+                ": trailing commas aren't JSON",
+                "b: the symbol 'mode' isn't JSON",
+                ": `@` sections aren't JSON",
+                ": `@` sections aren't JSON",
+                "d: a missing comma, JSON needs one between values",
+                ": trailing commas aren't JSON",
+            ]
+        );
+        for (number, json) in [
+            ("2.5e-3", true),
+            ("1E+5", true),
+            ("00", false),
+            ("1.", false),
+        ] {
+            assert_eq!(is_json_number(number), json, "{number}");
+        }
+    }
+
+    #[test]
+    fn key_rules() {
+        let profile = parse_str(
+            r#"{
+                custom: [
+                    {name: "loot", path: "enemies.*", require: ["loot_table"]},
+                    {name: "no_debug", path: "**", forbid: ["debug"], severity: "error"},
+                ],
+                loot: "error",
+                key_casing: "off",
+            }"#,
+        )
+        .unwrap();
+        let linter = Linter::from_value(&profile).unwrap();
+        let src = "{enemies: {goblin: {loot_table: \"common\"}, orc: {debug: true}}}";
+        let found: Vec<_> = linter
+            .lint(src)
+            .unwrap()
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "error: enemies.orc: missing 'loot_table' [loot]",
+                "error: enemies.orc.debug: 'debug' isn't allowed here [no_debug]",
+            ]
+        );
+        for rules in [r#"[{path: "a"}]"#, r#"[{name: "x", require: "a"}]"#] {
+            assert!(matches!(
+                Linter::empty().rules_from_value(&parse_str(rules).unwrap()),
+                Err(SpellConfigError::Invalid(..))
+            ));
+        }
+    }
+}
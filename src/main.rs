@@ -1,136 +1,1421 @@
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::fmt;
 use std::fs::File;
-use std::io::Read;
-use std::path::PathBuf;
+use std::io::{BufWriter, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{OnceLock, mpsc};
+use std::time::{Duration, Instant};
 
 use clap::Parser;
 use serde_json::Value as JsonValue;
 
+use gon::dialect::Dialect;
+use gon::graph::GraphFormat;
+use gon::key_order::KeyOrderPolicy;
+use gon::sniff::Format;
+use gon::table::TableStyle;
+use gon::value::Strictness;
 use gon::*;
 
-#[derive(Parser)]
+#[derive(Parser, Clone)]
 #[command(name = "gon", version, about = "CLI-utility for working with GON data", long_about = None)]
 struct Args {
     /// What can I do for you?
     verb: Verb,
-    /// How many characters to indent formatted output with?
-    /// Only works with the `fmt` and `from` verbs.
-    #[arg(long, short = 'w', default_value_t = 4)]
-    indent_width: usize,
-    /// What characters to indent formatted output with?
-    /// Only works with the `fmt` and `into` verbs.
-    #[arg(long, short = 'c', default_value_t = ' ')]
-    indent_char: char,
+    /// How many characters to indent formatted output with? Defaults to 4.
+    /// This and the other formatting flags override the `.gonfmt.gon` in the directory of the
+    /// input or any of its parents.
+    /// Only works with the `fmt`, `from`, `pick`, `omit`, `apply`, `render` and `query` verbs.
+    #[arg(long, short = 'w')]
+    indent_width: Option<usize>,
+    /// What characters to indent formatted output with? Defaults to a space.
+    /// Only works with the `fmt`, `from`, `pick`, `omit`, `apply`, `render` and `query` verbs.
+    #[arg(long, short = 'c')]
+    indent_char: Option<char>,
     /// Put commas after last entries in lists and objects in formatted output?
-    /// Only works with the `fmt` verb.
+    /// Only works with the `fmt`, `from`, `pick`, `omit`, `apply`, `render` and `query` verbs.
     #[arg(long, short, action)]
     trailing_commas: bool,
-    /// The maximum width to which string literals get wrapped.
-    /// This also squashes multiple spaces into a single one in every string. Use 0 to disable.
-    /// Only works with the `fmt` and `from` verbs.
-    #[arg(long, short, default_value_t = 0)]
-    max_width: usize,
+    /// The maximum width to which string literals get wrapped. Defaults to 0.
+    /// Long strings are split into literals joined with `+`. Use 0 to disable.
+    /// Only works with the `fmt`, `from`, `pick`, `omit`, `apply`, `render` and `query` verbs.
+    #[arg(long, short)]
+    max_width: Option<usize>,
+    /// Wrap strings by squashing their whitespace and breaking lines inside the literals
+    /// instead of joining literals with `+`? This changes the strings!
+    /// Only works with the `fmt`, `from`, `pick`, `omit`, `apply`, `render` and `query` verbs.
+    #[arg(long, action)]
+    squash_strings: bool,
+    /// Put objects with at most this many entries and no nested objects or lists on one line.
+    /// Only works with the `fmt`, `from`, `pick`, `omit`, `apply`, `render` and `query` verbs.
+    #[arg(long)]
+    inline_objects: Option<usize>,
     /// Format in-place?
     /// WARNING: Writes the formatted output directly into the old file. ABSOLUTELY NO WARRANTY!
-    /// Only works with `fmt` and `min`.
+    /// Only works with `fmt`, `min`, `apply`, `upgrade` and `merge`.
     #[arg(long, short, action)]
     in_place: bool,
-    /// The input file. Leave empty for stdin.
+    /// Keep the previous version of files changed `--in-place` in a `.gon.history`
+    /// directory next to them, so `undo` can restore it?
+    #[arg(long, action)]
+    history: bool,
+    /// Which keys to show as columns, separated by commas. Defaults to all keys.
+    /// Only works with the `table` verb.
+    #[arg(long, value_delimiter = ',')]
+    columns: Vec<String>,
+    /// Render a markdown table instead of an aligned one?
+    /// Only works with the `table` verb.
+    #[arg(long, action)]
+    markdown: bool,
+    /// Which keys to keep or remove, separated by commas. Nested keys are separated by
+    /// dots, `*` matches any characters, `[*]` any list element and `**` any nesting.
+    /// For `explain`, the keys to explain, without wildcards. For `get`, also slices like
+    /// `items[100:200]`.
+    /// Only works with the `pick`, `omit`, `get` and `explain` verbs.
+    #[arg(long, short, value_delimiter = ',')]
+    keys: Vec<String>,
+    /// Print at most this many of the selected values, or elements of a list `query` result.
+    /// Only works with the `get` and `query` verbs.
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Skip this many of the selected values, or elements of a list `query` result, before
+    /// printing any. Only works with the `get` and `query` verbs.
+    #[arg(long, default_value_t = 0)]
+    offset: usize,
+    /// Shorten long strings and big objects and lists in what's printed, see the docs of
+    /// `gon::truncate`. Only works with the `get` verb.
+    #[arg(long, action)]
+    preview: bool,
+    /// Print strings without quotes and the elements of lists one per line, without the
+    /// `path = ` of `get`, for shell scripts.
+    /// Only works with the `get` and `query` verbs.
+    #[arg(long, short, action)]
+    raw: bool,
+    /// The query to run, see the docs of `gon::query`.
+    /// Only works with the `query` verb.
+    #[arg(long, short)]
+    expr: Option<String>,
+    /// Documents the `--expr` refers to as `$name`, given as `name=file` or just the file
+    /// to call it after its name without the extension. Repeat it for more documents.
+    /// Only works with the `query` verb.
+    #[arg(long)]
+    arg: Vec<String>,
+    /// How many levels of nested objects and lists to outline. Defaults to all of them.
+    /// Only works with the `outline` verb.
+    #[arg(long)]
+    depth: Option<usize>,
+    /// The script of edits to apply, see the docs of `gon::script`.
+    /// Only works with the `apply` verb.
+    #[arg(long, short)]
+    script: Option<PathBuf>,
+    /// A rhai script to run on the input instead of a `--script`, see the docs of
+    /// `gon::scripting`. Only works with the `apply` verb.
+    #[cfg(feature = "rhai")]
+    #[arg(long)]
+    rhai: Option<PathBuf>,
+    /// The diagram language, `dot` or `mermaid`. Defaults to `dot`.
+    /// Only works with the `graph` verb.
+    #[arg(long, default_value = "dot")]
+    format: GraphFormat,
+    /// Print numbers about the input, like how many values of each kind it has.
+    /// Only works with the `verify` verb, which is how gon spells `check`.
+    #[arg(long, short, action)]
+    verbose: bool,
+    /// The translated catalog to write back into the input, see the docs of `gon::i18n`.
+    /// Only works with the `i18n-merge` verb.
+    #[arg(long)]
+    catalog: Option<PathBuf>,
+    /// Files to merge on top of the input, separated by commas, in order.
+    /// Only works with the `explain` verb.
+    #[arg(long, value_delimiter = ',')]
+    overlay: Vec<PathBuf>,
+    /// Merge environment variables starting with `<prefix>__` on top of the files, see the
+    /// docs of `gon::config`. Only works with the `explain` verb.
+    #[arg(long)]
+    env_prefix: Option<String>,
+    /// The version of the input that it and the `--theirs` were changed from.
+    /// Only works with the `merge` verb.
+    #[arg(long)]
+    base: Option<PathBuf>,
+    /// The other changed version of the `--base` to merge into the input.
+    /// Only works with the `merge` verb.
+    #[arg(long)]
+    theirs: Option<PathBuf>,
+    /// Ask how to resolve every conflict on the terminal instead of failing?
+    /// Only works with the `merge` verb.
+    #[arg(long, action)]
+    interactive: bool,
+    /// The annotated schema to document, see the docs of `gon::schema`.
+    /// Only works with the `doc` verb.
+    #[arg(long)]
+    schema: Option<PathBuf>,
+    /// Also document the examples of the schema, linked from the table?
+    /// Only works with the `doc` verb.
+    #[arg(long, action)]
+    examples: bool,
+    /// The paths the application read, one per line, to find what it never reads of the
+    /// input, see the docs of `gon::usage`. Only works with the `unused` verb.
+    #[arg(long)]
+    usage: Option<PathBuf>,
+    /// Where to write the output instead of stdout.
+    /// Only works with the `doc` and `render` verbs.
+    #[arg(long, short)]
+    out: Option<PathBuf>,
+    /// A file with a list of project rules to lint with besides the built-in ones, see the
+    /// docs of `gon::lint`. Only works with the `lint` verb.
+    #[arg(long)]
+    rules: Option<PathBuf>,
+    /// The variables to render a template with, see the docs of `gon::template`.
+    /// Only works with the `render` verb.
+    #[arg(long)]
+    vars: Option<PathBuf>,
+    /// Fail on numbers that JSON can't represent instead of turning integers too big for
+    /// 64 bits into floats and floats too big into `null`?
+    /// Only works with the `into` verb.
+    #[arg(long, action)]
+    strict_numbers: bool,
+    /// How many times to run every step. Defaults to 100.
+    /// Only works with the `bench` verb.
+    #[arg(long, short = 'n')]
+    iterations: Option<u32>,
+    /// Print nothing but the output of the verb: no status lines, reports or error messages.
+    /// The exit code tells how it went: 0 ok, 1 bad input, 2 bad usage, 3 validation failed.
+    #[arg(long, short, action, conflicts_with = "porcelain")]
+    quiet: bool,
+    /// Print status lines, reports and errors as stable, tab separated lines for scripts.
+    /// Changes the output of the `verify`, `explain-error`, `lint`, `explain`, `bench` and
+    /// `merge` verbs.
+    #[arg(long, action)]
+    porcelain: bool,
+    /// The format of the input, `gon`, `json` or `auto` to tell it from its bytes, see the
+    /// docs of `gon::sniff`. `auto` also reads YAML and MessagePack if gon was built with the
+    /// `yaml` and `msgpack` features, which the CLI build turns on.
+    /// Defaults to `json` for the `from` verb and to `gon` for the others.
+    /// Only works with the `min`, `fmt`, `into`, `from`, `table`, `md`, `pick`, `omit`, `get`,
+    /// `query`, `outline`, `apply`, `graph`, `i18n-extract` and `unused` verbs.
+    #[arg(long)]
+    from: Option<InputFormat>,
+    /// How strict to be about the syntax of the input and output: `standard`, `strict`,
+    /// `relaxed` or `json`, see the docs of `gon::dialect`. The formatting flags override it.
+    /// Works with the verbs that `--from` works with and `verify`.
+    #[arg(long)]
+    dialect: Option<Dialect>,
+    /// Don't draw progress bars, for example in CI? Progress bars are drawn on stderr if it's
+    /// a terminal, while working on several files or parsing inputs of at least 16 MiB, and
+    /// only if gon was built with the `progress` feature.
+    #[arg(long, action)]
+    no_progress: bool,
+    /// How many files to work on at once. Defaults to the number of cores.
+    /// Only works with several files and with the `validate-workspace` verb.
+    #[arg(long, short)]
+    jobs: Option<usize>,
+    /// How many seconds reading the input may take before gon gives up, so scripts don't
+    /// hang if nothing is piped in. Waits forever by default.
+    #[arg(long)]
+    timeout: Option<f64>,
+    /// The input file. Leave empty or pass `-` for stdin.
     file: Option<PathBuf>,
+    /// More input files, which the `min`, `fmt`, `into`, `from` and `verify` verbs work on
+    /// in parallel, printing the output of every file as a section of its own. One of them
+    /// may be `-` for stdin.
+    files: Vec<PathBuf>,
+}
+
+/// What the input is read as, see `--from`
+#[derive(clap::ValueEnum, Copy, Clone, Debug)]
+enum InputFormat {
+    Gon,
+    Json,
+    /// Tell the format from the bytes of the input
+    Auto,
 }
 
 #[derive(clap::ValueEnum, Clone, Debug)]
 enum Verb {
     /// Minify the input
     Min,
-    /// Format the input
+    /// Format the input, keys are ordered by the `key_order` of the `.gonfmt.gon`.
+    /// `// gon-fmt: off`, `on` and `inline` comments are honored, other comments are kept
     Fmt,
-    /// Convert input to json
+    /// Convert input to json. Plain data is converted while it's read, without building
+    /// the whole document in memory first
     Into,
     /// Convert json input to gon
     From,
-    /// Verify the syntax of the given file
+    /// Verify the syntax of the given file and that it follows the `key_order` of the
+    /// `.gonfmt.gon`
     Verify,
+    /// Verify the syntax of the given file and, if it's invalid, suggest how to fix it
+    ExplainError,
+    /// Check the input for duplicate keys and style issues, configured by the `lint` object
+    /// of the `.gonfmt.gon`
+    Lint,
+    /// Check that the input is a well-formed schema: known type names, constraints that
+    /// apply to their type and can be satisfied, see the docs of `gon::schema`
+    SchemaLint,
+    /// Print a list of flat objects as a table
+    Table,
+    /// Convert input to markdown
+    Md,
+    /// Keep only the given keys of objects, also inside of lists
+    Pick,
+    /// Remove the given keys from objects, also inside of lists
+    Omit,
+    /// Print everything the given keys select, one `path = value` line each. Slices like
+    /// `items[100:200]` select ranges of lists
+    Get,
+    /// Run the `--expr` on the input, like `enemies[*] | group_by(zone) | map(count)`
+    Query,
+    /// Print the keys and list lengths of the input without the values
+    Outline,
+    /// Apply the edits of a script to the input
+    Apply,
+    /// Draw which objects and lists contain which others
+    Graph,
+    /// Render the input as a template with the `--vars`
+    Render,
+    /// Write reference documentation of a `--schema` as markdown
+    Doc,
+    /// Print all strings of the input as a flat catalog of paths and strings
+    I18nExtract,
+    /// Write the strings of a translated `--catalog` back into the input, keeping its
+    /// formatting and comments
+    I18nMerge,
+    /// Merge the changes that the input and the `--theirs` made to the `--base`, see the
+    /// docs of `gon::merge`. Fails if they conflict, unless they're resolved `--interactive`ly
+    Merge,
+    /// Tell which of the input, the `--overlay`s and the environment variables set the
+    /// given `--keys`
+    Explain,
+    /// Rewrite the `#gon` pragma and `@version` entry of the input to declare the newest
+    /// version of the format, see the docs of `gon::pragma`
+    Upgrade,
+    /// Print the keys of the input that no path of the `--usage` report reaches, so config
+    /// keys the application never reads can be removed
+    Unused,
+    /// Check every file that a glob of the given manifest matches against the schema the
+    /// manifest maps the glob to, on several threads, see the docs of `gon::workspace`
+    ValidateWorkspace,
+    /// Print the schema files that the input schema refers to, directly or through others,
+    /// one per line, so build systems know when to regenerate what's made from it
+    Deps,
+    /// Restore the version of the file before its last `--in-place` edit with `--history`
+    Undo,
+    /// Measure how long parsing, formatting, minifying and converting the input to json
+    /// take, to report performance problems with reproducible numbers
+    Bench,
+    /// Start an interactive session, type `:help` for help
+    #[cfg(feature = "repl")]
+    Repl,
+}
+
+/// Exit code of invalid input, and of everything else that goes wrong
+const EXIT_DATA: u8 = 1;
+/// Exit code of missing or contradicting arguments, clap uses it too
+const EXIT_USAGE: u8 = 2;
+/// Exit code of `verify`, `explain-error`, `lint`, `schema-lint`, `unused`,
+/// `validate-workspace` and `merge` when they find problems
+const EXIT_INVALID: u8 = 3;
+
+/// An error with an exit code other than [`EXIT_DATA`]
+#[derive(Debug)]
+enum Failure {
+    Usage(String),
+    Validation(String),
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) | Self::Validation(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for Failure {}
+
+fn main() -> ExitCode {
     let args = Args::parse();
+    let (quiet, porcelain) = (args.quiet, args.porcelain);
+    PROGRESS.store(!args.no_progress && !quiet && !porcelain, Ordering::Relaxed);
+    STDIN_HINT.store(!quiet && !porcelain, Ordering::Relaxed);
+    let Err(e) = run(args) else {
+        return ExitCode::SUCCESS;
+    };
+    if porcelain {
+        eprintln!("error\t{}", e.to_string().replace('\n', " "));
+    } else if !quiet {
+        eprintln!("Error: {e}");
+    }
+    ExitCode::from(match e.downcast_ref::<Failure>() {
+        Some(Failure::Usage(_)) => EXIT_USAGE,
+        Some(Failure::Validation(_)) => EXIT_INVALID,
+        None => EXIT_DATA,
+    })
+}
+
+fn run(mut args: Args) -> Result<(), Box<dyn Error>> {
+    if let Some(seconds) = args.timeout {
+        let Ok(timeout) = Duration::try_from_secs_f64(seconds) else {
+            let message =
+                format!("the timeout must be a positive number of seconds, not {seconds}");
+            return Err(Failure::Usage(message).into());
+        };
+        let _ = TIMEOUT.set(timeout);
+    }
+    if !args.files.is_empty() {
+        return run_files(args);
+    }
+    if args.file.as_deref().is_some_and(is_stdin) {
+        args.file = None;
+    }
     match args.verb {
-        Verb::Min => {
-            let value = get_gon_input(args.file.as_ref().cloned())?;
-            print_or_write_to_file(&value.min_spell(), args.file)?;
+        Verb::Min | Verb::Fmt | Verb::Into | Verb::From | Verb::Verify => {
+            run_file(&args, &mut BufWriter::new(std::io::stdout().lock()))?;
         }
-        Verb::Fmt => {
-            let value = get_gon_input(args.file.as_ref().cloned())?;
-            let spell_config = SpellConfig {
-                indent_amount: args.indent_width,
-                indent_char: args.indent_char,
-                trailing_commas: args.trailing_commas,
-                max_width: args.max_width,
+        Verb::ExplainError => {
+            let src = get_src(args.file)?;
+            if let Err(e) = parse_str(&src) {
+                if args.porcelain {
+                    println!("invalid");
+                } else if !args.quiet {
+                    println!("INVALID: {e}");
+                }
+                let index = span::LineIndex::new(&src);
+                for hint in hints::hints(&src).into_iter().filter(|_| !args.quiet) {
+                    let at = index.line_col(hint.span.start);
+                    if args.porcelain {
+                        println!("hint\t{}\t{}\t{}", at.line, at.col, hint.message);
+                        continue;
+                    }
+                    println!("\n{}:{}: {}", at.line, at.col, hint.message);
+                    let line = src.lines().nth(at.line - 1).unwrap_or_default();
+                    println!("    {line}");
+                    println!("    {}^", " ".repeat(at.col - 1));
+                }
+                return Err(Failure::Validation(e.to_string()).into());
+            }
+            if args.porcelain {
+                println!("valid");
+            } else if !args.quiet {
+                println!("VALID");
+            }
+        }
+        Verb::Lint | Verb::SchemaLint => {
+            let mut linter = match format_profile(&args)? {
+                Some(path) => lint::Linter::from_file(path)?,
+                None => lint::Linter::new(),
             };
-            print_or_write_to_file(&value.spell(spell_config)?, args.file)?;
+            if matches!(args.verb, Verb::SchemaLint) {
+                linter = linter
+                    .rule(schema::InvalidSchema)
+                    .rule(schema::UnsatisfiableSchema)
+                    .rule(schema::UnusedDefinitions);
+            }
+            if let Some(rules) = &args.rules {
+                linter = linter.rules_from_file(rules)?;
+            }
+            let src = get_src(args.file.as_ref().cloned())?;
+            let name = args
+                .file
+                .as_ref()
+                .map_or("<stdin>".into(), |f| f.display().to_string());
+            let index = span::LineIndex::new(&src);
+            let diagnostics = linter.lint(&src)?;
+            for d in diagnostics.iter().filter(|_| !args.quiet) {
+                let at = d.span.map_or(span::LineCol { line: 1, col: 1 }, |span| {
+                    index.line_col(span.start)
+                });
+                if args.porcelain {
+                    println!(
+                        "{name}\t{}\t{}\t{}\t{}\t{}\t{}",
+                        at.line, at.col, d.severity, d.rule, d.path, d.message
+                    );
+                } else {
+                    println!("{name}:{}:{}: {d}", at.line, at.col);
+                }
+            }
+            let errors = diagnostics
+                .iter()
+                .filter(|d| d.severity == lint::Severity::Error)
+                .count();
+            if errors > 0 {
+                return Err(Failure::Validation(format!("{errors} lint errors")).into());
+            }
         }
-        Verb::Into => {
-            let value = get_gon_input(args.file)?;
-            println!(
+        Verb::Table => {
+            let value = get_input(args.file, args.from, args.dialect)?;
+            let style = if args.markdown {
+                TableStyle::Markdown
+            } else {
+                TableStyle::Aligned
+            };
+            print!(
                 "{}",
-                serde_json::to_string_pretty(&serde_json::Value::from(value))
-                    .map_err(|e| Box::new(e))?
+                table::render(<&List>::try_from(&value)?, &args.columns, style)?
             );
         }
-        Verb::From => {
-            let json = get_json_input(args.file)?;
-            let spell_config = SpellConfig {
-                indent_amount: args.indent_width,
-                indent_char: args.indent_char,
-                trailing_commas: args.trailing_commas,
-                max_width: args.max_width,
+        Verb::Md => {
+            let value = get_input(args.file, args.from, args.dialect)?;
+            print!("{}", markdown::to_markdown(&value));
+        }
+        Verb::Pick | Verb::Omit => {
+            let spell_config = spell_config(&args)?;
+            let value = get_input(args.file, args.from, args.dialect)?;
+            let patterns = args
+                .keys
+                .iter()
+                .map(|key| key.parse())
+                .collect::<Result<Vec<path::Pattern>, _>>()?;
+            let projected = if matches!(args.verb, Verb::Pick) {
+                path::pick(&value, &patterns)
+            } else {
+                path::omit(&value, &patterns)
             };
-            println!("{}", Value::from(json).spell(spell_config)?);
+            println!("{}", projected.spell(spell_config)?);
         }
-        Verb::Verify => match get_gon_input(args.file) {
-            Ok(value) => {
-                println!("VALID");
-                return Ok(());
+        Verb::Get => {
+            let value = get_input(args.file, args.from, args.dialect)?;
+            let patterns = args
+                .keys
+                .iter()
+                .map(|key| key.parse())
+                .collect::<Result<Vec<path::Pattern>, _>>()?;
+            let selected: Vec<_> = patterns
+                .iter()
+                .flat_map(|pattern| path::select(&value, pattern))
+                .collect();
+            let limit = args.limit.unwrap_or(usize::MAX);
+            let mut stdout = BufWriter::new(std::io::stdout().lock());
+            for (path, value) in selected.iter().skip(args.offset).take(limit) {
+                if args.raw {
+                    let preview;
+                    let value = if args.preview {
+                        preview = value.preview();
+                        &preview
+                    } else {
+                        *value
+                    };
+                    write_raw(&mut stdout, value)?;
+                } else if args.preview {
+                    writeln!(stdout, "{path} = {}", value.preview().min_spell())?;
+                } else {
+                    writeln!(stdout, "{path} = {}", value.min_spell())?;
+                }
+            }
+            stdout.flush()?;
+            let shown = selected.len().saturating_sub(args.offset).min(limit);
+            if args.offset + shown < selected.len() && !args.quiet {
+                eprintln!(
+                    "{} more, continue with --offset {}",
+                    selected.len() - args.offset - shown,
+                    args.offset + shown
+                );
+            }
+        }
+        Verb::Query => {
+            let spell_config = spell_config(&args)?;
+            let value = get_input(args.file, args.from, args.dialect)?;
+            let Some(expr) = &args.expr else {
+                return Err(Failure::Usage("the query verb needs an --expr".into()).into());
+            };
+            let mut docs = MapT::new();
+            for arg in &args.arg {
+                let (name, file) = match arg.split_once('=') {
+                    Some((name, file)) => (name.to_string(), PathBuf::from(file)),
+                    None => {
+                        let file = PathBuf::from(arg);
+                        let Some(name) = file.file_stem() else {
+                            return Err(
+                                Failure::Usage(format!("no document name in '{arg}'")).into()
+                            );
+                        };
+                        (name.to_string_lossy().into_owned(), file)
+                    }
+                };
+                docs.insert(KeyT::from(name), get_gon_input(Some(file))?);
+            }
+            let query = expr.parse::<query::Query>()?;
+            let mut result = query.run_with(&value, &Value::Obj(docs))?;
+            if let Value::List(xs) = &mut result {
+                let limit = args.limit.unwrap_or(usize::MAX);
+                *xs = xs.drain(..).skip(args.offset).take(limit).collect();
+            }
+            if args.raw {
+                let mut stdout = BufWriter::new(std::io::stdout().lock());
+                write_raw(&mut stdout, &result)?;
+                stdout.flush()?;
+            } else {
+                println!("{}", result.spell(spell_config)?);
+            }
+        }
+        Verb::Outline => {
+            let value = get_input(args.file, args.from, args.dialect)?;
+            println!("{}", value.outline(args.depth.unwrap_or(usize::MAX)));
+        }
+        Verb::Apply => {
+            let spell_config = spell_config(&args)?;
+            let mut value = get_input(args.file.clone(), args.from, args.dialect)?;
+            #[cfg(feature = "rhai")]
+            if let Some(rhai) = &args.rhai {
+                let script = std::fs::read_to_string(rhai)?;
+                scripting::ScriptEngine::new().run(&script, &mut value)?;
+                return print_or_write_to_file(
+                    &value.spell(spell_config)?,
+                    &args,
+                    &mut std::io::stdout(),
+                );
+            }
+            let Some(script) = &args.script else {
+                return Err(Failure::Usage("the apply verb needs a --script".into()).into());
+            };
+            let script: script::Script = std::fs::read_to_string(script)?.parse()?;
+            script.apply(&mut value)?;
+            print_or_write_to_file(&value.spell(spell_config)?, &args, &mut std::io::stdout())?;
+        }
+        Verb::Graph => {
+            let value = get_input(args.file, args.from, args.dialect)?;
+            print!("{}", graph::render(&value, args.format));
+        }
+        Verb::Render => {
+            let template = get_src(args.file.clone())?;
+            let vars = match &args.vars {
+                Some(vars) => get_gon_input(Some(vars.clone()))?,
+                None => Value::None,
+            };
+            let rendered = template::render(&template, &vars)?.spell(spell_config(&args)?)?;
+            match &args.out {
+                Some(out) => std::fs::write(out, rendered)?,
+                None => println!("{rendered}"),
+            }
+        }
+        Verb::Doc => {
+            let Some(schema) = &args.schema else {
+                return Err(Failure::Usage("the doc verb needs a --schema".into()).into());
+            };
+            let schema = schema::Schema::from_file(schema)?;
+            let reference = markdown::schema_reference(&schema, args.examples);
+            match &args.out {
+                Some(out) => std::fs::write(out, reference)?,
+                None => print!("{reference}"),
+            }
+        }
+        Verb::I18nExtract => {
+            let spell_config = spell_config(&args)?;
+            let value = get_input(args.file, args.from, args.dialect)?;
+            println!("{}", i18n::extract(&value).spell(spell_config)?);
+        }
+        Verb::I18nMerge => {
+            let Some(catalog) = &args.catalog else {
+                return Err(Failure::Usage("the i18n-merge verb needs a --catalog".into()).into());
+            };
+            let spell_config = spell_config(&args)?;
+            let catalog = get_gon_input(Some(catalog.clone()))?;
+            let src = get_src(args.file)?;
+            let mut value = parse_str(&src)?;
+            i18n::merge(&mut value, &catalog)?;
+            match document::Document::parse(src) {
+                Ok(mut doc) => {
+                    doc.update(&value)?;
+                    print!("{doc}");
+                }
+                Err(_) => println!("{}", value.spell(spell_config)?),
+            }
+        }
+        Verb::Merge => {
+            let (Some(base), Some(theirs)) = (&args.base, &args.theirs) else {
+                let message = "the merge verb needs a --base and --theirs";
+                return Err(Failure::Usage(message.into()).into());
+            };
+            if args.interactive && args.file.is_none() {
+                let message = "the merge verb reads the answers from stdin with --interactive, \
+                               so it needs a file";
+                return Err(Failure::Usage(message.into()).into());
+            }
+            let spell_config = spell_config(&args)?;
+            let base = get_gon_input(Some(base.clone()))?;
+            let theirs = get_gon_input(Some(theirs.clone()))?;
+            let src = get_src(args.file.clone())?;
+            let ours = parse_str(&src)?;
+            let mut merged = merge::three_way(&base, &ours, &theirs);
+            if args.interactive {
+                resolve_interactively(&mut merged)?;
+            }
+            for conflict in merged.conflicts.iter().filter(|_| !args.quiet) {
+                let side =
+                    |v: &Option<Value>| v.as_ref().map_or("removed".into(), Value::min_spell);
+                if args.porcelain {
+                    let (ours, theirs) = (side(&conflict.ours), side(&conflict.theirs));
+                    eprintln!("conflict\t{}\t{ours}\t{theirs}", conflict.path);
+                } else {
+                    eprintln!("CONFLICT {}", conflict.path);
+                    eprintln!("    ours:   {}", side(&conflict.ours));
+                    eprintln!("    theirs: {}", side(&conflict.theirs));
+                }
+            }
+            // our formatting and comments are kept where the merge didn't change anything
+            let merged_src = match document::Document::parse(src) {
+                Ok(mut doc) => {
+                    doc.update(&merged.value)?;
+                    doc.to_string().trim_end().to_string()
+                }
+                Err(_) => merged.value.spell(spell_config)?,
+            };
+            print_or_write_to_file(&merged_src, &args, &mut std::io::stdout())?;
+            if !merged.conflicts.is_empty() {
+                let message = format!("{} conflicts", merged.conflicts.len());
+                return Err(Failure::Validation(message).into());
+            }
+        }
+        Verb::Upgrade => {
+            let src = get_src(args.file.clone())?;
+            print_or_write_to_file(&pragma::upgrade(&src)?, &args, &mut std::io::stdout())?;
+        }
+        Verb::Unused => {
+            let Some(usage) = &args.usage else {
+                return Err(Failure::Usage("the unused verb needs a --usage".into()).into());
+            };
+            let usage: usage::Usage = std::fs::read_to_string(usage)?.parse()?;
+            let value = get_input(args.file, args.from, args.dialect)?;
+            let unused = usage.unused(&value);
+            for path in unused.iter().filter(|_| !args.quiet) {
+                println!("{path}");
+            }
+            if !unused.is_empty() {
+                let message = format!("{} keys are never read", unused.len());
+                return Err(Failure::Validation(message).into());
+            }
+        }
+        Verb::ValidateWorkspace => {
+            let Some(manifest) = &args.file else {
+                return Err(
+                    Failure::Usage("the validate-workspace verb needs a manifest".into()).into(),
+                );
+            };
+            let mut workspace = workspace::Workspace::from_file(manifest)?;
+            if let Some(jobs) = args.jobs {
+                workspace = workspace.threads(jobs);
+            }
+            let report = workspace.validate()?;
+            if args.porcelain {
+                for file in &report.files {
+                    if file.errors.is_empty() {
+                        println!("{}\tvalid", file.file);
+                    }
+                    for e in &file.errors {
+                        println!(
+                            "{}\tinvalid\t{}",
+                            file.file,
+                            e.to_string().replace('\n', " ")
+                        );
+                    }
+                }
+            } else if !args.quiet {
+                print!("{report}");
+            }
+            if !report.is_ok() {
+                let invalid = report.invalid().count();
+                let message = format!("{invalid} of {} files are invalid", report.files.len());
+                return Err(Failure::Validation(message).into());
+            }
+        }
+        Verb::Deps => {
+            let Some(file) = &args.file else {
+                return Err(Failure::Usage("the deps verb needs a schema file".into()).into());
+            };
+            let (_, dependencies) = schema::Schema::from_file_with_dependencies(file)?;
+            for dependency in dependencies {
+                println!("{}", dependency.display());
+            }
+        }
+        Verb::Undo => {
+            let Some(file) = &args.file else {
+                return Err(Failure::Usage("the undo verb needs a file".into()).into());
+            };
+            if !history::undo(file)? {
+                return Err(format!("no history of '{}' to undo", file.display()).into());
+            }
+        }
+        Verb::Explain => {
+            let Some(base) = &args.file else {
+                return Err(Failure::Usage("the explain verb needs a file".into()).into());
+            };
+            let mut loader = config::Loader::new(base);
+            for overlay in &args.overlay {
+                loader = loader.overlay(overlay);
             }
-            Err(e) => {
-                println!("INVALID");
-                return Err(e);
+            if let Some(prefix) = &args.env_prefix {
+                loader = loader.env_prefix(prefix);
             }
-        },
+            let explained = loader.load_explained()?;
+            for key in &args.keys {
+                let path: path::Path = key.parse()?;
+                match (
+                    path::get(&explained.value, &path),
+                    explained.provenance(&path),
+                ) {
+                    (Some(value), Some(provenance)) if args.porcelain => {
+                        let file = provenance.file.as_ref();
+                        let at = match (file, provenance.span) {
+                            (Some(file), Some(span)) => {
+                                let src = std::fs::read_to_string(file)?;
+                                let at = span::LineIndex::new(&src).line_col(span.start);
+                                format!("{}\t{}", at.line, at.col)
+                            }
+                            _ => "\t".to_string(),
+                        };
+                        let file = file.map(|f| f.display().to_string()).unwrap_or_default();
+                        let layer = provenance.layer.to_string();
+                        println!("{key}\t{}\t{layer}\t{file}\t{at}", value.min_spell());
+                    }
+                    (Some(value), Some(provenance)) => {
+                        println!("{key} = {}", value.min_spell());
+                        println!("    set by the {}", provenance.layer);
+                        if let Some(file) = &provenance.file {
+                            print!("    in {}", file.display());
+                            if let Some(span) = provenance.span {
+                                let src = std::fs::read_to_string(file)?;
+                                let at = span::LineIndex::new(&src).line_col(span.start);
+                                print!(":{}:{}", at.line, at.col);
+                            }
+                            println!();
+                        }
+                    }
+                    _ if args.porcelain => println!("{key}\tunset"),
+                    _ => println!("{key} isn't set"),
+                }
+            }
+        }
+        Verb::Bench => {
+            let spell_config = spell_config(&args)?;
+            let src = get_src(args.file)?;
+            let value = parse_str(&src)?;
+            let iterations = args.iterations.unwrap_or(100).max(1);
+            let steps: [(&str, &dyn Fn()); 4] = [
+                ("parse", &|| drop(std::hint::black_box(parse_str(&src)))),
+                ("fmt", &|| {
+                    drop(std::hint::black_box(value.spell(spell_config)))
+                }),
+                ("min", &|| drop(std::hint::black_box(value.min_spell()))),
+                ("into", &|| {
+                    drop(std::hint::black_box(json::to_json(
+                        &value,
+                        Strictness::Lenient,
+                    )))
+                }),
+            ];
+            if !args.porcelain {
+                println!("{} bytes, {iterations} iterations", src.len());
+            }
+            for (name, step) in steps {
+                let per_iteration = time(step, iterations);
+                let throughput = src.len() as f64 / per_iteration.as_secs_f64() / 1e6;
+                if args.porcelain {
+                    println!("{name}\t{}\t{throughput:.2}", per_iteration.as_nanos());
+                } else {
+                    println!("{name:<6}{per_iteration:>12.2?}{throughput:>10.2} MB/s");
+                }
+            }
+        }
+        #[cfg(feature = "repl")]
+        Verb::Repl => repl::Repl::new(spell_config(&args)?).run()?,
     }
     Ok(())
 }
 
-fn print_or_write_to_file(out: &str, file: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
-    if let Some(file) = file {
-        Ok(std::fs::write(file, out)?)
-    } else {
-        println!("{out}");
-        Ok(())
+/// Asks on the terminal how to resolve every conflict of `merged`: keep our or their
+/// version, the base, or a value that's typed in
+fn resolve_interactively(merged: &mut merge::Merged) -> Result<(), Box<dyn Error>> {
+    let side = |v: &Option<Value>| v.as_ref().map_or("(removed)".into(), Value::min_spell);
+    let mut lines = std::io::stdin().lines();
+    let total = merged.conflicts.len();
+    for (i, conflict) in merged.conflicts.clone().into_iter().enumerate() {
+        eprintln!("conflict {} of {total} at {}", i + 1, conflict.path);
+        eprintln!("    base:   {}", side(&conflict.base));
+        eprintln!("    ours:   {}", side(&conflict.ours));
+        eprintln!("    theirs: {}", side(&conflict.theirs));
+        let resolution = loop {
+            eprint!("keep (o)urs, (t)heirs, (b)ase, or type a gon value: ");
+            let Some(line) = lines.next() else {
+                return Err("stdin ended before every conflict was resolved".into());
+            };
+            match line?.trim() {
+                "" => continue,
+                "o" => break conflict.ours.clone(),
+                "t" => break conflict.theirs.clone(),
+                "b" => break conflict.base.clone(),
+                typed => match parse_str(typed) {
+                    Ok(value) => break Some(value),
+                    Err(e) => eprintln!("{e}"),
+                },
+            }
+        };
+        merged.resolve(&conflict.path, resolution)?;
     }
+    Ok(())
 }
 
-fn get_src(file: Option<PathBuf>) -> Result<String, Box<dyn Error>> {
-    let src = if let Some(file) = file {
-        let file = File::open(file).map_err(|e| Box::new(e))?;
-        std::io::read_to_string(file).map_err(|e| Box::new(e))?
+/// How long one run of `step` takes on average, after a warm up run
+fn time(step: &dyn Fn(), iterations: u32) -> Duration {
+    step();
+    let start = Instant::now();
+    for _ in 0..iterations {
+        step();
+    }
+    start.elapsed() / iterations
+}
+
+/// Runs the verb on the input file and every one of `files` on `--jobs` threads. The
+/// output of every file is printed as a section of its own, in the order of the files, and
+/// the errors of every file are reported before failing.
+fn run_files(args: Args) -> Result<(), Box<dyn Error>> {
+    if !matches!(
+        args.verb,
+        Verb::Min | Verb::Fmt | Verb::Into | Verb::From | Verb::Verify
+    ) {
+        let message = "only the min, fmt, into, from and verify verbs take several files";
+        return Err(Failure::Usage(message.into()).into());
+    }
+    let files: Vec<_> = args.file.iter().chain(&args.files).cloned().collect();
+    if files.iter().filter(|f| is_stdin(f)).count() > 1 {
+        return Err(Failure::Usage("only one of the files can be `-`, stdin".into()).into());
+    }
+    let threads = args
+        .jobs
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, usize::from))
+        .clamp(1, files.len());
+    let progress = Progress::new(files.len() as u64, "{bar:40} {pos}/{len} files ({eta})");
+    // the bar of the files is the only one, parsing large files doesn't draw its own
+    PROGRESS.store(false, Ordering::Relaxed);
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    let (mut failed, mut printed) = (Vec::new(), 0);
+    std::thread::scope(|scope| -> Result<(), Box<dyn Error>> {
+        for _ in 0..threads {
+            let (args, files, next, sender) = (&args, &files, &next, sender.clone());
+            scope.spawn(move || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(file) = files.get(i) else {
+                        return;
+                    };
+                    let args = Args {
+                        file: Some(file.clone()).filter(|f| !is_stdin(f)),
+                        files: Vec::new(),
+                        ..args.clone()
+                    };
+                    let mut out = Vec::new();
+                    // errors aren't `Send`, only whether they're validation failures is kept
+                    let result = run_file(&args, &mut out).map_err(|e| {
+                        let invalid = matches!(e.downcast_ref(), Some(Failure::Validation(_)));
+                        (invalid, e.to_string())
+                    });
+                    if sender.send((i, out, result)).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        // print the sections in order as soon as the ones before them are done
+        let mut done = BTreeMap::new();
+        let mut stdout = std::io::stdout().lock();
+        for (i, out, result) in receiver {
+            progress.inc(1);
+            done.insert(i, (out, result));
+            while let Some((out, result)) = done.remove(&printed) {
+                progress.suspend(|| {
+                    print_section(&args, &files[printed], &out, &result, &mut stdout)
+                })?;
+                failed.extend(result.err());
+                printed += 1;
+            }
+        }
+        Ok(())
+    })?;
+    if failed.is_empty() {
+        return Ok(());
+    }
+    let message = format!("{} of {} files failed", failed.len(), files.len());
+    if failed.iter().all(|(invalid, _)| *invalid) {
+        Err(Failure::Validation(message).into())
     } else {
+        Err(message.into())
+    }
+}
+
+/// Prints what the verb printed for `file` and its error, for [`run_files`]
+fn print_section(
+    args: &Args,
+    file: &Path,
+    out: &[u8],
+    result: &Result<(), (bool, String)>,
+    stdout: &mut impl Write,
+) -> std::io::Result<()> {
+    let file = file.display();
+    if args.porcelain {
+        for line in String::from_utf8_lossy(out).lines() {
+            writeln!(stdout, "{file}\t{line}")?;
+        }
+    } else if !out.is_empty() {
+        writeln!(stdout, "==> {file} <==")?;
+        stdout.write_all(out)?;
+    }
+    stdout.flush()?;
+    if let Err((_, e)) = result {
+        if args.porcelain {
+            eprintln!("error\t{file}\t{}", e.replace('\n', " "));
+        } else if !args.quiet {
+            eprintln!("Error: {file}: {e}");
+        }
+    }
+    Ok(())
+}
+
+/// Runs the verbs that work on one file at a time, see [`run_files`], writing what they
+/// print to `out`
+fn run_file(args: &Args, out: &mut dyn Write) -> Result<(), Box<dyn Error>> {
+    match args.verb {
+        Verb::Min => {
+            let value = get_input(args.file.as_ref().cloned(), args.from, args.dialect)?;
+            let spelled = match args.dialect {
+                Some(Dialect::JsonCompat) => value.min_spell_json_compat(),
+                _ => value.min_spell(),
+            };
+            print_or_write_to_file(&spelled, args, out)?;
+        }
+        Verb::Fmt => {
+            let bytes = get_bytes(args.file.as_ref().cloned())?;
+            let format = input_format(args.from.unwrap_or(InputFormat::Gon), &bytes)?;
+            let spell_config = spell_config(args)?;
+            let options = parser::ParseOptions::new().dotted_keys(spell_config.dotted_keys);
+            let options = args.dialect.unwrap_or_default().parse_options(options);
+            // only gon sources have comments and pragmas to keep
+            let (mut value, src) = match format {
+                Format::Gon => {
+                    let src = encoding::decode(&bytes, encoding::Encoding::Utf8)?;
+                    (parse_large(&src, &options)?, src)
+                }
+                format => (parse_foreign(format, &bytes)?, String::new()),
+            };
+            key_order_policy(args)?.apply(&mut value);
+            // documents keep comments, but only spell keys plainly
+            let keeps_comments = !spell_config.dotted_keys && !spell_config.json_compat;
+            let document = if format != Format::Gon {
+                None
+            } else if src.contains("gon-fmt:") {
+                Some(document::Document::parse(&*src)?)
+            } else if keeps_comments && has_comments(&src) {
+                document::Document::parse(&*src).ok()
+            } else {
+                None
+            };
+            let formatted = match document {
+                Some(document) => document.format_value(&value, spell_config)?,
+                None => match pragma::pragma(&src) {
+                    Some(p) => format!("{}\n{}", &src[p.span.range()], value.spell(spell_config)?),
+                    None => value.spell(spell_config)?,
+                },
+            };
+            print_or_write_to_file(&formatted, args, out)?;
+        }
+        Verb::Into => {
+            let strictness = if args.strict_numbers {
+                Strictness::Strict
+            } else {
+                Strictness::Lenient
+            };
+            let bytes = get_bytes(args.file.clone())?;
+            let format = input_format(args.from.unwrap_or(InputFormat::Gon), &bytes)?;
+            if format != Format::Gon {
+                let value = parse_foreign(format, &bytes)?;
+                json::write_json_with(&value, &mut *out, true, strictness)?;
+                return Ok(writeln!(out)?);
+            }
+            let src = encoding::decode(&bytes, encoding::Encoding::Utf8)?;
+            drop(bytes);
+            let dialect = args.dialect.unwrap_or_default();
+            // plain data is written while it's read, without holding the whole document
+            if format == Format::Gon && dialect == Dialect::Standard {
+                let _progress = Progress::for_input(&src);
+                match json::transcode(&src, &mut *out, true, strictness) {
+                    Ok(()) => return Ok(writeln!(out)?),
+                    Err(json::TranscodeError::NotPlain(_)) => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            let value = parse_large(&src, &parser::ParseOptions::from(dialect))?;
+            json::write_json_with(&value, &mut *out, true, strictness)?;
+            writeln!(out)?;
+        }
+        Verb::From => {
+            let spell_config = spell_config(args)?;
+            let from = args.from.unwrap_or(InputFormat::Json);
+            let value = get_input(args.file.clone(), Some(from), args.dialect)?;
+            writeln!(out, "{}", value.spell(spell_config)?)?;
+        }
+        Verb::Verify => {
+            let policy = key_order_policy(args)?;
+            let src = get_src(args.file.clone())?;
+            let options = parser::ParseOptions::from(args.dialect.unwrap_or_default());
+            let parsed = {
+                let _progress = Progress::for_input(&src);
+                parser::parse_str_with_report(&src, &options)
+            };
+            // every check runs before the verdict is printed
+            let (report, problem, violations) = match parsed {
+                Ok((value, report)) => {
+                    let problem = pragma::check(&src, &value).err().map(|e| e.to_string());
+                    // without `preserve_order` the keys don't come in source order
+                    let violations = if cfg!(feature = "preserve_order") {
+                        policy.check(&value)
+                    } else {
+                        Vec::new()
+                    };
+                    (Some(report), problem, violations)
+                }
+                Err(e) => (None, Some(e.to_string()), Vec::new()),
+            };
+            let valid = problem.is_none() && violations.is_empty();
+            if args.porcelain {
+                writeln!(out, "{}", if valid { "valid" } else { "invalid" })?;
+                for v in &violations {
+                    writeln!(out, "key-order\t{}\t{}\t{}", v.path, v.key, v.before)?;
+                }
+            } else if !args.quiet {
+                writeln!(out, "{}", if valid { "VALID" } else { "INVALID" })?;
+                if let Some(report) = report.filter(|_| args.verbose) {
+                    writeln!(out, "{report}")?;
+                }
+                for violation in &violations {
+                    writeln!(out, "KEY ORDER {violation}")?;
+                }
+            }
+            if let Some(problem) = problem {
+                return Err(Failure::Validation(problem).into());
+            }
+            if !violations.is_empty() {
+                let message = format!("{} objects break the key order", violations.len());
+                return Err(Failure::Validation(message).into());
+            }
+        }
+        _ => unreachable!("{:?} doesn't work on one file at a time", args.verb),
+    }
+    Ok(())
+}
+
+/// The formatting profile closest to the input, if there is one
+fn format_profile(args: &Args) -> Result<Option<PathBuf>, Box<dyn Error>> {
+    let dir = match args.file.as_deref().and_then(Path::parent) {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => std::env::current_dir()?,
+    };
+    Ok(dir
+        .ancestors()
+        .map(|d| d.join(SpellConfig::FILE_NAME))
+        .find(|path| path.is_file()))
+}
+
+fn key_order_policy(args: &Args) -> Result<KeyOrderPolicy, Box<dyn Error>> {
+    match format_profile(args)? {
+        Some(path) => Ok(KeyOrderPolicy::from_file(path)?),
+        None => Ok(KeyOrderPolicy::default()),
+    }
+}
+
+/// The `.gonfmt.gon` closest to the input with the formatting flags applied on top
+fn spell_config(args: &Args) -> Result<SpellConfig, Box<dyn Error>> {
+    let mut config = match format_profile(args)? {
+        Some(path) => SpellConfig::from_file(path)?,
+        None => SpellConfig::default(),
+    };
+    if let Some(dialect) = args.dialect {
+        config = dialect.spell_config(config);
+    }
+    if let Some(indent_width) = args.indent_width {
+        config.indent_amount = indent_width;
+    }
+    if let Some(indent_char) = args.indent_char {
+        config.indent_char = indent_char;
+    }
+    if args.trailing_commas {
+        config.trailing_commas = true;
+    }
+    if let Some(max_width) = args.max_width {
+        config.max_width = max_width;
+    }
+    if args.squash_strings {
+        config.preserve_strings = false;
+    }
+    if let Some(max_entries) = args.inline_objects {
+        config.inline_small_objects = Some(InlineLimits {
+            max_entries,
+            ..Default::default()
+        });
+    }
+    Ok(config)
+}
+
+fn print_or_write_to_file(
+    text: &str,
+    args: &Args,
+    out: &mut dyn Write,
+) -> Result<(), Box<dyn Error>> {
+    match &args.file {
+        Some(file) if args.in_place => {
+            if args.history && std::fs::read(file)? != text.as_bytes() {
+                history::record(file)?;
+            }
+            Ok(std::fs::write(file, text)?)
+        }
+        _ => Ok(writeln!(out, "{text}")?),
+    }
+}
+
+/// Whether `src` has comments besides its pragma
+fn has_comments(src: &str) -> bool {
+    let pragma = pragma::pragma(src).map(|p| p.span);
+    parser::tokenize(src)
+        .iter()
+        .any(|t| t.kind == parser::TokenKind::Comment && Some(t.span) != pragma)
+}
+
+/// Writes `value` for `--raw`: strings without quotes, lists one element per line and
+/// everything else minified
+fn write_raw(out: &mut impl Write, value: &Value) -> std::io::Result<()> {
+    match value {
+        Value::Str { s, .. } => writeln!(out, "{s}"),
+        Value::List(xs) => xs.iter().try_for_each(|x| match x {
+            Value::Str { s, .. } => writeln!(out, "{s}"),
+            x => writeln!(out, "{}", x.min_spell()),
+        }),
+        value => writeln!(out, "{}", value.min_spell()),
+    }
+}
+
+/// Whether `file` stands for stdin
+fn is_stdin(file: &Path) -> bool {
+    file == Path::new("-")
+}
+
+/// Whether to tell how to end the input before reading it from a terminal, off with
+/// `--quiet` and `--porcelain`. Set once the arguments are parsed, like [`PROGRESS`].
+static STDIN_HINT: AtomicBool = AtomicBool::new(false);
+
+/// How long reading the input may take, see `--timeout`
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+/// Tells users who ran gon without a file and without piping anything in why it waits,
+/// instead of leaving them with a silent terminal
+fn hint_stdin() {
+    if STDIN_HINT.load(Ordering::Relaxed) && std::io::stdin().is_terminal() {
+        let end = if cfg!(windows) {
+            "Ctrl-Z and Enter"
+        } else {
+            "Ctrl-D"
+        };
+        eprintln!(
+            "Reading the input from the terminal, end it with {end}. \
+             Pass a file to read it instead, see `gon --help`."
+        );
+    }
+}
+
+/// Reads all of `file`, or of stdin if there's none, failing after the `--timeout`
+fn get_bytes(file: Option<PathBuf>) -> Result<Vec<u8>, Box<dyn Error>> {
+    if file.is_none() {
+        hint_stdin();
+    }
+    let what = file
+        .as_ref()
+        .map_or("stdin".into(), |f| f.display().to_string());
+    let read = move || -> std::io::Result<Vec<u8>> {
         let mut input = Vec::new();
-        std::io::stdin()
-            .read_to_end(&mut input)
-            .map_err(|e| Box::new(e))?;
-        String::from_utf8(input).map_err(|e| Box::new(e))?
+        match file {
+            Some(file) => File::open(file)?.read_to_end(&mut input)?,
+            None => std::io::stdin().read_to_end(&mut input)?,
+        };
+        Ok(input)
+    };
+    let Some(&timeout) = TIMEOUT.get() else {
+        return Ok(read()?);
+    };
+    // on a timeout the thread is left waiting until gon exits
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || sender.send(read()));
+    match receiver.recv_timeout(timeout) {
+        Ok(input) => Ok(input?),
+        Err(_) => Err(format!("reading {what} took longer than {timeout:?}").into()),
+    }
+}
+
+fn get_src(file: Option<PathBuf>) -> Result<String, Box<dyn Error>> {
+    Ok(encoding::decode(
+        &get_bytes(file)?,
+        encoding::Encoding::Utf8,
+    )?)
+}
+
+/// Parses `src` like [`parser::parse_str_with`], with a spinner if it's large
+fn parse_large(src: &str, options: &parser::ParseOptions) -> Result<Value, GonError> {
+    let _progress = Progress::for_input(src);
+    parser::parse_str_with(src, options)
+}
+
+/// Whether to draw progress bars, see `--no-progress`. Set once the arguments are parsed,
+/// it's global so that reading the input doesn't need the arguments.
+static PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Inputs of at least this many bytes get a spinner while they're parsed
+const LARGE_INPUT: usize = 16 << 20;
+
+/// A progress bar on stderr, which indicatif hides if stderr isn't a terminal. Without the
+/// `progress` feature, or if [`PROGRESS`] is off, it draws nothing.
+struct Progress {
+    #[cfg(feature = "progress")]
+    bar: Option<indicatif::ProgressBar>,
+}
+
+impl Progress {
+    /// A bar that goes up to `len`, drawn like the indicatif `template`. There's no bar for
+    /// nothing to do.
+    fn new(len: u64, template: &str) -> Self {
+        #[cfg(feature = "progress")]
+        {
+            let bar = (len > 0 && PROGRESS.load(Ordering::Relaxed)).then(|| {
+                let bar = indicatif::ProgressBar::new(len);
+                if let Ok(style) = indicatif::ProgressStyle::with_template(template) {
+                    bar.set_style(style);
+                }
+                bar
+            });
+            Self { bar }
+        }
+        #[cfg(not(feature = "progress"))]
+        {
+            let _ = (len, template);
+            Self {}
+        }
+    }
+
+    /// A spinner while `src` is parsed if it's large, and nothing otherwise. The parser
+    /// doesn't say how far it got, so there's no bar to fill.
+    fn for_input(src: &str) -> Self {
+        let large = src.len() >= LARGE_INPUT && PROGRESS.load(Ordering::Relaxed);
+        #[cfg(feature = "progress")]
+        {
+            let bar = large.then(|| {
+                let bar = indicatif::ProgressBar::new_spinner();
+                if let Ok(style) =
+                    indicatif::ProgressStyle::with_template("{spinner} parsing {msg} ({elapsed})")
+                {
+                    bar.set_style(style);
+                }
+                bar.set_message(indicatif::HumanBytes(src.len() as u64).to_string());
+                bar.enable_steady_tick(std::time::Duration::from_millis(100));
+                bar
+            });
+            Self { bar }
+        }
+        #[cfg(not(feature = "progress"))]
+        {
+            let _ = large;
+            Self {}
+        }
+    }
+
+    fn inc(&self, delta: u64) {
+        #[cfg(feature = "progress")]
+        if let Some(bar) = &self.bar {
+            bar.inc(delta);
+        }
+        #[cfg(not(feature = "progress"))]
+        let _ = delta;
+    }
+
+    /// Runs `f` with the bar cleared, so that it can print
+    fn suspend<T>(&self, f: impl FnOnce() -> T) -> T {
+        #[cfg(feature = "progress")]
+        if let Some(bar) = &self.bar {
+            return bar.suspend(f);
+        }
+        f()
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        #[cfg(feature = "progress")]
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+fn parse_json(src: &str) -> Result<Value, Box<dyn Error>> {
+    Ok(Value::from(serde_json::from_str::<JsonValue>(src)?))
+}
+
+/// Reads `bytes` in a `format` other than gon, through `serde_json::Value`
+fn parse_foreign(format: Format, bytes: &[u8]) -> Result<Value, Box<dyn Error>> {
+    match format {
+        Format::Json => parse_json(&encoding::decode(bytes, encoding::Encoding::Utf8)?),
+        #[cfg(feature = "yaml")]
+        Format::Yaml => Ok(Value::from(serde_yaml::from_slice::<JsonValue>(bytes)?)),
+        #[cfg(feature = "msgpack")]
+        Format::MsgPack => Ok(Value::from(rmp_serde::from_slice::<JsonValue>(bytes)?)),
+        format => unreachable!("{format} input is rejected by input_format"),
+    }
+}
+
+/// The format to read `bytes` as, sniffed for `--from auto`. Fails for formats that can be
+/// recognized but not read because gon was built without their feature.
+fn input_format(from: InputFormat, bytes: &[u8]) -> Result<Format, Box<dyn Error>> {
+    let format = match from {
+        InputFormat::Gon => Format::Gon,
+        InputFormat::Json => Format::Json,
+        InputFormat::Auto => sniff::sniff(bytes),
     };
-    Ok(src)
+    let feature = match format {
+        Format::Yaml if !cfg!(feature = "yaml") => "yaml",
+        Format::MsgPack if !cfg!(feature = "msgpack") => "msgpack",
+        format => return Ok(format),
+    };
+    Err(format!(
+        "the input looks like {format}, which gon can only read when built with the `{feature}` \
+         feature, convert it to JSON first"
+    )
+    .into())
 }
 
-fn get_json_input(file: Option<PathBuf>) -> Result<JsonValue, Box<dyn Error>> {
-    let src = get_src(file)?;
-    serde_json::from_str(&src).map_err(|e| e.into())
+/// Reads the input as `--from` says, as gon if it's not given, in the `--dialect`
+fn get_input(
+    file: Option<PathBuf>,
+    from: Option<InputFormat>,
+    dialect: Option<Dialect>,
+) -> Result<Value, Box<dyn Error>> {
+    let bytes = get_bytes(file)?;
+    let format = input_format(from.unwrap_or(InputFormat::Gon), &bytes)?;
+    if format != Format::Gon {
+        return parse_foreign(format, &bytes);
+    }
+    let src = encoding::decode(&bytes, encoding::Encoding::Utf8)?;
+    let options = parser::ParseOptions::from(dialect.unwrap_or_default());
+    Ok(parse_large(&src, &options)?)
 }
 
 fn get_gon_input(file: Option<PathBuf>) -> Result<Value, Box<dyn Error>> {
-    let src = get_src(file)?;
-    parse_str(&src).map_err(|e| e.into())
+    if TIMEOUT.get().is_some() {
+        return Ok(parser::parse_reader(&get_bytes(file)?[..])?);
+    }
+    let value = if let Some(file) = file {
+        parser::parse_reader(File::open(file)?)?
+    } else {
+        hint_stdin();
+        parser::parse_reader(std::io::stdin().lock())?
+    };
+    Ok(value)
 }
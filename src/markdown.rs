@@ -0,0 +1,239 @@
+//! Exporting gon documents and schemas as markdown.
+
+use crate::schema::{Field, Schema};
+use crate::table::{self, TableStyle};
+use crate::{MapT, Value};
+
+/// The deepest markdown heading level. Keys nested deeper become bold lines.
+const MAX_HEADING_LEVEL: usize = 6;
+
+/// Renders `value` as nested markdown: keys of objects become headings, lists of objects
+/// become tables, other lists become bullet lists and scalars become code spans.
+/// # Usage example
+/// ```rust
+/// use gon::{markdown::to_markdown, parse_str};
+/// let value = parse_str(r#"{server: {port: 80}, tags: ["a", "b"]}"#).unwrap();
+/// assert!(to_markdown(&value).contains("## port\n\n`80`\n"));
+/// ```
+pub fn to_markdown(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value, 1);
+    out
+}
+
+fn write_value(out: &mut String, value: &Value, level: usize) {
+    match value {
+        Value::Obj(obj) => {
+            for (key, v) in obj.iter() {
+                if level <= MAX_HEADING_LEVEL {
+                    out.push_str(&format!("{} {key}\n\n", "#".repeat(level)));
+                } else {
+                    out.push_str(&format!("**{key}**\n\n"));
+                }
+                write_value(out, v, level + 1);
+            }
+        }
+        Value::List(xs) if !xs.is_empty() && xs.iter().all(|x| matches!(x, Value::Obj(_))) => {
+            let list = value.as_list().expect("checked to be a list");
+            let rendered = table::render(list, &[], TableStyle::Markdown)
+                .expect("checked to only contain objects");
+            out.push_str(&rendered);
+            out.push('\n');
+        }
+        Value::List(xs) if !xs.is_empty() => {
+            for x in xs {
+                out.push_str(&format!("- {}\n", code_span(&x.min_spell())));
+            }
+            out.push('\n');
+        }
+        scalar => out.push_str(&format!("{}\n\n", code_span(&scalar.min_spell()))),
+    }
+}
+
+/// Renders reference documentation of every field of an annotated `schema` (see
+/// [`schema`](crate::schema)) as a markdown table of keys, types, defaults and descriptions.
+/// With `examples`, the fields' examples follow in their own sections, linked from the
+/// table.
+/// # Usage example
+/// ```rust
+/// use gon::{markdown::schema_reference, parse_str, schema::Schema};
+/// let schema = Schema::from_value(&parse_str(r#"{
+///     port: {"@type": "num?", "@default": 80, "@doc": "Where to listen"},
+/// }"#).unwrap()).unwrap();
+/// assert_eq!(
+///     schema_reference(&schema, false),
+///     "| Key    | Type   | Default | Description     |\n\
+///      | ------ | ------ | ------- | --------------- |\n\
+///      | `port` | `num?` | `80`    | Where to listen |\n"
+/// );
+/// ```
+pub fn schema_reference(schema: &Schema, examples: bool) -> String {
+    let mut fields = Vec::new();
+    collect_fields(schema, &mut String::new(), &mut fields);
+    let rows = fields
+        .iter()
+        .map(|(path, field)| {
+            let annotations = &field.annotations;
+            let mut description = annotations.doc.clone().unwrap_or_default();
+            if let Some(deprecated) = &annotations.deprecated {
+                description.push_str(&format!(" **Deprecated:** {deprecated}"));
+            }
+            if examples && annotations.example.is_some() {
+                description.push_str(&format!(" ([example](#{}))", anchor(path)));
+            }
+            let optional = if field.optional { "?" } else { "" };
+            let cells = [
+                ("Key", code_span(path)),
+                ("Type", code_span(&format!("{}{optional}", field.schema))),
+                (
+                    "Default",
+                    annotations
+                        .default
+                        .as_ref()
+                        .map_or(String::new(), |v| code_span(&v.min_spell())),
+                ),
+                ("Description", description.trim().to_string()),
+            ];
+            Value::Obj(MapT::from_iter(
+                cells.map(|(column, s)| (column.into(), Value::Str { s, raw: false })),
+            ))
+        })
+        .collect();
+    let columns = ["Key", "Type", "Default", "Description"].map(String::from);
+    let rows = Value::List(rows);
+    let mut out = table::render(
+        rows.as_list().expect("just built as a list"),
+        &columns,
+        TableStyle::Markdown,
+    )
+    .expect("just built from objects");
+    if examples {
+        for (path, field) in &fields {
+            if let Some(example) = &field.annotations.example {
+                let spelled = example
+                    .spell(Default::default())
+                    .unwrap_or_else(|_| example.min_spell());
+                out.push_str(&format!("\n## {path}\n\n```gon\n{spelled}\n```\n"));
+            }
+        }
+    }
+    out
+}
+
+/// Every field of `schema` and its path, parents before their children
+fn collect_fields<'a>(schema: &'a Schema, path: &mut String, out: &mut Vec<(String, &'a Field)>) {
+    match schema {
+        Schema::Obj(fields) => {
+            for field in fields {
+                let len = path.len();
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(&field.key);
+                out.push((path.clone(), field));
+                collect_fields(&field.schema, path, out);
+                path.truncate(len);
+            }
+        }
+        Schema::List(inner) => {
+            path.push_str("[]");
+            collect_fields(inner, path, out);
+            path.truncate(path.len() - 2);
+        }
+        Schema::Conditional { schema, .. } => collect_fields(schema, path, out),
+        // the fields of each variant, e.g. `shape<circle>.radius`
+        Schema::Discriminated { variants, .. } => {
+            for (name, variant) in variants {
+                let len = path.len();
+                path.push_str(&format!("<{name}>"));
+                collect_fields(variant, path, out);
+                path.truncate(len);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The id GitHub gives the heading `heading`
+fn anchor(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Wraps `text` in enough backticks that backticks inside it don't end the span
+fn code_span(text: &str) -> String {
+    let text = text.replace('\n', " ");
+    let mut longest_run = 0;
+    let mut run = 0;
+    for c in text.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest_run = longest_run.max(run);
+    }
+    if longest_run == 0 {
+        format!("`{text}`")
+    } else {
+        let fence = "`".repeat(longest_run + 1);
+        format!("{fence} {text} {fence}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn headings_tables_and_lists() {
+        let value = parse_str(
+            r#"{enemies: {list: [{name: "slime"}, {name: "bat"}], spawn_rates: [0.5, "a`b"], boss: None}}"#,
+        )
+        .unwrap();
+        let md = to_markdown(&value);
+        assert!(md.starts_with("# enemies\n\n"));
+        assert!(md.contains("## list\n\n| name  |\n| ----- |\n| slime |\n| bat   |\n\n"));
+        assert!(md.contains("## spawn_rates\n\n- `0.5`\n- `` \"a`b\" ``\n\n"));
+        assert!(md.contains("## boss\n\n`None`\n\n"));
+    }
+
+    #[test]
+    fn schema_reference_with_examples() {
+        let schema = Schema::from_value(
+            &parse_str(
+                r#"{
+                    server: {"@type": {port: "num"}, "@doc": "Networking", "@example": {port: 1}},
+                    users: [{name: {"@type": "str", "@deprecated": "use id"}}],
+                }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            schema_reference(&schema, true),
+            "\
+| Key            | Type    | Default | Description                     |
+| -------------- | ------- | ------- | ------------------------------- |
+| `server`       | `obj`   |         | Networking ([example](#server)) |
+| `server.port`  | `num`   |         |                                 |
+| `users`        | `[obj]` |         |                                 |
+| `users[].name` | `str`   |         | **Deprecated:** use id          |
+
+## server
+
+```gon
+{
+    port: 1
+}
+```
+"
+        );
+        let bad_default = parse_str(r#"{a: {"@type": "num", "@default": "x"}}"#).unwrap();
+        assert!(Schema::from_value(&bad_default).is_err());
+    }
+}
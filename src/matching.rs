@@ -0,0 +1,161 @@
+//! Declarative destructuring of gon objects with [`match_gon!`](crate::match_gon).
+
+use thiserror::Error;
+
+use crate::value::TypeError;
+use crate::{Kind, Value};
+
+/// A value didn't have the shape a [`match_gon!`](crate::match_gon) pattern asked for
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum MatchError {
+    /// A required key wasn't there
+    #[error("missing key '{0}'")]
+    Missing(String),
+    /// The value at a path had the wrong type
+    #[error("'{0}': {1}")]
+    Type(String, TypeError),
+}
+
+/// Types that can be bound to a key in a [`match_gon!`](crate::match_gon) pattern.
+/// Implemented for strings, bools, all number types, [`Value`] and `Option`s of those,
+/// where `Option` makes the key optional.
+pub trait FromField: Sized {
+    /// Converts the value of a field, `None` if the key is missing.
+    /// Returns `Err(None)` if a required key is missing.
+    fn from_field(value: Option<&Value>) -> Result<Self, Option<TypeError>>;
+}
+
+macro_rules! impl_from_field {
+    ($($t:ty),*) => {
+        $(
+            impl FromField for $t {
+                fn from_field(value: Option<&Value>) -> Result<Self, Option<TypeError>> {
+                    <$t>::try_from(value.ok_or(None)?).map_err(Some)
+                }
+            }
+
+            impl FromField for Option<$t> {
+                fn from_field(value: Option<&Value>) -> Result<Self, Option<TypeError>> {
+                    match value {
+                        None | Some(Value::None) => Ok(None),
+                        Some(v) => <$t>::try_from(v).map(Some).map_err(Some),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_field!(
+    String, bool, f32, f64, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize
+);
+
+impl FromField for Value {
+    fn from_field(value: Option<&Value>) -> Result<Self, Option<TypeError>> {
+        value.cloned().ok_or(None)
+    }
+}
+
+impl FromField for Option<Value> {
+    fn from_field(value: Option<&Value>) -> Result<Self, Option<TypeError>> {
+        Ok(value.filter(|v| **v != Value::None).cloned())
+    }
+}
+
+/// Looks up `path` in nested objects and converts the value. Used by [`match_gon!`](crate::match_gon).
+#[doc(hidden)]
+pub fn field<T: FromField>(value: &Value, path: &[&str]) -> Result<T, MatchError> {
+    let mut current = Some(value);
+    for (i, key) in path.iter().enumerate() {
+        current = match current {
+            Some(Value::Obj(obj)) => obj.get(*key),
+            Some(Value::None) | None => None,
+            Some(other) => {
+                return Err(MatchError::Type(
+                    path[..i].join("."),
+                    TypeError {
+                        expected: Kind::Obj.name(),
+                        found: other.type_name(),
+                    },
+                ));
+            }
+        };
+    }
+    T::from_field(current).map_err(|e| match e {
+        Some(e) => MatchError::Type(path.join("."), e),
+        None => MatchError::Missing(path.join(".")),
+    })
+}
+
+/// Destructures an object [`Value`] into a struct-like result by binding keys to typed
+/// variables. Each entry reads `key: Type => binding`, where the key may be a dotted path
+/// into nested objects and `Type` is anything that implements
+/// [`FromField`](crate::matching::FromField) (use `Option<T>` for optional keys).
+/// Evaluates to `Result<_, MatchError>` whose `Ok` value has one field per binding.
+/// # Usage example
+/// ```rust
+/// use gon::{match_gon, parse_str, matching::MatchError};
+/// let v = parse_str(r#"{name: "slime", stats: {hp: 12}}"#).unwrap();
+/// let m = match_gon!(v, { name: String => n, stats.hp: i64 => hp, speed: Option<f64> => speed }).unwrap();
+/// assert_eq!((m.n.as_str(), m.hp, m.speed), ("slime", 12, None));
+/// assert_eq!(
+///     match_gon!(v, { stats.hp: String => hp }).unwrap_err(),
+///     MatchError::Type("stats.hp".into(), gon::value::TypeError { expected: "str", found: "num" }),
+/// );
+/// ```
+#[macro_export]
+macro_rules! match_gon {
+    ($value:expr, { $($($key:ident).+ : $ty:ty => $bind:ident),* $(,)? }) => {{
+        #[allow(dead_code)]
+        #[derive(Debug, Clone, PartialEq)]
+        struct Matched {
+            $($bind: $ty,)*
+        }
+        let value: &$crate::Value = &$value;
+        'matched: {
+            ::std::result::Result::<Matched, $crate::matching::MatchError>::Ok(Matched {
+                $($bind: match $crate::matching::field(value, &[$(stringify!($key)),+]) {
+                    ::std::result::Result::Ok(v) => v,
+                    ::std::result::Result::Err(e) => break 'matched ::std::result::Result::Err(e),
+                },)*
+            })
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn binds_nested_and_optional_keys() {
+        let v = parse_str(r#"{name: "gon", server: {port: 8080, tls: None}}"#).unwrap();
+        let m = match_gon!(v, {
+            name: String => name,
+            server.port: u16 => port,
+            server.tls: Option<bool> => tls,
+        })
+        .unwrap();
+        assert_eq!((m.name.as_str(), m.port, m.tls), ("gon", 8080, None));
+    }
+
+    #[test]
+    fn reports_paths() {
+        let v = parse_str("{server: [1]}").unwrap();
+        assert_eq!(
+            match_gon!(v, { name: String => name }),
+            Err(MatchError::Missing("name".into()))
+        );
+        assert_eq!(
+            match_gon!(&v, { server.port: u16 => port }),
+            Err(MatchError::Type(
+                "server".into(),
+                TypeError {
+                    expected: "obj",
+                    found: "list"
+                }
+            ))
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Three-way merges of gon values, like merging two branches that changed the same document.
+//!
+//! [`three_way`] applies the changes that `ours` and `theirs` each made to `base`. Objects
+//! are merged key by key, so changes to different keys don't conflict. Everything else,
+//! lists included, is changed as a whole: if both sides changed it, each in its own way,
+//! that's a [`Conflict`], and the merged value keeps `ours` until [`Merged::resolve`] is
+//! told what to keep.
+//! # Usage example
+//! ```rust
+//! use gon::{merge, parse_str};
+//! let base = parse_str(r#"{port: 80, host: "a", debug: false}"#).unwrap();
+//! let ours = parse_str(r#"{port: 8080, host: "a", debug: false}"#).unwrap();
+//! let theirs = parse_str(r#"{port: 443, host: "b"}"#).unwrap();
+//! let mut merged = merge::three_way(&base, &ours, &theirs);
+//! assert_eq!(merged.value, parse_str(r#"{port: 8080, host: "b"}"#).unwrap());
+//! assert_eq!(merged.conflicts.len(), 1);
+//! let path = merged.conflicts[0].path.clone();
+//! assert_eq!(path.to_string(), "port");
+//! merged.resolve(&path, Some(parse_str("443").unwrap())).unwrap();
+//! assert!(merged.conflicts.is_empty());
+//! assert_eq!(merged.value, parse_str(r#"{port: 443, host: "b"}"#).unwrap());
+//! ```
+
+use crate::path::{Path, Segment, SetError};
+use crate::value::spelling_order;
+use crate::{MapT, Value};
+
+/// Both sides changed the value at `path`, each in its own way. `None` stands for no value,
+/// because a side removed it or it wasn't in `base`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: Path,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+/// What [`three_way`] made of two changed versions of a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Merged {
+    /// The merged value, with `ours` wherever there's a conflict
+    pub value: Value,
+    /// The conflicts that aren't resolved yet, in the order of the merged value
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merged {
+    /// Puts `resolution` at `path`, or removes what's there if it's `None`, and drops the
+    /// conflict at `path`
+    pub fn resolve(&mut self, path: &Path, resolution: Option<Value>) -> Result<(), SetError> {
+        match resolution {
+            Some(value) => {
+                self.value.set_path(path, value)?;
+            }
+            None => {
+                self.value.take_path(path);
+            }
+        }
+        self.conflicts.retain(|c| !c.path.0.starts_with(&path.0));
+        Ok(())
+    }
+}
+
+/// Merges the changes `ours` and `theirs` made to `base`, see the [module docs](self)
+pub fn three_way(base: &Value, ours: &Value, theirs: &Value) -> Merged {
+    let mut conflicts = Vec::new();
+    let value = merge(
+        &mut Path::default(),
+        Some(base),
+        Some(ours),
+        Some(theirs),
+        &mut conflicts,
+    );
+    Merged {
+        // one of the sides is always kept at the root
+        value: value.unwrap_or(Value::None),
+        conflicts,
+    }
+}
+
+/// The merged value at `path`, `None` if it's removed
+fn merge(
+    path: &mut Path,
+    base: Option<&Value>,
+    ours: Option<&Value>,
+    theirs: Option<&Value>,
+    conflicts: &mut Vec<Conflict>,
+) -> Option<Value> {
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+    let (Some(Value::Obj(o)), Some(Value::Obj(t))) = (ours, theirs) else {
+        conflicts.push(Conflict {
+            path: path.clone(),
+            base: base.cloned(),
+            ours: ours.cloned(),
+            theirs: theirs.cloned(),
+        });
+        return ours.cloned();
+    };
+    let b = match base {
+        Some(Value::Obj(b)) => Some(b),
+        _ => None,
+    };
+    // our keys first, then the ones only they added
+    let added = spelling_order(t).filter(|(k, _)| !o.contains_key(&***k));
+    let keys = spelling_order(o).chain(added).map(|(k, _)| k);
+    let mut merged = MapT::new();
+    for k in keys {
+        path.0.push(Segment::Key(k.to_string()));
+        let base = b.and_then(|b| b.get(&**k));
+        if let Some(v) = merge(path, base, o.get(&**k), t.get(&**k), conflicts) {
+            merged.insert(k.clone(), v);
+        }
+        path.0.pop();
+    }
+    Some(Value::Obj(merged))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn merges_objects_by_key_and_everything_else_whole() {
+        let base = parse_str("{a: {x: 1, y: 2}, list: [1, 2], gone: 1, kept: 1}").unwrap();
+        let ours = parse_str("{a: {x: 5, y: 2}, list: [1, 2, 3], kept: 2}").unwrap();
+        let theirs = parse_str("{a: {x: 1, y: 6, z: 7}, list: [0, 1, 2], kept: 1}").unwrap();
+        let merged = three_way(&base, &ours, &theirs);
+        assert_eq!(
+            merged.value,
+            parse_str("{a: {x: 5, y: 6, z: 7}, list: [1, 2, 3], kept: 2}").unwrap()
+        );
+        assert_eq!(
+            merged.conflicts,
+            [Conflict {
+                path: "list".parse().unwrap(),
+                base: Some(parse_str("[1, 2]").unwrap()),
+                ours: Some(parse_str("[1, 2, 3]").unwrap()),
+                theirs: Some(parse_str("[0, 1, 2]").unwrap()),
+            }]
+        );
+    }
+
+    #[test]
+    fn removing_what_the_other_side_changed_conflicts() {
+        let base = parse_str("{a: 1, b: 1}").unwrap();
+        let ours = parse_str("{b: 1}").unwrap();
+        let theirs = parse_str("{a: 2, b: 1, c: 3}").unwrap();
+        let mut merged = three_way(&base, &ours, &theirs);
+        assert_eq!(merged.value, parse_str("{b: 1, c: 3}").unwrap());
+        assert_eq!(merged.conflicts.len(), 1);
+        assert_eq!(merged.conflicts[0].ours, None);
+        let path = merged.conflicts[0].path.clone();
+        let theirs = merged.conflicts[0].theirs.clone();
+        merged.resolve(&path, theirs).unwrap();
+        assert_eq!(merged.value, parse_str("{a: 2, b: 1, c: 3}").unwrap());
+        merged.resolve(&path, None).unwrap();
+        assert_eq!(merged.value, parse_str("{b: 1, c: 3}").unwrap());
+        assert!(merged.conflicts.is_empty());
+    }
+}
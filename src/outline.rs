@@ -0,0 +1,197 @@
+//! The structure of gon values without their contents.
+//!
+//! [`Value::outline`] keeps the keys of objects and the lengths of lists, scalars become
+//! their [`Kind`]. The elements of a list are outlined together: objects with every key any
+//! of them has, so a list of a million similar objects is outlined in a few lines. Two
+//! outlines are equal when the values have the same structure, which makes them a summary
+//! of what changed shape between two versions of a document.
+//!
+//! [`Outline`] displays as an indented tree, like `gon outline` prints it.
+//! # Usage example
+//! ```rust
+//! use gon::{outline::Outline, parse_str};
+//! let value = parse_str(r#"{
+//!     enemies: [{hp: 10, name: "Goblin"}, {hp: 14, name: "Wolf", tags: ["fast"]}],
+//!     name: "forest",
+//!     spawn: {x: 1, y: [2]},
+//! }"#).unwrap();
+//! assert_eq!(
+//!     value.outline(1).to_string(),
+//!     "{\n    enemies: [2] ...\n    name: str\n    spawn: {...}\n}",
+//! );
+//! assert_eq!(
+//!     value.outline(usize::MAX).to_string(),
+//!     "{
+//!     enemies: [2] {
+//!         hp: num
+//!         name: str
+//!         tags: [1] str
+//!     }
+//!     name: str
+//!     spawn: {
+//!         x: num
+//!         y: [1] num
+//!     }
+//! }"
+//! );
+//! assert_eq!(parse_str("[1, \"a\"]").unwrap().outline(usize::MAX), Outline::List {
+//!     len: 2,
+//!     elements: Some(Box::new(Outline::Mixed)),
+//! });
+//! ```
+
+use std::fmt;
+
+use crate::value::{spelling_order, write_key};
+use crate::{Kind, Value};
+
+/// What a value looks like without its contents, see the [module docs](self)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outline {
+    /// The keys of an object with the outlines of their values, in spelling order
+    Obj(Vec<(String, Outline)>),
+    /// A list with its length and the merged outline of its elements, `None` if it's empty
+    /// or deeper than the `max_depth`
+    List {
+        len: usize,
+        elements: Option<Box<Outline>>,
+    },
+    /// Anything but an object or a list
+    Scalar(Kind),
+    /// Elements of a list that have different kinds
+    Mixed,
+    /// An object deeper than the `max_depth`
+    Elided,
+}
+
+impl Value {
+    /// The keys and list lengths of this value down to `max_depth` nested objects and lists,
+    /// see the [`outline` module](crate::outline). With a `max_depth` of 0 only the kind of
+    /// the value (and its length if it's a list) is left.
+    pub fn outline(&self, max_depth: usize) -> Outline {
+        match self {
+            Self::Obj(_) if max_depth == 0 => Outline::Elided,
+            Self::Obj(obj) => Outline::Obj(
+                spelling_order(obj)
+                    .map(|(k, v)| (k.to_string(), v.outline(max_depth - 1)))
+                    .collect(),
+            ),
+            Self::List(xs) => Outline::List {
+                len: xs.len(),
+                elements: xs
+                    .iter()
+                    .filter(|_| max_depth > 0)
+                    .map(|x| x.outline(max_depth - 1))
+                    .reduce(Outline::merge)
+                    .map(Box::new),
+            },
+            Self::Tagged { value, .. } => value.outline(max_depth),
+            other => Outline::Scalar(other.kind()),
+        }
+    }
+}
+
+impl Outline {
+    /// One outline for two values in the same list: objects get the keys of both, lists
+    /// the longer length, anything else of different kinds is [`Outline::Mixed`]
+    pub fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Obj(mut entries), Self::Obj(others)) => {
+                for (key, outline) in others {
+                    match entries.iter().position(|(k, _)| *k == key) {
+                        Some(i) => {
+                            let merged = std::mem::replace(&mut entries[i].1, Self::Mixed);
+                            entries[i].1 = merged.merge(outline);
+                        }
+                        None => entries.push((key, outline)),
+                    }
+                }
+                Self::Obj(entries)
+            }
+            (
+                Self::List { len, elements },
+                Self::List {
+                    len: other_len,
+                    elements: other_elements,
+                },
+            ) => Self::List {
+                len: len.max(other_len),
+                elements: match (elements, other_elements) {
+                    (Some(a), Some(b)) => Some(Box::new(a.merge(*b))),
+                    (a, b) => a.or(b),
+                },
+            },
+            (a, b) if a == b => a,
+            _ => Self::Mixed,
+        }
+    }
+
+    fn fmt0(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        match self {
+            Self::Obj(entries) if entries.is_empty() => f.write_str("{}"),
+            Self::Obj(entries) => {
+                writeln!(f, "{{")?;
+                for (key, outline) in entries {
+                    write!(f, "{:1$}", "", indent + 4)?;
+                    let mut spelled = String::new();
+                    write_key(&mut spelled, key);
+                    write!(f, "{spelled}: ")?;
+                    outline.fmt0(f, indent + 4)?;
+                    writeln!(f)?;
+                }
+                write!(f, "{:1$}}}", "", indent)
+            }
+            Self::List { len, elements } => {
+                write!(f, "[{len}]")?;
+                match elements {
+                    Some(elements) => {
+                        write!(f, " ")?;
+                        elements.fmt0(f, indent)
+                    }
+                    None if *len > 0 => write!(f, " ..."),
+                    None => Ok(()),
+                }
+            }
+            Self::Scalar(kind) => write!(f, "{kind}"),
+            Self::Mixed => write!(f, "mixed"),
+            Self::Elided => write!(f, "{{...}}"),
+        }
+    }
+}
+
+impl fmt::Display for Outline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt0(f, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn merges_elements() {
+        let outline = |src| parse_str(src).unwrap().outline(usize::MAX);
+        assert_eq!(outline("[[], [1, 2], [3]]").to_string(), "[3] [2] num");
+        assert_eq!(
+            outline("[{a: 1}, {a: \"x\", b: true}, 5]").to_string(),
+            "[3] mixed"
+        );
+        assert_eq!(
+            outline("[{a: 1}, {a: \"x\", b: true}]").to_string(),
+            "[2] {\n    a: mixed\n    b: bool\n}"
+        );
+        let tagged = Value::tagged("point", parse_str("{\"a b\": {}}").unwrap());
+        assert_eq!(
+            tagged.outline(usize::MAX).to_string(),
+            "{\n    \"a b\": {}\n}"
+        );
+        assert_eq!(outline("[]").to_string(), "[0]");
+        assert_eq!(outline("[None, None]").to_string(), "[2] none");
+        assert_eq!(
+            parse_str("[{a: 1}]").unwrap().outline(0).to_string(),
+            "[1] ..."
+        );
+    }
+}
@@ -1,12 +1,560 @@
-use std::iter::Peekable;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Read;
+use std::time::{Duration, Instant};
 
 use klex::{Lexer, Loc, RichToken, Token};
+use thiserror::Error;
 
-use crate::{GonError, List, Object, Value};
+use crate::cancel::CancelToken;
+use crate::encoding::{Encoding, ReadError, decode};
+use crate::path::{Path, Segment};
+use crate::pragma;
+use crate::schema::{Field, Schema, ValidationError, ValidationErrors};
+use crate::span::Span;
+use crate::{ErrorKind, GonError, List, Object, Value};
 
-struct TokenIter {
-    inner: Peekable<std::vec::IntoIter<RichToken>>,
+struct TokenIter<'s> {
+    src: &'s str,
+    /// Borrowed, so that tokens are handed out by reference instead of cloned
+    tokens: &'s [RichToken],
+    /// The bytes of `src` every token spans, only found once they're needed
+    spans: Vec<Span>,
+    pos: usize,
     loc: Loc,
+    /// Values bound by `let` at the top of the document
+    bindings: HashMap<String, Value>,
+    /// Variables for `@if` conditions
+    defines: HashMap<String, String>,
+    scalar_hooks: Vec<ScalarHook>,
+    /// See [`ParseOptions::dotted_keys`]
+    dotted_keys: bool,
+    /// See [`ParseOptions::bare_strings`]
+    bare_strings: bool,
+    /// See [`ParseOptions::forbid_trailing_commas`]
+    forbid_trailing_commas: bool,
+    /// See [`ParseOptions::require_quoted_keys`]
+    require_quoted_keys: bool,
+    /// See [`ParseOptions::cancel`]
+    cancel: Option<CancelToken>,
+    /// The object keys seen so far, so that equal keys share one allocation
+    #[cfg(feature = "shared_keys")]
+    keys: std::collections::HashSet<crate::KeyT>,
+    /// Where [`parse_validated_with`] is and what it found so far
+    validation: Option<Validation>,
+}
+
+struct Validation {
+    path: Path,
+    errors: Vec<ValidationError>,
+    fail_fast: bool,
+    prune_unknown: bool,
+}
+
+impl<'s> TokenIter<'s> {
+    fn new(src: &'s str, tokens: &'s [RichToken], options: &ParseOptions) -> Self {
+        Self {
+            src,
+            tokens,
+            spans: Vec::new(),
+            pos: 0,
+            loc: Loc::start_of_file(0),
+            bindings: HashMap::new(),
+            defines: options.resolve.defines.clone(),
+            scalar_hooks: options.scalar_hooks.clone(),
+            dotted_keys: options.dotted_keys,
+            bare_strings: options.bare_strings,
+            forbid_trailing_commas: options.forbid_trailing_commas,
+            require_quoted_keys: options.require_quoted_keys,
+            cancel: options.cancel.clone(),
+            #[cfg(feature = "shared_keys")]
+            keys: Default::default(),
+            validation: None,
+        }
+    }
+
+    /// `key` as an object key, reusing an equal key seen before with `shared_keys` and
+    /// inline if it's short with `compact_keys`
+    fn intern(&mut self, key: String) -> crate::KeyT {
+        #[cfg(feature = "shared_keys")]
+        {
+            if let Some(shared) = self.keys.get(key.as_str()) {
+                return shared.clone();
+            }
+            let shared = crate::KeyT::from(key);
+            self.keys.insert(shared.clone());
+            shared
+        }
+        #[cfg(not(feature = "shared_keys"))]
+        crate::KeyT::from(key)
+    }
+}
+
+/// Turns the source text of a scalar and where it is in the document into a value, or
+/// declines with `None`. See [`ParseOptions::scalar_hook`].
+pub type ScalarHook = fn(&str, Span) -> Option<Value>;
+
+/// Variables that decide which `@if`/`@else` sections of a document are kept.
+/// # Usage example
+/// ```rust
+/// use gon::parser::{parse_str_with, ResolveOptions};
+/// let src = r#"{fps: 60, @if(platform == "switch") {fps: 30}}"#;
+/// let options = ResolveOptions::new().define("platform", "switch");
+/// assert_eq!(parse_str_with(src, &options.into()), gon::parse_str("{fps: 30}"));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResolveOptions {
+    defines: HashMap<String, String>,
+}
+
+impl ResolveOptions {
+    /// No variables defined, every `@if(name)` is false
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Defines (or redefines) the variable `name`
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// What a value can start with
+const VALUES: [&str; 6] = [
+    "None",
+    "\"...\"",
+    "<number>",
+    "true/false",
+    "[values]",
+    "{key: value}",
+];
+
+/// Symbols that can't be bound by `let`
+const KEYWORDS: [&str; 6] = ["none", "null", "true", "false", "r", "let"];
+
+/// Everything that changes how [`parse_with`] parses a document
+#[derive(Clone, Debug, Default)]
+pub struct ParseOptions {
+    resolve: ResolveOptions,
+    scalar_hooks: Vec<ScalarHook>,
+    fail_fast: bool,
+    prune_unknown: bool,
+    dotted_keys: bool,
+    bare_strings: bool,
+    forbid_comments: bool,
+    forbid_trailing_commas: bool,
+    require_quoted_keys: bool,
+    cancel: Option<CancelToken>,
+}
+
+impl ParseOptions {
+    /// No variables and no hooks, parses like [`parse`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Uses the variables of `resolve` for `@if` sections
+    pub fn resolve(mut self, resolve: ResolveOptions) -> Self {
+        self.resolve = resolve;
+        self
+    }
+
+    /// Defines (or redefines) the `@if` variable `name`, see [`ResolveOptions::define`]
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.resolve = self.resolve.define(name, value);
+        self
+    }
+
+    /// Adds a hook that sees every scalar before it's parsed. A scalar is everything up to
+    /// the next `,`, `:`, `;`, bracket or key outside of parentheses, its tokens joined
+    /// without whitespace: `vec(1, 2)` is seen as `"vec(1,2)"`. If a hook returns a value,
+    /// that value is used, usually a [`Value::Tagged`], else the next hook is asked and
+    /// finally the scalar is parsed as usual.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parser::{parse_str_with, ParseOptions}, span::Span, Value};
+    /// fn color(src: &str, _: Span) -> Option<Value> {
+    ///     let hex = src.strip_prefix('#')?;
+    ///     Some(Value::tagged("color", Value::Str { s: hex.into(), raw: false }))
+    /// }
+    /// let options = ParseOptions::new().scalar_hook(color);
+    /// let value = parse_str_with("{sky: #00aaff}", &options).unwrap();
+    /// assert_eq!(
+    ///     value.as_obj().unwrap()["sky"],
+    ///     Value::tagged("color", Value::Str { s: "00aaff".into(), raw: false }),
+    /// );
+    /// ```
+    pub fn scalar_hook(mut self, hook: ScalarHook) -> Self {
+        self.scalar_hooks.push(hook);
+        self
+    }
+
+    /// Makes [`parse_validated_with`] stop at the first value that doesn't match the schema
+    pub fn fail_fast(mut self, fail_fast: bool) -> Self {
+        self.fail_fast = fail_fast;
+        self
+    }
+
+    /// Makes keys joined with dots stand for nested objects, like in TOML: `server.port: 80`
+    /// is `server: {port: 80}`. Objects at the same key are merged if one of them comes
+    /// from a dotted key, quoted keys are never split.
+    /// # Usage example
+    /// ```rust
+    /// use gon::parser::{parse_str_with, ParseOptions};
+    /// let src = r#"{server.host: "localhost", server.tls.port: 443, "a.b": 1}"#;
+    /// let options = ParseOptions::new().dotted_keys(true);
+    /// assert_eq!(
+    ///     parse_str_with(src, &options),
+    ///     gon::parse_str(r#"{server: {host: "localhost", tls: {port: 443}}, "a.b": 1}"#)
+    /// );
+    /// assert!(gon::parse_str(src).is_err());
+    /// ```
+    pub fn dotted_keys(mut self, dotted_keys: bool) -> Self {
+        self.dotted_keys = dotted_keys;
+        self
+    }
+
+    /// Makes [`parse_validated_with`] leave out the keys of objects that the schema doesn't
+    /// mention
+    pub fn prune_unknown(mut self, prune_unknown: bool) -> Self {
+        self.prune_unknown = prune_unknown;
+        self
+    }
+
+    /// Makes symbols that are neither keywords nor bound by `let` parse as strings, so
+    /// `{mode: fast}` is `{mode: "fast"}`
+    /// # Usage example
+    /// ```rust
+    /// use gon::parser::{parse_str_with, ParseOptions};
+    /// let options = ParseOptions::new().bare_strings(true);
+    /// assert_eq!(
+    ///     parse_str_with("{mode: fast, on: true}", &options),
+    ///     gon::parse_str(r#"{mode: "fast", on: true}"#)
+    /// );
+    /// assert!(gon::parse_str("{mode: fast}").is_err());
+    /// ```
+    pub fn bare_strings(mut self, bare_strings: bool) -> Self {
+        self.bare_strings = bare_strings;
+        self
+    }
+
+    /// Makes comments an error
+    pub fn forbid_comments(mut self, forbid_comments: bool) -> Self {
+        self.forbid_comments = forbid_comments;
+        self
+    }
+
+    /// Makes commas after the last entry of an object or element of a list an error
+    pub fn forbid_trailing_commas(mut self, forbid_trailing_commas: bool) -> Self {
+        self.forbid_trailing_commas = forbid_trailing_commas;
+        self
+    }
+
+    /// Makes keys that aren't string literals, like `name` or `1`, an error
+    pub fn require_quoted_keys(mut self, require_quoted_keys: bool) -> Self {
+        self.require_quoted_keys = require_quoted_keys;
+        self
+    }
+
+    /// Makes parsing fail with [`ErrorKind::Cancelled`] soon after `cancel` is cancelled,
+    /// see the docs of [`cancel`](crate::cancel)
+    pub fn cancel(mut self, cancel: CancelToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+}
+
+impl From<ResolveOptions> for ParseOptions {
+    fn from(resolve: ResolveOptions) -> Self {
+        Self::new().resolve(resolve)
+    }
+}
+
+/// Whether a source text could be a whole value yet, see [`is_complete`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Completeness {
+    /// Every delimiter and string is closed
+    Complete,
+    /// Nothing but whitespace and comments yet, or a delimiter or string is still open
+    Incomplete,
+    /// A delimiter is closed that wasn't opened, more input can't fix that
+    Invalid,
+}
+
+/// Checks whether `src` is finished as far as brackets, braces, parentheses, strings and
+/// comments are concerned, so interactive frontends know whether to ask for more input.
+/// A complete source may still fail to parse.
+/// # Usage example
+/// ```rust
+/// use gon::parser::{is_complete, Completeness};
+/// assert_eq!(is_complete("{a: [1, 2"), Completeness::Incomplete);
+/// assert_eq!(is_complete("{a: \"]}"), Completeness::Incomplete);
+/// assert_eq!(is_complete("{a: [1, 2]} // done"), Completeness::Complete);
+/// assert_eq!(is_complete("{a: 1]"), Completeness::Invalid);
+/// ```
+pub fn is_complete(src: &str) -> Completeness {
+    let mut expected_closers = Vec::new();
+    let mut saw_value = false;
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => expected_closers.push('}'),
+            '[' => expected_closers.push(']'),
+            '(' => expected_closers.push(')'),
+            '}' | ']' | ')' => match expected_closers.pop() {
+                Some(closer) if closer == c => {}
+                _ => return Completeness::Invalid,
+            },
+            '"' => loop {
+                match chars.next() {
+                    Some('\\') => {
+                        chars.next();
+                    }
+                    Some('"') => break,
+                    Some(_) => {}
+                    None => return Completeness::Incomplete,
+                }
+            },
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut last = ' ';
+                loop {
+                    match chars.next() {
+                        Some('/') if last == '*' => break,
+                        Some(c) => last = c,
+                        None => return Completeness::Incomplete,
+                    }
+                }
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            _ => {}
+        }
+        saw_value = true;
+    }
+    if saw_value && expected_closers.is_empty() {
+        Completeness::Complete
+    } else {
+        Completeness::Incomplete
+    }
+}
+
+/// What a [`SpannedToken`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// `{`
+    LBrace,
+    /// `}`
+    RBrace,
+    /// `[`
+    LBrack,
+    /// `]`
+    RBrack,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `:`
+    Colon,
+    /// `,`
+    Comma,
+    /// `;`
+    Semicolon,
+    /// `-`
+    Dash,
+    /// `+`, joins string literals
+    Plus,
+    /// `=`
+    Equals,
+    /// `@`, starts a conditional section
+    At,
+    /// A string literal including its quotes
+    Str,
+    /// A raw string literal including the `r` prefix and its quotes
+    RawStr,
+    /// A number, without its sign
+    Num,
+    /// `None`, `null`, `true`, `false` or `let`, in any case
+    Keyword,
+    /// Any other identifier: keys, variables and symbols
+    Sym,
+    /// A line or block comment
+    Comment,
+    /// Any other character
+    Other,
+    /// An unterminated string or block comment, spanning the rest of the source
+    Unterminated,
+}
+
+/// A token of gon source together with the bytes it spans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpannedToken {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+impl SpannedToken {
+    /// The text of this token in `src`, the source it was taken from
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.span.range()]
+    }
+}
+
+/// Splits `src` into tokens with byte spans, for formatters, highlighters and other tooling.
+/// [`LineIndex`](crate::span::LineIndex) turns the spans into lines and columns.
+/// Whitespace is skipped, comments are kept. This never fails: input the lexer can't make
+/// sense of becomes [`TokenKind::Other`] or [`TokenKind::Unterminated`] tokens.
+/// # Usage example
+/// ```rust
+/// use gon::parser::{tokenize, TokenKind};
+/// let src = "{name: r\"slime\"} // enemy";
+/// let kinds: Vec<_> = tokenize(src).into_iter().map(|t| t.kind).collect();
+/// assert_eq!(kinds, [
+///     TokenKind::LBrace,
+///     TokenKind::Sym,
+///     TokenKind::Colon,
+///     TokenKind::RawStr,
+///     TokenKind::RBrace,
+///     TokenKind::Comment,
+/// ]);
+/// assert_eq!(tokenize(src)[3].text(src), "r\"slime\"");
+/// ```
+pub fn tokenize(src: &str) -> Vec<SpannedToken> {
+    let mut i = 0;
+    std::iter::from_fn(|| next_token(src, &mut i)).collect()
+}
+
+/// The token that starts at or after byte `pos` of `src`, moving `pos` past it. See
+/// [`tokenize`].
+pub(crate) fn next_token(src: &str, pos: &mut usize) -> Option<SpannedToken> {
+    let bytes = src.as_bytes();
+    let mut i = *pos;
+    while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+        i += 1;
+    }
+    if i >= bytes.len() {
+        *pos = i;
+        return None;
+    }
+    let start = i;
+    let kind = match bytes[i] {
+        b'#' if i == 0 && pragma::pragma(src).is_some() => {
+            i = src.find('\n').unwrap_or(bytes.len());
+            TokenKind::Comment
+        }
+        b'/' if bytes.get(i + 1) == Some(&b'/') => {
+            i = src[i..].find('\n').map_or(bytes.len(), |n| i + n);
+            TokenKind::Comment
+        }
+        b'/' if bytes.get(i + 1) == Some(&b'*') => match src[i + 2..].find("*/") {
+            Some(n) => {
+                i += n + 4;
+                TokenKind::Comment
+            }
+            None => {
+                i = bytes.len();
+                TokenKind::Unterminated
+            }
+        },
+        b'"' => match string_end(bytes, i) {
+            Some(end) => {
+                i = end;
+                TokenKind::Str
+            }
+            None => {
+                i = bytes.len();
+                TokenKind::Unterminated
+            }
+        },
+        b'0'..=b'9' => {
+            // in hex numbers, `e` is a digit and not the start of an exponent
+            let hex = bytes[i] == b'0' && matches!(bytes.get(i + 1), Some(b'x' | b'X'));
+            while let Some(&b) = bytes.get(i) {
+                let exponent_sign = matches!(b, b'+' | b'-')
+                    && !hex
+                    && matches!(bytes[i - 1], b'e' | b'E')
+                    && bytes.get(i + 1).is_some_and(u8::is_ascii_digit);
+                if !(b.is_ascii_alphanumeric() || b == b'_' || b == b'.' || exponent_sign) {
+                    break;
+                }
+                i += 1;
+            }
+            TokenKind::Num
+        }
+        b if b.is_ascii_alphabetic() || b == b'_' || !b.is_ascii() => {
+            let rest = &src[i..];
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            i += len;
+            let word = &src[start..i];
+            let quote = i + (src[i..].len() - src[i..].trim_start().len());
+            if len == 0 {
+                // a non-ascii character that can't be part of a symbol, like `→`
+                i += rest.chars().next().map_or(1, char::len_utf8);
+                TokenKind::Other
+            } else if word.eq_ignore_ascii_case("r") && bytes.get(quote) == Some(&b'"') {
+                match string_end(bytes, quote) {
+                    Some(end) => {
+                        i = end;
+                        TokenKind::RawStr
+                    }
+                    None => {
+                        i = bytes.len();
+                        TokenKind::Unterminated
+                    }
+                }
+            } else if KEYWORDS.contains(&word.to_lowercase().as_str())
+                && !word.eq_ignore_ascii_case("r")
+            {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Sym
+            }
+        }
+        b => {
+            i += 1;
+            match b {
+                b'{' => TokenKind::LBrace,
+                b'}' => TokenKind::RBrace,
+                b'[' => TokenKind::LBrack,
+                b']' => TokenKind::RBrack,
+                b'(' => TokenKind::LParen,
+                b')' => TokenKind::RParen,
+                b':' => TokenKind::Colon,
+                b',' => TokenKind::Comma,
+                b';' => TokenKind::Semicolon,
+                b'-' => TokenKind::Dash,
+                b'+' => TokenKind::Plus,
+                b'=' => TokenKind::Equals,
+                b'@' => TokenKind::At,
+                _ => TokenKind::Other,
+            }
+        }
+    };
+    *pos = i;
+    Some(SpannedToken {
+        kind,
+        span: Span::from(start..i),
+    })
+}
+
+/// The end of the string literal whose opening quote is at `start`, `None` if it isn't closed
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    loop {
+        match bytes.get(i)? {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
 }
 
 /// Try to parse the given `&str` into a gon [`Value`]. This is just a short-hand:
@@ -25,159 +573,1134 @@ struct TokenIter {
 /// );
 /// ```
 pub fn parse_str(src: &str) -> Result<Value, GonError> {
-    parse(src.chars())
+    parse0(src, &ParseOptions::default(), None)
+}
+
+/// Reads everything from `reader` and parses it. The encoding is taken from the byte order
+/// mark at the start, input without one has to be UTF-8. See [`parse_reader_as`].
+/// # Usage example
+/// ```rust
+/// use gon::parser::parse_reader;
+/// let file: &[u8] = b"\xEF\xBB\xBF[1, 2]";
+/// assert_eq!(parse_reader(file).unwrap(), gon::parse_str("[1, 2]").unwrap());
+/// ```
+pub fn parse_reader(reader: impl Read) -> Result<Value, ReadError> {
+    parse_reader_as(reader, Encoding::Utf8)
+}
+
+/// Like [`parse_reader`], but input without a byte order mark is decoded as `fallback`.
+/// Decoding UTF-16 and Latin-1 needs the `encoding` feature.
+pub fn parse_reader_as(mut reader: impl Read, fallback: Encoding) -> Result<Value, ReadError> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    Ok(parse_str(&decode(&bytes, fallback)?)?)
+}
+
+/// Like [`parse_str`], but with variables and hooks from `options`. See [`parse_with`].
+pub fn parse_str_with(src: &str, options: &ParseOptions) -> Result<Value, GonError> {
+    parse0(src, options, None)
 }
 
 /// Try to parse the given char iterator into a gon [`Value`].
+///
+/// The value may be preceded by any number of `let <name> = <value>;` bindings. Every
+/// later use of `<name>` as a value is replaced by the bound value, so bindings never
+/// show up in the result. A binding may refer to earlier bindings and a name that is
+/// bound again shadows the previous binding from there on.
+///
+/// Objects and lists may contain conditional sections, which are always false here.
+/// See [`parse_with`].
 /// # Usage example
 /// ```rust
 /// use gon::{MapT, parse, Value};
 /// let src = "{a: 1, b: []}".chars();
 /// assert_eq!(
 ///     Ok(Value::Obj(MapT::from([
-///         ("a".to_string(), Value::Num(1.to_string())),
-///         ("b".to_string(), Value::List(vec![])),
+///         ("a".into(), Value::Num(1.to_string())),
+///         ("b".into(), Value::List(vec![])),
 ///     ]))),
 ///     parse(src),
 /// );
 /// ```
+/// With bindings:
+/// ```rust
+/// use gon::{parse_str, Value};
+/// let src = "let hp = 100; let hp = [hp, 2]; {hp: hp}";
+/// assert_eq!(parse_str(src), parse_str("{hp: [100, 2]}"));
+/// ```
 pub fn parse<I: Iterator<Item = char>>(src: I) -> Result<Value, GonError> {
-    let tokens = Lexer::from_iter(src, 0)
+    parse_with(src, &ParseOptions::default())
+}
+
+/// Try to parse the given char iterator into a gon [`Value`], see [`parse`].
+///
+/// Objects may contain sections `@if(<cond>) {...}` whose entries are merged into the
+/// object if `<cond>` holds, lists may contain `@if(<cond>) [...]` whose elements are
+/// spliced into the list. A section may be followed by `@else {...}`/`@else [...]` or
+/// `@else @if(...)`. `<cond>` is either `name == <value>`, `name != <value>` or just
+/// `name`, which holds if `name` is defined in `options` and not `false`.
+///
+/// Scalars are first offered to the hooks in `options`, see [`ParseOptions::scalar_hook`].
+/// # Usage example
+/// ```rust
+/// use gon::parser::{parse_str_with, ResolveOptions};
+/// let src = r#"[1, @if(debug) [2, 3] @else [4]]"#;
+/// let options = ResolveOptions::new().define("debug", "true");
+/// assert_eq!(parse_str_with(src, &options.into()), gon::parse_str("[1, 2, 3]"));
+/// assert_eq!(parse_str_with(src, &ResolveOptions::new().into()), gon::parse_str("[1, 4]"));
+/// ```
+pub fn parse_with<I: Iterator<Item = char>>(
+    src: I,
+    options: &ParseOptions,
+) -> Result<Value, GonError> {
+    parse0(&src.collect::<String>(), options, None)
+}
+
+/// Numbers about a parsed document, see [`parse_with_report`]
+///
+/// There's no list of recovered errors in it: the parser stops at the first syntax error
+/// instead of recovering, so a report only exists for a document without any. Mismatches
+/// with a schema are all collected by [`parse_validated_with`] with
+/// [`ParseOptions::fail_fast`] off.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseReport {
+    /// How many tokens the source has, without comments
+    pub tokens: usize,
+    pub comments: usize,
+    /// How many `let` bindings the source starts with
+    pub bindings: usize,
+    /// How many values of each kind the result has, after bindings and sections are resolved
+    pub nodes: NodeCounts,
+    /// How deeply objects and lists are nested, 0 for a scalar
+    pub max_depth: usize,
+    pub lex_time: Duration,
+    pub parse_time: Duration,
+}
+
+/// How many values of each kind there are in a value, counting the value itself
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NodeCounts {
+    pub objects: usize,
+    pub lists: usize,
+    pub strings: usize,
+    pub numbers: usize,
+    pub bools: usize,
+    pub nones: usize,
+    pub tagged: usize,
+}
+
+impl NodeCounts {
+    pub fn total(&self) -> usize {
+        self.objects
+            + self.lists
+            + self.strings
+            + self.numbers
+            + self.bools
+            + self.nones
+            + self.tagged
+    }
+}
+
+impl fmt::Display for ParseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let n = &self.nodes;
+        writeln!(
+            f,
+            "tokens:     {} (+{} comments)",
+            self.tokens, self.comments
+        )?;
+        writeln!(f, "bindings:   {}", self.bindings)?;
+        writeln!(f, "values:     {}", n.total())?;
+        writeln!(f, "  objects:  {}", n.objects)?;
+        writeln!(f, "  lists:    {}", n.lists)?;
+        writeln!(f, "  strings:  {}", n.strings)?;
+        writeln!(f, "  numbers:  {}", n.numbers)?;
+        writeln!(f, "  bools:    {}", n.bools)?;
+        writeln!(f, "  nones:    {}", n.nones)?;
+        writeln!(f, "  tagged:   {}", n.tagged)?;
+        writeln!(f, "max depth:  {}", self.max_depth)?;
+        writeln!(f, "lex time:   {:?}", self.lex_time)?;
+        write!(f, "parse time: {:?}", self.parse_time)
+    }
+}
+
+/// Like [`parse_with`], but also reports numbers about the document, for example to
+/// track how a config grows over time.
+/// # Usage example
+/// ```rust
+/// use gon::parser::{parse_with_report, ParseOptions};
+/// let src = "let x = 1; {a: [x, x], b: None} // two";
+/// let (_, report) = parse_with_report(src.chars(), &ParseOptions::new()).unwrap();
+/// assert_eq!((report.tokens, report.comments, report.bindings), (18, 1, 1));
+/// assert_eq!((report.nodes.numbers, report.nodes.total()), (2, 5));
+/// assert_eq!(report.max_depth, 2);
+/// ```
+pub fn parse_with_report<I: Iterator<Item = char>>(
+    src: I,
+    options: &ParseOptions,
+) -> Result<(Value, ParseReport), GonError> {
+    let mut report = ParseReport::default();
+    let value = parse0(&src.collect::<String>(), options, Some(&mut report))?;
+    Ok((value, report))
+}
+
+/// Like [`parse_with_report`], but for a `&str`, which isn't copied
+pub fn parse_str_with_report(
+    src: &str,
+    options: &ParseOptions,
+) -> Result<(Value, ParseReport), GonError> {
+    let mut report = ParseReport::default();
+    let value = parse0(src, options, Some(&mut report))?;
+    Ok((value, report))
+}
+
+/// Parses `src`, filling in `report` if there is one. The numbers are only collected
+/// then, as timing and counting the nodes isn't free.
+fn parse0(
+    src: &str,
+    options: &ParseOptions,
+    mut report: Option<&mut ParseReport>,
+) -> Result<Value, GonError> {
+    let start = report.is_some().then(Instant::now);
+    trace_span!(lexing, "gon::lex");
+    // lexing can't be stopped halfway, but a cancelled token doesn't need to wait for it
+    if options
+        .cancel
+        .as_ref()
+        .is_some_and(CancelToken::is_cancelled)
+    {
+        return Err(ErrorKind::Cancelled.into());
+    }
+    let mut tokens = Lexer::new(&pragma::blanked(src), 0)
         .lex()
-        .map_err(|e| GonError::LexerErr(e))?
-        .into_iter()
-        .peekable();
-    let mut token_iter = TokenIter {
-        inner: tokens,
-        loc: Loc::start_of_file(0),
-    };
-    let value = next_value(&mut token_iter)?;
-    if let Some(tok) = token_iter.next() {
-        Err(GonError::LeftoverTokens(tok.inner, token_iter.loc))
-    } else {
-        Ok(value)
+        .map_err(|e| lex_error(src, e))?;
+    if options.forbid_comments {
+        check_no_comments(src, &tokens)?;
+    }
+    let with_comments = tokens.len();
+    tokens.retain(|rt| !matches!(rt.inner, Token::Comment(_)));
+    if let (Some(report), Some(start)) = (report.as_deref_mut(), start) {
+        report.tokens = tokens.len();
+        report.comments = with_comments - tokens.len();
+        report.lex_time = start.elapsed();
+    }
+    trace_done!(lexing, tokens = tokens.len(), "lexed");
+    let start = report.is_some().then(Instant::now);
+    trace_span!(parsing, "gon::parse");
+    let mut token_iter = TokenIter::new(src, &tokens, options);
+    let mut bindings = 0;
+    let result = (|| {
+        while let Some((name, value)) = next_binding(&mut token_iter)? {
+            token_iter.bindings.insert(name, value);
+            bindings += 1;
+        }
+        let value = next_value(&mut token_iter)?;
+        match token_iter.next() {
+            Some(rt) => Err(leftover(rt)),
+            None => Ok(value),
+        }
+    })();
+    trace_done!(
+        parsing,
+        tokens = token_iter.pos,
+        bindings = token_iter.bindings.len(),
+        "parsed"
+    );
+    let value = result.map_err(|e| token_iter.locate(e))?;
+    if let (Some(report), Some(start)) = (report, start) {
+        report.bindings = bindings;
+        report.parse_time = start.elapsed();
+        report.max_depth = count_nodes(&value, &mut report.nodes);
+    }
+    Ok(value)
+}
+
+/// Why [`parse_validated`] failed
+#[derive(Debug, Error, PartialEq)]
+pub enum ValidatedParseError {
+    #[error("{0}")]
+    Parse(#[from] GonError),
+    /// The document parsed, but doesn't match the schema
+    #[error("{0}")]
+    Invalid(ValidationErrors),
+}
+
+/// Parses `src` and checks it against `schema` in the same pass. Every value is checked as
+/// soon as it's complete, so a document is never walked a second time. See
+/// [`parse_validated_with`] to stop at the first mismatch or leave out unknown keys.
+/// # Usage example
+/// ```rust
+/// use gon::{parse_str, parser::parse_validated, schema::Schema};
+/// let schema = Schema::from_value(&parse_str(r#"{size: "num", name: "str?"}"#).unwrap()).unwrap();
+/// assert_eq!(parse_validated("{size: 3}", &schema), parse_str("{size: 3}").map_err(Into::into));
+/// let err = parse_validated(r#"{size: "big"}"#, &schema).unwrap_err();
+/// assert_eq!(err.to_string(), "size: expected num, found str");
+/// ```
+pub fn parse_validated(src: &str, schema: &Schema) -> Result<Value, ValidatedParseError> {
+    parse_validated_with(src, schema, &ParseOptions::default())
+}
+
+/// Like [`parse_validated`], but with the variables and hooks of `options`. With
+/// [`ParseOptions::fail_fast`], parsing stops at the first value that doesn't match and
+/// only that mismatch is reported. With [`ParseOptions::prune_unknown`], keys of objects
+/// that the schema doesn't mention are dropped right after they're parsed.
+/// # Usage example
+/// ```rust
+/// use gon::{parse_str, parser::{parse_validated_with, ParseOptions}, schema::Schema};
+/// let schema = Schema::from_value(&parse_str(r#"{meshes: [{name: "str"}]}"#).unwrap()).unwrap();
+/// let options = ParseOptions::new().prune_unknown(true);
+/// let src = "{meshes: [{name: \"crate\", vertices: [0, 1, 2]}], thumbnail: \"...\"}";
+/// assert_eq!(
+///     parse_validated_with(src, &schema, &options).unwrap(),
+///     parse_str("{meshes: [{name: \"crate\"}]}").unwrap(),
+/// );
+/// ```
+pub fn parse_validated_with(
+    src: &str,
+    schema: &Schema,
+    options: &ParseOptions,
+) -> Result<Value, ValidatedParseError> {
+    trace_span!(parsing, "gon::parse_validated");
+    let mut tokens = Lexer::new(&pragma::blanked(src), 0)
+        .lex()
+        .map_err(|e| lex_error(src, e))?;
+    if options.forbid_comments {
+        check_no_comments(src, &tokens)?;
+    }
+    tokens.retain(|rt| !matches!(rt.inner, Token::Comment(_)));
+    let mut token_iter = TokenIter::new(src, &tokens, options);
+    token_iter.validation = Some(Validation {
+        path: Path::default(),
+        errors: Vec::new(),
+        fail_fast: options.fail_fast,
+        prune_unknown: options.prune_unknown,
+    });
+    let result = (|| {
+        while let Some((name, value)) = next_binding(&mut token_iter)? {
+            token_iter.bindings.insert(name, value);
+        }
+        let value = next_checked_value(&mut token_iter, schema, false)?;
+        match token_iter.next() {
+            Some(rt) => Err(leftover(rt)),
+            None => Ok(value),
+        }
+    })()
+    .map_err(|e| token_iter.locate(e));
+    let errors = token_iter.validation.map_or(Vec::new(), |v| v.errors);
+    trace_done!(
+        parsing,
+        tokens = token_iter.pos,
+        errors = errors.len(),
+        "parsed"
+    );
+    // a mismatch stops a fail fast parse with an error that doesn't mean anything
+    if !errors.is_empty() {
+        return Err(ValidatedParseError::Invalid(ValidationErrors(errors)));
+    }
+    Ok(result?)
+}
+
+/// Adds `value` and everything in it to `counts`, returns how deeply it's nested
+fn count_nodes(value: &Value, counts: &mut NodeCounts) -> usize {
+    match value {
+        Value::Obj(obj) => {
+            counts.objects += 1;
+            1 + obj
+                .values()
+                .map(|v| count_nodes(v, counts))
+                .max()
+                .unwrap_or(0)
+        }
+        Value::List(xs) => {
+            counts.lists += 1;
+            1 + xs.iter().map(|x| count_nodes(x, counts)).max().unwrap_or(0)
+        }
+        Value::Str { .. } => {
+            counts.strings += 1;
+            0
+        }
+        Value::Num(_) => {
+            counts.numbers += 1;
+            0
+        }
+        Value::Bool(_) => {
+            counts.bools += 1;
+            0
+        }
+        Value::None => {
+            counts.nones += 1;
+            0
+        }
+        Value::Tagged { value, .. } => {
+            counts.tagged += 1;
+            count_nodes(value, counts)
+        }
+        // the parser never produces raw text
+        Value::Raw(_) => 0,
     }
 }
 
 fn next_value(tokens: &mut TokenIter) -> Result<Value, GonError> {
+    if tokens
+        .cancel
+        .as_ref()
+        .is_some_and(CancelToken::is_cancelled)
+    {
+        return Err(GonError::new(ErrorKind::Cancelled, Some(tokens.loc)));
+    }
+    if let Some(value) = next_hooked_scalar(tokens) {
+        return Ok(value);
+    }
     let Some(first_token) = tokens.next() else {
-        return Err(GonError::NoValueErr);
+        return Err(ErrorKind::NoValue.into());
     };
-    match first_token.inner {
+    match &first_token.inner {
         Token::Sym(sym) => {
-            let sym_lower = sym.to_lowercase();
-            if sym_lower == "none" || sym_lower == "null" {
+            let is = |keyword| sym.eq_ignore_ascii_case(keyword);
+            if is("none") || is("null") {
                 Ok(Value::None)
-            } else if sym_lower == "true" {
+            } else if is("true") {
                 Ok(Value::Bool(true))
-            } else if sym_lower == "false" {
+            } else if is("false") {
                 Ok(Value::Bool(false))
-            } else if sym_lower == "r" {
+            } else if is("r") {
                 if let Some(Token::Str(string)) = tokens.peek().map(|rt| &rt.inner) {
-                    let value = Value::Str {
-                        s: string.to_owned(),
-                        raw: true,
-                    };
+                    let mut s = string.to_owned();
                     tokens.next();
-                    Ok(value)
+                    next_continuations(tokens, &mut s)?;
+                    Ok(Value::Str { s, raw: true })
+                } else if tokens.bare_strings {
+                    Ok(Value::Str {
+                        s: sym.clone(),
+                        raw: false,
+                    })
                 } else {
-                    Err(GonError::InvalidValue(sym, first_token.loc))
+                    Err(
+                        GonError::new(ErrorKind::InvalidValue(sym.clone()), Some(first_token.loc))
+                            .expecting(&VALUES),
+                    )
                 }
+            } else if let Some(value) = tokens.bindings.get(sym) {
+                Ok(value.clone())
+            } else if tokens.bare_strings {
+                Ok(Value::Str {
+                    s: sym.clone(),
+                    raw: false,
+                })
+            } else if !tokens.bindings.is_empty() {
+                Err(GonError::new(
+                    ErrorKind::UndefinedVariable(sym.clone()),
+                    Some(first_token.loc),
+                ))
             } else {
-                Err(GonError::InvalidValue(sym, first_token.loc))
+                Err(
+                    GonError::new(ErrorKind::InvalidValue(sym.clone()), Some(first_token.loc))
+                        .expecting(&VALUES),
+                )
             }
         }
-        Token::Str(string) => Ok(Value::Str {
-            s: string,
-            raw: false,
-        }),
-        Token::Num(num) => Ok(Value::Num(num)),
+        Token::Str(s) => {
+            let mut s = s.clone();
+            next_continuations(tokens, &mut s)?;
+            Ok(Value::Str { s, raw: false })
+        }
+        Token::Num(num) => number(num.clone(), first_token.loc),
         Token::Dash => {
-            if let Some(Token::Num(ns)) = tokens.peek().map(|t| &t.inner) {
-                let value = Value::Num(format!("-{ns}"));
+            let mut dashes = 1;
+            while matches!(tokens.peek().map(|t| &t.inner), Some(Token::Dash)) {
                 tokens.next();
-                Ok(value)
-            } else {
-                Err(GonError::UnexpectedToken(Token::Dash, first_token.loc))
+                dashes += 1;
             }
-        }
-        Token::LBrace => {
-            let mut map = crate::MapT::new();
-            let opening_loc = tokens.loc;
-            loop {
-                if matches![tokens.peek().map(|t| &t.inner), Some(Token::RBrace)] {
+            match tokens.peek().map(|t| &t.inner) {
+                Some(Token::Num(ns)) => {
+                    let num = format!("{}{ns}", "-".repeat(dashes));
                     tokens.next();
-                    break;
+                    if dashes > 1 {
+                        Err(malformed_number(num, first_token.loc))
+                    } else {
+                        number(num, first_token.loc)
+                    }
                 }
-                let Some((key, value)) = next_key_value_pair(tokens)? else {
-                    return Err(GonError::UnclosedDelimiter('}', opening_loc));
-                };
-                map.insert(key, value);
-                consume_optional_comma(tokens);
-            }
-            Ok(Value::Obj(map))
-        }
-        Token::LBrack => {
-            let mut list = Vec::new();
-            let opening_loc = tokens.loc;
-            loop {
-                if matches![tokens.peek().map(|t| &t.inner), Some(Token::RBrack)] {
-                    tokens.next();
-                    break;
+                _ => Err(unexpected(&Token::Dash, first_token.loc, &["<number>"])),
+            }
+        }
+        Token::LBrace => next_obj(tokens, None),
+        Token::LBrack => next_list(tokens, None),
+        token => Err(unexpected(token, first_token.loc, &VALUES)),
+    }
+}
+
+/// `num` as a [`Value::Num`] if it's spelled like a number, see [`is_number_literal`]
+fn number(num: String, loc: Loc) -> Result<Value, GonError> {
+    if is_number_literal(num.strip_prefix('-').unwrap_or(&num)) {
+        Ok(Value::Num(num))
+    } else {
+        Err(malformed_number(num, loc))
+    }
+}
+
+fn malformed_number(num: String, loc: Loc) -> GonError {
+    GonError::new(ErrorKind::MalformedNumber(num), Some(loc))
+        .expecting(&["digits, optionally with a fraction and an exponent, like 1_000, 0.5 or 2e-3"])
+}
+
+/// Whether `num` is digits, optionally followed by a fraction and an exponent, like `1_000`,
+/// `0.5` or `2e-3`. Digits may be separated by `_`.
+pub(crate) fn is_number_literal(num: &str) -> bool {
+    fn digits(s: &str) -> Option<&str> {
+        let end = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '_'))
+            .unwrap_or(s.len());
+        s.starts_with(|c: char| c.is_ascii_digit())
+            .then_some(&s[end..])
+    }
+    let Some(mut rest) = digits(num) else {
+        return false;
+    };
+    if let Some(fraction) = rest.strip_prefix('.') {
+        match digits(fraction) {
+            Some(after) => rest = after,
+            None => return false,
+        }
+    }
+    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+        match digits(exponent.strip_prefix(['-', '+']).unwrap_or(exponent)) {
+            Some(after) => rest = after,
+            None => return false,
+        }
+    }
+    rest.is_empty()
+}
+
+/// Like [`next_value`], but checks the value against `schema` as it's built, see
+/// [`parse_validated`]. `None` is fine if `optional`.
+fn next_checked_value(
+    tokens: &mut TokenIter,
+    schema: &Schema,
+    optional: bool,
+) -> Result<Value, GonError> {
+    let (value, deep) = match tokens.peek().map(|t| &t.inner) {
+        Some(Token::LBrace) => {
+            tokens.next();
+            let fields = schema.fields();
+            (next_obj(tokens, fields)?, fields.is_none())
+        }
+        Some(Token::LBrack) => {
+            tokens.next();
+            let elements = schema.elements();
+            (next_list(tokens, elements)?, elements.is_none())
+        }
+        _ => (next_value(tokens)?, true),
+    };
+    if !(optional && value == Value::None) {
+        tokens.check(schema, &value, deep)?;
+    }
+    Ok(value)
+}
+
+/// Parses the rest of an object after its `{`, checking the values of `fields` on the way
+fn next_obj(tokens: &mut TokenIter, fields: Option<&[Field]>) -> Result<Value, GonError> {
+    let mut map = crate::MapT::new();
+    let opening_loc = tokens.loc;
+    let field = |key: &str| fields.and_then(|fields| fields.iter().find(|f| f.key == key));
+    let mut dotted_keys = Vec::new();
+    loop {
+        if matches![tokens.peek().map(|t| &t.inner), Some(Token::RBrace)] {
+            tokens.next();
+            break;
+        }
+        if tokens.peek_is(0, "@") {
+            if let Some(Value::Obj(entries)) = next_section(tokens, "{")? {
+                for (key, value) in entries {
+                    if fields.is_some() {
+                        match field(&key) {
+                            Some(field) => tokens.check_at(
+                                Segment::Key(key.to_string()),
+                                &field.schema,
+                                &value,
+                                field.optional,
+                            )?,
+                            None if tokens.prunes_unknown() => continue,
+                            None => {}
+                        }
+                    }
+                    map.insert(key, value);
+                }
+            }
+            consume_optional_comma(tokens)?;
+            continue;
+        }
+        let Some((key, value, dotted)) = next_key_value_pair(tokens, fields.map(|_| field))? else {
+            return Err(unclosed('}', opening_loc));
+        };
+        if fields.is_some() && field(&key).is_none() && tokens.prunes_unknown() {
+            consume_optional_comma(tokens)?;
+            continue;
+        }
+        if dotted && !dotted_keys.contains(&key) {
+            dotted_keys.push(key.clone());
+        }
+        match map.get_mut(key.as_str()) {
+            Some(existing @ Value::Obj(_)) if dotted_keys.contains(&key) => existing.merge(value),
+            _ => {
+                map.insert(tokens.intern(key), value);
+            }
+        }
+        consume_optional_comma(tokens)?;
+    }
+    // dotted entries are only complete once all of them are merged
+    for key in dotted_keys {
+        if let (Some(field), Some(value)) = (field(&key), map.get(key.as_str())) {
+            tokens.check_at(Segment::Key(key), &field.schema, value, field.optional)?;
+        }
+    }
+    Ok(Value::Obj(map))
+}
+
+/// Parses the rest of a list after its `[`, checking the elements against `elements`
+fn next_list(tokens: &mut TokenIter, elements: Option<&Schema>) -> Result<Value, GonError> {
+    let mut list = Vec::new();
+    let opening_loc = tokens.loc;
+    loop {
+        if matches![tokens.peek().map(|t| &t.inner), Some(Token::RBrack)] {
+            tokens.next();
+            break;
+        }
+        if tokens.peek_is(0, "@") {
+            if let Some(Value::List(spliced)) = next_section(tokens, "[")? {
+                for value in spliced {
+                    if let Some(schema) = elements {
+                        tokens.check_at(Segment::Index(list.len()), schema, &value, false)?;
+                    }
+                    list.push(value);
                 }
-                let Ok(value) = next_value(tokens) else {
-                    return Err(GonError::UnclosedDelimiter(']', opening_loc));
-                };
-                list.push(value);
-                consume_optional_comma(tokens);
             }
-            Ok(Value::List(list))
+            consume_optional_comma(tokens)?;
+            continue;
+        }
+        let value = match elements {
+            Some(schema) => {
+                tokens.enter(Segment::Index(list.len()));
+                let value = next_checked_value(tokens, schema, false);
+                tokens.leave();
+                value
+            }
+            None => next_value(tokens),
+        };
+        let value = match value {
+            Ok(value) => value,
+            Err(e) if tokens.aborted() => return Err(e),
+            Err(e) if is_fatal(&e) => return Err(e),
+            Err(_) => return Err(unclosed(']', opening_loc)),
+        };
+        list.push(value);
+        consume_optional_comma(tokens)?;
+    }
+    Ok(Value::List(list))
+}
+
+/// Appends every `+ "..."` (or `+ r"..."`) that follows a string literal to `s`
+fn next_continuations(tokens: &mut TokenIter, s: &mut String) -> Result<(), GonError> {
+    while tokens.peek_is(0, "+") {
+        tokens.next();
+        if tokens
+            .peek_sym(0)
+            .is_some_and(|sym| sym.eq_ignore_ascii_case("r"))
+        {
+            tokens.next();
+        }
+        match tokens.next() {
+            Some(RichToken {
+                inner: Token::Str(piece),
+                ..
+            }) => s.push_str(piece),
+            Some(rt) => return Err(unexpected(&rt.inner, rt.loc, &["\"...\""])),
+            None => return Err(ErrorKind::NoValue.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Parses `let <name> = <value>;` if the next token is `let`
+fn next_binding(tokens: &mut TokenIter) -> Result<Option<(String, Value)>, GonError> {
+    match tokens.peek().map(|t| &t.inner) {
+        Some(Token::Sym(sym)) if sym.eq_ignore_ascii_case("let") => tokens.next(),
+        _ => return Ok(None),
+    };
+    let let_loc = tokens.loc;
+    let name = match tokens.next() {
+        Some(RichToken {
+            inner: Token::Sym(name),
+            loc,
+        }) => {
+            if KEYWORDS
+                .iter()
+                .any(|keyword| name.eq_ignore_ascii_case(keyword))
+            {
+                return Err(invalid_binding(name.clone(), *loc));
+            }
+            name.clone()
+        }
+        Some(rt) => return Err(unexpected(&rt.inner, rt.loc, &["<name>"])),
+        None => return Err(invalid_binding("let".into(), let_loc)),
+    };
+    let at_end = |loc| invalid_binding(name.clone(), loc);
+    expect_spelling(tokens, "=", at_end)?;
+    let value = next_value(tokens)?;
+    expect_spelling(tokens, ";", at_end)?;
+    Ok(Some((name, value)))
+}
+
+/// Consumes the next token if it's spelled `spelling`, calls `at_end` if there's none
+fn expect_spelling(
+    tokens: &mut TokenIter,
+    spelling: &'static str,
+    at_end: impl FnOnce(Loc) -> GonError,
+) -> Result<(), GonError> {
+    match tokens.next() {
+        Some(rt) if is_spelled(&rt.inner, spelling) => Ok(()),
+        Some(rt) => Err(unexpected(&rt.inner, rt.loc, &[spelling])),
+        None => Err(at_end(tokens.loc)),
+    }
+}
+
+/// Parses `@if(<cond>) <body> (@else <body>)?` where the bodies open with `open` and
+/// returns the body that was selected, if any
+fn next_section(tokens: &mut TokenIter, open: &str) -> Result<Option<Value>, GonError> {
+    tokens.next();
+    match tokens.next() {
+        Some(RichToken {
+            inner: Token::Sym(sym),
+            ..
+        }) if sym.eq_ignore_ascii_case("if") => {}
+        _ => return Err(section_error("expected `if` after `@`", tokens.loc)),
+    }
+    let condition = next_condition(tokens)?;
+    let then = next_section_body(tokens, open)?;
+    let is_else = tokens.peek_is(0, "@")
+        && tokens
+            .peek_sym(1)
+            .is_some_and(|s| s.eq_ignore_ascii_case("else"));
+    let otherwise = if is_else {
+        tokens.next();
+        tokens.next();
+        if tokens.peek_is(0, "@") {
+            next_section(tokens, open)?
+        } else {
+            Some(next_section_body(tokens, open)?)
+        }
+    } else {
+        None
+    };
+    Ok(if condition { Some(then) } else { otherwise })
+}
+
+/// Parses and evaluates `(name)`, `(name == <value>)` or `(name != <value>)`
+fn next_condition(tokens: &mut TokenIter) -> Result<bool, GonError> {
+    expect_spelling(tokens, "(", |loc| section_error("expected `(`", loc))?;
+    let name = match tokens.next() {
+        Some(RichToken {
+            inner: Token::Sym(name),
+            ..
+        }) => name,
+        _ => return Err(section_error("expected a variable name", tokens.loc)),
+    };
+    let defined = tokens.defines.get(name).cloned();
+    if tokens.peek_is(0, ")") {
+        tokens.next();
+        return Ok(defined.is_some_and(|v| v != "false"));
+    }
+    let op_loc = tokens.peek_loc();
+    let equal = match next_operator(tokens).as_deref() {
+        Some("==") => true,
+        Some("!=") => false,
+        _ => return Err(section_error("expected `==`, `!=` or `)`", op_loc)),
+    };
+    let literal = match next_value(tokens)? {
+        Value::Str { s, .. } | Value::Num(s) => s,
+        Value::Bool(b) => b.to_string(),
+        _ => {
+            return Err(section_error(
+                "expected a string, number or bool",
+                tokens.loc,
+            ));
+        }
+    };
+    expect_spelling(tokens, ")", |loc| section_error("expected `)`", loc))?;
+    Ok((defined.as_deref() == Some(literal.as_str())) == equal)
+}
+
+/// `==` and `!=` may come as one token or as two
+fn next_operator(tokens: &mut TokenIter) -> Option<String> {
+    let first = tokens.next()?.inner.spelling();
+    match first.as_str() {
+        "==" | "!=" => Some(first),
+        "=" | "!" if tokens.peek_is(0, "=") => {
+            tokens.next();
+            Some(format!("{first}="))
+        }
+        _ => None,
+    }
+}
+
+fn next_section_body(tokens: &mut TokenIter, open: &str) -> Result<Value, GonError> {
+    if !tokens.peek_is(0, open) {
+        let loc = tokens.peek_loc();
+        return Err(section_error(&format!("expected `{open}`"), loc));
+    }
+    next_value(tokens)
+}
+
+/// `token` isn't what should come next, which is one of `expected`
+fn unexpected(token: &Token, loc: Loc, expected: &[&'static str]) -> GonError {
+    GonError::new(ErrorKind::UnexpectedToken(token.spelling()), Some(loc)).expecting(expected)
+}
+
+/// The rest of the document after its value
+fn leftover(rt: &RichToken) -> GonError {
+    GonError::new(ErrorKind::LeftoverTokens(rt.inner.spelling()), Some(rt.loc))
+        .expecting(&["the end of the input"])
+}
+
+fn unclosed(delimiter: char, opening_loc: Loc) -> GonError {
+    GonError::new(ErrorKind::UnclosedDelimiter(delimiter), Some(opening_loc))
+}
+
+fn missing_colon(key: String, loc: Loc) -> GonError {
+    GonError::new(ErrorKind::MissingColon(key), Some(loc)).expecting(&[":"])
+}
+
+fn invalid_binding(name: String, loc: Loc) -> GonError {
+    GonError::new(ErrorKind::InvalidBinding(name), Some(loc)).expecting(&["let <name> = <value>;"])
+}
+
+/// Whether `e` is reported as it is instead of as the value or delimiter it's in
+fn is_fatal(e: &GonError) -> bool {
+    matches!(
+        e.kind(),
+        ErrorKind::UndefinedVariable(_)
+            | ErrorKind::MalformedNumber(_)
+            | ErrorKind::Disallowed(_)
+            | ErrorKind::Cancelled
+    )
+}
+
+/// A lexer error, at the first string or comment that isn't closed if there is one
+fn lex_error(src: &str, e: klex::KlexError) -> GonError {
+    let error = GonError::from(ErrorKind::Lex(e.to_string()));
+    match tokenize(src)
+        .into_iter()
+        .find(|t| t.kind == TokenKind::Unterminated)
+    {
+        Some(token) => error.located(src, token.span),
+        None => error,
+    }
+}
+
+/// The bytes every token of `tokens` spans in `src`. klex only knows the rows and columns
+/// of tokens, so this walks `src` along them, skipping whitespace and comments.
+fn token_spans(src: &str, tokens: &[RichToken]) -> Vec<Span> {
+    let mut pos = 0;
+    let mut spans = Vec::with_capacity(tokens.len());
+    for rt in tokens {
+        let start = loop {
+            let mut next = pos;
+            match next_token(src, &mut next) {
+                Some(token) if token.kind == TokenKind::Comment => pos = next,
+                Some(token) => break token.span.start,
+                None => break src.len(),
+            }
+        };
+        let mut end = match &rt.inner {
+            Token::Str(_) => string_end(src.as_bytes(), start).unwrap_or(src.len()),
+            token => start + token.spelling().len(),
+        }
+        .min(src.len());
+        while !src.is_char_boundary(end) {
+            end += 1;
         }
-        token => Err(GonError::UnexpectedToken(token, first_token.loc)),
+        spans.push(Span::from(start..end));
+        pos = end;
     }
+    spans
 }
 
-fn consume_optional_comma(tokens: &mut TokenIter) {
+fn section_error(message: &str, loc: Loc) -> GonError {
+    GonError::new(ErrorKind::InvalidSection(message.into()), Some(loc))
+}
+
+/// Offers the upcoming scalar to the scalar hooks and consumes it if one accepts it
+fn next_hooked_scalar(tokens: &mut TokenIter) -> Option<Value> {
+    if tokens.scalar_hooks.is_empty() {
+        return None;
+    }
+    let end = tokens.scalar_end();
+    if end == tokens.pos {
+        return None;
+    }
+    let src: String = tokens.tokens[tokens.pos..end]
+        .iter()
+        .map(|rt| rt.inner.spelling())
+        .collect();
+    let span = tokens.span(tokens.pos).unwrap_or_default();
+    let value = tokens
+        .scalar_hooks
+        .iter()
+        .find_map(|hook| hook(&src, span))?;
+    tokens.loc = tokens.tokens[end - 1].loc;
+    tokens.pos = end;
+    Some(value)
+}
+
+fn consume_optional_comma(tokens: &mut TokenIter) -> Result<(), GonError> {
     if let Some(rt) = tokens.peek() {
         if matches![rt.inner, Token::Comma] {
             tokens.next();
+            if tokens.forbid_trailing_commas
+                && matches!(
+                    tokens.peek().map(|t| &t.inner),
+                    Some(Token::RBrace | Token::RBrack)
+                )
+            {
+                let e = GonError::new(ErrorKind::Disallowed("trailing commas"), Some(tokens.loc));
+                return Err(e);
+            }
         }
     }
+    Ok(())
+}
+
+/// Fails at the first comment klex found in `src`, see [`ParseOptions::forbid_comments`]
+fn check_no_comments(src: &str, tokens: &[RichToken]) -> Result<(), GonError> {
+    let Some(index) = (tokens.iter()).position(|rt| matches!(rt.inner, Token::Comment(_))) else {
+        return Ok(());
+    };
+    let error = GonError::from(ErrorKind::Disallowed("comments"));
+    // the comment is the first one after the tokens before it, other than a pragma
+    let mut pos = (token_spans(src, &tokens[..index]).last()).map_or(0, Span::end);
+    let has_pragma = pragma::pragma(src).is_some();
+    let comment = std::iter::from_fn(|| next_token(src, &mut pos))
+        .find(|t| t.kind == TokenKind::Comment && !(has_pragma && t.span.start == 0));
+    match comment {
+        Some(comment) => Err(error.located(src, comment.span)),
+        None => Err(error),
+    }
 }
 
-fn next_key_value_pair(tokens: &mut TokenIter) -> Result<Option<(String, Value)>, GonError> {
-    let Some(token) = tokens.next().map(|t| t.inner) else {
+/// Parses `key: value`. With `field`, the value is checked against the schema of the field
+/// `field` finds for the key, if any.
+fn next_key_value_pair<'s>(
+    tokens: &mut TokenIter,
+    field: Option<impl Fn(&str) -> Option<&'s Field>>,
+) -> Result<Option<(String, Value, bool)>, GonError> {
+    let Some(token) = tokens.next().map(|t| &t.inner) else {
         return Ok(None);
     };
     let key = match token {
-        Token::Str(s) | Token::Num(s) | Token::Sym(s) => s,
-        Token::Comment(_) => return Err(GonError::UnexpectedToken(token, tokens.loc)),
+        Token::Str(s) => s.clone(),
+        Token::Num(_) | Token::Sym(_) if tokens.require_quoted_keys => {
+            let e = GonError::new(ErrorKind::Disallowed("unquoted keys"), Some(tokens.loc));
+            return Err(e.expecting(&["\"key\""]));
+        }
+        Token::Num(s) | Token::Sym(s) => s.clone(),
         otherwise => otherwise.spelling(),
     };
-    let Some(Token::Colon) = tokens.next().map(|t| t.inner) else {
-        return Err(GonError::MissingColon(key, tokens.loc));
+    // the keys after the first one of `a.b.c`, see `ParseOptions::dotted_keys`
+    let mut nested = Vec::new();
+    while tokens.dotted_keys && tokens.peek_is(0, ".") {
+        tokens.next();
+        match tokens.next() {
+            Some(RichToken {
+                inner: Token::Str(s) | Token::Num(s) | Token::Sym(s),
+                ..
+            }) => nested.push(s.clone()),
+            Some(rt) => return Err(unexpected(&rt.inner, rt.loc, &["a key"])),
+            None => return Err(missing_colon(key, tokens.loc)),
+        }
+    }
+    let Some(Token::Colon) = tokens.next().map(|t| &t.inner) else {
+        return Err(missing_colon(key, tokens.loc));
+    };
+    // the objects that dotted keys stand for are checked once they're complete
+    let field = field.filter(|_| nested.is_empty());
+    let value = match field.and_then(|field| field(&key)) {
+        Some(field) => {
+            tokens.enter(Segment::Key(key.clone()));
+            let value = next_checked_value(tokens, &field.schema, field.optional);
+            tokens.leave();
+            value
+        }
+        None => next_value(tokens),
     };
-    let Ok(value) = next_value(tokens) else {
-        return Err(GonError::MissingValue(key, tokens.loc));
+    let value = match value {
+        Ok(value) => value,
+        Err(e) if tokens.aborted() => return Err(e),
+        Err(e) if is_fatal(&e) => return Err(e),
+        Err(_) => {
+            let e = GonError::new(ErrorKind::MissingValue(key), Some(tokens.loc));
+            return Err(e.expecting(&["a value"]));
+        }
     };
-    Ok(Some((key, value)))
+    let dotted = !nested.is_empty();
+    let value = nested.into_iter().rev().fold(value, |value, key| {
+        let mut map = crate::MapT::new();
+        map.insert(tokens.intern(key), value);
+        Value::Obj(map)
+    });
+    Ok(Some((key, value, dotted)))
 }
 
-impl TokenIter {
-    pub fn peek(&mut self) -> Option<&<Self as Iterator>::Item> {
-        self.inner.peek()
+impl<'s> TokenIter<'s> {
+    /// Checks `value` against `schema` at the current path, see [`Schema::validate0`].
+    /// Fails if that ends a fail fast parse.
+    fn check(&mut self, schema: &Schema, value: &Value, deep: bool) -> Result<(), GonError> {
+        let Some(validation) = self.validation.as_mut() else {
+            return Ok(());
+        };
+        let (path, errors) = (&mut validation.path, &mut validation.errors);
+        schema.validate0(value, path, errors, deep, None);
+        if self.aborted() {
+            return Err(ErrorKind::NoValue.into());
+        }
+        Ok(())
     }
-}
 
-impl Iterator for TokenIter {
-    type Item = RichToken;
+    /// Checks all of `value` against `schema` at `segment` below the current path
+    fn check_at(
+        &mut self,
+        segment: Segment,
+        schema: &Schema,
+        value: &Value,
+        optional: bool,
+    ) -> Result<(), GonError> {
+        if optional && *value == Value::None {
+            return Ok(());
+        }
+        self.enter(segment);
+        let result = self.check(schema, value, true);
+        self.leave();
+        result
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(rt) = self.inner.next() {
-            self.loc = rt.loc;
-            Some(rt)
-        } else {
-            None
+    fn enter(&mut self, segment: Segment) {
+        if let Some(validation) = self.validation.as_mut() {
+            validation.path.0.push(segment);
+        }
+    }
+
+    fn leave(&mut self) {
+        if let Some(validation) = self.validation.as_mut() {
+            validation.path.0.pop();
         }
     }
+
+    /// Whether a fail fast parse found a mismatch and has to stop
+    fn aborted(&self) -> bool {
+        self.validation
+            .as_ref()
+            .is_some_and(|v| v.fail_fast && !v.errors.is_empty())
+    }
+
+    fn prunes_unknown(&self) -> bool {
+        self.validation.as_ref().is_some_and(|v| v.prune_unknown)
+    }
+
+    /// The next token, moving past it
+    fn next(&mut self) -> Option<&'s RichToken> {
+        let rt = self.tokens.get(self.pos)?;
+        self.pos += 1;
+        self.loc = rt.loc;
+        Some(rt)
+    }
+
+    fn peek(&self) -> Option<&'s RichToken> {
+        self.tokens.get(self.pos)
+    }
+
+    /// The bytes the token at `index` spans in the source
+    fn span(&mut self, index: usize) -> Option<Span> {
+        if self.spans.is_empty() {
+            self.spans = token_spans(self.src, self.tokens);
+        }
+        self.spans.get(index).copied()
+    }
+
+    /// `error` with where in the source it happened
+    fn locate(&mut self, error: GonError) -> GonError {
+        let index = error
+            .loc()
+            .and_then(|loc| self.tokens.iter().position(|t| t.loc == loc));
+        match index.and_then(|i| self.span(i)) {
+            Some(span) => error.located(self.src, span),
+            None => error,
+        }
+    }
+
+    /// The location of the next token or the current one at the end
+    fn peek_loc(&self) -> Loc {
+        self.tokens.get(self.pos).map_or(self.loc, |t| t.loc)
+    }
+
+    /// The position after the scalar starting at the next token, see
+    /// [`ParseOptions::scalar_hook`]
+    fn scalar_end(&self) -> usize {
+        let mut depth = 0usize;
+        let mut end = self.pos;
+        while let Some(rt) = self.tokens.get(end) {
+            let spelling = spelling(&rt.inner);
+            let is_key = end > self.pos
+                && self
+                    .tokens
+                    .get(end + 1)
+                    .is_some_and(|next| matches!(next.inner, Token::Colon));
+            match &*spelling {
+                "(" => depth += 1,
+                ")" if depth == 0 => break,
+                ")" => depth -= 1,
+                "," | ":" | ";" | "{" | "}" | "[" | "]" | "@" if depth == 0 => break,
+                _ if depth == 0 && is_key => break,
+                _ => {}
+            }
+            end += 1;
+        }
+        end
+    }
+
+    /// Whether the token `n` tokens ahead is spelled `spelling`
+    fn peek_is(&self, n: usize, spelling: &str) -> bool {
+        (self.tokens.get(self.pos + n)).is_some_and(|t| is_spelled(&t.inner, spelling))
+    }
+
+    /// The symbol `n` tokens ahead, if it's one
+    fn peek_sym(&self, n: usize) -> Option<&'s str> {
+        match self.tokens.get(self.pos + n).map(|t| &t.inner) {
+            Some(Token::Sym(sym)) => Some(sym),
+            _ => None,
+        }
+    }
+}
+
+/// How `token` is spelled, without allocating for symbols, numbers and the punctuation
+/// that klex has variants for
+fn spelling(token: &Token) -> Cow<'_, str> {
+    match token {
+        Token::Sym(s) | Token::Num(s) => Cow::Borrowed(s),
+        Token::LBrace => Cow::Borrowed("{"),
+        Token::RBrace => Cow::Borrowed("}"),
+        Token::LBrack => Cow::Borrowed("["),
+        Token::RBrack => Cow::Borrowed("]"),
+        Token::Colon => Cow::Borrowed(":"),
+        Token::Comma => Cow::Borrowed(","),
+        Token::Dash => Cow::Borrowed("-"),
+        token => Cow::Owned(token.spelling()),
+    }
+}
+
+/// Whether `token` is spelled `spelling`, see [`spelling`]. String literals are only
+/// spelled out if `spelling` could be one.
+fn is_spelled(token: &Token, spelling: &str) -> bool {
+    match token {
+        Token::Str(_) if !spelling.starts_with('"') => false,
+        token => self::spelling(token) == spelling,
+    }
 }
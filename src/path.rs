@@ -0,0 +1,830 @@
+//! Selecting parts of gon values by paths like `address.street` or `friends[*].name`.
+//!
+//! A [`Pattern`] is a sequence of segments separated by dots:
+//! - `key` matches a key of an object. Keys are globs where `*` matches any number of
+//!   characters and `?` exactly one, so `internal_*` matches every key starting with
+//!   `internal_`. Keys with dots, brackets or quotes in them can be written as `"..."`.
+//! - `[2]` matches the element of a list at index 2, `[*]` matches every element.
+//! - `[100:200]` matches the elements from index 100 up to but not including 200, either
+//!   end can be left out like in `[:10]` or `[5:]`.
+//! - `**` matches any number of segments, including none, so `**.id` matches every `id`
+//!   anywhere.
+//!
+//! [`select`] finds everything a pattern matches together with the concrete [`Path`] to it.
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, path::{select, Pattern}};
+//! let value = parse_str("{friends: [{name: \"a\"}, {name: \"b\"}]}").unwrap();
+//! let pattern: Pattern = "friends[*].name".parse().unwrap();
+//! let names: Vec<_> = select(&value, &pattern)
+//!     .map(|(path, name)| format!("{path} = {}", name.min_spell()))
+//!     .collect();
+//! assert_eq!(names, ["friends[0].name = \"a\"", "friends[1].name = \"b\""]);
+//! ```
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::value::spelling_order;
+use crate::{MapT, Value};
+
+/// One step of a [`Path`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Segment {
+    /// The value of a key of an object
+    Key(String),
+    /// An element of a list
+    Index(usize),
+}
+
+/// Where a value is inside of another, like `friends[2].name`
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Path(pub Vec<Segment>);
+
+/// One step of a [`Pattern`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PatternSegment {
+    /// Keys of objects that match the glob
+    Key(String),
+    /// The element of a list at the index
+    Index(usize),
+    /// `[*]`, every element of a list
+    AnyIndex,
+    /// `[start:end]`, the elements of a list from `start` up to but not including `end`
+    Slice { start: usize, end: Option<usize> },
+    /// `**`, any number of segments
+    AnyDepth,
+}
+
+/// A path that may contain wildcards, see the [module docs](self)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Pattern(pub Vec<PatternSegment>);
+
+/// A [`Pattern`] couldn't be parsed
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("invalid path '{path}' at {at}: {message}")]
+pub struct PathError {
+    pub path: String,
+    /// The byte offset of the problem in `path`
+    pub at: usize,
+    pub message: &'static str,
+}
+
+/// A value couldn't be put at a [`Path`], see [`set`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("can't set '{path}', {reason}")]
+pub struct SetError {
+    pub path: Path,
+    /// What's in the way
+    pub reason: String,
+}
+
+impl Path {
+    /// The path with `key` appended
+    /// # Usage example
+    /// ```rust
+    /// use gon::path::Path;
+    /// let path = Path::default().key("servers").index(0).key("host.name");
+    /// assert_eq!(path.to_string(), "servers[0].\"host.name\"");
+    /// assert_eq!(path, "servers[0].\"host.name\"".parse().unwrap());
+    /// ```
+    pub fn key(mut self, key: impl Into<String>) -> Self {
+        self.0.push(Segment::Key(key.into()));
+        self
+    }
+
+    /// The path with `index` appended
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push(Segment::Index(index));
+        self
+    }
+
+    /// Whether this is the empty path to the value itself
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The path to what contains the value at this one, `None` for the root
+    pub fn parent(&self) -> Option<Path> {
+        let (_, parent) = self.0.split_last()?;
+        Some(Self(parent.to_vec()))
+    }
+
+    /// The last step of the path, `None` for the root
+    pub fn last(&self) -> Option<&Segment> {
+        self.0.last()
+    }
+}
+
+impl FromIterator<Segment> for Path {
+    fn from_iter<I: IntoIterator<Item = Segment>>(segments: I) -> Self {
+        Self(segments.into_iter().collect())
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                Segment::Key(key) => {
+                    if i > 0 {
+                        write!(f, ".")?;
+                    }
+                    write_key(f, key)?;
+                }
+                Segment::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Spells the pattern in the syntax [`FromStr`] parses
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, segment) in self.0.iter().enumerate() {
+            match segment {
+                PatternSegment::Key(_) | PatternSegment::AnyDepth if i > 0 => write!(f, ".")?,
+                _ => {}
+            }
+            match segment {
+                // globs stay plain, but a key `**` would read as any depth
+                PatternSegment::Key(key) if key != "**" && is_glob(key) => write!(f, "{key}")?,
+                PatternSegment::Key(key) => write_key(f, key)?,
+                PatternSegment::Index(index) => write!(f, "[{index}]")?,
+                PatternSegment::AnyIndex => write!(f, "[*]")?,
+                PatternSegment::Slice { start, end } => {
+                    write!(f, "[")?;
+                    if *start > 0 {
+                        write!(f, "{start}")?;
+                    }
+                    write!(f, ":")?;
+                    if let Some(end) = end {
+                        write!(f, "{end}")?;
+                    }
+                    write!(f, "]")?;
+                }
+                PatternSegment::AnyDepth => write!(f, "**")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_glob(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '*' | '?'))
+}
+
+/// Writes `key` as a path segment, quoted if it could be mistaken for something else
+fn write_key(f: &mut fmt::Formatter<'_>, key: &str) -> fmt::Result {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        return write!(f, "{key}");
+    }
+    write!(f, "\"")?;
+    for c in key.chars() {
+        if c == '"' || c == '\\' {
+            write!(f, "\\")?;
+        }
+        write!(f, "{c}")?;
+    }
+    write!(f, "\"")
+}
+
+impl From<&Path> for Pattern {
+    fn from(path: &Path) -> Self {
+        Self(
+            path.0
+                .iter()
+                .map(|segment| match segment {
+                    Segment::Key(key) => PatternSegment::Key(key.clone()),
+                    Segment::Index(index) => PatternSegment::Index(*index),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = PathError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let err = |at, message| PathError {
+            path: src.to_string(),
+            at,
+            message,
+        };
+        let bytes = src.as_bytes();
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            let after_dot = i > 0;
+            match bytes[i] {
+                b'"' => {
+                    let mut key = String::new();
+                    let mut chars = src[i + 1..].char_indices();
+                    loop {
+                        match chars.next() {
+                            Some((n, '"')) => {
+                                i += n + 2;
+                                break;
+                            }
+                            Some((_, '\\')) => match chars.next() {
+                                Some((_, c)) => key.push(c),
+                                None => return Err(err(bytes.len(), "unclosed quote")),
+                            },
+                            Some((_, c)) => key.push(c),
+                            None => return Err(err(bytes.len(), "unclosed quote")),
+                        }
+                    }
+                    segments.push(PatternSegment::Key(key));
+                }
+                b'[' if !after_dot => {}
+                b'[' | b'.' => return Err(err(i, "empty key")),
+                _ => {
+                    let len = src[i..].find(['.', '[']).unwrap_or(src.len() - i);
+                    let key = &src[i..i + len];
+                    segments.push(if key == "**" {
+                        PatternSegment::AnyDepth
+                    } else {
+                        PatternSegment::Key(key.to_string())
+                    });
+                    i += len;
+                }
+            }
+            while bytes.get(i) == Some(&b'[') {
+                let Some(len) = src[i..].find(']') else {
+                    return Err(err(i, "unclosed ["));
+                };
+                let index = |s: &str| {
+                    s.parse()
+                        .map_err(|_| err(i + 1, "expected an index, a slice or * inside of []"))
+                };
+                segments.push(match &src[i + 1..i + len] {
+                    "*" => PatternSegment::AnyIndex,
+                    inner => match inner.split_once(':') {
+                        Some((start, end)) => PatternSegment::Slice {
+                            start: if start.is_empty() { 0 } else { index(start)? },
+                            end: if end.is_empty() {
+                                None
+                            } else {
+                                Some(index(end)?)
+                            },
+                        },
+                        None => PatternSegment::Index(index(inner)?),
+                    },
+                });
+                i += len + 1;
+            }
+            match bytes.get(i) {
+                None => {}
+                Some(b'.') if i + 1 < bytes.len() => i += 1,
+                Some(b'.') => return Err(err(i + 1, "empty key")),
+                Some(_) => return Err(err(i, "expected . or [")),
+            }
+        }
+        Ok(Self(segments))
+    }
+}
+
+/// Parses a path in the syntax of patterns. `*` and `?` in keys are just characters, `[*]`
+/// and `**` are errors.
+impl FromStr for Path {
+    type Err = PathError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let pattern: Pattern = src.parse()?;
+        pattern
+            .0
+            .into_iter()
+            .map(|segment| match segment {
+                PatternSegment::Key(key) => Ok(Segment::Key(key)),
+                PatternSegment::Index(i) => Ok(Segment::Index(i)),
+                PatternSegment::AnyIndex | PatternSegment::AnyDepth => Err(PathError {
+                    path: src.to_string(),
+                    at: src.find("[*]").or_else(|| src.find("**")).unwrap_or(0),
+                    message: "a path can't have wildcards",
+                }),
+                PatternSegment::Slice { .. } => Err(PathError {
+                    path: src.to_string(),
+                    at: src.find(':').unwrap_or(0),
+                    message: "a path can't have slices",
+                }),
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Pattern {
+    /// The pattern as a path if it doesn't have any wildcards or slices
+    pub fn concrete(&self) -> Option<Path> {
+        self.0
+            .iter()
+            .map(|segment| match segment {
+                PatternSegment::Key(key) if !key.contains(['*', '?']) => {
+                    Some(Segment::Key(key.clone()))
+                }
+                PatternSegment::Index(i) => Some(Segment::Index(*i)),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .map(Path)
+    }
+
+    /// Whether `path` is one of the paths the pattern matches, like [`select`] would
+    pub fn matches(&self, path: &Path) -> bool {
+        let mut rests = vec![self.0.as_slice()];
+        for segment in &path.0 {
+            rests = advance(&rests, segment);
+        }
+        rests.iter().any(|p| is_done(p))
+    }
+}
+
+impl PatternSegment {
+    fn matches(&self, segment: &Segment) -> bool {
+        match (self, segment) {
+            (Self::Key(glob), Segment::Key(key)) => glob_match(glob, key),
+            (Self::Index(i), Segment::Index(j)) => i == j,
+            (Self::Slice { start, end }, Segment::Index(i)) => {
+                start <= i && end.is_none_or(|end| *i < end)
+            }
+            (Self::AnyIndex, Segment::Index(_)) | (Self::AnyDepth, _) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Everything in `value` that `pattern` matches, together with where it is, depth first
+/// and with keys in the order they're spelled in
+pub fn select<'a>(value: &'a Value, pattern: &Pattern) -> impl Iterator<Item = (Path, &'a Value)> {
+    let mut matches = Vec::new();
+    select0(value, &pattern.0, &mut Vec::new(), &mut matches);
+    matches.into_iter()
+}
+
+fn select0<'a>(
+    value: &'a Value,
+    pattern: &[PatternSegment],
+    path: &mut Vec<Segment>,
+    matches: &mut Vec<(Path, &'a Value)>,
+) {
+    match pattern.split_first() {
+        None => matches.push((Path(path.clone()), value)),
+        Some((first, rest)) => {
+            if *first == PatternSegment::AnyDepth {
+                select0(value, rest, path, matches);
+            }
+            for (segment, child) in children(value) {
+                if first.matches(&segment) {
+                    let rest = if *first == PatternSegment::AnyDepth {
+                        pattern
+                    } else {
+                        rest
+                    };
+                    path.push(segment);
+                    select0(child, rest, path, matches);
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+/// The value at `path` in `value`, `None` if there is nothing
+pub fn get<'a>(value: &'a Value, path: &Path) -> Option<&'a Value> {
+    path.0
+        .iter()
+        .try_fold(value, |value, segment| match (untagged(value), segment) {
+            (Value::Obj(obj), Segment::Key(key)) => obj.get(key.as_str()),
+            (Value::List(xs), Segment::Index(i)) => xs.get(*i),
+            _ => None,
+        })
+}
+
+/// The value at `path` in `value` to change it, `None` if there is nothing
+pub fn get_mut<'a>(value: &'a mut Value, path: &Path) -> Option<&'a mut Value> {
+    path.0.iter().try_fold(value, |value, segment| {
+        match (untagged_mut(value), segment) {
+            (Value::Obj(obj), Segment::Key(key)) => obj.get_mut(key.as_str()),
+            (Value::List(xs), Segment::Index(i)) => xs.get_mut(*i),
+            _ => None,
+        }
+    })
+}
+
+/// Puts `new` at `path` in `value`, creating objects for missing keys on the way. Returns
+/// what was there before. Lists aren't extended, an index past their end is an error.
+pub fn set(value: &mut Value, path: &Path, new: Value) -> Result<Option<Value>, SetError> {
+    let existed = get(value, path).is_some();
+    let mut current = value;
+    for segment in &path.0 {
+        current = match (untagged_mut(current), segment) {
+            (Value::Obj(obj), Segment::Key(key)) => obj
+                .entry(key.as_str().into())
+                .or_insert_with(|| Value::Obj(MapT::new())),
+            (Value::List(xs), Segment::Index(i)) => {
+                let len = xs.len();
+                xs.get_mut(*i).ok_or_else(|| SetError {
+                    path: path.clone(),
+                    reason: format!("a list on the way has {len} elements"),
+                })?
+            }
+            (other, _) => {
+                return Err(SetError {
+                    path: path.clone(),
+                    reason: format!("found {} on the way", other.type_name()),
+                });
+            }
+        };
+    }
+    let old = std::mem::replace(current, new);
+    Ok(existed.then_some(old))
+}
+
+/// `value` without any tags around it
+pub(crate) fn untagged(mut value: &Value) -> &Value {
+    while let Value::Tagged { value: inner, .. } = value {
+        value = inner;
+    }
+    value
+}
+
+/// `value` without any tags around it, to change it
+pub(crate) fn untagged_mut(mut value: &mut Value) -> &mut Value {
+    while let Value::Tagged { value: inner, .. } = value {
+        value = inner;
+    }
+    value
+}
+
+/// The direct children of `value` and where they are in it
+pub(crate) fn children(value: &Value) -> Vec<(Segment, &Value)> {
+    match value {
+        Value::Obj(obj) => spelling_order(obj)
+            .map(|(k, v)| (Segment::Key(k.to_string()), v))
+            .collect(),
+        Value::List(xs) => xs
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (Segment::Index(i), x))
+            .collect(),
+        Value::Tagged { value, .. } => children(value),
+        _ => Vec::new(),
+    }
+}
+
+/// `value` with only what `patterns` match (and the objects and lists leading there) kept.
+/// Lists are looked through by patterns that expect a key, so `name` picks the `name` of
+/// every object in a list of objects. Scalars that aren't inside of an object are kept as
+/// they are.
+/// # Usage example
+/// ```rust
+/// use gon::{parse_str, path::pick};
+/// let value = parse_str("[{name: 1, address: {street: 2, zip: 3}, debug: 4}]").unwrap();
+/// let patterns = ["name".parse().unwrap(), "address.street".parse().unwrap()];
+/// assert_eq!(
+///     pick(&value, &patterns),
+///     parse_str("[{name: 1, address: {street: 2}}]").unwrap(),
+/// );
+/// ```
+pub fn pick(value: &Value, patterns: &[Pattern]) -> Value {
+    let patterns: Vec<_> = patterns.iter().map(|p| p.0.as_slice()).collect();
+    if patterns.iter().any(|p| is_done(p)) {
+        return value.clone();
+    }
+    pick0(value, &patterns)
+}
+
+/// `value` without what `patterns` match, see [`pick`] for how lists are treated.
+/// The whole `value` is never removed.
+/// # Usage example
+/// ```rust
+/// use gon::{parse_str, path::omit};
+/// let value = parse_str("{debug: 1, internal: {a: 2, b: 3}, name: 4}").unwrap();
+/// let patterns = ["debug".parse().unwrap(), "internal.*".parse().unwrap()];
+/// assert_eq!(
+///     omit(&value, &patterns),
+///     parse_str("{internal: {}, name: 4}").unwrap(),
+/// );
+/// ```
+pub fn omit(value: &Value, patterns: &[Pattern]) -> Value {
+    let patterns: Vec<_> = patterns.iter().map(|p| p.0.as_slice()).collect();
+    omit0(value, &patterns)
+}
+
+/// Whether `text` matches `pattern`, where `*` matches any number of characters and `?`
+/// exactly one
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was and which char of text it's currently matched up to
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Whether a pattern matches where it is, which is when nothing but `**` is left of it
+pub(crate) fn is_done(pattern: &[PatternSegment]) -> bool {
+    pattern.iter().all(|s| *s == PatternSegment::AnyDepth)
+}
+
+/// What is left of `patterns` to match below `segment`
+pub(crate) fn advance<'p>(
+    patterns: &[&'p [PatternSegment]],
+    segment: &Segment,
+) -> Vec<&'p [PatternSegment]> {
+    let mut rests = Vec::new();
+    for pattern in patterns {
+        advance1(pattern, segment, &mut rests);
+    }
+    rests
+}
+
+fn advance1<'p>(
+    pattern: &'p [PatternSegment],
+    segment: &Segment,
+    rests: &mut Vec<&'p [PatternSegment]>,
+) {
+    match pattern.split_first() {
+        Some((PatternSegment::AnyDepth, rest)) => {
+            rests.push(pattern);
+            advance1(rest, segment, rests);
+        }
+        Some((first, rest)) if first.matches(segment) => rests.push(rest),
+        _ => {}
+    }
+}
+
+/// What is left of `patterns` to match below the element `index` of a list. Patterns that
+/// expect a key look through the list.
+fn advance_into_list<'p>(
+    patterns: &[&'p [PatternSegment]],
+    index: usize,
+) -> Vec<&'p [PatternSegment]> {
+    let mut rests = advance(patterns, &Segment::Index(index));
+    rests.extend(
+        patterns
+            .iter()
+            .filter(|p| matches!(p.first(), Some(PatternSegment::Key(_)))),
+    );
+    rests
+}
+
+fn pick0(value: &Value, patterns: &[&[PatternSegment]]) -> Value {
+    match value {
+        Value::Obj(obj) => {
+            let mut picked = MapT::new();
+            for (key, v) in obj.iter() {
+                let rests = advance(patterns, &Segment::Key(key.to_string()));
+                if rests.iter().any(|p| is_done(p)) {
+                    picked.insert(key.clone(), v.clone());
+                } else if !rests.is_empty() && is_container(v) {
+                    picked.insert(key.clone(), pick0(v, &rests));
+                }
+            }
+            Value::Obj(picked)
+        }
+        Value::List(xs) => Value::List(
+            xs.iter()
+                .enumerate()
+                .filter_map(|(i, x)| {
+                    let rests = advance_into_list(patterns, i);
+                    if rests.iter().any(|p| is_done(p)) {
+                        Some(x.clone())
+                    } else if rests.is_empty() {
+                        None
+                    } else {
+                        Some(pick0(x, &rests))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Tagged { tag, value } => Value::tagged(tag.clone(), pick0(value, patterns)),
+        scalar => scalar.clone(),
+    }
+}
+
+fn omit0(value: &Value, patterns: &[&[PatternSegment]]) -> Value {
+    match value {
+        Value::Obj(obj) => {
+            let mut kept = MapT::new();
+            for (key, v) in obj.iter() {
+                let rests = advance(patterns, &Segment::Key(key.to_string()));
+                if !rests.iter().any(|p| is_done(p)) {
+                    kept.insert(key.clone(), omit0(v, &rests));
+                }
+            }
+            Value::Obj(kept)
+        }
+        Value::List(xs) => Value::List(
+            xs.iter()
+                .enumerate()
+                .filter_map(|(i, x)| {
+                    let rests = advance_into_list(patterns, i);
+                    (!rests.iter().any(|p| is_done(p))).then(|| omit0(x, &rests))
+                })
+                .collect(),
+        ),
+        Value::Tagged { tag, value } => Value::tagged(tag.clone(), omit0(value, patterns)),
+        scalar => scalar.clone(),
+    }
+}
+
+fn is_container(value: &Value) -> bool {
+    matches!(untagged(value), Value::Obj(_) | Value::List(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn patterns(srcs: &[&str]) -> Vec<Pattern> {
+        srcs.iter().map(|src| src.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("de*g", "debug"));
+        assert!(glob_match("?ebu?", "debug"));
+        assert!(glob_match("*_*_id", "a_b_c_id"));
+        assert!(!glob_match("de*g", "debugger"));
+        assert!(!glob_match("?", "ab"));
+    }
+
+    #[test]
+    fn parses_patterns() {
+        use PatternSegment::*;
+        assert_eq!(
+            "**.a[*][2].\"b.\\\"c\"".parse(),
+            Ok(Pattern(vec![
+                AnyDepth,
+                Key("a".into()),
+                AnyIndex,
+                Index(2),
+                Key("b.\"c".into()),
+            ]))
+        );
+        assert_eq!("[0]".parse(), Ok(Pattern(vec![Index(0)])));
+        assert_eq!(
+            "a[1:3][:2][5:]".parse(),
+            Ok(Pattern(vec![
+                Key("a".into()),
+                Slice {
+                    start: 1,
+                    end: Some(3)
+                },
+                Slice {
+                    start: 0,
+                    end: Some(2)
+                },
+                Slice {
+                    start: 5,
+                    end: None
+                },
+            ]))
+        );
+        assert_eq!("".parse(), Ok(Pattern(vec![])));
+        for (src, at) in [
+            ("a.", 2),
+            ("a..b", 2),
+            ("a.[0]", 2),
+            ("a[x]", 2),
+            ("a[1:x]", 2),
+            ("\"a\"b", 3),
+        ] {
+            assert_eq!(src.parse::<Pattern>().unwrap_err().at, at, "{src}");
+        }
+        for src in [
+            "**.a[*][2].\"b.\\\"c\"",
+            "int_*.x[1:3][:2][5:]",
+            "\"**\".\"\"",
+        ] {
+            let pattern: Pattern = src.parse().unwrap();
+            assert_eq!(pattern.to_string(), src);
+        }
+    }
+
+    #[test]
+    fn selects_with_concrete_paths() {
+        let value = parse_str("{id: 1, xs: [{id: 2}, {y: {id: 3}}], \"a.b\": {id: 4}}").unwrap();
+        let found: Vec<_> = select(&value, &"**.id".parse().unwrap())
+            .map(|(path, v)| (path.to_string(), v.min_spell()))
+            .collect();
+        let mut expected = [
+            ("id".to_string(), "1".to_string()),
+            ("\"a.b\".id".into(), "4".into()),
+            ("xs[0].id".into(), "2".into()),
+            ("xs[1].y.id".into(), "3".into()),
+        ];
+        // keys come in source order with `preserve_order`, sorted without
+        if cfg!(feature = "preserve_order") {
+            expected[1..].rotate_left(1);
+        }
+        assert_eq!(found, expected);
+        let (path, _) = select(&value, &"xs[1].y".parse().unwrap()).next().unwrap();
+        assert_eq!(select(&value, &Pattern::from(&path)).count(), 1);
+        assert_eq!(
+            path.to_string().parse::<Pattern>(),
+            Ok(Pattern::from(&path))
+        );
+        assert_eq!(path.to_string().parse(), Ok(path));
+        assert_eq!("xs[*].id".parse::<Path>().unwrap_err().at, 2);
+        assert_eq!("xs[0:1]".parse::<Path>().unwrap_err().at, 4);
+    }
+
+    #[test]
+    fn selects_slices() {
+        let value = parse_str("{xs: [0, 1, 2, 3, 4]}").unwrap();
+        let selected = |pattern: &str| {
+            select(&value, &pattern.parse().unwrap())
+                .map(|(_, v)| v.min_spell())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(selected("xs[1:3]"), ["1", "2"]);
+        assert_eq!(selected("xs[:2]"), ["0", "1"]);
+        assert_eq!(selected("xs[3:]"), ["3", "4"]);
+        assert_eq!(selected("xs[4:100]"), ["4"]);
+        assert!(selected("xs[3:1]").is_empty());
+        assert_eq!(
+            omit(&value, &["xs[1:]".parse().unwrap()]),
+            parse_str("{xs: [0]}").unwrap()
+        );
+    }
+
+    #[test]
+    fn builds_and_sets_paths() {
+        let path = Path::default().key("a b").index(2).key("c");
+        assert_eq!(path.to_string(), "\"a b\"[2].c");
+        assert_eq!(path.parent(), Some(Path::default().key("a b").index(2)));
+        assert_eq!(path.last(), Some(&Segment::Key("c".into())));
+        assert!(Path::default().is_root());
+        let mut value = parse_str("{x: [1], y: 2}").unwrap();
+        assert_eq!(
+            set(&mut value, &"x[3]".parse().unwrap(), Value::None),
+            Err(SetError {
+                path: "x[3]".parse().unwrap(),
+                reason: "a list on the way has 1 elements".into(),
+            })
+        );
+        assert_eq!(
+            set(&mut value, &"z.w".parse().unwrap(), Value::None),
+            Ok(None)
+        );
+        assert_eq!(value, parse_str("{x: [1], y: 2, z: {w: None}}").unwrap());
+    }
+
+    #[test]
+    fn pick_and_omit_through_lists() {
+        let value =
+            parse_str("{enemies: [{name: 1, stats: {hp: 2, mp: 3}}, 4], internal_x: 5, v: 6}")
+                .unwrap();
+        assert_eq!(
+            pick(&value, &patterns(&["enemies.stats.h*", "v"])),
+            parse_str("{enemies: [{stats: {hp: 2}}, 4], v: 6}").unwrap()
+        );
+        assert_eq!(
+            omit(&value, &patterns(&["enemies.name", "internal_*"])),
+            parse_str("{enemies: [{stats: {hp: 2, mp: 3}}, 4], v: 6}").unwrap()
+        );
+        assert_eq!(
+            pick(&value, &patterns(&["enemies[0].**.hp"])),
+            parse_str("{enemies: [{stats: {hp: 2}}]}").unwrap()
+        );
+        assert_eq!(
+            omit(&value, &patterns(&["**.mp", "enemies[1]"])),
+            parse_str("{enemies: [{name: 1, stats: {hp: 2}}], internal_x: 5, v: 6}").unwrap()
+        );
+    }
+}
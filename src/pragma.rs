@@ -0,0 +1,210 @@
+//! Declaring which version of the gon format a file is written in.
+//!
+//! A file may start with a `#gon <version>` line. The parser skips it like a comment, and
+//! tooling keeps it: [`tokenize`](crate::parser::tokenize) makes it a comment token and
+//! [`Document::format`](crate::document::Document::format) spells it first. Documents that
+//! are objects may declare their version with a `"@version"` entry instead, holding a
+//! number or a str of one, which the parser keeps like any other entry. If both are there,
+//! they have to agree.
+//!
+//! [`check`] is what validators call: it fails for versions newer than
+//! [`CURRENT_VERSION`], which this crate can't know the rules of. [`upgrade`] rewrites a
+//! source to declare [`CURRENT_VERSION`].
+//! # Usage example
+//! ```rust
+//! use gon::pragma::{check, pragma, upgrade, CURRENT_VERSION};
+//! let src = "#gon 1\n{name: \"slime\"}";
+//! assert_eq!(pragma(src).unwrap().version, 1);
+//! assert_eq!(check(src, &gon::parse_str(src).unwrap()), Ok(Some(1)));
+//! assert!(check("#gon 99\n{}", &gon::parse_str("{}").unwrap()).is_err());
+//! assert_eq!(upgrade("{a: 1}").unwrap(), format!("#gon {CURRENT_VERSION}\n{{a: 1}}"));
+//! ```
+
+use std::borrow::Cow;
+
+use thiserror::Error;
+
+use crate::document::{Document, DocumentError};
+use crate::path::untagged;
+use crate::span::Span;
+use crate::{KeyT, Value};
+
+/// The newest version of the format this crate knows
+pub const CURRENT_VERSION: u32 = 1;
+
+/// The key of the entry that declares the version of an object document
+pub const VERSION_KEY: &str = "@version";
+
+/// A `#gon <version>` line at the start of a source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Pragma {
+    pub version: u32,
+    /// The bytes of the line, without the line break
+    pub span: Span,
+}
+
+/// Something is wrong with the version a document declares
+#[derive(Debug, Error, PartialEq)]
+pub enum VersionError {
+    #[error("invalid {VERSION_KEY} {0}, expected a whole number")]
+    Invalid(String),
+    #[error("the pragma declares version {pragma}, but {VERSION_KEY} declares {key}")]
+    Conflict { pragma: u32, key: u32 },
+    #[error("version {0} is newer than {CURRENT_VERSION}, the newest this gon knows")]
+    Unsupported(u32),
+    #[error(transparent)]
+    Document(#[from] DocumentError),
+}
+
+/// The pragma `src` starts with, if it starts with one
+pub fn pragma(src: &str) -> Option<Pragma> {
+    let line = src.split('\n').next()?.trim_end();
+    let version = line.strip_prefix("#gon")?;
+    if !version.starts_with([' ', '\t']) {
+        return None;
+    }
+    let version = version.trim_start();
+    if !version.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some(Pragma {
+        version: version.parse().ok()?,
+        span: Span::from(0..line.len()),
+    })
+}
+
+/// `src` with its pragma replaced by spaces, so lexers that don't know pragmas see the
+/// rest of the source at the same offsets
+pub(crate) fn blanked(src: &str) -> Cow<'_, str> {
+    match pragma(src) {
+        Some(p) => {
+            let mut blanked = src.to_string();
+            blanked.replace_range(p.span.range(), &" ".repeat(p.span.range().len()));
+            Cow::Owned(blanked)
+        }
+        None => Cow::Borrowed(src),
+    }
+}
+
+/// The version declared by the pragma of `src` or the `@version` entry of `value`, its
+/// parsed value
+pub fn declared_version(src: &str, value: &Value) -> Result<Option<u32>, VersionError> {
+    let key = match untagged(value) {
+        Value::Obj(obj) => obj.get(VERSION_KEY).map(key_version).transpose()?,
+        _ => None,
+    };
+    match (pragma(src).map(|p| p.version), key) {
+        (Some(pragma), Some(key)) if pragma != key => Err(VersionError::Conflict { pragma, key }),
+        (pragma, key) => Ok(pragma.or(key)),
+    }
+}
+
+/// Like [`declared_version`], but also fails for versions newer than [`CURRENT_VERSION`]
+pub fn check(src: &str, value: &Value) -> Result<Option<u32>, VersionError> {
+    match declared_version(src, value)? {
+        Some(version) if version > CURRENT_VERSION => Err(VersionError::Unsupported(version)),
+        version => Ok(version),
+    }
+}
+
+/// Rewrites `src` to declare [`CURRENT_VERSION`]: the pragma is replaced or added and a
+/// `@version` entry is updated in place, keeping its str or number spelling
+pub fn upgrade(src: &str) -> Result<String, VersionError> {
+    let mut doc = Document::parse(src)?;
+    check(src, doc.value())?;
+    let updated = match doc.value() {
+        Value::Obj(obj) if obj.contains_key(VERSION_KEY) => {
+            let version = match &obj[VERSION_KEY] {
+                Value::Str { raw, .. } => Value::Str {
+                    s: CURRENT_VERSION.to_string(),
+                    raw: *raw,
+                },
+                _ => Value::Num(CURRENT_VERSION.to_string()),
+            };
+            let mut obj = obj.clone();
+            obj.insert(KeyT::from(VERSION_KEY), version);
+            Some(Value::Obj(obj))
+        }
+        _ => None,
+    };
+    if let Some(updated) = updated {
+        doc.update(&updated)?;
+    }
+    let header = format!("#gon {CURRENT_VERSION}");
+    let src = doc.as_str();
+    Ok(match pragma(src) {
+        Some(p) => format!("{header}{}", &src[p.span.range().end..]),
+        None => format!("{header}\n{src}"),
+    })
+}
+
+fn key_version(value: &Value) -> Result<u32, VersionError> {
+    match value {
+        Value::Num(n) | Value::Str { s: n, .. } => n
+            .parse()
+            .map_err(|_| VersionError::Invalid(value.min_spell())),
+        other => Err(VersionError::Invalid(other.min_spell())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn pragmas_and_version_keys() {
+        assert_eq!(
+            pragma("#gon 2\r\n{}").map(|p| (p.version, p.span)),
+            Some((2, Span::from(0..6)))
+        );
+        assert_eq!(pragma("#gon\n{}"), None);
+        assert_eq!(pragma("#gonx 1\n{}"), None);
+        assert_eq!(pragma(" #gon 1\n{}"), None);
+
+        let src = "#gon 1\n{\"@version\": \"1\", a: [1, 2]}";
+        let value = parse_str(src).unwrap();
+        assert_eq!(
+            value,
+            parse_str("{\"@version\": \"1\", a: [1, 2]}").unwrap()
+        );
+        assert_eq!(check(src, &value), Ok(Some(1)));
+        assert_eq!(check("{}", &parse_str("{}").unwrap()), Ok(None));
+
+        let value = parse_str("{\"@version\": 3}").unwrap();
+        assert_eq!(
+            declared_version("#gon 1\n{\"@version\": 3}", &value),
+            Err(VersionError::Conflict { pragma: 1, key: 3 })
+        );
+        assert_eq!(check("", &value), Err(VersionError::Unsupported(3)));
+        let value = parse_str("{\"@version\": \"one\"}").unwrap();
+        assert_eq!(
+            check("", &value),
+            Err(VersionError::Invalid("\"one\"".into()))
+        );
+    }
+
+    #[test]
+    fn documents_keep_the_pragma() {
+        let doc = Document::parse("#gon 1\n{a: [1,2]}").unwrap();
+        let span = doc.span(&"a[1]".parse().unwrap()).unwrap();
+        assert_eq!(&doc.as_str()[span.range()], "2");
+        assert_eq!(
+            doc.format(crate::SpellConfig::default()).unwrap(),
+            "#gon 1\n{\n    a: [1, 2]\n}"
+        );
+    }
+
+    #[test]
+    fn upgrades() {
+        assert_eq!(upgrade("#gon 0\n[1, 2]").unwrap(), "#gon 1\n[1, 2]");
+        assert_eq!(
+            upgrade("{\n    \"@version\": \"0\", // old\n    a: 1,\n}").unwrap(),
+            "#gon 1\n{\n    \"@version\": \"1\", // old\n    a: 1,\n}"
+        );
+        assert_eq!(
+            upgrade("#gon 7\n{}").unwrap_err(),
+            VersionError::Unsupported(7)
+        );
+    }
+}
@@ -0,0 +1,556 @@
+//! Queries that pick values out of gon and compute stats over them, like
+//! `enemies[*] | group_by(zone) | map(count)`.
+//!
+//! A query is a pipeline of steps separated by `|`, every step gets what the one before it
+//! produced:
+//! - A pattern from [`path`](crate::path) like `enemies[*].hp` selects from the value.
+//!   Patterns without wildcards or slices select a single value, `None` if there is
+//!   nothing, other patterns a list of everything they match. A leading `.` and `[]` for
+//!   `[*]` are accepted like in jq, so `.enemies[]` works too and `.` is the value itself.
+//! - `count` is the number of elements of a list or entries of an object.
+//! - `sum`, `min`, `max` and `avg` compute over the numbers of a list, or the values of an
+//!   object. `min`, `max` and `avg` of nothing are `None`.
+//! - `group_by(<pattern>)` turns a list into an object of lists, keyed by what the pattern
+//!   selects from every element. Strings are keys as they are, other values as they're
+//!   spelled minimally.
+//! - `map(<query>)` runs the query on every element of a list or value of an object.
+//! - `$name` is the document called `name` that the query is [run with](Query::run_with).
+//! - `lookup($name, <pattern>)` is the entry of the document `name` at the key that the
+//!   pattern selects from the value, keyed like `group_by` does. Lists are looked up by
+//!   index. This joins documents that refer to each other by ids.
+//!
+//! A key with the name of a function, like `count`, is selected with `.count`.
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, query::Query, Value};
+//! let value = parse_str(r#"{enemies: [
+//!     {name: "Goblin", zone: "forest", hp: 10},
+//!     {name: "Wolf", zone: "forest", hp: 14},
+//!     {name: "Golem", zone: "cave", hp: 90},
+//! ]}"#).unwrap();
+//! let query: Query = ".enemies[] | group_by(.zone) | map(count)".parse().unwrap();
+//! assert_eq!(query.run(&value).unwrap(), parse_str("{cave: 1, forest: 2}").unwrap());
+//! let query: Query = "enemies[*].hp | avg".parse().unwrap();
+//! assert_eq!(query.run(&value).unwrap(), Value::Num("38".into()));
+//!
+//! let docs = parse_str(r#"{defs: {sword: {damage: 3}, bow: {damage: 2}}}"#).unwrap();
+//! let inventory = parse_str(r#"[{item_id: "sword"}, {item_id: "bow"}]"#).unwrap();
+//! let query: Query = "map(lookup($defs, .item_id) | damage) | sum".parse().unwrap();
+//! assert_eq!(query.run_with(&inventory, &docs).unwrap(), Value::Num("5".into()));
+//! ```
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::path::{PathError, Pattern, get, select, untagged};
+use crate::value::{normalize_number, spelling_order};
+use crate::{MapT, Value};
+
+/// One step of a [`Query`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Step {
+    Select(Pattern),
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+    GroupBy(Pattern),
+    Map(Query),
+    Var(String),
+    Lookup(String, Pattern),
+}
+
+/// A pipeline of [`Step`]s, see the [module docs](self)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Query(pub Vec<Step>);
+
+/// A [`Query`] couldn't be parsed or run
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum QueryError {
+    /// The query isn't spelled right
+    #[error("invalid query at {at}: {message}")]
+    Syntax { at: usize, message: &'static str },
+    /// A pattern in the query couldn't be parsed
+    #[error("{0}")]
+    Path(#[from] PathError),
+    /// A step got a value it can't work with
+    #[error("{step} expects {expected}, found {found}")]
+    Unexpected {
+        step: &'static str,
+        expected: &'static str,
+        found: &'static str,
+    },
+    /// The result of a step is too big for a number
+    #[error("the {0} is too big")]
+    Overflow(&'static str),
+    /// The query uses a document it wasn't run with
+    #[error("unknown document ${0}")]
+    UnknownVar(String),
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut parser = QueryParser { src, pos: 0 };
+        let query = parser.query()?;
+        if parser.pos < src.len() {
+            return Err(parser.syntax("unexpected )"));
+        }
+        Ok(query)
+    }
+}
+
+struct QueryParser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl QueryParser<'_> {
+    fn query(&mut self) -> Result<Query, QueryError> {
+        let mut steps = vec![self.step()?];
+        while self.eat('|') {
+            steps.push(self.step()?);
+        }
+        Ok(Query(steps))
+    }
+
+    fn step(&mut self) -> Result<Step, QueryError> {
+        self.skip_whitespace();
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        let after = rest[len..].trim_start();
+        let ends = after.is_empty() || after.starts_with(['|', ')']);
+        if rest.starts_with('$') {
+            return Ok(Step::Var(self.var()?));
+        }
+        let step = match &rest[..len] {
+            "count" if ends => Step::Count,
+            "sum" if ends => Step::Sum,
+            "min" if ends => Step::Min,
+            "max" if ends => Step::Max,
+            "avg" if ends => Step::Avg,
+            word @ ("group_by" | "map") if after.starts_with('(') => {
+                self.pos += len;
+                self.eat('(');
+                let step = if word == "map" {
+                    Step::Map(self.query()?)
+                } else {
+                    Step::GroupBy(self.pattern()?)
+                };
+                if !self.eat(')') {
+                    return Err(self.syntax("expected )"));
+                }
+                return Ok(step);
+            }
+            "lookup" if after.starts_with('(') => {
+                self.pos += len;
+                self.eat('(');
+                let var = self.var()?;
+                if !self.eat(',') {
+                    return Err(self.syntax("expected ,"));
+                }
+                let step = Step::Lookup(var, self.pattern()?);
+                if !self.eat(')') {
+                    return Err(self.syntax("expected )"));
+                }
+                return Ok(step);
+            }
+            _ => return Ok(Step::Select(self.pattern()?)),
+        };
+        self.pos += len;
+        Ok(step)
+    }
+
+    /// A pattern up to the next `|` or `)` that isn't quoted
+    fn pattern(&mut self) -> Result<Pattern, QueryError> {
+        let start = self.pos;
+        let mut pattern = String::new();
+        let mut in_quotes = false;
+        let mut chars = self.src[start..].char_indices().peekable();
+        self.pos = self.src.len();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '|' | ')' if !in_quotes => {
+                    self.pos = start + i;
+                    break;
+                }
+                '\\' if in_quotes => {
+                    pattern.push(c);
+                    if let Some((_, escaped)) = chars.next() {
+                        pattern.push(escaped);
+                    }
+                }
+                '"' => {
+                    in_quotes = !in_quotes;
+                    pattern.push(c);
+                }
+                '[' if !in_quotes && chars.peek().is_some_and(|(_, c)| *c == ']') => {
+                    chars.next();
+                    pattern.push_str("[*]");
+                }
+                _ => pattern.push(c),
+            }
+        }
+        let pattern = pattern.trim();
+        match pattern.strip_prefix('.') {
+            Some(pattern) => Ok(pattern.parse()?),
+            None if pattern.is_empty() => Err(QueryError::Syntax {
+                at: start,
+                message: "expected a step",
+            }),
+            None => Ok(pattern.parse()?),
+        }
+    }
+
+    /// A `$name`, without the `$`
+    fn var(&mut self) -> Result<String, QueryError> {
+        if !self.eat('$') {
+            return Err(self.syntax("expected a $document"));
+        }
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.syntax("expected the name of a document"));
+        }
+        self.pos += len;
+        Ok(rest[..len].to_string())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skips whitespace and then `c` if it's next
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let found = self.src[self.pos..].starts_with(c);
+        if found {
+            self.pos += c.len_utf8();
+        }
+        found
+    }
+
+    fn syntax(&self, message: &'static str) -> QueryError {
+        QueryError::Syntax {
+            at: self.pos,
+            message,
+        }
+    }
+}
+
+impl Query {
+    /// Runs the steps on `value` one after the other
+    pub fn run(&self, value: &Value) -> Result<Value, QueryError> {
+        self.run_with(value, &Value::None)
+    }
+
+    /// Like [`run`](Self::run), `docs` is an object of the documents that `$name` and
+    /// `lookup` refer to by their keys
+    pub fn run_with(&self, value: &Value, docs: &Value) -> Result<Value, QueryError> {
+        let mut current = Cow::Borrowed(value);
+        for step in &self.0 {
+            current = Cow::Owned(step.run(&current, docs)?);
+        }
+        Ok(current.into_owned())
+    }
+}
+
+impl Step {
+    /// How the step is called in queries
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Select(_) => "select",
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Min => "min",
+            Self::Max => "max",
+            Self::Avg => "avg",
+            Self::GroupBy(_) => "group_by",
+            Self::Map(_) => "map",
+            Self::Var(_) => "$",
+            Self::Lookup(..) => "lookup",
+        }
+    }
+
+    fn run(&self, value: &Value, docs: &Value) -> Result<Value, QueryError> {
+        Ok(match self {
+            Self::Select(pattern) => selected(value, pattern),
+            Self::Count => Value::Num(self.elements(value)?.len().to_string()),
+            Self::Sum => {
+                let numbers = self.numbers(value)?;
+                let ints: Option<Vec<i128>> = numbers
+                    .iter()
+                    .map(|(_, n)| match n {
+                        Value::Num(s) => normalize_number(s).parse().ok(),
+                        _ => None,
+                    })
+                    .collect();
+                match ints {
+                    Some(ints) => Value::Num(
+                        ints.into_iter()
+                            .try_fold(0i128, i128::checked_add)
+                            .ok_or(QueryError::Overflow("sum"))?
+                            .to_string(),
+                    ),
+                    None => num(numbers.iter().map(|(f, _)| f).sum(), "sum")?,
+                }
+            }
+            Self::Min | Self::Max => {
+                let numbers = self.numbers(value)?;
+                let extreme = if matches!(self, Self::Min) {
+                    numbers.into_iter().min_by(|(a, _), (b, _)| a.total_cmp(b))
+                } else {
+                    numbers.into_iter().max_by(|(a, _), (b, _)| a.total_cmp(b))
+                };
+                extreme.map_or(Value::None, |(_, n)| n.clone())
+            }
+            Self::Avg => {
+                let numbers = self.numbers(value)?;
+                if numbers.is_empty() {
+                    return Ok(Value::None);
+                }
+                let sum: f64 = numbers.iter().map(|(f, _)| f).sum();
+                num(sum / numbers.len() as f64, "average")?
+            }
+            Self::GroupBy(pattern) => {
+                let Value::List(xs) = untagged(value) else {
+                    return Err(self.unexpected("a list", value));
+                };
+                let mut groups = MapT::new();
+                for x in xs {
+                    let key = key(selected(x, pattern));
+                    let group = groups
+                        .entry(crate::KeyT::from(key))
+                        .or_insert_with(|| Value::List(Vec::new()));
+                    if let Value::List(group) = group {
+                        group.push(x.clone());
+                    }
+                }
+                Value::Obj(groups)
+            }
+            Self::Map(query) => match untagged(value) {
+                Value::List(xs) => Value::List(
+                    xs.iter()
+                        .map(|x| query.run_with(x, docs))
+                        .collect::<Result<_, _>>()?,
+                ),
+                Value::Obj(obj) => Value::Obj(
+                    spelling_order(obj)
+                        .map(|(k, v)| Ok((k.clone(), query.run_with(v, docs)?)))
+                        .collect::<Result<_, QueryError>>()?,
+                ),
+                _ => return Err(self.unexpected("a list or an obj", value)),
+            },
+            Self::Var(name) => doc(docs, name)?.clone(),
+            Self::Lookup(name, pattern) => {
+                let key = key(selected(value, pattern));
+                let found = match untagged(doc(docs, name)?) {
+                    Value::Obj(obj) => obj.get(key.as_str()),
+                    Value::List(xs) => key.parse().ok().and_then(|i: usize| xs.get(i)),
+                    other => return Err(self.unexpected("a list or an obj", other)),
+                };
+                found.cloned().unwrap_or(Value::None)
+            }
+        })
+    }
+
+    /// The elements of a list or the values of an object
+    fn elements<'a>(&self, value: &'a Value) -> Result<Vec<&'a Value>, QueryError> {
+        match untagged(value) {
+            Value::List(xs) => Ok(xs.iter().collect()),
+            Value::Obj(obj) => Ok(spelling_order(obj).map(|(_, v)| v).collect()),
+            _ => Err(self.unexpected("a list or an obj", value)),
+        }
+    }
+
+    /// The [`elements`](Self::elements), which all have to be numbers, as `f64`s and as
+    /// they are
+    fn numbers<'a>(&self, value: &'a Value) -> Result<Vec<(f64, &'a Value)>, QueryError> {
+        self.elements(value)?
+            .into_iter()
+            .map(|x| match untagged(x) {
+                n @ Value::Num(s) => normalize_number(s)
+                    .parse()
+                    .map(|f| (f, n))
+                    .map_err(|_| self.unexpected("numbers", x)),
+                _ => Err(self.unexpected("numbers", x)),
+            })
+            .collect()
+    }
+
+    fn unexpected(&self, expected: &'static str, found: &Value) -> QueryError {
+        QueryError::Unexpected {
+            step: self.name(),
+            expected,
+            found: found.type_name(),
+        }
+    }
+}
+
+/// The document called `name` in `docs`
+fn doc<'a>(docs: &'a Value, name: &str) -> Result<&'a Value, QueryError> {
+    docs.as_obj()
+        .and_then(|docs| docs.get(name))
+        .ok_or_else(|| QueryError::UnknownVar(name.to_string()))
+}
+
+/// `value` as the key of an object: strings as they are, other values spelled minimally
+fn key(value: Value) -> String {
+    match value {
+        Value::Str { s, .. } => s,
+        other => other.min_spell(),
+    }
+}
+
+/// What `pattern` selects from `value`, see the [module docs](self)
+fn selected(value: &Value, pattern: &Pattern) -> Value {
+    match pattern.concrete() {
+        Some(path) => get(value, &path).cloned().unwrap_or(Value::None),
+        None => Value::List(select(value, pattern).map(|(_, v)| v.clone()).collect()),
+    }
+}
+
+fn num(f: f64, what: &'static str) -> Result<Value, QueryError> {
+    if f.is_finite() {
+        Ok(Value::Num(f.to_string()))
+    } else {
+        Err(QueryError::Overflow(what))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn run(query: &str, src: &str) -> Result<Value, QueryError> {
+        query.parse::<Query>()?.run(&parse_str(src).unwrap())
+    }
+
+    #[test]
+    fn parses_pipelines() {
+        use Step::*;
+        assert_eq!(
+            ".xs[] | group_by(\"a|b\") | map(count | sum) | min".parse(),
+            Ok(Query(vec![
+                Select("xs[*]".parse().unwrap()),
+                GroupBy("\"a|b\"".parse().unwrap()),
+                Map(Query(vec![Count, Sum])),
+                Min,
+            ]))
+        );
+        assert_eq!(
+            ". | .count | count.x".parse(),
+            Ok(Query(vec![
+                Select(Pattern::default()),
+                Select("count".parse().unwrap()),
+                Select("count.x".parse().unwrap()),
+            ]))
+        );
+        for (src, at) in [("a |", 3), ("map(count", 9), ("a | b)", 5)] {
+            assert!(
+                matches!(src.parse::<Query>(), Err(QueryError::Syntax { at: a, .. }) if a == at),
+                "{src}"
+            );
+        }
+        assert!(matches!("a..b".parse::<Query>(), Err(QueryError::Path(_))));
+        assert_eq!(
+            "$defs | lookup($ids, \"a b\")".parse(),
+            Ok(Query(vec![
+                Var("defs".into()),
+                Lookup("ids".into(), "\"a b\"".parse().unwrap()),
+            ]))
+        );
+        for (src, at) in [
+            ("lookup(defs, a)", 7),
+            ("lookup($, a)", 8),
+            ("lookup($d a)", 10),
+        ] {
+            assert!(
+                matches!(src.parse::<Query>(), Err(QueryError::Syntax { at: a, .. }) if a == at),
+                "{src}"
+            );
+        }
+    }
+
+    #[test]
+    fn looks_up_other_documents() {
+        let docs = parse_str(
+            "{defs: {sword: {damage: 3}, \"1\": {damage: 1}}, ids: [\"sword\", \"bow\"]}",
+        )
+        .unwrap();
+        let run = |query: &str, src: &str| {
+            query
+                .parse::<Query>()
+                .unwrap()
+                .run_with(&parse_str(src).unwrap(), &docs)
+        };
+        assert_eq!(
+            run(
+                "map(lookup($defs, item) | damage)",
+                "[{item: \"sword\"}, {item: 1}, {item: \"axe\"}]"
+            ),
+            Ok(parse_str("[3, 1, None]").unwrap())
+        );
+        assert_eq!(
+            run("lookup($ids, .)", "1"),
+            Ok(parse_str("\"bow\"").unwrap())
+        );
+        assert_eq!(run("$ids | count", "None"), Ok(Value::Num("2".into())));
+        assert_eq!(
+            run("$other", "None"),
+            Err(QueryError::UnknownVar("other".into()))
+        );
+        assert_eq!(
+            "$defs".parse::<Query>().unwrap().run(&Value::None),
+            Err(QueryError::UnknownVar("defs".into()))
+        );
+    }
+
+    #[test]
+    fn aggregates() {
+        let src = "{xs: [3, 1_000, -2], fs: [0.5, 2], empty: [], zones: [
+            {zone: \"a\", hp: 1}, {zone: 2, hp: 3}, {zone: \"a\", hp: 5}, {hp: 7},
+        ]}";
+        let num = |n: &str| Ok(Value::Num(n.into()));
+        assert_eq!(run("xs | count", src), num("3"));
+        assert_eq!(run("xs | sum", src), num("1001"));
+        assert_eq!(run("fs | sum", src), num("2.5"));
+        assert_eq!(run("xs | min", src), num("-2"));
+        assert_eq!(run("xs | max", src), num("1_000"));
+        assert_eq!(run("fs | avg", src), num("1.25"));
+        assert_eq!(run("empty | max", src), Ok(Value::None));
+        assert_eq!(run("empty | avg", src), Ok(Value::None));
+        assert_eq!(run("empty | sum", src), num("0"));
+        assert_eq!(
+            run("zones | group_by(zone) | map(.[*].hp | sum)", src),
+            Ok(parse_str("{a: 6, \"2\": 3, \"None\": 7}").unwrap())
+        );
+        assert_eq!(run("missing", src), Ok(Value::None));
+        assert_eq!(
+            run("zones[1:] | map(hp)", src),
+            Ok(parse_str("[3, 5, 7]").unwrap())
+        );
+        assert_eq!(
+            run("zones | sum", src),
+            Err(QueryError::Unexpected {
+                step: "sum",
+                expected: "numbers",
+                found: "obj",
+            })
+        );
+        assert_eq!(
+            run("xs[0] | count", src).unwrap_err().to_string(),
+            "count expects a list or an obj, found num"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! An interactive session for trying out gon, which `gon repl` starts.
+//!
+//! A [`Repl`] reads values from the terminal with line editing and history, which may span
+//! several lines until their brackets are balanced, and prints them spelled. Commands like
+//! `:json` or `:load <name> <file>` start with a colon, `:help` lists them. Files loaded into
+//! variables can be used inside of the values that are typed, for querying them.
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, repl::Repl, SpellConfig};
+//! let mut repl = Repl::new(SpellConfig::default());
+//! repl.set_var("enemies", parse_str("[{hp: 3}, {hp: 5}]").unwrap()).unwrap();
+//! repl.command(":min").unwrap();
+//! assert_eq!(repl.eval("[enemies, 9]").unwrap(), "[[{hp:3},{hp:5}],9]");
+//! ```
+
+use std::path::PathBuf;
+
+use rustyline::{DefaultEditor, error::ReadlineError};
+use thiserror::Error;
+
+use crate::parser::{Completeness, is_complete};
+use crate::{GonError, SpellConfig, Value, parse_str};
+
+const HELP: &str = "\
+Type a value to see it spelled, values may span multiple lines.
+Variables can be used inside of values.
+:fmt                print values formatted (the default)
+:min                print values minified
+:json               print values as json
+:preview            shorten big values when printing them, again to stop
+:load <name> <file> parse a file into the variable <name>
+:vars               list all variables
+:help               show this help
+:quit               leave (or press Ctrl-D)";
+
+/// Something went wrong in a [`Repl`]
+#[derive(Debug, Error)]
+pub enum ReplError {
+    /// A typed value or a loaded file isn't valid gon
+    #[error(transparent)]
+    Parse(#[from] GonError),
+    /// A file couldn't be loaded
+    #[error("couldn't read '{0}': {1}")]
+    Read(String, std::io::Error),
+    /// A variable name isn't a symbol, or is a keyword
+    #[error("invalid variable name '{0}'")]
+    InvalidName(String),
+    /// A line started with `:` but isn't a command
+    #[error("unknown command '{0}', try :help")]
+    UnknownCommand(String),
+    /// A value is nested too deeply or too big to be spelled
+    #[error("the value can't be spelled")]
+    Spell(#[from] std::fmt::Error),
+    /// A value can't be printed as json
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// The terminal couldn't be read
+    #[error(transparent)]
+    Readline(#[from] ReadlineError),
+}
+
+/// How the repl prints values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Output {
+    Fmt,
+    Min,
+    Json,
+}
+
+/// An interactive session, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Repl {
+    spell_config: SpellConfig,
+    output: Output,
+    preview: bool,
+    vars: Vec<(String, Value)>,
+}
+
+impl Repl {
+    /// A session that prints formatted values spelled with `spell_config`
+    pub fn new(spell_config: SpellConfig) -> Self {
+        Self {
+            spell_config,
+            output: Output::Fmt,
+            preview: false,
+            vars: Vec::new(),
+        }
+    }
+
+    /// Reads and evaluates values and commands from the terminal until `:quit` or Ctrl-D.
+    /// The history is kept in `~/.gon_history`.
+    pub fn run(mut self) -> Result<(), ReplError> {
+        let mut editor = DefaultEditor::new()?;
+        let history = std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".gon_history"));
+        if let Some(history) = &history {
+            let _ = editor.load_history(history);
+        }
+        let mut input = String::new();
+        loop {
+            let prompt = if input.is_empty() { "gon> " } else { "...> " };
+            let line = match editor.readline(prompt) {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => {
+                    input.clear();
+                    continue;
+                }
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+            if input.is_empty() && line.trim_start().starts_with(':') {
+                editor.add_history_entry(line.as_str())?;
+                match self.command(line.trim()) {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(e) => eprintln!("{e}"),
+                }
+                continue;
+            }
+            input.push_str(&line);
+            input.push('\n');
+            if input.trim().is_empty() {
+                input.clear();
+                continue;
+            }
+            if is_complete(&input) == Completeness::Incomplete {
+                continue;
+            }
+            editor.add_history_entry(input.trim_end())?;
+            match self.eval(&input) {
+                Ok(out) => println!("{out}"),
+                Err(e) => eprintln!("{e}"),
+            }
+            input.clear();
+        }
+        if let Some(history) = &history {
+            editor.save_history(history)?;
+        }
+        Ok(())
+    }
+
+    /// Runs a `:command`, returns whether to quit
+    pub fn command(&mut self, line: &str) -> Result<bool, ReplError> {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some(":fmt"), None, _) => self.output = Output::Fmt,
+            (Some(":min"), None, _) => self.output = Output::Min,
+            (Some(":json"), None, _) => self.output = Output::Json,
+            (Some(":preview"), None, _) => self.preview = !self.preview,
+            (Some(":load"), Some(name), Some(file)) => {
+                let src = std::fs::read_to_string(file)
+                    .map_err(|e| ReplError::Read(file.to_string(), e))?;
+                self.set_var(name, parse_str(&src)?)?;
+            }
+            (Some(":vars"), None, _) => {
+                for (name, value) in &self.vars {
+                    println!("{name}: {}", value.type_name());
+                }
+            }
+            (Some(":help"), None, _) => println!("{HELP}"),
+            (Some(":quit" | ":q"), None, _) => return Ok(true),
+            _ => return Err(ReplError::UnknownCommand(line.to_string())),
+        }
+        Ok(false)
+    }
+
+    /// Binds `value` to the variable `name` for the values evaluated after, replacing what
+    /// it was bound to
+    pub fn set_var(&mut self, name: &str, value: Value) -> Result<(), ReplError> {
+        if !is_var_name(name) {
+            return Err(ReplError::InvalidName(name.to_string()));
+        }
+        self.vars.retain(|(n, _)| n != name);
+        self.vars.push((name.to_string(), value));
+        Ok(())
+    }
+
+    /// Parses `input` with all variables bound and spells it the way the commands chose
+    pub fn eval(&self, input: &str) -> Result<String, ReplError> {
+        let mut src: String = self
+            .vars
+            .iter()
+            .map(|(name, value)| format!("let {name} = {}; ", value.min_spell()))
+            .collect();
+        src.push_str(input);
+        let mut value = parse_str(&src)?;
+        if self.preview {
+            value = value.preview();
+        }
+        Ok(match self.output {
+            Output::Fmt => value.spell(self.spell_config)?,
+            Output::Min => value.min_spell(),
+            Output::Json => serde_json::to_string_pretty(&serde_json::Value::from(value))?,
+        })
+    }
+}
+
+fn is_var_name(name: &str) -> bool {
+    let keywords = ["none", "null", "true", "false", "r", "let"];
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !keywords.contains(&name.to_lowercase().as_str())
+}
@@ -0,0 +1,1718 @@
+//! Validation of gon [`Value`]s against a schema.
+//!
+//! Schemas are written in gon themselves:
+//! - a string names a type: `"str"`, `"num"`, `"bool"`, `"none"`, `"obj"`, `"list"` or `"any"`.
+//!   A trailing `?` (`"num?"`) marks the value as optional: it may be missing or `None`.
+//!   Type names separated by `|` (`"str | num"`) match values of any of the types.
+//! - a list with a single element `[s]` matches lists whose elements all match `s`.
+//! - an object `{key: s, ...}` matches objects where every key matches its schema.
+//!   Keys that aren't mentioned in the schema are allowed.
+//! - an object with the key `"@type"` annotates the schema under `"@type"` for
+//!   documentation (see [`markdown::schema_reference`](crate::markdown::schema_reference)):
+//!   `"@doc"` describes it, `"@default"` is the value used when it's missing, `"@example"`
+//!   shows how it's used and `"@deprecated"` says what to use instead. Annotations only
+//!   have an effect on fields of objects.
+//!   Constraints under `"@type"` narrow down what matches: `"@min"` and `"@max"` bound nums,
+//!   `"@exclusive_min"` and `"@exclusive_max"` bound them without the bound itself,
+//!   `"@multiple_of"` only lets multiples through, `"@min_len"` and `"@max_len"` bound the
+//!   number of chars of strs and elements of lists, `"@pattern"` is a regex strs have to
+//!   match (with the `patterns` feature) and `"@unique": true` forbids equal elements in
+//!   lists.
+//! - an object with the key `"@tag"` is a union of object schemas told apart by a tag field:
+//!   `{"@tag": "kind", "@variants": {circle: {radius: "num"}, rect: {w: "num", h: "num"}}}`
+//!   matches objects whose `kind` is `"circle"` or `"rect"` and which match that variant.
+//! - the key `"@if"` of an object schema lists keys that are only required sometimes:
+//!   `{"@if": [{if: {mode: "advanced"}, require: ["threads"]}]}` requires `threads` when
+//!   `mode` is `"advanced"`. Every key and value under `if` has to be there for it to apply.
+//! - the key `"@definitions"` of the root object names schemas that are used in several
+//!   places: `{"@ref": "stats"}` stands for the definition `stats`, `{"@ref": "stats?"}`
+//!   for an optional one. [`Schema::from_file`] also resolves references to the definitions
+//!   of other schema files, like `{"@ref": "common.gon#stats"}`. Definitions can't refer to
+//!   themselves.
+//!
+//! # Example
+//! ```rust
+//! use gon::{parse_str, schema::Schema};
+//! let schema = Schema::from_value(&parse_str(r#"{
+//!     name: "str",
+//!     port: "num?",
+//!     tags: ["str"],
+//! }"#).unwrap()).unwrap();
+//! assert!(schema.validate(&parse_str(r#"{name: "gon", tags: ["a"]}"#).unwrap()).is_ok());
+//! assert!(schema.validate(&parse_str(r#"{name: 1, tags: []}"#).unwrap()).is_err());
+//!
+//! let schema = Schema::from_value(&parse_str(r#"{
+//!     id: "str | num",
+//!     mode: "str",
+//!     threads: "num?",
+//!     "@if": [{if: {mode: "advanced"}, require: ["threads"]}],
+//! }"#).unwrap()).unwrap();
+//! assert!(schema.validate(&parse_str(r#"{id: 7, mode: "simple"}"#).unwrap()).is_ok());
+//! let errors = schema.validate(&parse_str(r#"{id: 7, mode: "advanced"}"#).unwrap()).unwrap_err();
+//! assert_eq!(errors.to_string(), r#"threads: required when mode is "advanced""#);
+
+//! let schema = Schema::from_value(&parse_str(r#"{
+//!     port: {"@type": "num", "@min": 1, "@max": 65535},
+//!     user: {"@type": "str", "@max_len": 8},
+//! }"#).unwrap()).unwrap();
+//! let errors = schema.validate(&parse_str(r#"{port: 0, user: "root"}"#).unwrap()).unwrap_err();
+//! assert_eq!(errors.to_string(), "port: expected at least 1, found 0");
+//!
+//! let schema = Schema::from_value(&parse_str(r#"{
+//!     "@definitions": {stats: {hp: "num", speed: "num"}},
+//!     enemies: [{name: "str", stats: {"@ref": "stats"}}],
+//!     player: {"@ref": "stats"},
+//! }"#).unwrap()).unwrap();
+//! let errors = schema.validate(&parse_str(r#"{
+//!     enemies: [{name: "Goblin", stats: {hp: 4, speed: "fast"}}],
+//!     player: {hp: 10, speed: 2},
+//! }"#).unwrap()).unwrap_err();
+//! assert_eq!(errors.to_string(), "enemies[0].stats.speed: expected num, found str");
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Component, PathBuf};
+
+#[cfg(feature = "patterns")]
+use regex::Regex;
+use thiserror::Error;
+
+use crate::cancel::{CancelToken, Cancelled};
+use crate::lint::{Finding, Rule, Severity, Source};
+use crate::path::{Path, Segment};
+use crate::value::spelling_order;
+use crate::{Kind, Value};
+
+#[cfg(feature = "derive")]
+pub use gon_derive::GonSchema;
+
+/// The keys of an annotated schema
+const ANNOTATIONS: [&str; 5] = ["@type", "@doc", "@default", "@example", "@deprecated"];
+
+/// The keys of the constraints of an annotated schema
+const CONSTRAINTS: [&str; 9] = [
+    "@min",
+    "@max",
+    "@exclusive_min",
+    "@exclusive_max",
+    "@multiple_of",
+    "@min_len",
+    "@max_len",
+    "@pattern",
+    "@unique",
+];
+
+/// The keys of a discriminated union
+const UNION_KEYS: [&str; 2] = ["@tag", "@variants"];
+
+/// The expected shape of a [`Value`]. See the [module docs](self) for the gon syntax.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Matches everything
+    Any,
+    /// Matches [`Value::None`]
+    None,
+    /// Matches any [`Value::Str`]
+    Str,
+    /// Matches any [`Value::Num`]
+    Num,
+    /// Matches any [`Value::Bool`]
+    Bool,
+    /// Matches any [`Value::Obj`]
+    AnyObj,
+    /// Matches any [`Value::List`]
+    AnyList,
+    /// Matches lists whose elements all match the inner schema
+    List(Box<Schema>),
+    /// Matches objects with the given fields
+    Obj(Vec<Field>),
+    /// Matches values that match any of the schemas
+    Union(Vec<Schema>),
+    /// Matches objects whose str at `tag` names a variant and that match that variant
+    Discriminated {
+        tag: String,
+        variants: Vec<(String, Schema)>,
+    },
+    /// Matches values that match `schema` and satisfy all the constraints
+    Constrained(Box<Schema>, Constraints),
+    /// Matches values that match `schema` and, where a condition applies, have the keys
+    /// it requires
+    Conditional {
+        schema: Box<Schema>,
+        conditions: Vec<Condition>,
+    },
+}
+
+/// Narrower bounds of a [`Schema`], see the [module docs](self)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Constraints {
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub exclusive_min: Option<f64>,
+    pub exclusive_max: Option<f64>,
+    pub multiple_of: Option<f64>,
+    /// The least number of chars of a str or elements of a list
+    pub min_len: Option<usize>,
+    /// The greatest number of chars of a str or elements of a list
+    pub max_len: Option<usize>,
+    #[cfg(feature = "patterns")]
+    pub pattern: Option<StrPattern>,
+    /// Whether the elements of lists have to differ from each other
+    pub unique: bool,
+}
+
+/// A regex strs have to match, compared by its source
+#[cfg(feature = "patterns")]
+#[derive(Debug, Clone)]
+pub struct StrPattern(pub Regex);
+
+#[cfg(feature = "patterns")]
+impl PartialEq for StrPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// Keys an object needs to have if it has certain other keys and values
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    /// The keys and the values they need to have for the condition to apply
+    pub when: Vec<(String, Value)>,
+    pub require: Vec<String>,
+}
+
+/// A key of an object schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub key: String,
+    pub schema: Schema,
+    /// Whether the key may be missing (or `None`)
+    pub optional: bool,
+    pub annotations: Annotations,
+}
+
+/// Documentation of a [`Field`], see the [module docs](self)
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotations {
+    /// What the field is for
+    pub doc: Option<String>,
+    /// The value used when the field is missing
+    pub default: Option<Value>,
+    /// An example value of the field
+    pub example: Option<Value>,
+    /// Why the field shouldn't be used anymore, or what to use instead
+    pub deprecated: Option<String>,
+}
+
+/// The schema document itself is malformed
+#[derive(Debug, Error, Clone, PartialEq)]
+#[error("invalid schema at {path}: {message}")]
+pub struct SchemaError {
+    pub path: String,
+    pub message: String,
+}
+
+/// A value didn't match its schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    /// Where the offending value is, e.g. `server.ports[2]`
+    pub path: Path,
+    pub message: String,
+}
+
+/// All the [`ValidationError`]s found in a value
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationErrors(pub Vec<ValidationError>);
+
+impl Schema {
+    /// Builds a schema from its gon representation. Fails with the first problem of the
+    /// document, [`Linter::for_schemas`](crate::lint::Linter::for_schemas) finds all of them.
+    pub fn from_value(value: &Value) -> Result<Self, SchemaError> {
+        Reader::default().finish(value)
+    }
+
+    /// Reads the schema file at `path`. Unlike [`from_value`](Self::from_value), references
+    /// to the definitions of other schema files, like `{"@ref": "common.gon#item"}`, are
+    /// resolved, relative to the directory of `path`.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, SchemaError> {
+        Self::from_file_with_dependencies(path).map(|(schema, _)| schema)
+    }
+
+    /// Like [`from_file`](Self::from_file), but also returns the other schema files it
+    /// refers to, directly or through them, in the order they were read. A build system
+    /// has to read the schema again when any of them changes.
+    pub fn from_file_with_dependencies(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(Self, Vec<PathBuf>), SchemaError> {
+        let (schema, references) = Self::from_file_with_references(path)?;
+        let mut dependencies = Vec::new();
+        for (_, to) in references {
+            if !dependencies.contains(&to) {
+                dependencies.push(to);
+            }
+        }
+        Ok((schema, dependencies))
+    }
+
+    /// Like [`from_file_with_dependencies`](Self::from_file_with_dependencies), but with
+    /// which file refers to which
+    pub(crate) fn from_file_with_references(
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<(Self, Vec<(PathBuf, PathBuf)>), SchemaError> {
+        let path = path.as_ref();
+        let value = read_file(path)?;
+        let mut reader = Reader {
+            files: Files::RelativeTo(normalize(path)),
+            ..Default::default()
+        };
+        let schema = reader.finish(&value)?;
+        Ok((schema, reader.references))
+    }
+}
+
+/// Reads schemas from their gon representation, collecting every problem of the document
+#[derive(Default)]
+struct Reader {
+    /// Where the value being read is in the schema document
+    path: Path,
+    /// What keeps the document from being a schema, and where
+    errors: Vec<(Path, String)>,
+    /// Constraints that no value satisfies, and where
+    contradictions: Vec<(Path, String)>,
+    /// Definitions that nothing refers to, and where
+    unused: Vec<(Path, String)>,
+    /// The `"@definitions"` of the document
+    definitions: crate::MapT,
+    /// The definitions read so far, by [`qualified`](Self::qualified) name
+    resolved: HashMap<String, Schema>,
+    /// The definitions being read, innermost last, to catch definitions that refer to
+    /// themselves
+    resolving: Vec<String>,
+    files: Files,
+    /// Which file refers to which other file's definitions, in the order they were read
+    references: Vec<(PathBuf, PathBuf)>,
+}
+
+/// How references to the definitions of other files are resolved
+#[derive(Default)]
+enum Files {
+    /// They're errors, the document wasn't read from a file
+    #[default]
+    Unknown,
+    /// Relative to the directory of the document's file
+    RelativeTo(PathBuf),
+    /// They aren't followed and match anything, for linting a document on its own
+    Skip,
+}
+
+impl Reader {
+    /// Reads the schema document and fails with its first problem
+    fn finish(&mut self, value: &Value) -> Result<Schema, SchemaError> {
+        let schema = self.read_root(value);
+        match self.errors.drain(..).next() {
+            Some((path, message)) => Err(SchemaError {
+                path: display_path(&path),
+                message,
+            }),
+            None => Ok(schema),
+        }
+    }
+
+    /// Reads a whole schema document, including the definitions nothing refers to. Those
+    /// are unused, unless the document is nothing but definitions for other files.
+    fn read_root(&mut self, value: &Value) -> Schema {
+        self.load_definitions(value);
+        let (schema, ..) = self.read(value);
+        let library = value.as_obj().is_some_and(|obj| obj.len() == 1);
+        let unused: Vec<_> = (self.definitions.keys())
+            .filter(|name| !self.resolved.contains_key(&self.qualified(name)))
+            .map(|name| name.to_string())
+            .collect();
+        for name in unused {
+            if !library {
+                let path = Path::default().key("@definitions").key(&name);
+                self.unused
+                    .push((path, format!("nothing refers to the definition '{name}'")));
+            }
+            self.definition(&name);
+        }
+        schema
+    }
+
+    fn load_definitions(&mut self, value: &Value) {
+        let Some(definitions) = value.as_obj().and_then(|obj| obj.get("@definitions")) else {
+            return;
+        };
+        self.within(Segment::Key("@definitions".into()), |r| match definitions {
+            Value::Obj(definitions) => r.definitions = definitions.clone(),
+            _ => r.error("expected an obj of schemas for @definitions"),
+        });
+    }
+
+    /// The name of a definition of this document that tells it apart from the definitions
+    /// of other files
+    fn qualified(&self, name: &str) -> String {
+        match &self.files {
+            Files::RelativeTo(file) => qualify(file, name),
+            _ => name.into(),
+        }
+    }
+
+    /// The schema of the definition `name` of this document
+    fn definition(&mut self, name: &str) -> Schema {
+        let qualified = self.qualified(name);
+        if let Some(schema) = self.resolved.get(&qualified) {
+            return schema.clone();
+        }
+        if !self.refers_to_itself(&qualified) {
+            return Schema::Any;
+        }
+        let Some(value) = self.definitions.get(name).cloned() else {
+            self.error(format!("unknown definition '{name}'"));
+            return Schema::Any;
+        };
+        let path = Path::default().key("@definitions").key(name);
+        let outer = std::mem::replace(&mut self.path, path);
+        self.resolving.push(qualified.clone());
+        let (schema, ..) = self.read(&value);
+        self.resolving.pop();
+        self.path = outer;
+        self.resolved.insert(qualified, schema.clone());
+        schema
+    }
+
+    /// Reports references to the definition `qualified` from inside of itself, returns
+    /// whether the definition can be read
+    fn refers_to_itself(&mut self, qualified: &str) -> bool {
+        let Some(start) = self.resolving.iter().position(|name| name == qualified) else {
+            return true;
+        };
+        let chain = self.resolving[start..].join(" -> ");
+        self.error(format!(
+            "'{qualified}' refers to itself: {chain} -> {qualified}"
+        ));
+        false
+    }
+
+    /// The schema of the definition `name` of the schema file `file`
+    fn foreign_definition(&mut self, file: &str, name: &str) -> Schema {
+        let this = match &self.files {
+            Files::Unknown => {
+                self.error(format!(
+                    "can't refer to {file} unless the schema is read with Schema::from_file"
+                ));
+                return Schema::Any;
+            }
+            Files::Skip => return Schema::Any,
+            Files::RelativeTo(this) => this.clone(),
+        };
+        // spelled the same no matter how it's referred to, to notice cycles
+        let path = normalize(
+            &this
+                .parent()
+                .map_or_else(|| file.into(), |dir| dir.join(file)),
+        );
+        let qualified = qualify(&path, name);
+        if let Some(schema) = self.resolved.get(&qualified) {
+            return schema.clone();
+        }
+        if !self.refers_to_itself(&qualified) {
+            return Schema::Any;
+        }
+        let reference = (this, path.clone());
+        if !self.references.contains(&reference) {
+            self.references.push(reference);
+        }
+        let value = match read_file(&path) {
+            Ok(value) => value,
+            Err(e) => {
+                self.error(e.message);
+                return Schema::Any;
+            }
+        };
+        let mut reader = Reader {
+            files: Files::RelativeTo(path),
+            resolving: self.resolving.clone(),
+            ..Default::default()
+        };
+        reader.load_definitions(&value);
+        let schema = reader.definition(name);
+        for reference in reader.references {
+            if !self.references.contains(&reference) {
+                self.references.push(reference);
+            }
+        }
+        for (path, message) in reader.errors {
+            self.error(format!(
+                "{file}: invalid schema at {}: {message}",
+                display_path(&path)
+            ));
+        }
+        self.resolved.insert(qualified, schema.clone());
+        schema
+    }
+
+    fn error(&mut self, message: impl Into<String>) {
+        self.errors.push((self.path.clone(), message.into()));
+    }
+
+    /// Runs `read` with `segment` appended to the path
+    fn within<T>(&mut self, segment: Segment, read: impl FnOnce(&mut Self) -> T) -> T {
+        self.path.0.push(segment);
+        let read = read(self);
+        self.path.0.pop();
+        read
+    }
+
+    /// Reads the schema, whether it was marked optional and its annotations. What can't be
+    /// read is an error and matches anything.
+    fn read(&mut self, value: &Value) -> (Schema, bool, Annotations) {
+        match value {
+            Value::Obj(obj) if obj.contains_key("@type") => self.read_annotated(obj),
+            Value::Obj(obj) if obj.contains_key("@tag") => {
+                (self.read_union(obj), false, Annotations::default())
+            }
+            Value::Obj(obj) if obj.contains_key("@ref") => self.read_ref(obj),
+            Value::Str { s, .. } => {
+                let (names, optional) = match s.strip_suffix('?') {
+                    Some(names) => (names, true),
+                    None => (s.as_str(), false),
+                };
+                let mut schemas: Vec<_> = names
+                    .split('|')
+                    .map(str::trim)
+                    .map(|name| match name {
+                        "any" => Schema::Any,
+                        name => name.parse::<Kind>().map(Schema::from).unwrap_or_else(|_| {
+                            self.error(format!("unknown type '{name}'"));
+                            Schema::Any
+                        }),
+                    })
+                    .collect();
+                let schema = if schemas.len() == 1 {
+                    schemas.remove(0)
+                } else {
+                    Schema::Union(schemas)
+                };
+                (schema, optional, Annotations::default())
+            }
+            Value::List(xs) => {
+                if xs.len() != 1 {
+                    self.error(format!(
+                        "list schemas need exactly one element, got {}",
+                        xs.len()
+                    ));
+                }
+                let mut inners: Vec<_> = (xs.iter().enumerate())
+                    .map(|(i, x)| self.within(Segment::Index(i), |r| r.read(x).0))
+                    .collect();
+                let schema = match inners.len() {
+                    1 => Schema::List(Box::new(inners.remove(0))),
+                    _ => Schema::AnyList,
+                };
+                (schema, false, Annotations::default())
+            }
+            Value::Obj(obj) => {
+                if obj.contains_key("@definitions") && !self.path.is_root() {
+                    self.within(Segment::Key("@definitions".into()), |r| {
+                        r.error("definitions are only allowed at the root of a schema")
+                    });
+                }
+                let fields = spelling_order(obj)
+                    .filter(|(k, _)| !["@if", "@definitions"].contains(&&***k))
+                    .map(|(key, v)| {
+                        let (schema, optional, annotations) =
+                            self.within(Segment::Key(key.to_string()), |r| r.read(v));
+                        Field {
+                            key: key.to_string(),
+                            schema,
+                            optional,
+                            annotations,
+                        }
+                    })
+                    .collect();
+                let schema = match obj.get("@if") {
+                    Some(conditions) => Schema::Conditional {
+                        schema: Box::new(Schema::Obj(fields)),
+                        conditions: self.within(Segment::Key("@if".into()), |r| {
+                            Condition::read(conditions, r)
+                        }),
+                    },
+                    None => Schema::Obj(fields),
+                };
+                (schema, false, Annotations::default())
+            }
+            other => {
+                self.error(format!(
+                    "expected a type name, list or object, found {}",
+                    other.type_name()
+                ));
+                (Schema::Any, false, Annotations::default())
+            }
+        }
+    }
+
+    /// Reads an object with the key `"@type"`
+    fn read_annotated(&mut self, obj: &crate::MapT) -> (Schema, bool, Annotations) {
+        let (mut schema, optional, _) = self.read(&obj["@type"]);
+        let mut problems = Vec::new();
+        let constraints = Constraints::from_obj(obj, &schema, &mut problems);
+        for problem in problems {
+            self.error(problem);
+        }
+        for contradiction in constraints.contradictions() {
+            self.contradictions.push((self.path.clone(), contradiction));
+        }
+        if constraints != Constraints::default() {
+            schema = Schema::Constrained(Box::new(schema), constraints);
+        }
+        let mut text = |key: &str| match obj.get(key) {
+            None => None,
+            Some(Value::Str { s, .. }) => Some(s.clone()),
+            Some(other) => {
+                self.error(format!(
+                    "expected a str for {key}, found {}",
+                    other.type_name()
+                ));
+                None
+            }
+        };
+        let annotations = Annotations {
+            doc: text("@doc"),
+            default: obj.get("@default").cloned(),
+            example: obj.get("@example").cloned(),
+            deprecated: text("@deprecated"),
+        };
+        for (key, v) in [
+            ("@default", &annotations.default),
+            ("@example", &annotations.example),
+        ] {
+            if let Some(Err(errors)) = v.as_ref().map(|v| schema.validate(v)) {
+                self.error(format!("{key} doesn't match the schema: {errors}"));
+            }
+        }
+        for key in obj
+            .keys()
+            .map(|k| &**k)
+            .filter(|k| !ANNOTATIONS.contains(k) && !CONSTRAINTS.contains(k))
+        {
+            self.error(format!("unknown annotation '{key}'"));
+        }
+        (schema, optional, annotations)
+    }
+
+    /// Reads an object with the key `"@ref"`
+    fn read_ref(&mut self, obj: &crate::MapT) -> (Schema, bool, Annotations) {
+        for key in obj.keys().filter(|k| &***k != "@ref") {
+            self.error(format!("unknown key '{key}' of a reference"));
+        }
+        let Some(Value::Str { s, .. }) = obj.get("@ref") else {
+            self.error("expected the name of a definition for @ref");
+            return (Schema::Any, false, Annotations::default());
+        };
+        let (target, optional) = match s.strip_suffix('?') {
+            Some(target) => (target, true),
+            None => (s.as_str(), false),
+        };
+        let schema = match target.split_once('#') {
+            Some((file, name)) => self.foreign_definition(file, name),
+            None => self.definition(target),
+        };
+        (schema, optional, Annotations::default())
+    }
+
+    /// Reads an object with the key `"@tag"`
+    fn read_union(&mut self, obj: &crate::MapT) -> Schema {
+        let tag = match obj.get("@tag") {
+            Some(Value::Str { s, .. }) => s.clone(),
+            _ => {
+                self.error("expected a str for @tag");
+                String::new()
+            }
+        };
+        let variants = match obj.get("@variants") {
+            Some(Value::Obj(variants)) => Some(variants),
+            _ => {
+                self.error("expected an obj of variants for @variants");
+                None
+            }
+        };
+        for key in obj.keys().map(|k| &**k).filter(|k| !UNION_KEYS.contains(k)) {
+            self.error(format!("unknown key '{key}' of a tagged union"));
+        }
+        let Some(variants) = variants else {
+            return Schema::AnyObj;
+        };
+        let variants = self.within(Segment::Key("@variants".into()), |r| {
+            spelling_order(variants)
+                .map(|(name, v)| {
+                    let schema = r.within(Segment::Key(name.to_string()), |r| {
+                        let errors = r.errors.len();
+                        let (schema, ..) = r.read(v);
+                        // a variant that can't be read isn't an object either
+                        if errors == r.errors.len()
+                            && !matches!(schema.object(), Some(Schema::Obj(_)))
+                        {
+                            r.error(format!(
+                                "variants need to be objects, found {}",
+                                schema.name()
+                            ));
+                        }
+                        schema
+                    });
+                    (name.to_string(), schema)
+                })
+                .collect()
+        });
+        Schema::Discriminated { tag, variants }
+    }
+}
+
+impl Schema {
+    /// Checks `value` against this schema, collecting every mismatch
+    pub fn validate(&self, value: &Value) -> Result<(), ValidationErrors> {
+        self.validate_with(value, None)
+    }
+
+    /// Like [`validate`](Self::validate), but stops soon after `cancel` is cancelled, see
+    /// the docs of [`cancel`](crate::cancel). Fails if the token is cancelled by the time
+    /// it's done, the inner result is that of [`validate`](Self::validate).
+    pub fn validate_cancellable(
+        &self,
+        value: &Value,
+        cancel: &CancelToken,
+    ) -> Result<Result<(), ValidationErrors>, Cancelled> {
+        let validated = self.validate_with(value, Some(cancel));
+        cancel.check()?;
+        Ok(validated)
+    }
+
+    fn validate_with(
+        &self,
+        value: &Value,
+        cancel: Option<&CancelToken>,
+    ) -> Result<(), ValidationErrors> {
+        trace_span!(validating, "gon::validate");
+        let mut errors = Vec::new();
+        self.validate0(value, &mut Path::default(), &mut errors, true, cancel);
+        trace_done!(validating, errors = errors.len(), "validated");
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(errors))
+        }
+    }
+
+    /// Checks `value` at `path` against this schema. Unless `deep`, the elements of lists and
+    /// the fields of objects are assumed to be checked already, see
+    /// [`parse_validated`](crate::parser::parse_validated). Once `cancel` is cancelled,
+    /// nothing more is checked.
+    pub(crate) fn validate0(
+        &self,
+        value: &Value,
+        path: &mut Path,
+        errors: &mut Vec<ValidationError>,
+        deep: bool,
+        cancel: Option<&CancelToken>,
+    ) {
+        if cancel.is_some_and(CancelToken::is_cancelled) {
+            return;
+        }
+        let matches = match (self, value) {
+            (Self::Any, _)
+            | (Self::None, Value::None)
+            | (Self::Str, Value::Str { .. })
+            | (Self::Num, Value::Num(_))
+            | (Self::Bool, Value::Bool(_))
+            | (Self::AnyObj, Value::Obj(_))
+            | (Self::AnyList, Value::List(_)) => true,
+            (_, Value::Tagged { value, .. }) => {
+                self.validate0(value, path, errors, deep, cancel);
+                true
+            }
+            (Self::List(inner), Value::List(xs)) => {
+                for (i, x) in xs.iter().enumerate().filter(|_| deep) {
+                    path.0.push(Segment::Index(i));
+                    inner.validate0(x, path, errors, deep, cancel);
+                    path.0.pop();
+                }
+                true
+            }
+            (Self::Union(schemas), _) => schemas.iter().any(|s| s.validate(value).is_ok()),
+            (Self::Discriminated { tag, variants }, Value::Obj(obj)) => {
+                path.0.push(Segment::Key(tag.clone()));
+                let names = || {
+                    let names: Vec<_> = variants.iter().map(|(n, _)| format!("\"{n}\"")).collect();
+                    names.join(", ")
+                };
+                match obj.get(tag.as_str()) {
+                    Some(Value::Str { s, .. }) => match variants.iter().find(|(n, _)| n == s) {
+                        Some((_, variant)) => {
+                            path.0.pop();
+                            // the fields of variants are only known now
+                            variant.validate0(value, path, errors, true, cancel);
+                            return;
+                        }
+                        None => errors.push(ValidationError {
+                            path: path.clone(),
+                            message: format!("expected one of {}, found \"{s}\"", names()),
+                        }),
+                    },
+                    Some(other) => errors.push(ValidationError {
+                        path: path.clone(),
+                        message: format!(
+                            "expected one of {}, found {}",
+                            names(),
+                            other.type_name()
+                        ),
+                    }),
+                    None => errors.push(ValidationError {
+                        path: path.clone(),
+                        message: format!("missing tag, expected one of {}", names()),
+                    }),
+                }
+                path.0.pop();
+                true
+            }
+            (Self::Constrained(schema, constraints), _) => {
+                let len = errors.len();
+                schema.validate0(value, path, errors, deep, cancel);
+                // bounds of a value of the wrong type don't tell anything new
+                if errors.len() == len {
+                    constraints.check(value, path, errors);
+                }
+                true
+            }
+            (Self::Conditional { schema, conditions }, _) => {
+                schema.validate0(value, path, errors, deep, cancel);
+                if let Value::Obj(obj) = value {
+                    for condition in conditions.iter().filter(|c| c.applies(obj)) {
+                        for key in &condition.require {
+                            if matches!(obj.get(key.as_str()), None | Some(Value::None)) {
+                                path.0.push(Segment::Key(key.clone()));
+                                errors.push(ValidationError {
+                                    path: path.clone(),
+                                    message: format!("required when {}", condition.describe()),
+                                });
+                                path.0.pop();
+                            }
+                        }
+                    }
+                }
+                true
+            }
+            (Self::Obj(fields), Value::Obj(obj)) => {
+                for field in fields {
+                    path.0.push(Segment::Key(field.key.clone()));
+                    match obj.get(field.key.as_str()) {
+                        None | Some(Value::None) if field.optional => {}
+                        None => errors.push(ValidationError {
+                            path: path.clone(),
+                            message: "missing required key".into(),
+                        }),
+                        Some(v) if deep => field.schema.validate0(v, path, errors, deep, cancel),
+                        Some(_) => {}
+                    }
+                    path.0.pop();
+                }
+                true
+            }
+            _ => false,
+        };
+        if !matches {
+            let expected = match self {
+                Self::Union(_) => self.to_string(),
+                _ => self.name().into(),
+            };
+            errors.push(ValidationError {
+                path: path.clone(),
+                message: format!("expected {expected}, found {}", value.type_name()),
+            });
+        }
+    }
+
+    /// The kind of the values this schema accepts, `None` for `any` and unions of several
+    /// kinds
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, schema::Schema, Kind};
+    /// let schema = |src| Schema::from_value(&parse_str(src).unwrap()).unwrap();
+    /// assert_eq!(schema(r#"{"@type": "num", "@min": 1}"#).kind(), Some(Kind::Num));
+    /// assert_eq!(schema(r#"["str"]"#).kind(), Some(Kind::List));
+    /// assert_eq!(schema(r#""str | num""#).kind(), None);
+    /// ```
+    pub fn kind(&self) -> Option<Kind> {
+        match self {
+            Self::Any => None,
+            Self::None => Some(Kind::None),
+            Self::Str => Some(Kind::Str),
+            Self::Num => Some(Kind::Num),
+            Self::Bool => Some(Kind::Bool),
+            Self::AnyObj | Self::Obj(_) | Self::Discriminated { .. } => Some(Kind::Obj),
+            Self::AnyList | Self::List(_) => Some(Kind::List),
+            Self::Union(schemas) => {
+                let mut kinds = schemas.iter().map(Self::kind);
+                let first = kinds.next().flatten();
+                kinds.all(|kind| kind == first).then_some(first).flatten()
+            }
+            Self::Conditional { schema, .. } | Self::Constrained(schema, _) => schema.kind(),
+        }
+    }
+
+    /// A short, human readable name of the schema, used in error messages
+    fn name(&self) -> &'static str {
+        match (self, self.kind()) {
+            (Self::Union(_), _) => "union",
+            (_, Some(kind)) => kind.name(),
+            (_, None) => "any",
+        }
+    }
+
+    /// The fields of the objects this schema checks field by field
+    pub(crate) fn fields(&self) -> Option<&[Field]> {
+        match self {
+            Self::Obj(fields) => Some(fields),
+            Self::Conditional { schema, .. } | Self::Constrained(schema, _) => schema.fields(),
+            _ => None,
+        }
+    }
+
+    /// The schema of the elements of the lists this schema checks element by element
+    pub(crate) fn elements(&self) -> Option<&Self> {
+        match self {
+            Self::List(inner) => Some(inner),
+            Self::Conditional { schema, .. } | Self::Constrained(schema, _) => schema.elements(),
+            _ => None,
+        }
+    }
+
+    /// Whether values of the type `type_name` may match
+    fn admits(&self, kind: Kind) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Union(schemas) => schemas.iter().any(|s| s.admits(kind)),
+            Self::Conditional { schema, .. } | Self::Constrained(schema, _) => schema.admits(kind),
+            other => other.kind() == Some(kind),
+        }
+    }
+
+    /// The object schema under any conditions
+    fn object(&self) -> Option<&Self> {
+        match self {
+            Self::Conditional { schema, .. } => schema.object(),
+            Self::Obj(_) => Some(self),
+            _ => None,
+        }
+    }
+}
+
+impl Constraints {
+    /// Reads the constraints among the keys of an annotated schema and checks that they
+    /// make sense for `schema`. Constraints that don't are left out and explained in
+    /// `problems`.
+    fn from_obj(obj: &crate::MapT, schema: &Schema, problems: &mut Vec<String>) -> Self {
+        let mut constraints = Self::default();
+        for (key, v) in spelling_order(obj).filter(|(k, _)| CONSTRAINTS.contains(&&***k)) {
+            if let Err(problem) = constraints.read(key, v, schema) {
+                problems.push(problem);
+            }
+        }
+        constraints
+    }
+
+    /// Reads the constraint `key` with the value `v`
+    fn read(&mut self, key: &str, v: &Value, schema: &Schema) -> Result<(), String> {
+        let applies_to = match key {
+            "@pattern" => &[Kind::Str][..],
+            "@unique" => &[Kind::List],
+            "@min_len" | "@max_len" => &[Kind::Str, Kind::List],
+            _ => &[Kind::Num],
+        };
+        if !applies_to.iter().any(|kind| schema.admits(*kind)) {
+            let names: Vec<_> = applies_to.iter().map(|kind| kind.name()).collect();
+            return Err(format!(
+                "{key} only applies to {}, not {schema}",
+                names.join(" and ")
+            ));
+        }
+        let num = || {
+            v.as_f64()
+                .ok_or_else(|| format!("expected a num for {key}, found {}", v.type_name()))
+        };
+        let len = || {
+            v.as_f64()
+                .filter(|n| *n >= 0.0 && n.fract() == 0.0)
+                .map(|n| n as usize)
+                .ok_or_else(|| format!("expected a length for {key}, found {}", v.min_spell()))
+        };
+        match key {
+            "@min" => self.min = Some(num()?),
+            "@max" => self.max = Some(num()?),
+            "@exclusive_min" => self.exclusive_min = Some(num()?),
+            "@exclusive_max" => self.exclusive_max = Some(num()?),
+            "@multiple_of" => {
+                let n = num()?;
+                if n <= 0.0 {
+                    return Err(format!("@multiple_of has to be positive, got {n}"));
+                }
+                self.multiple_of = Some(n);
+            }
+            "@min_len" => self.min_len = Some(len()?),
+            "@max_len" => self.max_len = Some(len()?),
+            #[cfg(not(feature = "patterns"))]
+            "@pattern" => {
+                return Err("@pattern needs gon to be built with the `patterns` feature".into());
+            }
+            #[cfg(feature = "patterns")]
+            "@pattern" => {
+                let Value::Str { s, .. } = v else {
+                    return Err(format!(
+                        "expected a str for @pattern, found {}",
+                        v.type_name()
+                    ));
+                };
+                let regex = Regex::new(s).map_err(|e| format!("invalid @pattern: {e}"))?;
+                self.pattern = Some(StrPattern(regex));
+            }
+            _ => {
+                let Value::Bool(unique) = v else {
+                    return Err(format!(
+                        "expected a bool for @unique, found {}",
+                        v.type_name()
+                    ));
+                };
+                self.unique = *unique;
+            }
+        }
+        Ok(())
+    }
+
+    /// Explains why no value satisfies the constraints, if that's the case. Bounds of nums
+    /// only contradict each other for nums, so a schema like `"num | str"` still matches
+    /// strs.
+    pub fn contradictions(&self) -> Vec<String> {
+        let mut contradictions = Vec::new();
+        // the tightest bounds, and whether they're exclusive
+        let lower = [(self.min, false), (self.exclusive_min, true)]
+            .into_iter()
+            .filter_map(|(n, exclusive)| n.map(|n| (n, exclusive)))
+            .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        let upper = [(self.max, false), (self.exclusive_max, true)]
+            .into_iter()
+            .filter_map(|(n, exclusive)| n.map(|n| (n, exclusive)))
+            .min_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)));
+        let describe = |(n, exclusive): (f64, bool), inclusive: &str, strict: &str| {
+            format!("{} {n}", if exclusive { strict } else { inclusive })
+        };
+        if let (Some(lo), Some(hi)) = (lower, upper) {
+            if lo.0 > hi.0 || (lo.0 == hi.0 && (lo.1 || hi.1)) {
+                contradictions.push(format!(
+                    "no num is {} and {}",
+                    describe(lo, "at least", "more than"),
+                    describe(hi, "at most", "less than")
+                ));
+            } else if let Some(m) = self.multiple_of {
+                // the least multiple that satisfies the lower bound
+                let first = match (lo.0 / m).ceil() * m {
+                    n if lo.1 && n <= lo.0 => n + m,
+                    n => n,
+                };
+                if first > hi.0 || (hi.1 && first >= hi.0) {
+                    contradictions.push(format!(
+                        "no multiple of {m} is {} and {}",
+                        describe(lo, "at least", "more than"),
+                        describe(hi, "at most", "less than")
+                    ));
+                }
+            }
+        }
+        let lens = self.min_len.zip(self.max_len);
+        if let Some((min, max)) = lens.filter(|(min, max)| min > max) {
+            contradictions.push(format!(
+                "nothing has at least {min} and at most {max} chars or elements"
+            ));
+        }
+        contradictions
+    }
+
+    fn check(&self, value: &Value, path: &mut Path, errors: &mut Vec<ValidationError>) {
+        let mut fail = |message: String| {
+            errors.push(ValidationError {
+                path: path.clone(),
+                message,
+            })
+        };
+        match crate::path::untagged(value) {
+            Value::Num(spelled) => {
+                let Some(n) = value.as_f64() else {
+                    return;
+                };
+                if let Some(min) = self.min.filter(|min| n < *min) {
+                    fail(format!("expected at least {min}, found {spelled}"));
+                }
+                if let Some(max) = self.max.filter(|max| n > *max) {
+                    fail(format!("expected at most {max}, found {spelled}"));
+                }
+                if let Some(min) = self.exclusive_min.filter(|min| n <= *min) {
+                    fail(format!("expected more than {min}, found {spelled}"));
+                }
+                if let Some(max) = self.exclusive_max.filter(|max| n >= *max) {
+                    fail(format!("expected less than {max}, found {spelled}"));
+                }
+                if let Some(m) = self.multiple_of {
+                    let quotient = n / m;
+                    // tolerate the rounding of e.g. 0.3 / 0.1
+                    if (quotient - quotient.round()).abs() > 1e-9 * quotient.abs().max(1.0) {
+                        fail(format!("expected a multiple of {m}, found {spelled}"));
+                    }
+                }
+            }
+            Value::Str { s, .. } => {
+                let len = s.chars().count();
+                if let Some(min) = self.min_len.filter(|min| len < *min) {
+                    fail(format!(
+                        "expected at least {min} chars, found {len} in {}",
+                        value.preview().min_spell()
+                    ));
+                }
+                if let Some(max) = self.max_len.filter(|max| len > *max) {
+                    fail(format!(
+                        "expected at most {max} chars, found {len} in {}",
+                        value.preview().min_spell()
+                    ));
+                }
+                #[cfg(feature = "patterns")]
+                if let Some(StrPattern(regex)) = self.pattern.as_ref().filter(|p| !p.0.is_match(s))
+                {
+                    fail(format!(
+                        "{} doesn't match the pattern '{}'",
+                        value.preview().min_spell(),
+                        regex.as_str()
+                    ));
+                }
+            }
+            Value::List(xs) => {
+                if let Some(min) = self.min_len.filter(|min| xs.len() < *min) {
+                    fail(format!(
+                        "expected at least {min} elements, found {}",
+                        xs.len()
+                    ));
+                }
+                if let Some(max) = self.max_len.filter(|max| xs.len() > *max) {
+                    fail(format!(
+                        "expected at most {max} elements, found {}",
+                        xs.len()
+                    ));
+                }
+                if self.unique {
+                    for (i, x) in xs.iter().enumerate() {
+                        if let Some(first) = xs[..i].iter().position(|y| y == x) {
+                            path.0.push(Segment::Index(i));
+                            errors.push(ValidationError {
+                                path: path.clone(),
+                                message: format!(
+                                    "{} is already element [{first}]",
+                                    x.preview().min_spell()
+                                ),
+                            });
+                            path.0.pop();
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Condition {
+    /// Reads a list of `{if: {key: value, ...}, require: [key, ...]}`, leaving out the
+    /// conditions `reader` can't make sense of
+    fn read(value: &Value, reader: &mut Reader) -> Vec<Self> {
+        let Some(conditions) = value.as_list() else {
+            reader.error("expected a list of {if, require}");
+            return Vec::new();
+        };
+        let read = |condition: &Value| {
+            let obj = condition.as_obj().ok_or("expected {if, require}")?;
+            if let Some(key) = obj.keys().find(|k| !["if", "require"].contains(&&***k)) {
+                return Err(format!("unknown key '{key}' of a condition"));
+            }
+            let when = obj
+                .get("if")
+                .and_then(Value::as_obj)
+                .ok_or("if: expected an obj of keys and values")?;
+            let require = obj
+                .get("require")
+                .and_then(Value::as_list)
+                .and_then(|keys| {
+                    keys.iter()
+                        .map(|k| <&str>::try_from(k).ok().map(String::from))
+                        .collect()
+                })
+                .ok_or("require: expected a list of keys")?;
+            Ok(Self {
+                when: spelling_order(when)
+                    .map(|(k, v)| (k.to_string(), v.clone()))
+                    .collect(),
+                require,
+            })
+        };
+        conditions
+            .iter()
+            .filter_map(|condition| {
+                read(condition)
+                    .map_err(|message: String| reader.error(message))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Whether `obj` has every key and value the condition applies for
+    fn applies(&self, obj: &crate::MapT) -> bool {
+        self.when.iter().all(|(key, expected)| {
+            match (obj.get(key.as_str()), expected) {
+                // raw and escaped strings are the same value
+                (Some(Value::Str { s, .. }), Value::Str { s: e, .. }) => s == e,
+                (Some(v), expected) => v == expected,
+                (None, _) => false,
+            }
+        })
+    }
+
+    /// E.g. `mode is "advanced" and debug is true`
+    fn describe(&self) -> String {
+        let parts: Vec<_> = self
+            .when
+            .iter()
+            .map(|(key, v)| format!("{key} is {}", v.min_spell()))
+            .collect();
+        parts.join(" and ")
+    }
+}
+
+/// The schema that accepts every value of `kind`
+impl From<Kind> for Schema {
+    fn from(kind: Kind) -> Self {
+        match kind {
+            Kind::None => Self::None,
+            Kind::Str => Self::Str,
+            Kind::Num => Self::Num,
+            Kind::Bool => Self::Bool,
+            Kind::Obj => Self::AnyObj,
+            Kind::List => Self::AnyList,
+        }
+    }
+}
+
+/// Rust types that know the [`Schema`] of the values they're read from, so validation can't
+/// drift from the types a program actually uses. Derive it for structs with
+/// `#[derive(GonSchema)]` from the `gon-derive` crate, which the `derive` feature
+/// re-exports: named fields become [`Field`]s, their doc comments the `doc` annotation and
+/// `#[gon(rename = "key")]` changes their key. Newtypes have the schema of the type they wrap.
+/// Schemas can't refer to themselves, so types that contain themselves have no schema.
+/// # Usage example
+/// ```rust
+/// use gon::schema::{GonSchema, Schema};
+/// assert_eq!(<Vec<u16>>::schema(), Schema::List(Box::new(Schema::Num)));
+/// assert_eq!(<Option<String>>::schema(), Schema::Union(vec![Schema::Str, Schema::None]));
+/// assert!(<Option<String>>::optional());
+/// ```
+pub trait GonSchema {
+    fn schema() -> Schema;
+
+    /// Whether fields of this type may be missing or `None`, true for [`Option`]s
+    fn optional() -> bool {
+        false
+    }
+}
+
+macro_rules! impl_gon_schema {
+    ($schema:expr => $($t:ty),* $(,)?) => {
+        $(impl GonSchema for $t {
+            fn schema() -> Schema {
+                $schema
+            }
+        })*
+    };
+}
+
+impl_gon_schema!(Schema::Num => i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+impl_gon_schema!(Schema::Str => String, str, char, std::path::PathBuf);
+impl_gon_schema!(Schema::Bool => bool);
+impl_gon_schema!(Schema::None => ());
+impl_gon_schema!(Schema::Any => Value);
+
+impl<T: GonSchema> GonSchema for Option<T> {
+    fn schema() -> Schema {
+        Schema::Union(vec![T::schema(), Schema::None])
+    }
+
+    fn optional() -> bool {
+        true
+    }
+}
+
+impl<T: GonSchema + ?Sized> GonSchema for Box<T> {
+    fn schema() -> Schema {
+        T::schema()
+    }
+
+    fn optional() -> bool {
+        T::optional()
+    }
+}
+
+impl<T: GonSchema> GonSchema for Vec<T> {
+    fn schema() -> Schema {
+        Schema::List(Box::new(T::schema()))
+    }
+}
+
+impl<T: GonSchema> GonSchema for [T] {
+    fn schema() -> Schema {
+        Schema::List(Box::new(T::schema()))
+    }
+}
+
+impl<T: GonSchema, const N: usize> GonSchema for [T; N] {
+    fn schema() -> Schema {
+        Schema::List(Box::new(T::schema()))
+    }
+}
+
+/// Maps are objects with any keys, the schema can't say what their values are
+impl<V, S> GonSchema for std::collections::HashMap<String, V, S> {
+    fn schema() -> Schema {
+        Schema::AnyObj
+    }
+}
+
+impl<V> GonSchema for std::collections::BTreeMap<String, V> {
+    fn schema() -> Schema {
+        Schema::AnyObj
+    }
+}
+
+/// Spells the schema in its gon syntax, but without the fields of objects
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::List(inner) => write!(f, "[{inner}]"),
+            Self::Constrained(schema, _) => write!(f, "{schema}"),
+            Self::Union(schemas) => {
+                for (i, schema) in schemas.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{schema}")?;
+                }
+                Ok(())
+            }
+            other => write!(f, "{}", other.name()),
+        }
+    }
+}
+
+/// Reports what keeps a schema document from being a schema: unknown type names and
+/// annotations, constraints that don't apply to their type, malformed unions and conditions
+#[derive(Copy, Clone, Debug, Default)]
+pub struct InvalidSchema;
+
+impl Rule for InvalidSchema {
+    fn name(&self) -> &str {
+        "invalid_schema"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        findings.extend(lint(source).errors.into_iter().map(finding));
+    }
+}
+
+/// Reports constraints that no value satisfies, like `"@min": 10, "@max": 1`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UnsatisfiableSchema;
+
+impl Rule for UnsatisfiableSchema {
+    fn name(&self) -> &str {
+        "unsatisfiable_schema"
+    }
+
+    fn default_severity(&self) -> Severity {
+        Severity::Error
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        findings.extend(lint(source).contradictions.into_iter().map(finding));
+    }
+}
+
+/// Reports definitions of a schema that nothing refers to
+#[derive(Copy, Clone, Debug, Default)]
+pub struct UnusedDefinitions;
+
+impl Rule for UnusedDefinitions {
+    fn name(&self) -> &str {
+        "unused_definitions"
+    }
+
+    fn check(&self, source: &Source, findings: &mut Vec<Finding>) {
+        findings.extend(lint(source).unused.into_iter().map(finding));
+    }
+}
+
+/// Reads a schema document on its own, without following references to other files
+fn lint(source: &Source) -> Reader {
+    let mut reader = Reader {
+        files: Files::Skip,
+        ..Default::default()
+    };
+    reader.read_root(&source.value);
+    reader
+}
+
+fn finding((path, message): (Path, String)) -> Finding {
+    Finding {
+        path,
+        span: None,
+        message,
+    }
+}
+
+/// The name of the definition `name` of the schema file `file`, e.g. `schemas/common.gon#item`
+fn qualify(file: &std::path::Path, name: &str) -> String {
+    format!("{}#{name}", file.display())
+}
+
+/// `path` with `.` and `..` resolved without looking at the file system
+pub(crate) fn normalize(path: &std::path::Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normal.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normal.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => normal.push(".."),
+            },
+            _ => normal.push(component),
+        }
+    }
+    normal
+}
+
+/// Reads and parses the schema file at `path`
+fn read_file(path: &std::path::Path) -> Result<Value, SchemaError> {
+    let fail = |message| SchemaError {
+        path: display_path(&Path::default()),
+        message,
+    };
+    let src = std::fs::read_to_string(path)
+        .map_err(|e| fail(format!("can't read {}: {e}", path.display())))?;
+    crate::parse_str(&src).map_err(|e| fail(format!("can't parse {}: {e}", path.display())))
+}
+
+/// Spells where something is in a schema document, e.g. `@variants.circle` or `tags[]`
+fn display_path(path: &Path) -> String {
+    let mut spelled = String::new();
+    for segment in &path.0 {
+        match segment {
+            Segment::Key(key) => {
+                if !spelled.is_empty() {
+                    spelled.push('.');
+                }
+                spelled.push_str(key);
+            }
+            Segment::Index(_) => spelled.push_str("[]"),
+        }
+    }
+    if spelled.is_empty() {
+        "(root)".into()
+    } else {
+        spelled
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.0.is_empty() {
+            write!(f, "(root): {}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, e) in self.0.iter().enumerate() {
+            if i != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{e}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+    use crate::testing::TempDir;
+
+    fn schema(src: &str) -> Schema {
+        Schema::from_value(&parse_str(src).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn validates_nested_objects_and_lists() {
+        let s = schema(r#"{server: {port: "num", host: "str?"}, users: [{name: "str"}]}"#);
+        assert!(
+            s.validate(&parse_str("{server: {port: 80}, users: []}").unwrap())
+                .is_ok()
+        );
+        let errors = s
+            .validate(&parse_str(r#"{server: {host: 1}, users: [{name: "a"}, {}]}"#).unwrap())
+            .unwrap_err();
+        let mut paths: Vec<_> = errors.0.iter().map(|e| e.path.to_string()).collect();
+        paths.sort();
+        assert_eq!(paths, ["server.host", "server.port", "users[1].name"]);
+    }
+
+    #[test]
+    fn validates_unions_and_conditions() {
+        let s = schema(
+            r#"{
+                id: "str | num",
+                shape: {"@tag": "kind", "@variants": {
+                    circle: {radius: "num"},
+                    rect: {w: "num", h: "num", "@if": [{if: {rounded: true}, require: ["r"]}]},
+                }},
+            }"#,
+        );
+        assert!(
+            s.validate(&parse_str(r#"{id: "a", shape: {kind: "circle", radius: 1}}"#).unwrap())
+                .is_ok()
+        );
+        let errors = |src: &str| -> Vec<String> {
+            let errors = s.validate(&parse_str(src).unwrap()).unwrap_err();
+            errors.0.iter().map(ToString::to_string).collect()
+        };
+        assert_eq!(
+            errors(r#"{id: true, shape: {kind: "rect", w: 1, h: 2, rounded: true}}"#),
+            [
+                "id: expected str | num, found bool",
+                r#"shape.r: required when rounded is true"#
+            ]
+        );
+        assert_eq!(
+            errors(r#"{id: 1, shape: {kind: "star"}}"#),
+            [r#"shape.kind: expected one of "circle", "rect", found "star""#]
+        );
+        assert_eq!(
+            errors(r#"{id: 1, shape: {kind: "circle"}}"#),
+            ["shape.radius: missing required key"]
+        );
+        let err =
+            Schema::from_value(&parse_str(r#"{"@tag": "kind", "@variants": {a: "num"}}"#).unwrap())
+                .unwrap_err();
+        assert_eq!(err.path, "@variants.a");
+    }
+
+    #[cfg(not(feature = "patterns"))]
+    #[test]
+    fn needs_a_feature_for_patterns() {
+        let src = r#"{a: {"@type": "str", "@pattern": "^v"}}"#;
+        let err = Schema::from_value(&parse_str(src).unwrap()).unwrap_err();
+        assert_eq!(err.path, "a");
+    }
+
+    #[cfg(feature = "patterns")]
+    #[test]
+    fn checks_constraints() {
+        let s = schema(
+            r#"{
+                ratio: {"@type": "num", "@exclusive_min": 0, "@max": 1, "@multiple_of": 0.1},
+                version: {"@type": "str", "@pattern": "^v", "@min_len": 2},
+                tags: [{"@type": ["str"], "@unique": true, "@max_len": 3}],
+            }"#,
+        );
+        assert!(
+            s.validate(&parse_str(r#"{ratio: 0.3, version: "v1", tags: [["a", "b"]]}"#).unwrap())
+                .is_ok()
+        );
+        let errors = s
+            .validate(
+                &parse_str(r#"{ratio: 0, version: "1", tags: [["a", "b", "a", "c"]]}"#).unwrap(),
+            )
+            .unwrap_err();
+        let mut errors: Vec<_> = errors.0.iter().map(ToString::to_string).collect();
+        errors.sort();
+        assert_eq!(
+            errors,
+            [
+                r#"ratio: expected more than 0, found 0"#,
+                r#"tags[0]: expected at most 3 elements, found 4"#,
+                r#"tags[0][2]: "a" is already element [0]"#,
+                r#"version: "1" doesn't match the pattern '^v'"#,
+                r#"version: expected at least 2 chars, found 1 in "1""#,
+            ]
+        );
+        assert!(
+            s.validate(&parse_str(r#"{ratio: 0.35, version: "v1", tags: []}"#).unwrap())
+                .is_err()
+        );
+        let err = Schema::from_value(&parse_str(r#"{a: {"@type": "str", "@min": 1}}"#).unwrap())
+            .unwrap_err();
+        assert_eq!(err.message, "@min only applies to num, not str");
+    }
+
+    #[test]
+    fn rejects_unknown_types() {
+        let err = Schema::from_value(&parse_str(r#"{a: ["int"]}"#).unwrap()).unwrap_err();
+        assert_eq!(err.path, "a[]");
+    }
+
+    #[test]
+    fn lints_schema_documents() {
+        let src = r#"{
+            age: {"@type": "num", "@min": 10, "@max": 1},
+            name: ["text"],
+            port: {"@type": "num", "@exclusive_min": 0, "@exclusive_max": 1, "@multiple_of": 1},
+            tags: {"@type": ["str"], "@sorted": true, "@min_len": 2},
+        }"#;
+        let linter = crate::lint::Linter::empty()
+            .rule(InvalidSchema)
+            .rule(UnsatisfiableSchema);
+        let found: Vec<_> = (linter.lint(src).unwrap().iter())
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            found,
+            [
+                "error: age: no num is at least 10 and at most 1 [unsatisfiable_schema]",
+                "error: name[0]: unknown type 'text' [invalid_schema]",
+                "error: port: no multiple of 1 is more than 0 and less than 1 [unsatisfiable_schema]",
+                "error: tags: unknown annotation '@sorted' [invalid_schema]",
+            ]
+        );
+        let err = Schema::from_value(&parse_str(src).unwrap()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid schema at name[]: unknown type 'text'"
+        );
+    }
+
+    #[test]
+    fn resolves_definitions() {
+        let s = schema(
+            r#"{
+                "@definitions": {
+                    item: {name: "str", stats: {"@ref": "stats?"}},
+                    stats: {"@type": {power: "num"}, "@doc": "what an item does"},
+                },
+                loot: [{"@ref": "item"}],
+            }"#,
+        );
+        assert!(
+            s.validate(&parse_str(r#"{loot: [{name: "axe"}]}"#).unwrap())
+                .is_ok()
+        );
+        assert!(
+            s.validate(&parse_str(r#"{loot: [{name: "axe", stats: {power: "x"}}]}"#).unwrap())
+                .is_err()
+        );
+
+        let src = r#"{
+            "@definitions": {a: {next: {"@ref": "b"}}, b: [{"@ref": "a"}], c: "num"},
+            start: {"@ref": "a"},
+            then: {"@ref": "d"},
+        }"#;
+        let err = Schema::from_value(&parse_str(src).unwrap()).unwrap_err();
+        assert_eq!(err.path, "@definitions.b[]");
+        assert_eq!(err.message, "'a' refers to itself: a -> b -> a");
+        let linter = crate::lint::Linter::for_schemas();
+        let found: Vec<_> = (linter.lint(src).unwrap().iter())
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            found,
+            [
+                r#"error: "@definitions".b[0]: 'a' refers to itself: a -> b -> a [invalid_schema]"#,
+                r#"warning: "@definitions".c: nothing refers to the definition 'c' [unused_definitions]"#,
+                "error: then: unknown definition 'd' [invalid_schema]",
+            ]
+        );
+    }
+
+    #[test]
+    fn resolves_definitions_of_other_files() {
+        let dir = TempDir::new("schema");
+        std::fs::create_dir_all(dir.join("common")).unwrap();
+        std::fs::write(
+            dir.join("common/items.gon"),
+            r#"{"@definitions": {item: {name: "str", stats: {"@ref": "stats.gon#stats"}}}}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("common/stats.gon"),
+            r#"{"@definitions": {stats: {power: "num"}}}"#,
+        )
+        .unwrap();
+        let src = r#"{loot: [{"@ref": "common/items.gon#item"}]}"#;
+        std::fs::write(dir.join("enemy.gon"), src).unwrap();
+        let (s, dependencies) = Schema::from_file_with_dependencies(dir.join("enemy.gon")).unwrap();
+        assert_eq!(
+            dependencies,
+            [dir.join("common/items.gon"), dir.join("common/stats.gon")]
+        );
+        let errors = s
+            .validate(&parse_str(r#"{loot: [{name: "axe", stats: {power: "x"}}]}"#).unwrap())
+            .unwrap_err();
+        assert_eq!(
+            errors.to_string(),
+            "loot[0].stats.power: expected num, found str"
+        );
+        let err = Schema::from_value(&parse_str(src).unwrap()).unwrap_err();
+        assert_eq!(
+            err.message,
+            "can't refer to common/items.gon unless the schema is read with Schema::from_file"
+        );
+
+        std::fs::write(
+            dir.join("broken.gon"),
+            r#"{a: {"@ref": "common/items.gon#tool"}}"#,
+        )
+        .unwrap();
+        let err = Schema::from_file(dir.join("broken.gon")).unwrap_err();
+        assert_eq!(
+            err.message,
+            "common/items.gon: invalid schema at (root): unknown definition 'tool'"
+        );
+
+        // the same file spelled differently is still the same file
+        std::fs::write(
+            dir.join("cycle.gon"),
+            r#"{"@definitions": {x: {"@ref": "common/../cycle.gon#x"}}, v: {"@ref": "x"}}"#,
+        )
+        .unwrap();
+        let err = Schema::from_file(dir.join("./cycle.gon")).unwrap_err();
+        let cycle = dir.join("cycle.gon#x").display().to_string();
+        assert_eq!(
+            err.message,
+            format!("'{cycle}' refers to itself: {cycle} -> {cycle}")
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! Scripts of edits to apply to gon values, usually kept in `.gonq` files.
+//!
+//! A script is a sequence of operations, one per line. Values may span multiple lines,
+//! `//` starts a comment.
+//! - `set <pattern> = <value>` replaces everything the pattern matches with the value. If
+//!   the pattern has no wildcards, missing objects along the way are created.
+//! - `delete <pattern>` removes everything the pattern matches.
+//! - `rename <pattern> to <key>` gives the keys the pattern matches a new name.
+//! - `merge <pattern> <value>` merges the value into everything the pattern matches, see
+//!   [`Value::merge`].
+//!
+//! Patterns are the ones from [`path`](crate::path).
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, script::Script};
+//! let script: Script = "
+//!     // slow everything down
+//!     set enemies[*].speed = 1
+//!     delete **.debug
+//!     rename server.host to hostname
+//!     merge server {
+//!         tls: true,
+//!     }
+//! ".parse().unwrap();
+//! let mut value = parse_str(r#"{
+//!     enemies: [{speed: 3, debug: true}, {speed: 5}],
+//!     server: {host: "localhost"},
+//! }"#).unwrap();
+//! script.apply(&mut value).unwrap();
+//! assert_eq!(value, parse_str(r#"{
+//!     enemies: [{speed: 1}, {speed: 1}],
+//!     server: {hostname: "localhost", tls: true},
+//! }"#).unwrap());
+//! ```
+
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::parser::{Completeness, is_complete};
+use crate::path::{self, PathError, Pattern, Segment, select, untagged_mut};
+use crate::{GonError, Value, parse_str};
+
+/// One edit of a [`Script`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum Op {
+    Set(Pattern, Value),
+    Delete(Pattern),
+    Rename(Pattern, String),
+    Merge(Pattern, Value),
+}
+
+/// A sequence of [`Op`]s together with the lines they start on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Script(pub Vec<(usize, Op)>);
+
+/// Something went wrong reading or applying a [`Script`]
+#[derive(Debug, Error, PartialEq)]
+pub enum ScriptError {
+    /// An operation isn't spelled right
+    #[error("line {0}: {1}")]
+    Syntax(usize, String),
+    /// A pattern couldn't be parsed
+    #[error("line {0}: {1}")]
+    Path(usize, PathError),
+    /// A value couldn't be parsed
+    #[error("line {0}: {1}")]
+    Value(usize, GonError),
+    /// An operation doesn't fit the value it's applied to
+    #[error("line {0}: {1}")]
+    Apply(usize, String),
+}
+
+impl FromStr for Script {
+    type Err = ScriptError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut ops = Vec::new();
+        let mut lines = src.lines().enumerate().map(|(i, line)| (i + 1, line));
+        while let Some((n, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with("//") {
+                continue;
+            }
+            let (word, rest) = split_word(line);
+            let syntax = |message: &str| ScriptError::Syntax(n, message.to_string());
+            let op = match word {
+                "set" | "merge" => {
+                    let (pattern, rest) = split_word(rest);
+                    let pattern = parse_pattern(n, pattern)?;
+                    let rest = match (word, rest.strip_prefix('=')) {
+                        ("set", Some(rest)) => rest,
+                        ("set", None) => return Err(syntax("expected set <pattern> = <value>")),
+                        _ => rest,
+                    };
+                    let mut src = rest.to_string();
+                    while is_complete(&src) == Completeness::Incomplete {
+                        let Some((_, line)) = lines.next() else {
+                            break;
+                        };
+                        src.push('\n');
+                        src.push_str(line);
+                    }
+                    let value = parse_str(&src).map_err(|e| ScriptError::Value(n, e))?;
+                    if word == "set" {
+                        Op::Set(pattern, value)
+                    } else {
+                        Op::Merge(pattern, value)
+                    }
+                }
+                "delete" => match split_word(rest) {
+                    (pattern, "") => Op::Delete(parse_pattern(n, pattern)?),
+                    _ => return Err(syntax("expected delete <pattern>")),
+                },
+                "rename" => {
+                    let (pattern, rest) = split_word(rest);
+                    match split_word(rest) {
+                        ("to", key) if !key.is_empty() => {
+                            Op::Rename(parse_pattern(n, pattern)?, key.to_string())
+                        }
+                        _ => return Err(syntax("expected rename <pattern> to <key>")),
+                    }
+                }
+                _ => {
+                    return Err(syntax(&format!(
+                        "unknown operation '{word}'\n\tExpected one of: set, delete, rename, merge"
+                    )));
+                }
+            };
+            ops.push((n, op));
+        }
+        Ok(Self(ops))
+    }
+}
+
+/// The first word of `s` (respecting quotes) and the rest with leading whitespace removed
+fn split_word(s: &str) -> (&str, &str) {
+    let mut in_quotes = false;
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            c if c.is_whitespace() && !in_quotes => return (&s[..i], s[i..].trim_start()),
+            _ => {}
+        }
+    }
+    (s, "")
+}
+
+fn parse_pattern(line: usize, src: &str) -> Result<Pattern, ScriptError> {
+    src.parse().map_err(|e| ScriptError::Path(line, e))
+}
+
+impl Script {
+    /// Applies every operation to `value` in order. Stops at the first one that fails,
+    /// leaving the edits before it in place.
+    pub fn apply(&self, value: &mut Value) -> Result<(), ScriptError> {
+        for (line, op) in &self.0 {
+            let apply_err = |message: String| ScriptError::Apply(*line, message);
+            match op {
+                Op::Set(pattern, new) => match pattern.concrete() {
+                    Some(path) => {
+                        path::set(value, &path, new.clone())
+                            .map_err(|e| apply_err(e.to_string()))?;
+                    }
+                    None => {
+                        // a match may be gone if something around it was replaced before
+                        for path in matched_paths(value, pattern) {
+                            if let Some(matched) = path::get_mut(value, &path) {
+                                *matched = new.clone();
+                            }
+                        }
+                    }
+                },
+                Op::Delete(pattern) => {
+                    // backwards, so that removing list elements doesn't move later matches
+                    for path in matched_paths(value, pattern).into_iter().rev() {
+                        if path.0.is_empty() {
+                            return Err(apply_err("can't delete the whole value".into()));
+                        }
+                        value.take_path(&path);
+                    }
+                }
+                Op::Rename(pattern, new_key) => {
+                    for path in matched_paths(value, pattern).into_iter().rev() {
+                        let Some((Segment::Key(old_key), parent)) = path.0.split_last() else {
+                            return Err(apply_err(format!(
+                                "can't rename '{path}', it isn't a key"
+                            )));
+                        };
+                        let parent = path::Path(parent.to_vec());
+                        let Some(Value::Obj(obj)) = path::get_mut(value, &parent).map(untagged_mut)
+                        else {
+                            unreachable!("was just matched");
+                        };
+                        if old_key == new_key {
+                            continue;
+                        }
+                        if obj.contains_key(new_key.as_str()) {
+                            return Err(apply_err(format!(
+                                "can't rename '{path}', '{new_key}' already exists"
+                            )));
+                        }
+                        *obj = std::mem::take(obj)
+                            .into_iter()
+                            .map(|(k, v)| {
+                                if *k == **old_key {
+                                    (new_key.as_str().into(), v)
+                                } else {
+                                    (k, v)
+                                }
+                            })
+                            .collect();
+                    }
+                }
+                Op::Merge(pattern, overlay) => {
+                    for path in matched_paths(value, pattern) {
+                        if let Some(matched) = path::get_mut(value, &path) {
+                            matched.merge(overlay.clone());
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The paths `pattern` matches in `value`
+fn matched_paths(value: &Value, pattern: &Pattern) -> Vec<path::Path> {
+    select(value, pattern).map(|(path, _)| path).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(script: &str, src: &str) -> Result<Value, ScriptError> {
+        let mut value = parse_str(src).unwrap();
+        script.parse::<Script>()?.apply(&mut value)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn creates_missing_objects_and_keeps_going() {
+        assert_eq!(
+            applied(
+                "set a.b.c = [1,\n  2]\ndelete x[0]\nrename x to y",
+                "{x: [1, 2]}"
+            ),
+            Ok(parse_str("{a: {b: {c: [1, 2]}}, y: [2]}").unwrap())
+        );
+        assert_eq!(
+            applied("set x[5] = 1", "{x: [1, 2]}")
+                .unwrap_err()
+                .to_string(),
+            "line 1: can't set 'x[5]', a list on the way has 2 elements"
+        );
+    }
+
+    #[test]
+    fn reports_lines() {
+        assert!(matches!(
+            applied("\n// comment\nremove a", "{}"),
+            Err(ScriptError::Syntax(3, _))
+        ));
+        assert!(matches!(
+            applied("set a = {", "{}"),
+            Err(ScriptError::Value(1, _))
+        ));
+        assert!(matches!(
+            applied("delete a..b", "{}"),
+            Err(ScriptError::Path(1, _))
+        ));
+        assert!(matches!(
+            applied("set a = 1\nrename * to a", "{b: 2}"),
+            Err(ScriptError::Apply(2, _))
+        ));
+    }
+}
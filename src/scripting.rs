@@ -0,0 +1,170 @@
+//! Transforming gon values with [rhai](https://rhai.rs) scripts, behind the `rhai` feature.
+//!
+//! The script sees the value as the variable `value`, objects as maps, lists as arrays and
+//! `None` as `()`, and whatever `value` holds when the script ends is the result. Scripts
+//! can't touch the file system or the network and run within [`ScriptLimits`].
+//!
+//! Numbers become integers if they fit into one and floats otherwise, so numbers like
+//! `1.50` come back as `1.5` even if the script didn't change them.
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, scripting::ScriptEngine};
+//! let mut value = parse_str("{enemies: [{hp: 10}, {hp: 20}]}").unwrap();
+//! ScriptEngine::new()
+//!     .run("for i in 0..value.enemies.len() { value.enemies[i].hp *= 2; }", &mut value)
+//!     .unwrap();
+//! assert_eq!(value, parse_str("{enemies: [{hp: 20}, {hp: 40}]}").unwrap());
+//! ```
+
+use rhai::{Array, Dynamic, Engine, Map, Scope};
+use thiserror::Error;
+
+use crate::{MapT, Value};
+
+/// How much a script may do before it's stopped
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ScriptLimits {
+    /// How many operations a script may run, roughly a measure of time
+    pub max_operations: u64,
+    /// How deep functions may call each other
+    pub max_call_levels: usize,
+    /// How deeply expressions may nest
+    pub max_expr_depth: usize,
+    /// The longest string a script may build, in bytes
+    pub max_string_size: usize,
+    /// The most elements a list may have
+    pub max_array_size: usize,
+    /// The most keys an object may have
+    pub max_map_size: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 10_000_000,
+            max_call_levels: 64,
+            max_expr_depth: 64,
+            max_string_size: 1 << 20,
+            max_array_size: 1 << 20,
+            max_map_size: 1 << 16,
+        }
+    }
+}
+
+/// Something went wrong running a script
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum ScriptEngineError {
+    /// The script failed or hit one of its limits
+    #[error("script failed: {0}")]
+    Script(String),
+    /// The script left something in `value` that gon can't represent
+    #[error("script left a {0} in the value, which gon can't represent")]
+    Unrepresentable(String),
+}
+
+/// Runs rhai scripts on gon values, see the [module docs](self)
+pub struct ScriptEngine {
+    engine: Engine,
+}
+
+impl Default for ScriptEngine {
+    fn default() -> Self {
+        Self::with_limits(ScriptLimits::default())
+    }
+}
+
+impl ScriptEngine {
+    /// An engine with the default [`ScriptLimits`]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(limits: ScriptLimits) -> Self {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(limits.max_operations)
+            .set_max_call_levels(limits.max_call_levels)
+            .set_max_expr_depths(limits.max_expr_depth, limits.max_expr_depth)
+            .set_max_string_size(limits.max_string_size)
+            .set_max_array_size(limits.max_array_size)
+            .set_max_map_size(limits.max_map_size);
+        Self { engine }
+    }
+
+    /// Runs `script` with `value` bound to the variable `value` and puts what the variable
+    /// holds afterwards back into `value`. `value` stays as it was if anything fails.
+    pub fn run(&self, script: &str, value: &mut Value) -> Result<(), ScriptEngineError> {
+        let mut scope = Scope::new();
+        scope.push_dynamic("value", to_dynamic(value));
+        self.engine
+            .run_with_scope(&mut scope, script)
+            .map_err(|e| ScriptEngineError::Script(e.to_string()))?;
+        let result = scope.get_value::<Dynamic>("value").unwrap_or(Dynamic::UNIT);
+        *value = from_dynamic(result)?;
+        Ok(())
+    }
+}
+
+fn to_dynamic(value: &Value) -> Dynamic {
+    match value {
+        Value::None => Dynamic::UNIT,
+        Value::Bool(b) => Dynamic::from_bool(*b),
+        Value::Num(n) => match (
+            value.as_i128().and_then(|i| i64::try_from(i).ok()),
+            value.as_f64(),
+        ) {
+            (Some(i), _) => Dynamic::from_int(i),
+            (None, Some(f)) => Dynamic::from_float(f),
+            (None, None) => Dynamic::from(n.clone()),
+        },
+        Value::Str { s, .. } => Dynamic::from(s.clone()),
+        Value::List(xs) => Dynamic::from_array(xs.iter().map(to_dynamic).collect()),
+        Value::Obj(obj) => Dynamic::from_map(
+            obj.iter()
+                .map(|(k, v)| ((&**k).into(), to_dynamic(v)))
+                .collect(),
+        ),
+        Value::Tagged { value, .. } => to_dynamic(value),
+        Value::Raw(text) => to_dynamic(&crate::value::parse_raw(text)),
+    }
+}
+
+fn from_dynamic(dynamic: Dynamic) -> Result<Value, ScriptEngineError> {
+    if dynamic.is_unit() {
+        return Ok(Value::None);
+    }
+    if let Ok(b) = dynamic.as_bool() {
+        return Ok(Value::Bool(b));
+    }
+    if let Ok(i) = dynamic.as_int() {
+        return Ok(Value::Num(i.to_string()));
+    }
+    if let Ok(f) = dynamic.as_float() {
+        return if f.is_finite() {
+            Ok(Value::Num(f.to_string()))
+        } else {
+            Err(ScriptEngineError::Unrepresentable(format!("float {f}")))
+        };
+    }
+    if dynamic.is_string() {
+        let s = dynamic.into_string().expect("checked to be a string");
+        return Ok(Value::Str { s, raw: false });
+    }
+    if dynamic.is_array() {
+        let xs: Array = dynamic.into_array().expect("checked to be an array");
+        return xs
+            .into_iter()
+            .map(from_dynamic)
+            .collect::<Result<_, _>>()
+            .map(Value::List);
+    }
+    let type_name = dynamic.type_name().to_string();
+    match dynamic.try_cast::<Map>() {
+        Some(map) => map
+            .into_iter()
+            .map(|(k, v)| Ok((k.as_str().into(), from_dynamic(v)?)))
+            .collect::<Result<MapT, _>>()
+            .map(Value::Obj),
+        None => Err(ScriptEngineError::Unrepresentable(type_name)),
+    }
+}
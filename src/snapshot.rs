@@ -0,0 +1,194 @@
+//! Immutable snapshots of values that share unchanged parts, for undo and redo.
+//!
+//! A [`Snapshot`] is a persistent tree: cloning it is cheap and every object, list and scalar
+//! in it is reference counted. [`Value::snapshot_from`] takes a snapshot that reuses the
+//! parts of an older one that didn't change, so keeping a long history of a big document
+//! only costs memory for what was edited in each step. Taking a snapshot still walks the
+//! whole value to find the changes. [`Value::restore`] turns a snapshot back into a value.
+//!
+//! Only available with the `snapshots` feature.
+//! # Usage example
+//! ```rust
+//! use gon::parse_str;
+//! let mut doc = parse_str("{title: \"draft\", chapters: [{words: 1000}, {words: 20}]}").unwrap();
+//! let mut undo = vec![doc.snapshot()];
+//!
+//! doc.set_path(&"title".parse().unwrap(), parse_str("\"final\"").unwrap()).unwrap();
+//! undo.push(doc.snapshot_from(undo.last().unwrap()));
+//! assert!(undo[1].shares(&undo[0], &"chapters".parse().unwrap()));
+//!
+//! undo.pop();
+//! doc.restore(undo.last().unwrap());
+//! assert_eq!(doc.get_path(&"title".parse().unwrap()), Some(&parse_str("\"draft\"").unwrap()));
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::path::{Path, Segment};
+use crate::{KeyT, MapT, Value};
+
+/// A value frozen in time, see the [module docs](self)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot(Arc<Node>);
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    /// Anything but an object, a list or a tagged value
+    Scalar(Value),
+    /// The entries in the order of the map they're from
+    Obj(Vec<(KeyT, Snapshot)>),
+    List(Vec<Snapshot>),
+    Tagged(String, Snapshot),
+}
+
+impl Value {
+    /// Takes a snapshot of this value. See the [`snapshot` module](crate::snapshot).
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot(Arc::new(match self {
+            Self::Obj(obj) => {
+                Node::Obj(obj.iter().map(|(k, v)| (k.clone(), v.snapshot())).collect())
+            }
+            Self::List(xs) => Node::List(xs.iter().map(Value::snapshot).collect()),
+            Self::Tagged { tag, value } => Node::Tagged(tag.clone(), value.snapshot()),
+            scalar => Node::Scalar(scalar.clone()),
+        }))
+    }
+
+    /// Takes a snapshot of this value that shares everything that didn't change with `base`,
+    /// usually the snapshot taken before the last edit
+    pub fn snapshot_from(&self, base: &Snapshot) -> Snapshot {
+        let node = match (self, &*base.0) {
+            (Self::Obj(obj), Node::Obj(entries)) => {
+                let old: HashMap<&str, &Snapshot> =
+                    entries.iter().map(|(k, s)| (&**k, s)).collect();
+                let new: Vec<_> = obj
+                    .iter()
+                    .map(|(k, v)| {
+                        let snapshot = match old.get(&**k) {
+                            Some(old) => v.snapshot_from(old),
+                            None => v.snapshot(),
+                        };
+                        (k.clone(), snapshot)
+                    })
+                    .collect();
+                // the order of the entries only matters with `preserve_order`
+                let unchanged = new.len() == entries.len()
+                    && new
+                        .iter()
+                        .all(|(k, s)| old.get(&**k).is_some_and(|old| s.same(old)))
+                    && (!cfg!(feature = "preserve_order")
+                        || new
+                            .iter()
+                            .zip(entries)
+                            .all(|((k, _), (old_k, _))| k == old_k));
+                if unchanged {
+                    return base.clone();
+                }
+                Node::Obj(new)
+            }
+            (Self::List(xs), Node::List(items)) => {
+                let new: Vec<_> = xs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| match items.get(i) {
+                        Some(old) => x.snapshot_from(old),
+                        None => x.snapshot(),
+                    })
+                    .collect();
+                if new.len() == items.len() && new.iter().zip(items).all(|(s, old)| s.same(old)) {
+                    return base.clone();
+                }
+                Node::List(new)
+            }
+            (Self::Tagged { tag, value }, Node::Tagged(old_tag, old)) => {
+                let inner = value.snapshot_from(old);
+                if tag == old_tag && inner.same(old) {
+                    return base.clone();
+                }
+                Node::Tagged(tag.clone(), inner)
+            }
+            (scalar, Node::Scalar(old)) if scalar == old => return base.clone(),
+            _ => return self.snapshot(),
+        };
+        Snapshot(Arc::new(node))
+    }
+
+    /// Replaces this value with the one `snapshot` was taken of
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        *self = snapshot.to_value();
+    }
+}
+
+impl Snapshot {
+    /// The value this snapshot was taken of
+    pub fn to_value(&self) -> Value {
+        match &*self.0 {
+            Node::Scalar(value) => value.clone(),
+            Node::Obj(entries) => Value::Obj(
+                entries
+                    .iter()
+                    .map(|(k, s)| (k.clone(), s.to_value()))
+                    .collect::<MapT>(),
+            ),
+            Node::List(items) => Value::List(items.iter().map(Snapshot::to_value).collect()),
+            Node::Tagged(tag, inner) => Value::tagged(tag.clone(), inner.to_value()),
+        }
+    }
+
+    /// Whether this and `other` hold the very same part at `path`, rather than equal copies.
+    /// `false` if either of them has nothing there.
+    pub fn shares(&self, other: &Snapshot, path: &Path) -> bool {
+        match (self.get(path), other.get(path)) {
+            (Some(a), Some(b)) => a.same(b),
+            _ => false,
+        }
+    }
+
+    fn get(&self, path: &Path) -> Option<&Snapshot> {
+        path.0.iter().try_fold(self, |snapshot, segment| {
+            let mut node = &*snapshot.0;
+            while let Node::Tagged(_, inner) = node {
+                node = &*inner.0;
+            }
+            match (node, segment) {
+                (Node::Obj(entries), Segment::Key(key)) => {
+                    entries.iter().find(|(k, _)| **k == **key).map(|(_, s)| s)
+                }
+                (Node::List(items), Segment::Index(i)) => items.get(*i),
+                _ => None,
+            }
+        })
+    }
+
+    fn same(&self, other: &Snapshot) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn shares_unchanged_parts() {
+        let mut value = parse_str("{a: {b: [1, 2]}, d: 1}").unwrap();
+        let c = Value::tagged("color", parse_str("\"red\"").unwrap());
+        value.set_path(&"c".parse().unwrap(), c).unwrap();
+        let original = value.clone();
+        let first = value.snapshot();
+        assert!(value.snapshot_from(&first).same(&first));
+        value
+            .set_path(&"d".parse().unwrap(), parse_str("2").unwrap())
+            .unwrap();
+        value.take_path(&"a.b[1]".parse().unwrap());
+        let second = value.snapshot_from(&first);
+        assert!(second.shares(&first, &"c".parse().unwrap()));
+        assert!(second.shares(&first, &"a.b[0]".parse().unwrap()));
+        assert!(!second.shares(&first, &"a".parse().unwrap()));
+        assert_eq!(second.to_value(), value);
+        value.restore(&first);
+        assert_eq!(value, original);
+    }
+}
@@ -0,0 +1,122 @@
+//! Telling the format of an input from its bytes, for tools that read mixed directories.
+//!
+//! [`sniff`] looks at byte patterns first and then tries to parse the input: bytes that
+//! aren't text but start like a MessagePack map or array are [`Format::MsgPack`], text that
+//! parses as JSON is [`Format::Json`], text that parses as gon is [`Format::Gon`] and text
+//! that starts like a YAML document or mapping is [`Format::Yaml`]. Everything else is
+//! [`Format::Gon`], so parsing it reports what's wrong with it as gon. JSON is only told
+//! apart with the `json` feature, without it JSON is [`Format::Gon`], which parses it too.
+//! # Usage example
+//! ```rust
+//! use gon::sniff::{sniff, Format};
+//! assert_eq!(sniff(b"{name: \"gon\"}"), Format::Gon);
+//! assert_eq!(sniff(b"---\nname: yaml\n"), Format::Yaml);
+//! assert_eq!(sniff(b"\x81\xA4name\xA7msgpack"), Format::MsgPack);
+//! ```
+
+use std::fmt;
+
+use crate::encoding::{Encoding, decode};
+use crate::parser::parse_str;
+
+/// A format that [`sniff`] tells apart
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    Gon,
+    Json,
+    Yaml,
+    MsgPack,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Gon => "gon",
+            Self::Json => "JSON",
+            Self::Yaml => "YAML",
+            Self::MsgPack => "MessagePack",
+        })
+    }
+}
+
+/// The format of `bytes`, see the [`sniff` module](crate::sniff)
+pub fn sniff(bytes: &[u8]) -> Format {
+    let Ok(text) = decode(bytes, Encoding::Utf8) else {
+        return if bytes.first().is_some_and(|b| is_msgpack_container(*b)) {
+            Format::MsgPack
+        } else {
+            Format::Gon
+        };
+    };
+    if is_json(&text) {
+        Format::Json
+    } else if parse_str(&text).is_err() && looks_like_yaml(&text) {
+        Format::Yaml
+    } else {
+        Format::Gon
+    }
+}
+
+/// Is `byte` the first byte of a MessagePack map or array? Those are continuation bytes or
+/// start invalid sequences in UTF-8, so text never starts with them.
+fn is_msgpack_container(byte: u8) -> bool {
+    // fixmap, fixarray, array 16/32 and map 16/32
+    matches!(byte, 0x80..=0x9F | 0xDC..=0xDF)
+}
+
+#[cfg(feature = "json")]
+fn is_json(text: &str) -> bool {
+    serde_json::from_str::<serde_json::Value>(text).is_ok()
+}
+
+#[cfg(not(feature = "json"))]
+fn is_json(_text: &str) -> bool {
+    false
+}
+
+/// Does the first line that isn't blank or a comment start a YAML document, list or mapping?
+fn looks_like_yaml(text: &str) -> bool {
+    let Some(line) = text
+        .lines()
+        .map(str::trim_end)
+        .find(|line| !line.trim_start().is_empty() && !line.trim_start().starts_with('#'))
+    else {
+        return false;
+    };
+    if line.starts_with("---") || line.starts_with("%YAML") || line.starts_with("- ") {
+        return true;
+    }
+    line.split_once(':').is_some_and(|(key, rest)| {
+        let key = key.trim_matches(|c| c == '"' || c == '\'');
+        !key.is_empty()
+            && key
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ' '))
+            && (rest.is_empty() || rest.starts_with(' '))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_formats_apart() {
+        assert_eq!(sniff(b"[1, 2, 3,]"), Format::Gon);
+        assert_eq!(sniff(b"{a: 1 // comment\n}"), Format::Gon);
+        assert_eq!(sniff(b"\xEF\xBB\xBF{a: 1}"), Format::Gon);
+        // broken gon stays gon, so parsing it reports the error
+        assert_eq!(sniff(b"{a: [1, 2}"), Format::Gon);
+        assert_eq!(sniff(b"\xFF\x00"), Format::Gon);
+        assert_eq!(sniff(b"# config\nname: gon\nport: 80\n"), Format::Yaml);
+        assert_eq!(sniff(b"- one\n- two\n"), Format::Yaml);
+        assert_eq!(sniff(b"\x92\x01\x02"), Format::MsgPack);
+        assert_eq!(sniff(b"\xDE\x00\x01\xA1a\x01"), Format::MsgPack);
+        let json = br#"{"a": [1, 2.5, null], "b": "c"}"#;
+        if cfg!(feature = "json") {
+            assert_eq!(sniff(json), Format::Json);
+        } else {
+            assert_eq!(sniff(json), Format::Gon);
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Byte offset based locations in gon source text.
+//!
+//! [`parser::tokenize`](crate::parser::tokenize) reports where tokens are as [`Span`]s, a
+//! [`LineIndex`] converts between those byte offsets and the lines and columns that humans
+//! (and the parser's error messages) use.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::span::{LineCol, LineIndex};
+//! let src = "{\n    speed: 1,\n}";
+//! let index = LineIndex::new(src);
+//! assert_eq!(index.line_col(6), LineCol { line: 2, col: 5 });
+//! assert_eq!(index.offset(LineCol { line: 2, col: 5 }), Some(6));
+//! ```
+
+use std::ops::Range;
+
+/// A part of the source text, `len` bytes starting at byte `start`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Span {
+    pub start: usize,
+    pub len: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, len: usize) -> Self {
+        Self { start, len }
+    }
+
+    /// The byte right after the span
+    pub fn end(&self) -> usize {
+        self.start + self.len
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end()
+    }
+
+    /// Whether the byte at `offset` is part of the span
+    pub fn contains(&self, offset: usize) -> bool {
+        self.range().contains(&offset)
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Self::new(range.start, range.end.saturating_sub(range.start))
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.range()
+    }
+}
+
+/// A position as line and column, both starting at 1. Columns count chars, not bytes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Converts between byte offsets and [`LineCol`]s of one source text
+#[derive(Clone, Debug)]
+pub struct LineIndex {
+    src: String,
+    /// The byte offset of the start of every line
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(src: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            src: src.to_string(),
+            line_starts,
+        }
+    }
+
+    /// The number of lines, which is one more than the number of line breaks
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The line and column of the byte at `offset`. Offsets inside of a char count as that
+    /// char, offsets past the end as the end.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let offset = offset.min(self.src.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        let col = self.src[line_start..]
+            .char_indices()
+            .take_while(|(i, _)| line_start + i < offset)
+            .count();
+        let col = if self.src.is_char_boundary(offset) {
+            col
+        } else {
+            col - 1
+        };
+        LineCol {
+            line: line + 1,
+            col: col + 1,
+        }
+    }
+
+    /// The byte offset of `pos`, `None` if there is no such line or the line is too short.
+    /// The column right after the end of a line is allowed.
+    pub fn offset(&self, pos: LineCol) -> Option<usize> {
+        let line_start = *self.line_starts.get(pos.line.checked_sub(1)?)?;
+        let line_end = self
+            .line_starts
+            .get(pos.line)
+            .map_or(self.src.len(), |next| next - 1);
+        let line = &self.src[line_start..line_end];
+        let col = pos.col.checked_sub(1)?;
+        line.char_indices()
+            .map(|(i, _)| i)
+            .chain(std::iter::once(line.len()))
+            .nth(col)
+            .map(|i| line_start + i)
+    }
+
+    /// The positions of the first and right after the last byte of `span`
+    pub fn span_line_cols(&self, span: Span) -> (LineCol, LineCol) {
+        (self.line_col(span.start), self.line_col(span.end()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets_and_line_cols_round_trip() {
+        let src = "a\nüb\n\nc";
+        let index = LineIndex::new(src);
+        assert_eq!(index.line_count(), 4);
+        for (offset, line, col) in [
+            (0, 1, 1),
+            (1, 1, 2),
+            (2, 2, 1),
+            (4, 2, 2),
+            (6, 3, 1),
+            (7, 4, 1),
+            (8, 4, 2),
+        ] {
+            let pos = LineCol { line, col };
+            assert_eq!(index.line_col(offset), pos, "{offset}");
+            assert_eq!(index.offset(pos), Some(offset), "{offset}");
+        }
+        assert_eq!(index.line_col(3), LineCol { line: 2, col: 1 });
+        assert_eq!(index.line_col(100), LineCol { line: 4, col: 2 });
+        assert_eq!(index.offset(LineCol { line: 2, col: 4 }), None);
+        assert_eq!(index.offset(LineCol { line: 5, col: 1 }), None);
+        assert_eq!(index.offset(LineCol { line: 0, col: 1 }), None);
+        assert_eq!(
+            index.span_line_cols(Span::from(2..5)),
+            (LineCol { line: 2, col: 1 }, LineCol { line: 2, col: 3 })
+        );
+    }
+}
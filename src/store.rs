@@ -0,0 +1,583 @@
+//! Named documents shared between threads and changed in transactions.
+//!
+//! A [`Store`] holds gon values by name. Reading is done through cheap snapshots that never
+//! change underneath the reader. Writing is done in a [`Transaction`]: it sees the documents
+//! as they were when it first read them, collects changes and applies all of them on
+//! [`Transaction::commit`], unless another transaction committed a change to a document it
+//! read in the meantime. Then the commit fails with [`StoreError::Conflict`] and nothing is
+//! changed, so the caller can start over on fresh data.
+//!
+//! A store [opened](Store::open) on a directory keeps every document in a `<name>.gon` file.
+//! Commits change all of their files or none of them, editing the existing text as little
+//! as possible like [`Document::update`] does.
+//!
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, store::{Store, StoreError}};
+//! let store = Store::new();
+//! let mut tx = store.begin();
+//! tx.put("limits", parse_str("{players: 8}").unwrap()).unwrap();
+//! tx.commit().unwrap();
+//!
+//! let mut alice = store.begin();
+//! let mut bob = store.begin();
+//! alice.modify("limits", |v| *v = parse_str("{players: 16}").unwrap()).unwrap();
+//! bob.modify("limits", |v| *v = parse_str("{players: 4}").unwrap()).unwrap();
+//! alice.commit().unwrap();
+//! assert!(matches!(bob.commit(), Err(StoreError::Conflict(name)) if name == "limits"));
+//! assert_eq!(*store.get("limits").unwrap(), parse_str("{players: 16}").unwrap());
+//! ```
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use thiserror::Error;
+
+use crate::document::Document;
+use crate::{GonError, Value, parse_str};
+
+/// The extension of the files of a store opened on a directory
+const EXTENSION: &str = "gon";
+
+/// Named documents, see the [module docs](self). Share it between threads with an [`Arc`]
+/// or by reference.
+#[derive(Debug, Default)]
+pub struct Store {
+    /// Where the documents are kept, `None` for stores that only live in memory
+    dir: Option<PathBuf>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    docs: HashMap<String, Doc>,
+    /// The version the next committed change gets
+    next_version: u64,
+}
+
+#[derive(Debug)]
+struct Doc {
+    value: Arc<Value>,
+    /// Changes with every commit of the document, never repeats
+    version: u64,
+    /// The text of the file last read or written
+    src: Option<String>,
+}
+
+/// The documents of a [`Store`] at one point in time
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    docs: HashMap<String, Arc<Value>>,
+}
+
+/// Changes to a [`Store`] that are applied together, see the [module docs](self). Dropping
+/// it without committing discards the changes.
+#[derive(Debug)]
+pub struct Transaction<'s> {
+    store: &'s Store,
+    /// The version of every document read and what it was, `None` if it didn't exist
+    reads: HashMap<String, (u64, Option<Arc<Value>>)>,
+    /// The new value of every changed document, `None` to remove it
+    writes: HashMap<String, Option<Value>>,
+}
+
+/// Something went wrong while using a [`Store`]
+#[derive(Debug, Error)]
+pub enum StoreError {
+    /// A document file couldn't be read
+    #[error("couldn't read store document '{0}': {1}")]
+    Io(String, std::io::Error),
+    /// A document file isn't valid gon
+    #[error("couldn't parse store document '{0}': {1}")]
+    Parse(String, GonError),
+    /// A document file couldn't be written or removed
+    #[error("couldn't write store document '{0}': {1}")]
+    Write(String, std::io::Error),
+    /// Another transaction committed a change to a document after it was read
+    #[error("document '{0}' was changed by another transaction")]
+    Conflict(String),
+    /// There is no document with the name
+    #[error("no document named '{0}'")]
+    NotFound(String),
+    /// The name can't be used as a file name
+    #[error("invalid document name '{0}'")]
+    InvalidName(String),
+}
+
+impl Store {
+    /// An empty store that only lives in memory
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A store that keeps its documents in `dir`, which is created if it doesn't exist.
+    /// Every `<name>.gon` file in it is loaded as the document `name`.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self, StoreError> {
+        let dir = dir.into();
+        let name = || dir.display().to_string();
+        std::fs::create_dir_all(&dir).map_err(|e| StoreError::Io(name(), e))?;
+        let mut state = State::default();
+        for entry in std::fs::read_dir(&dir).map_err(|e| StoreError::Io(name(), e))? {
+            let path = entry.map_err(|e| StoreError::Io(name(), e))?.path();
+            let Some(doc_name) = doc_name(&path) else {
+                continue;
+            };
+            let file = || path.display().to_string();
+            let src = std::fs::read_to_string(&path).map_err(|e| StoreError::Io(file(), e))?;
+            let value = parse_str(&src).map_err(|e| StoreError::Parse(file(), e))?;
+            let version = state.bump();
+            state.docs.insert(
+                doc_name,
+                Doc {
+                    value: Arc::new(value),
+                    version,
+                    src: Some(src),
+                },
+            );
+        }
+        Ok(Self {
+            dir: Some(dir),
+            state: Mutex::new(state),
+        })
+    }
+
+    /// The current value of the document `name`
+    pub fn get(&self, name: &str) -> Option<Arc<Value>> {
+        self.state()
+            .docs
+            .get(name)
+            .map(|doc| Arc::clone(&doc.value))
+    }
+
+    /// The current values of all documents
+    pub fn snapshot(&self) -> Snapshot {
+        let docs = self
+            .state()
+            .docs
+            .iter()
+            .map(|(name, doc)| (name.clone(), Arc::clone(&doc.value)))
+            .collect();
+        Snapshot { docs }
+    }
+
+    /// Starts a transaction
+    pub fn begin(&self) -> Transaction<'_> {
+        Transaction {
+            store: self,
+            reads: HashMap::new(),
+            writes: HashMap::new(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        // the state is only changed once all fallible work is done, so it's consistent
+        // even if another thread panicked while holding the lock
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn file(&self, name: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{name}.{EXTENSION}")))
+    }
+
+    /// Writes the new text of every file `writes` changes to a temporary file, spelled by
+    /// editing the text in `based_on`
+    fn stage(
+        &self,
+        writes: &HashMap<String, Option<Value>>,
+        based_on: &HashMap<&str, (u64, Option<String>)>,
+    ) -> Result<Staged, StoreError> {
+        static NEXT_TMP: AtomicU64 = AtomicU64::new(0);
+        let mut staged = Staged::default();
+        for (name, value) in writes {
+            let Some(file) = self.file(name) else {
+                continue;
+            };
+            let new = value.as_ref().map(|value| {
+                let old = based_on
+                    .get(name.as_str())
+                    .and_then(|(_, src)| src.as_deref());
+                // every transaction stages to its own file, even for the same document
+                let n = NEXT_TMP.fetch_add(1, Ordering::Relaxed);
+                let tmp = file.with_extension(format!("{EXTENSION}.{n}.tmp"));
+                (tmp, spell_updated(old, value))
+            });
+            let written = new.as_ref().map(|(tmp, src)| {
+                write_synced(tmp, src).map_err(|e| StoreError::Write(tmp.display().to_string(), e))
+            });
+            // pushed even if writing failed, to remove what was written
+            staged.changes.push(Change {
+                name: name.clone(),
+                file,
+                new,
+            });
+            if let Some(Err(e)) = written {
+                staged.discard();
+                return Err(e);
+            }
+        }
+        Ok(staged)
+    }
+}
+
+impl State {
+    fn bump(&mut self) -> u64 {
+        self.next_version += 1;
+        self.next_version
+    }
+
+    /// The version of the document `name`, 0 if it doesn't exist
+    fn version(&self, name: &str) -> u64 {
+        self.docs.get(name).map_or(0, |doc| doc.version)
+    }
+
+    fn version_and_src(&self, name: &str) -> (u64, Option<String>) {
+        let src = self.docs.get(name).and_then(|doc| doc.src.clone());
+        (self.version(name), src)
+    }
+}
+
+/// The files a commit changes, with their new text written next to them
+#[derive(Debug, Default)]
+struct Staged {
+    changes: Vec<Change>,
+}
+
+#[derive(Debug)]
+struct Change {
+    name: String,
+    file: PathBuf,
+    /// The new text and the temporary file it's in, `None` to remove the file
+    new: Option<(PathBuf, String)>,
+}
+
+impl Snapshot {
+    /// The value of the document `name`
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.docs.get(name).map(|v| &**v)
+    }
+
+    /// The names of all documents, in no particular order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.docs.keys().map(String::as_str)
+    }
+}
+
+impl Transaction<'_> {
+    /// The document `name` as this transaction sees it: with its own changes, otherwise as
+    /// it was when the transaction first read it
+    pub fn get(&mut self, name: &str) -> Option<&Value> {
+        if self.writes.contains_key(name) {
+            return self.writes[name].as_ref();
+        }
+        self.read(name).as_deref()
+    }
+
+    /// Sets the document `name`, creating it if it doesn't exist. This doesn't read the
+    /// document, so it can't conflict with other transactions.
+    pub fn put(&mut self, name: &str, value: Value) -> Result<(), StoreError> {
+        check_name(name)?;
+        self.writes.insert(name.to_string(), Some(value));
+        Ok(())
+    }
+
+    /// Removes the document `name`, if it exists
+    pub fn remove(&mut self, name: &str) -> Result<(), StoreError> {
+        check_name(name)?;
+        self.writes.insert(name.to_string(), None);
+        Ok(())
+    }
+
+    /// Changes the document `name` with `f`. The document counts as read, so the commit
+    /// fails if another transaction changes it first.
+    pub fn modify(&mut self, name: &str, f: impl FnOnce(&mut Value)) -> Result<(), StoreError> {
+        check_name(name)?;
+        if !self.writes.contains_key(name) {
+            let Some(current) = self.read(name).as_deref().cloned() else {
+                return Err(StoreError::NotFound(name.to_string()));
+            };
+            self.writes.insert(name.to_string(), Some(current));
+        }
+        match self.writes.get_mut(name) {
+            Some(Some(value)) => {
+                f(value);
+                Ok(())
+            }
+            _ => Err(StoreError::NotFound(name.to_string())),
+        }
+    }
+
+    /// Applies all changes, unless a document this transaction read was changed by another
+    /// transaction since. Then nothing is changed.
+    ///
+    /// The new text of every changed file is written to a temporary file first, without
+    /// blocking other transactions. Then the old files are moved aside and the new ones
+    /// renamed into place. If one of these renames fails, the ones before it are undone
+    /// and the commit fails. Only a crash in the middle of this can leave some of the
+    /// files changed, with the old versions kept as `<name>.gon.bak`.
+    pub fn commit(self) -> Result<(), StoreError> {
+        let store = self.store;
+        let (mut state, staged) = loop {
+            // the version and text of every document to change, as the new text is spelled
+            // by editing the old one
+            let based_on: HashMap<_, _> = {
+                let state = store.state();
+                self.check_reads(&state)?;
+                (self.writes.keys())
+                    .map(|name| (name.as_str(), state.version_and_src(name)))
+                    .collect()
+            };
+            let staged = store.stage(&self.writes, &based_on)?;
+            let state = store.state();
+            if let Err(e) = self.check_reads(&state) {
+                staged.discard();
+                return Err(e);
+            }
+            // a document that's only written was changed by another transaction while
+            // staging, so the text has to be spelled again
+            let unchanged =
+                (based_on.iter()).all(|(name, (version, _))| state.version(name) == *version);
+            if unchanged {
+                break (state, staged);
+            }
+            drop(state);
+            staged.discard();
+        };
+        let mut srcs = staged.apply()?;
+        for (name, value) in self.writes {
+            match value {
+                Some(value) => {
+                    let version = state.bump();
+                    let doc = Doc {
+                        value: Arc::new(value),
+                        version,
+                        src: srcs.remove(&name),
+                    };
+                    state.docs.insert(name, doc);
+                }
+                None => {
+                    state.docs.remove(&name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_reads(&self, state: &State) -> Result<(), StoreError> {
+        for (name, (version, _)) in &self.reads {
+            if state.version(name) != *version {
+                return Err(StoreError::Conflict(name.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// The document `name` as it was when this transaction first read it
+    fn read(&mut self, name: &str) -> &Option<Arc<Value>> {
+        let store = self.store;
+        &self
+            .reads
+            .entry(name.to_string())
+            .or_insert_with(|| match store.state().docs.get(name) {
+                Some(doc) => (doc.version, Some(Arc::clone(&doc.value))),
+                None => (0, None),
+            })
+            .1
+    }
+}
+
+impl Staged {
+    /// Moves the new files into place, or none of them if one of the renames fails.
+    /// Returns the new text of every document by name.
+    fn apply(self) -> Result<HashMap<String, String>, StoreError> {
+        let mut applied = Vec::with_capacity(self.changes.len());
+        for change in &self.changes {
+            match change.apply() {
+                Ok(backup) => applied.push((change, backup)),
+                Err(e) => {
+                    for (change, backup) in applied.into_iter().rev() {
+                        change.undo(backup);
+                    }
+                    let file = change.file.display().to_string();
+                    self.discard();
+                    return Err(StoreError::Write(file, e));
+                }
+            }
+        }
+        for backup in applied.into_iter().filter_map(|(_, backup)| backup) {
+            let _ = std::fs::remove_file(backup);
+        }
+        let srcs = (self.changes.into_iter())
+            .filter_map(|change| Some((change.name, change.new?.1)))
+            .collect();
+        Ok(srcs)
+    }
+
+    /// Removes the temporary files
+    fn discard(&self) {
+        for (tmp, _) in self.changes.iter().filter_map(|change| change.new.as_ref()) {
+            let _ = std::fs::remove_file(tmp);
+        }
+    }
+}
+
+impl Change {
+    /// Moves the old file aside and the new one into place. Returns where the old file is
+    /// now, `None` if there was none.
+    fn apply(&self) -> io::Result<Option<PathBuf>> {
+        let backup = self.file.with_extension(format!("{EXTENSION}.bak"));
+        let backup = match std::fs::rename(&self.file, &backup) {
+            Ok(()) => Some(backup),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let renamed = match &self.new {
+            Some((tmp, _)) => std::fs::rename(tmp, &self.file),
+            None => Ok(()),
+        };
+        if let Err(e) = renamed {
+            self.undo(backup);
+            return Err(e);
+        }
+        Ok(backup)
+    }
+
+    /// Puts the old file back in place
+    fn undo(&self, backup: Option<PathBuf>) {
+        match backup {
+            Some(backup) => {
+                let _ = std::fs::rename(backup, &self.file);
+            }
+            None => {
+                let _ = std::fs::remove_file(&self.file);
+            }
+        }
+    }
+}
+
+/// The name of the document kept in the file at `path`, if it is a document file
+fn doc_name(path: &Path) -> Option<String> {
+    if path.extension()? != EXTENSION || !path.is_file() {
+        return None;
+    }
+    let name = path.file_stem()?.to_str()?;
+    check_name(name).ok()?;
+    Some(name.to_string())
+}
+
+fn check_name(name: &str) -> Result<(), StoreError> {
+    let invalid = name.is_empty()
+        || name.starts_with('.')
+        || name.contains(['/', '\\', '\0'])
+        || name.ends_with(".tmp");
+    if invalid {
+        Err(StoreError::InvalidName(name.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+/// `value` spelled by editing `old` as little as possible, or from scratch if there is no
+/// old text or it can't be edited in place
+fn spell_updated(old: Option<&str>, value: &Value) -> String {
+    let edited = old.and_then(|old| {
+        let mut doc = Document::parse(old).ok()?;
+        doc.update(value).ok()?;
+        Some(doc.to_string())
+    });
+    edited.unwrap_or_else(|| {
+        value
+            .spell(Default::default())
+            .unwrap_or_else(|_| value.min_spell())
+    })
+}
+
+fn write_synced(path: &Path, src: &str) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(src.as_bytes())?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn commits_persist_and_conflict() {
+        let dir = TempDir::new("store");
+        std::fs::write(
+            dir.join("limits.gon"),
+            "{\n    // per shard\n    players: 8,\n}\n",
+        )
+        .unwrap();
+        let store = Store::open(&dir).unwrap();
+        let before = store.snapshot();
+
+        let mut tx = store.begin();
+        tx.modify("limits", |v| *v = parse_str("{players: 12}").unwrap())
+            .unwrap();
+        tx.put("motd", parse_str("\"hi\"").unwrap()).unwrap();
+        assert!(matches!(
+            tx.modify("missing", |_| {}),
+            Err(StoreError::NotFound(_))
+        ));
+        // a failed modify doesn't remove the document
+        assert_eq!(tx.writes.get("missing"), None);
+        let mut late = store.begin();
+        assert_eq!(late.get("motd"), None);
+        tx.commit().unwrap();
+        late.put("motd", Value::None).unwrap();
+        assert!(matches!(late.commit(), Err(StoreError::Conflict(n)) if n == "motd"));
+
+        assert_eq!(
+            before.get("limits"),
+            Some(&parse_str("{players: 8}").unwrap())
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("limits.gon")).unwrap(),
+            "{\n    // per shard\n    players: 12,\n}\n"
+        );
+        let reopened = Store::open(&dir).unwrap();
+        assert_eq!(*reopened.get("motd").unwrap(), parse_str("\"hi\"").unwrap());
+
+        let mut tx = reopened.begin();
+        tx.remove("motd").unwrap();
+        tx.commit().unwrap();
+        assert!(!dir.join("motd.gon").exists());
+        // a file that can't be moved aside fails the commit, and the other file is put back
+        std::fs::create_dir_all(dir.join("limits.gon.bak/full")).unwrap();
+        let mut tx = reopened.begin();
+        tx.put("motd", parse_str("\"bye\"").unwrap()).unwrap();
+        tx.put("limits", Value::None).unwrap();
+        assert!(matches!(tx.commit(), Err(StoreError::Write(..))));
+        assert!(reopened.get("motd").is_none());
+        assert!(!dir.join("motd.gon").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.join("limits.gon")).unwrap(),
+            "{\n    // per shard\n    players: 12,\n}\n"
+        );
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["limits.gon", "limits.gon.bak"]);
+
+        let mut tx = store.begin();
+        assert!(matches!(
+            tx.put("../x", Value::None),
+            Err(StoreError::InvalidName(_))
+        ));
+        assert!(matches!(tx.remove("../x"), Err(StoreError::InvalidName(_))));
+        assert!(matches!(
+            tx.modify("../x", |_| {}),
+            Err(StoreError::InvalidName(_))
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+//! Rendering lists of flat objects as human readable tables.
+
+use crate::value::{ElementError, List};
+use crate::{Object, Value};
+
+/// How [`render`] draws a table
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TableStyle {
+    /// Columns padded with spaces and a dashed line below the header
+    #[default]
+    Aligned,
+    /// A GitHub flavored markdown table
+    Markdown,
+}
+
+/// Renders every object in `list` as a row of a table. `columns` selects and orders the
+/// columns, if it's empty all keys are used in the order they first appear. Strings are
+/// printed without quotes, missing keys leave their cell empty and everything else is
+/// spelled minimally. Fails with the index of the first element that isn't an object.
+/// # Usage example
+/// ```rust
+/// use gon::{parse_str, table::{render, TableStyle}};
+/// let value = parse_str(r#"[{name: "slime", hp: 12}, {name: "dragon", hp: 900}]"#).unwrap();
+/// let table = render(value.as_list().unwrap(), &["name".into(), "hp".into()], TableStyle::Aligned);
+/// assert_eq!(table.unwrap(), "name    hp\n------  ---\nslime   12\ndragon  900\n");
+/// ```
+pub fn render(list: &List, columns: &[String], style: TableStyle) -> Result<String, ElementError> {
+    let rows = list.as_vec_of_obj()?;
+    let columns = if columns.is_empty() {
+        all_keys(&rows)
+    } else {
+        columns.to_vec()
+    };
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|c| cell(row.get(c.as_str()), style))
+                .collect()
+        })
+        .collect();
+    let header: Vec<String> = columns.iter().map(|c| cell_text(c, style)).collect();
+    let min_width = if style == TableStyle::Markdown { 3 } else { 0 };
+    let widths: Vec<usize> = header
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([h.chars().count(), min_width])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let mut out = String::new();
+    write_row(&mut out, &header, &widths, style);
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    write_row(&mut out, &rule, &widths, style);
+    for row in &cells {
+        write_row(&mut out, row, &widths, style);
+    }
+    Ok(out)
+}
+
+/// All keys of all rows, in the order they first appear
+fn all_keys(rows: &[&Object]) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for row in rows {
+        for key in row.keys() {
+            if !keys.iter().any(|k| **k == **key) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    keys
+}
+
+fn cell(value: Option<&Value>, style: TableStyle) -> String {
+    match value {
+        None => String::new(),
+        Some(Value::Str { s, .. }) => cell_text(s, style),
+        Some(other) => cell_text(&other.min_spell(), style),
+    }
+}
+
+/// Makes `text` fit into a single cell
+fn cell_text(text: &str, style: TableStyle) -> String {
+    let text = text.replace('\n', " ");
+    match style {
+        TableStyle::Aligned => text,
+        TableStyle::Markdown => text.replace('|', "\\|"),
+    }
+}
+
+fn write_row(out: &mut String, cells: &[String], widths: &[usize], style: TableStyle) {
+    let padded: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{cell:width$}"))
+        .collect();
+    match style {
+        TableStyle::Aligned => out.push_str(padded.join("  ").trim_end()),
+        TableStyle::Markdown => {
+            out.push_str("| ");
+            out.push_str(&padded.join(" | "));
+            out.push_str(" |");
+        }
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn markdown_with_missing_cells() {
+        let value = parse_str(r#"[{name: "a|b", tags: [1]}, {name: "c"}]"#).unwrap();
+        let table = render(
+            value.as_list().unwrap(),
+            &["name".into(), "tags".into()],
+            TableStyle::Markdown,
+        )
+        .unwrap();
+        assert_eq!(
+            table,
+            "| name | tags |\n| ---- | ---- |\n| a\\|b | [1]  |\n| c    |      |\n"
+        );
+    }
+
+    #[test]
+    fn rejects_non_objects() {
+        let value = parse_str("[{a: 1}, 2]").unwrap();
+        let err = render(value.as_list().unwrap(), &[], TableStyle::Aligned).unwrap_err();
+        assert_eq!(err.index, 1);
+    }
+}
@@ -0,0 +1,354 @@
+//! Templates that render into concrete gon values.
+//!
+//! A template is a gon document with placeholders, rendered with a value of variables,
+//! usually an object read from a file.
+//! - `let` bindings work as usual and `@if` sections are resolved with the variables that
+//!   are strings, numbers or bools, see [`parse_with`](crate::parser::parse_with).
+//! - `${name}` in strings and keys is replaced with the variable `name`. A path into the
+//!   variable may follow the name, like `${level.spawns[0].x}`. A string that is nothing but
+//!   a placeholder becomes the variable itself, which may be any value, inside of longer
+//!   strings only strings, numbers, bools and `None` fit. `$${` spells a literal `${`, raw
+//!   strings are left alone.
+//! - An object `{"@each": "enemy", "@in": "enemies", "@do": <value>}` in a list is replaced
+//!   with `<value>` rendered for every element of the list variable `enemies`, with the
+//!   variable `enemy` bound to the element. With `"@if": "<cond>"`, only the elements the
+//!   condition holds for are rendered. Conditions are `name`, `name == <value>` and
+//!   `name != <value>` like in `@if` sections, but `name` may be a path. Outside of lists,
+//!   a loop renders into a list.
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, template::render};
+//! let template = r#"{
+//!     name: "${level.name}",
+//!     @if(difficulty == "hard") {damage: 2},
+//!     enemies: [
+//!         {"@each": "e", "@in": "level.enemies", "@if": "e.boss", "@do": {
+//!             kind: "${e.kind}",
+//!             "${e.kind}_music": "boss_${level.name}.ogg",
+//!         }},
+//!     ],
+//! }"#;
+//! let vars = parse_str(r#"{
+//!     difficulty: "hard",
+//!     level: {name: "crypt", enemies: [{kind: "bat"}, {kind: "lich", boss: true}]},
+//! }"#).unwrap();
+//! assert_eq!(render(template, &vars).unwrap(), parse_str(r#"{
+//!     name: "crypt",
+//!     damage: 2,
+//!     enemies: [{kind: "lich", lich_music: "boss_crypt.ogg"}],
+//! }"#).unwrap());
+//! ```
+
+use std::fmt;
+
+use crate::parser::{ParseOptions, parse_str_with};
+use crate::path::{Path, Segment, untagged};
+use crate::value::spelling_order;
+use crate::{GonError, MapT, Value, parse_str};
+
+/// Something went wrong rendering a template
+#[derive(Debug, PartialEq)]
+pub enum TemplateError {
+    /// The template isn't valid gon
+    Parse(GonError),
+    /// A placeholder, loop or condition names a variable that isn't there
+    Undefined(Path, String),
+    /// A placeholder, loop or condition doesn't fit its variable or is spelled wrong
+    Invalid(Path, String),
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (path, message) = match self {
+            Self::Parse(e) => return write!(f, "{e}"),
+            Self::Undefined(path, name) => (path, format!("undefined variable '{name}'")),
+            Self::Invalid(path, message) => (path, message.clone()),
+        };
+        if path.is_root() {
+            write!(f, "{message}")
+        } else {
+            write!(f, "{path}: {message}")
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<GonError> for TemplateError {
+    fn from(e: GonError) -> Self {
+        Self::Parse(e)
+    }
+}
+
+/// The keys of a loop object
+const LOOP_KEYS: [&str; 4] = ["@each", "@in", "@if", "@do"];
+
+/// Renders `template` with `vars`, see the [module docs](self). `vars` is an object of
+/// variables, anything else defines none.
+pub fn render(template: &str, vars: &Value) -> Result<Value, TemplateError> {
+    let mut options = ParseOptions::new();
+    if let Some(vars) = vars.as_obj() {
+        for (name, value) in vars.iter() {
+            match untagged(value) {
+                Value::Str { s, .. } | Value::Num(s) => options = options.define(&**name, s),
+                Value::Bool(b) => options = options.define(&**name, b.to_string()),
+                _ => {}
+            }
+        }
+    }
+    let value = parse_str_with(template, &options)?;
+    let mut scope = Scope {
+        vars,
+        bound: Vec::new(),
+    };
+    scope.render(&value, &mut Path::default())
+}
+
+/// The variables a part of a template is rendered with
+struct Scope<'v> {
+    vars: &'v Value,
+    /// The variables of the loops the part is in, innermost last
+    bound: Vec<(String, Value)>,
+}
+
+impl Scope<'_> {
+    fn render(&mut self, value: &Value, path: &mut Path) -> Result<Value, TemplateError> {
+        Ok(match value {
+            Value::Str { s, raw: false } => match whole_placeholder(s) {
+                Some(name) => self.lookup(name, path)?.clone(),
+                None => Value::Str {
+                    s: self.interpolate(s, path)?,
+                    raw: false,
+                },
+            },
+            Value::Obj(obj) if obj.contains_key("@each") => Value::List(self.iterate(obj, path)?),
+            Value::Obj(obj) => {
+                let mut rendered = MapT::new();
+                for (key, v) in spelling_order(obj) {
+                    path.0.push(Segment::Key(key.to_string()));
+                    let key = self.interpolate(key, path)?;
+                    rendered.insert(crate::KeyT::from(key), self.render(v, path)?);
+                    path.0.pop();
+                }
+                Value::Obj(rendered)
+            }
+            Value::List(xs) => {
+                let mut rendered = Vec::with_capacity(xs.len());
+                for (i, x) in xs.iter().enumerate() {
+                    path.0.push(Segment::Index(i));
+                    match x {
+                        Value::Obj(obj) if obj.contains_key("@each") => {
+                            rendered.extend(self.iterate(obj, path)?)
+                        }
+                        x => rendered.push(self.render(x, path)?),
+                    }
+                    path.0.pop();
+                }
+                Value::List(rendered)
+            }
+            Value::Tagged { tag, value } => Value::tagged(tag.clone(), self.render(value, path)?),
+            other => other.clone(),
+        })
+    }
+
+    /// Renders the body of the loop `obj` for every element it goes over
+    fn iterate(&mut self, obj: &MapT, path: &mut Path) -> Result<Vec<Value>, TemplateError> {
+        if let Some(key) = obj.keys().find(|k| !LOOP_KEYS.contains(&&***k)) {
+            return Err(invalid(path, format!("unknown loop option '{key}'")));
+        }
+        let option = |key: &str| match obj.get(key) {
+            Some(Value::Str { s, .. }) => Ok(Some(s.as_str())),
+            Some(_) => Err(invalid(path, format!("{key} has to be a str"))),
+            None => Ok(None),
+        };
+        let (Some(name), Some(list)) = (option("@each")?, option("@in")?) else {
+            return Err(invalid(path, "a loop needs @each and @in"));
+        };
+        let condition = option("@if")?;
+        let Some(body) = obj.get("@do") else {
+            return Err(invalid(path, "a loop needs a @do"));
+        };
+        let elements = match untagged(self.lookup(list, path)?) {
+            Value::List(xs) => xs.clone(),
+            other => {
+                return Err(invalid(
+                    path,
+                    format!("'{list}' has to be a list, not {}", other.type_name()),
+                ));
+            }
+        };
+        let mut rendered = Vec::new();
+        path.0.push(Segment::Key("@do".into()));
+        for element in elements {
+            self.bound.push((name.to_string(), element));
+            let result = match condition {
+                Some(condition) => self.holds(condition, path),
+                None => Ok(true),
+            }
+            .and_then(|holds| {
+                if holds {
+                    self.render(body, path).map(Some)
+                } else {
+                    Ok(None)
+                }
+            });
+            self.bound.pop();
+            rendered.extend(result?);
+        }
+        path.0.pop();
+        Ok(rendered)
+    }
+
+    /// Evaluates `name`, `name == <value>` or `name != <value>`
+    fn holds(&self, condition: &str, path: &Path) -> Result<bool, TemplateError> {
+        let (name, rest) = match condition.find(['=', '!']) {
+            Some(i) => condition.split_at(i),
+            None => (condition, ""),
+        };
+        let value = match self.lookup(name, path) {
+            Ok(value) => Some(untagged(value)),
+            Err(TemplateError::Undefined(..)) => None,
+            Err(e) => return Err(e),
+        };
+        let (equal, literal) = match (rest.strip_prefix("=="), rest.strip_prefix("!=")) {
+            _ if rest.is_empty() => {
+                return Ok(value.is_some_and(|v| !matches!(v, Value::None | Value::Bool(false))));
+            }
+            (Some(literal), _) => (true, literal),
+            (_, Some(literal)) => (false, literal),
+            _ => {
+                return Err(invalid(
+                    path,
+                    format!("expected ==, != or nothing in '{condition}'"),
+                ));
+            }
+        };
+        let literal = parse_str(literal).map_err(|e| invalid(path, format!("{condition}: {e}")))?;
+        Ok((value == Some(&literal)) == equal)
+    }
+
+    /// The value of the variable `name`, which may continue with a path into it
+    fn lookup(&self, name: &str, path: &Path) -> Result<&Value, TemplateError> {
+        let name = name.trim();
+        let parsed: Path = name.parse().map_err(|e| invalid(path, format!("{e}")))?;
+        let Some((Segment::Key(var), rest)) = parsed.0.split_first() else {
+            return Err(invalid(
+                path,
+                format!("'{name}' doesn't start with a variable"),
+            ));
+        };
+        let bound = self.bound.iter().rev().find(|(n, _)| n == var);
+        let value = match bound {
+            Some((_, value)) => Some(value),
+            None => self.vars.as_obj().and_then(|vars| vars.get(var.as_str())),
+        };
+        value
+            .and_then(|value| value.get_path(&Path(rest.to_vec())))
+            .ok_or_else(|| TemplateError::Undefined(path.clone(), name.to_string()))
+    }
+
+    /// Replaces the placeholders in `s` with their variables
+    fn interpolate(&self, s: &str, path: &Path) -> Result<String, TemplateError> {
+        let mut out = String::with_capacity(s.len());
+        let mut rest = s;
+        while let Some(i) = rest.find('$') {
+            out.push_str(&rest[..i]);
+            let after = &rest[i + 1..];
+            if let Some(after) = after.strip_prefix("${") {
+                out.push_str("${");
+                rest = after;
+                continue;
+            }
+            let Some(inner) = after.strip_prefix('{') else {
+                out.push('$');
+                rest = after;
+                continue;
+            };
+            let Some(end) = inner.find('}') else {
+                return Err(invalid(path, format!("unclosed placeholder in \"{s}\"")));
+            };
+            let name = &inner[..end];
+            match untagged(self.lookup(name, path)?) {
+                Value::Str { s, .. } | Value::Num(s) => out.push_str(s),
+                Value::Bool(b) => out.push_str(&b.to_string()),
+                Value::None => out.push_str("None"),
+                other => {
+                    return Err(invalid(
+                        path,
+                        format!(
+                            "{} '{}' doesn't fit into a string",
+                            other.type_name(),
+                            name.trim()
+                        ),
+                    ));
+                }
+            }
+            rest = &inner[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+}
+
+/// The name in `s` if it's nothing but one placeholder
+fn whole_placeholder(s: &str) -> Option<&str> {
+    let name = s.strip_prefix("${")?.strip_suffix('}')?;
+    (!name.contains(['{', '}'])).then_some(name)
+}
+
+fn invalid(path: &Path, message: impl Into<String>) -> TemplateError {
+    TemplateError::Invalid(path.clone(), message.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn placeholders_and_loops() {
+        let vars =
+            parse_str(r#"{n: 3, pos: {x: 1}, names: ["a", "b"], grid: [[1], [2, 3]]}"#).unwrap();
+        let template = r#"{
+            n: "${n}",
+            label: "n = ${ n }, $${n} and $5",
+            raw: r"${n}",
+            pos: "${pos}",
+            rows: [0, {"@each": "row", "@in": "grid", "@do": [
+                {"@each": "cell", "@in": "row", "@if": "cell != 2", "@do": "${cell}"},
+            ]}],
+            "${names[1]}": {"@each": "x", "@in": "names", "@do": "${x}${n}"},
+        }"#;
+        assert_eq!(
+            render(template, &vars),
+            Ok(parse_str(
+                r#"{
+                    n: 3,
+                    label: "n = 3, ${n} and $5",
+                    raw: r"${n}",
+                    pos: {x: 1},
+                    rows: [0, [1], [3]],
+                    b: ["a3", "b3"],
+                }"#
+            )
+            .unwrap())
+        );
+        let error = |template| render(template, &vars).unwrap_err().to_string();
+        assert_eq!(error(r#"{a: ["${m}"]}"#), "a[0]: undefined variable 'm'");
+        assert_eq!(error(r#""x${pos}""#), "obj 'pos' doesn't fit into a string");
+        assert_eq!(
+            error(r#"[{"@each": "x", "@in": "n", "@do": 1}]"#),
+            "[0]: 'n' has to be a list, not num"
+        );
+        assert_eq!(
+            error(r#"[{"@each": "x", "@in": "names", "@od": 1}]"#),
+            "[0]: unknown loop option '@od'"
+        );
+    }
+}
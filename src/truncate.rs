@@ -0,0 +1,153 @@
+//! Shortening big values so they can be shown in logs, error messages and previews.
+//!
+//! [`Value::truncated`] keeps the first nodes of a value in breadth first order, so the top
+//! levels of a document survive and only its depths are cut off. The elided rest of a list
+//! becomes a last element like `"…(+240 items)"`, the rest of an object an entry
+//! `"…": "(+3 entries)"`, and long strings end in `…(+1200 chars)`. The result is only
+//! meant to be looked at, its markers are ordinary strings.
+//! # Usage example
+//! ```rust
+//! use gon::parse_str;
+//! let value = parse_str(r#"{
+//!     id: 7,
+//!     log: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10],
+//!     name: "Sir Reginald the Bold",
+//! }"#).unwrap();
+//! assert_eq!(
+//!     value.truncated(6, 8).min_spell(),
+//!     r#"{id:7,log:[1,2,"…(+8 items)"],name:"Sir Regi…(+13 chars)"}"#,
+//! );
+//! assert_eq!(value.truncated(usize::MAX, usize::MAX), value);
+//! ```
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::path::untagged;
+use crate::value::spelling_order;
+use crate::{KeyT, MapT, Value};
+
+/// How many nodes [`Value::preview`] keeps
+pub const PREVIEW_NODES: usize = 32;
+/// How many chars of a string [`Value::preview`] keeps
+pub const PREVIEW_STR_LEN: usize = 64;
+
+impl Value {
+    /// A copy of this value with at most `max_nodes` values and strings of at most
+    /// `max_str_len` chars, the rest replaced with markers, see the
+    /// [`truncate` module](crate::truncate). The value itself is always kept, tags don't
+    /// count as nodes. Raw text that's too long becomes a string.
+    pub fn truncated(&self, max_nodes: usize, max_str_len: usize) -> Value {
+        truncate(self, &kept_nodes(self, max_nodes), max_str_len)
+    }
+
+    /// This value [`truncated`](Self::truncated) to [`PREVIEW_NODES`] and
+    /// [`PREVIEW_STR_LEN`], small enough for an error message or a terminal
+    pub fn preview(&self) -> Value {
+        self.truncated(PREVIEW_NODES, PREVIEW_STR_LEN)
+    }
+}
+
+/// The addresses of the first `max_nodes` values in `value`, breadth first in spelling order
+fn kept_nodes(value: &Value, max_nodes: usize) -> HashSet<*const Value> {
+    let mut kept = HashSet::new();
+    let mut queue = VecDeque::from([value]);
+    while let Some(v) = queue.pop_front() {
+        kept.insert(v as *const Value);
+        // only queue what can still be kept, so huge lists aren't queued as a whole
+        let room = max_nodes.saturating_sub(kept.len() + queue.len());
+        match untagged(v) {
+            Value::Obj(obj) => queue.extend(spelling_order(obj).map(|(_, v)| v).take(room)),
+            Value::List(xs) => queue.extend(xs.iter().take(room)),
+            _ => {}
+        }
+    }
+    kept
+}
+
+fn truncate(value: &Value, kept: &HashSet<*const Value>, max_str_len: usize) -> Value {
+    let is_kept = |v: &Value| kept.contains(&(v as *const Value));
+    match value {
+        Value::Obj(obj) => {
+            let mut truncated = MapT::new();
+            let mut elided = 0;
+            for (key, v) in spelling_order(obj) {
+                if is_kept(v) {
+                    truncated.insert(key.clone(), truncate(v, kept, max_str_len));
+                } else {
+                    elided += 1;
+                }
+            }
+            if elided > 0 {
+                truncated.insert(KeyT::from("…"), marker(format!("(+{elided} entries)")));
+            }
+            Value::Obj(truncated)
+        }
+        Value::List(xs) => {
+            let mut truncated: Vec<_> = xs
+                .iter()
+                .take_while(|x| is_kept(x))
+                .map(|x| truncate(x, kept, max_str_len))
+                .collect();
+            if truncated.len() < xs.len() {
+                truncated.push(marker(format!("…(+{} items)", xs.len() - truncated.len())));
+            }
+            Value::List(truncated)
+        }
+        Value::Tagged { tag, value } => Value::Tagged {
+            tag: tag.clone(),
+            value: Box::new(truncate(value, kept, max_str_len)),
+        },
+        Value::Str { s, raw } => Value::Str {
+            s: shortened(s, max_str_len).unwrap_or_else(|| s.clone()),
+            raw: *raw,
+        },
+        Value::Raw(text) => shortened(text, max_str_len).map_or_else(|| value.clone(), marker),
+        scalar => scalar.clone(),
+    }
+}
+
+/// The first `max_len` chars of `s` and how many were cut off, `None` if it's short enough
+fn shortened(s: &str, max_len: usize) -> Option<String> {
+    let (cut, _) = s.char_indices().nth(max_len)?;
+    let rest = s[cut..].chars().count();
+    Some(format!("{}…(+{rest} chars)", &s[..cut]))
+}
+
+fn marker(s: String) -> Value {
+    Value::Str { s, raw: false }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn keeps_the_top_levels() {
+        let value = parse_str("{a: [[1, 2], [3]], b: {c: {d: 1}}, e: 5}").unwrap();
+        assert_eq!(
+            value.truncated(7, usize::MAX).min_spell(),
+            r#"{a:[["…(+2 items)"],["…(+1 items)"]],b:{c:{"…":"(+1 entries)"}},e:5}"#
+        );
+        assert_eq!(
+            value.truncated(0, usize::MAX).min_spell(),
+            r#"{"…":"(+3 entries)"}"#
+        );
+        let tagged = Value::tagged("t", parse_str("[\"αβγδ\", 2]").unwrap());
+        assert_eq!(
+            tagged.truncated(2, 2),
+            Value::tagged(
+                "t",
+                parse_str(r#"["αβ…(+2 chars)", "…(+1 items)"]"#).unwrap()
+            )
+        );
+        assert_eq!(
+            Value::Raw("[1, 2, 3]".into()).truncated(1, 3),
+            parse_str(r#""[1,…(+6 chars)""#).unwrap()
+        );
+        assert_eq!(
+            Value::Raw("[1]".into()).truncated(1, 3),
+            Value::Raw("[1]".into())
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! Finding the keys of a config that the application never reads.
+//!
+//! A [`Usage`] report lists what an application read of its config, one [`Pattern`] per
+//! line like `server.port` or `enemies[*].hp`, `//` starts a comment. What a line matches
+//! was read with everything inside of it, so `server` means all of `server` was read, like
+//! when it's deserialized as a whole. The elements of lists are recorded as `[*]`, so the
+//! report doesn't depend on how long the lists are.
+//!
+//! [`Value::track_access`] records a report while the application reads its config, and
+//! [`Usage::unused`] finds what no line of it reaches. A [`TrackedValue`] does the same for
+//! a config the application keeps around and shares between threads, and
+//! [`Usage::reads`] tells if a key was read at all, like when an override seems ignored.
+//! # Usage example
+//! ```rust
+//! use gon::{parse_str, usage::Usage};
+//! let config = parse_str(r#"{
+//!     debug: false,
+//!     server: {host: "localhost", port: 8080},
+//! }"#).unwrap();
+//! let tracked = config.track_access();
+//! let server = tracked.get("server").unwrap();
+//! assert_eq!(server.get("port").unwrap().value().as_i128(), Some(8080));
+//! let usage = tracked.usage();
+//! assert_eq!(usage.to_string(), "server.port\n");
+//! let unused: Vec<_> = usage.unused(&config).iter().map(|p| p.to_string()).collect();
+//! assert_eq!(unused, ["debug", "server.host"]);
+//! // the same report, written by hand
+//! assert_eq!(usage, "server.port // the port to listen on".parse::<Usage>().unwrap());
+//! ```
+
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use thiserror::Error;
+
+use crate::Value;
+use crate::parser::{TokenKind, next_token};
+use crate::path::{
+    self, Path, PathError, Pattern, PatternSegment, Segment, advance, children, is_done, untagged,
+};
+
+/// What an application read of a value, see the [module docs](self)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    patterns: HashSet<Pattern>,
+}
+
+/// A line of a [`Usage`] report isn't a pattern
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("line {line}: {source}")]
+pub struct UsageError {
+    pub line: usize,
+    pub source: PathError,
+}
+
+impl Usage {
+    /// Records that what `pattern` matches was read
+    pub fn record(&mut self, pattern: Pattern) {
+        self.patterns.insert(pattern);
+    }
+
+    /// Everything recorded, in no particular order
+    pub fn patterns(&self) -> impl Iterator<Item = &Pattern> {
+        self.patterns.iter()
+    }
+
+    /// Whether the value at `path` counts as read, see [`unused`](Self::unused)
+    /// # Usage example
+    /// ```rust
+    /// use gon::usage::Usage;
+    /// let usage: Usage = "server.port\nplugins[*].name".parse().unwrap();
+    /// assert!(usage.reads(&"server".parse().unwrap()));
+    /// assert!(usage.reads(&"plugins[3].name.en".parse().unwrap()));
+    /// assert!(!usage.reads(&"server.host".parse().unwrap()));
+    /// ```
+    pub fn reads(&self, path: &Path) -> bool {
+        let mut rests: Vec<_> = self.patterns.iter().map(|p| p.0.as_slice()).collect();
+        for segment in &path.0 {
+            if rests.iter().any(|p| is_done(p)) {
+                return true;
+            }
+            rests = advance(&rests, segment);
+        }
+        !rests.is_empty()
+    }
+
+    /// The paths of everything in `value` that nothing recorded reaches, depth first with
+    /// keys in the order they're spelled in. Only the outermost of them are listed: if
+    /// nothing in `server` was read, that's `server` but not `server.host`. A value counts
+    /// as read if a pattern matches it, anything inside of it or anything it's inside of.
+    pub fn unused(&self, value: &Value) -> Vec<Path> {
+        let rests: Vec<_> = self.patterns.iter().map(|p| p.0.as_slice()).collect();
+        let mut unused = Vec::new();
+        unused0(value, &rests, &mut Path::default(), &mut unused);
+        unused
+    }
+}
+
+fn unused0(value: &Value, rests: &[&[PatternSegment]], path: &mut Path, unused: &mut Vec<Path>) {
+    if rests.iter().any(|p| is_done(p)) {
+        return;
+    }
+    for (segment, child) in children(value) {
+        let child_rests = advance(rests, &segment);
+        path.0.push(segment);
+        if child_rests.is_empty() {
+            unused.push(path.clone());
+        } else {
+            unused0(child, &child_rests, path, unused);
+        }
+        path.0.pop();
+    }
+}
+
+/// `line` up to its comment, which the tokenizer finds so that `//` in quoted keys stays
+fn strip_comment(line: &str) -> &str {
+    let mut pos = 0;
+    let comment = std::iter::from_fn(|| next_token(line, &mut pos))
+        .find(|token| token.kind == TokenKind::Comment);
+    comment.map_or(line, |comment| &line[..comment.span.start])
+}
+
+impl FromStr for Usage {
+    type Err = UsageError;
+
+    fn from_str(src: &str) -> Result<Self, Self::Err> {
+        let mut usage = Self::default();
+        for (i, line) in src.lines().enumerate() {
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let pattern = line.parse().map_err(|source| UsageError {
+                line: i + 1,
+                source,
+            })?;
+            usage.record(pattern);
+        }
+        Ok(usage)
+    }
+}
+
+/// One pattern per line, sorted
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut lines: Vec<_> = self.patterns.iter().map(Pattern::to_string).collect();
+        lines.sort_unstable();
+        for line in lines {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A view of a [`Value`] that records what's read of it, see [`Value::track_access`]
+#[derive(Clone, Debug)]
+pub struct Tracked<'a> {
+    value: &'a Value,
+    path: Pattern,
+    usage: Arc<Mutex<Usage>>,
+}
+
+impl Value {
+    /// A view of this value that records which parts of it are read into a [`Usage`]
+    /// report, see the [`usage` module](crate::usage). Getting keys and elements only
+    /// navigates, reading the [`value`](Tracked::value) at the end is what's recorded.
+    /// Views got from each other share their report.
+    pub fn track_access(&self) -> Tracked<'_> {
+        Tracked {
+            value: self,
+            path: Pattern::default(),
+            usage: Arc::default(),
+        }
+    }
+}
+
+/// An owned [`Value`] that records which parts of it are read, like a [`Tracked`] view.
+/// Its clones and the values got from it share the value and the report, also across
+/// threads.
+/// # Usage example
+/// ```rust
+/// use gon::{parse_str, usage::TrackedValue};
+/// let config = TrackedValue::new(parse_str("{port: 80, workers: 4}").unwrap());
+/// let handles: Vec<_> = (0..2)
+///     .map(|_| {
+///         let config = config.clone();
+///         std::thread::spawn(move || config.get("workers").unwrap().value().as_i128())
+///     })
+///     .collect();
+/// for handle in handles {
+///     assert_eq!(handle.join().unwrap(), Some(4));
+/// }
+/// assert_eq!(config.usage().to_string(), "workers\n");
+/// assert!(!config.usage().reads(&"port".parse().unwrap()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct TrackedValue {
+    root: Arc<Value>,
+    path: Path,
+    usage: Arc<Mutex<Usage>>,
+}
+
+impl TrackedValue {
+    /// Starts tracking what's read of `value` with an empty report
+    pub fn new(value: Value) -> Self {
+        Self {
+            root: Arc::new(value),
+            path: Path::default(),
+            usage: Arc::default(),
+        }
+    }
+
+    /// The value of `key`, `None` if this isn't an object with that key
+    pub fn get(&self, key: &str) -> Option<Self> {
+        match untagged(self.peek()) {
+            Value::Obj(obj) if obj.contains_key(key) => Some(self.child(Segment::Key(key.into()))),
+            _ => None,
+        }
+    }
+
+    /// The element at `index`, `None` if this isn't a list that long
+    pub fn index(&self, index: usize) -> Option<Self> {
+        match untagged(self.peek()) {
+            Value::List(xs) if index < xs.len() => Some(self.child(Segment::Index(index))),
+            _ => None,
+        }
+    }
+
+    /// The elements of a list, empty for anything else
+    pub fn elements(&self) -> Vec<Self> {
+        match untagged(self.peek()) {
+            Value::List(xs) => (0..xs.len())
+                .map(|i| self.child(Segment::Index(i)))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The value, recorded as read together with everything inside of it
+    pub fn value(&self) -> &Value {
+        lock(&self.usage).record(recorded(&self.path));
+        self.peek()
+    }
+
+    /// Where the value is in the one tracking started with
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// What was read so far of the value tracking started with
+    pub fn usage(&self) -> Usage {
+        lock(&self.usage).clone()
+    }
+
+    /// The value without recording it as read
+    fn peek(&self) -> &Value {
+        path::get(&self.root, &self.path).expect("only existing paths are tracked")
+    }
+
+    fn child(&self, segment: Segment) -> Self {
+        Self {
+            root: Arc::clone(&self.root),
+            path: Path(self.path.0.iter().cloned().chain([segment]).collect()),
+            usage: Arc::clone(&self.usage),
+        }
+    }
+}
+
+impl From<Value> for TrackedValue {
+    fn from(value: Value) -> Self {
+        Self::new(value)
+    }
+}
+
+/// How a read of `path` is recorded, with `[*]` for indexes
+fn recorded(path: &Path) -> Pattern {
+    Pattern(
+        path.0
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => PatternSegment::Key(key.clone()),
+                Segment::Index(_) => PatternSegment::AnyIndex,
+            })
+            .collect(),
+    )
+}
+
+fn lock(usage: &Mutex<Usage>) -> MutexGuard<'_, Usage> {
+    // the report is only ever inserted into, it's fine after a panic elsewhere
+    usage.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl<'a> Tracked<'a> {
+    /// The view of the value of `key`, `None` if this isn't an object with that key
+    pub fn get(&self, key: &str) -> Option<Tracked<'a>> {
+        let Value::Obj(obj) = untagged(self.value) else {
+            return None;
+        };
+        Some(self.child(obj.get(key)?, PatternSegment::Key(key.to_string())))
+    }
+
+    /// The view of the element at `index`, `None` if this isn't a list that long
+    pub fn index(&self, index: usize) -> Option<Tracked<'a>> {
+        let Value::List(xs) = untagged(self.value) else {
+            return None;
+        };
+        Some(self.child(xs.get(index)?, PatternSegment::AnyIndex))
+    }
+
+    /// The views of the elements of a list, empty for anything else
+    pub fn elements(&self) -> Vec<Tracked<'a>> {
+        match untagged(self.value) {
+            Value::List(xs) => xs
+                .iter()
+                .map(|x| self.child(x, PatternSegment::AnyIndex))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The value, recorded as read together with everything inside of it
+    pub fn value(&self) -> &'a Value {
+        lock(&self.usage).record(self.path.clone());
+        self.value
+    }
+
+    /// Where the value is, with `[*]` for the elements of lists
+    pub fn path(&self) -> &Pattern {
+        &self.path
+    }
+
+    /// What was read so far through this view and the ones it shares its report with
+    pub fn usage(&self) -> Usage {
+        lock(&self.usage).clone()
+    }
+
+    fn child(&self, value: &'a Value, segment: PatternSegment) -> Tracked<'a> {
+        let mut path = self.path.clone();
+        path.0.push(segment);
+        Tracked {
+            value,
+            path,
+            usage: Arc::clone(&self.usage),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    fn unused(usage: &str, value: &str) -> Vec<String> {
+        let usage: Usage = usage.parse().unwrap();
+        let value = parse_str(value).unwrap();
+        usage.unused(&value).iter().map(Path::to_string).collect()
+    }
+
+    #[test]
+    fn finds_unused_keys() {
+        let config = "{a: {b: 1, c: {d: 2}}, e: [{f: 3, g: 4}, {f: 5}], h: 6}";
+        assert_eq!(unused("", config), ["a", "e", "h"]);
+        assert_eq!(unused("a.c.d\ne[*].f", config), ["a.b", "e[0].g", "h"]);
+        assert_eq!(unused("a.**\n// h isn't read\ne[1]", config), ["e[0]", "h"]);
+        assert_eq!(unused("**", config), Vec::<String>::new());
+        assert_eq!(unused("a.c\ne.**\nh", config), ["a.b"]);
+        assert_eq!(unused("a\ne\nh", config), Vec::<String>::new());
+        let config = r#"{"a//b": 1, c: 2}"#;
+        assert_eq!(unused(r#""a//b" // quoted"#, config), ["c"]);
+        assert_eq!(
+            "a\n\nb[x]".parse::<Usage>().unwrap_err().line,
+            3,
+            "lines count from one"
+        );
+    }
+
+    #[test]
+    fn tracks_access() {
+        let config = parse_str("{a: {b: 1, c: [{d: 2}, {d: 3}]}, e: 4}").unwrap();
+        let tracked = config.track_access();
+        let a = tracked.get("a").unwrap();
+        assert!(a.get("x").is_none());
+        for x in a.get("c").unwrap().elements() {
+            x.get("d").unwrap().value();
+        }
+        assert_eq!(
+            a.get("c").unwrap().index(1).unwrap().path().to_string(),
+            "a.c[*]"
+        );
+        let usage = tracked.usage();
+        assert_eq!(usage.to_string(), "a.c[*].d\n");
+        assert_eq!(usage.unused(&config).len(), 2);
+        tracked.get("e").unwrap().value();
+        a.value();
+        assert_eq!(tracked.usage().to_string(), "a\na.c[*].d\ne\n");
+        assert!(tracked.usage().unused(&config).is_empty());
+        let reread: Usage = tracked.usage().to_string().parse().unwrap();
+        assert_eq!(reread, tracked.usage());
+    }
+
+    #[test]
+    fn tracks_owned_values() {
+        let config = TrackedValue::new(parse_str("{a: [{b: 1}, {b: 2}], c: 3}").unwrap());
+        let second = config.get("a").unwrap().index(1).unwrap();
+        assert_eq!(second.path().to_string(), "a[1]");
+        assert!(config.get("a").unwrap().index(2).is_none());
+        assert!(config.get("x").is_none() && config.index(0).is_none());
+        assert_eq!(second.get("b").unwrap().value().as_i128(), Some(2));
+        let usage = config.usage();
+        assert_eq!(usage.to_string(), "a[*].b\n");
+        assert!(usage.reads(&"a[0]".parse().unwrap()));
+        assert!(!usage.reads(&"c".parse().unwrap()));
+        assert_eq!(config.elements().len(), 0);
+        config.value();
+        assert!(config.usage().reads(&"c".parse().unwrap()));
+    }
+}
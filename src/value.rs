@@ -1,4 +1,15 @@
+use std::borrow::Cow;
 use std::fmt::Write;
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::GonError;
+
+#[cfg(feature = "serde")]
+mod serde;
+#[cfg(feature = "serde")]
+pub use self::serde::{SerdeError, from_value, to_value};
 
 /// A gon value
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -9,8 +20,10 @@ pub enum Value {
     None,
     /// A string in double quotes. Writing `r` or `R` in front of it turns it into
     /// a "raw" string which tells the formatter not to put line breaks when spelling.
+    /// Literals joined with `+` are concatenated, which is how long strings are wrapped.
     /// # Grammar
-    /// `Str = ( "r" | "R" )? STR_LIT ;` (where STR_LIT is whatever `klex` tokenizes as a string)
+    /// `Str = Piece ( "+" Piece )* ;`
+    /// `Piece = ( "r" | "R" )? STR_LIT ;` (where STR_LIT is whatever `klex` tokenizes as a string)
     /// Arbitrary whitespace may be between the r and the string literal.
     Str { s: String, raw: bool },
     /// A number value.
@@ -30,29 +43,373 @@ pub enum Value {
     /// # Grammar
     /// `List = "[" ( Value ","? )* "]" ;`
     List(Vec<Value>),
+    /// A value of a custom type, produced by a scalar hook while parsing (see
+    /// [`crate::parser::ParseOptions::scalar_hook`]). `tag` names the type, `value` is what
+    /// the tagged value is spelled, converted and validated as.
+    Tagged { tag: String, value: Box<Value> },
+    /// Gon text that is spelled verbatim, for splicing pre-formatted fragments into generated
+    /// documents. The parser never produces it and the text isn't checked. Conversions into
+    /// other formats parse it, text that doesn't parse becomes a string. See [`RawText`] for
+    /// how comments and line breaks are handled.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, SpellConfig, Value};
+    /// let mut value = parse_str("{}").unwrap();
+    /// let matrix = Value::Raw("[[1, 0], [0, 1]]".into());
+    /// value.set_path(&"matrix".parse().unwrap(), matrix).unwrap();
+    /// let spelled = value.spell(SpellConfig::default()).unwrap();
+    /// assert_eq!(spelled, "{\n    matrix: [[1, 0], [0, 1]]\n}");
+    /// assert_eq!(parse_str(&spelled).unwrap(), parse_str("{matrix: [[1, 0], [0, 1]]}").unwrap());
+    /// ```
+    Raw(RawText),
 }
 
+/// The entries of an object value. Borrow one from a [`Value`] with [`Value::as_obj`].
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(transparent)]
 pub struct Object {
     inner: crate::MapT,
 }
 
+/// The elements of a list value. Borrow one from a [`Value`] with [`Value::as_list`].
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[repr(transparent)]
 pub struct List {
     inner: Vec<Value>,
 }
 
+/// The text of a [`Value::Raw`], kept exactly as given. If it ends in a line comment, the
+/// line is ended after it when it's spelled, so that what follows isn't commented out. Lines
+/// after the first are indented like the value when it's spelled with [`Value::spell`].
+/// # Usage example
+/// ```rust
+/// use gon::{value::RawText, SpellConfig, Value};
+/// let text = RawText::from("[1, 2] // one and two");
+/// assert_eq!(&*text, "[1, 2] // one and two");
+/// let value = Value::List(vec![Value::Raw(text), Value::Raw("{\n    a: 3,\n}".into())]);
+/// assert_eq!(value.min_spell(), "[[1, 2] // one and two\n,{\n    a: 3,\n}]");
+/// let spelled = value.spell(SpellConfig::default()).unwrap();
+/// assert_eq!(spelled, "[\n    [1, 2] // one and two\n    {\n        a: 3,\n    }\n]");
+/// ```
+#[derive(Clone, Default)]
+pub struct RawText {
+    text: String,
+    /// What kind of value the text parses into, once it was asked for
+    kind: std::sync::OnceLock<Kind>,
+    /// Whether the last token of the text is a line comment, once it was asked for
+    line_comment: std::sync::OnceLock<bool>,
+}
+
+/// Converting an element of a [`List`] failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElementError<E = TypeError> {
+    /// The index of the offending element
+    pub index: usize,
+    pub error: E,
+}
+
+/// A value wasn't of the type that was asked for
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[error("expected {expected}, found {found}")]
+pub struct TypeError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+/// What kind of value a [`Value`] is, see [`Value::kind`]. Error messages and schemas
+/// call the kinds by their [`name`](Kind::name).
+/// # Usage example
+/// ```rust
+/// use gon::{parse_str, Kind};
+/// let value = parse_str("[1, 2]").unwrap();
+/// assert_eq!(Kind::from(&value), Kind::List);
+/// assert_eq!(value.kind().name(), "list");
+/// assert_eq!("num".parse(), Ok(Kind::Num));
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Kind {
+    None,
+    Str,
+    Num,
+    Bool,
+    Obj,
+    List,
+}
+
+/// A name that isn't the [`name`](Kind::name) of a [`Kind`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("unknown kind '{0}', expected none, str, num, bool, obj or list")]
+pub struct UnknownKind(pub String);
+
+impl Kind {
+    /// Every kind, in the order they're declared in
+    pub const ALL: [Self; 6] = [
+        Self::None,
+        Self::Str,
+        Self::Num,
+        Self::Bool,
+        Self::Obj,
+        Self::List,
+    ];
+
+    /// The name of the kind as used in schemas and error messages, like `str`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Str => "str",
+            Self::Num => "num",
+            Self::Bool => "bool",
+            Self::Obj => "obj",
+            Self::List => "list",
+        }
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Parses the [`name`](Kind::name) of a kind
+impl std::str::FromStr for Kind {
+    type Err = UnknownKind;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| UnknownKind(name.to_string()))
+    }
+}
+
+impl From<&Value> for Kind {
+    fn from(value: &Value) -> Self {
+        value.kind()
+    }
+}
+
+/// A value isn't of the [`Kind`] it's expected to be, see [`Value::assert_homogeneous`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KindError {
+    /// Where the value is, empty if it's the checked value itself
+    pub path: crate::path::Path,
+    pub expected: Kind,
+    pub found: Kind,
+}
+
+impl std::fmt::Display for KindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.path.0.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "expected {}, found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for KindError {}
+
+/// What to do with numbers that don't fit the type they're converted to
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Strictness {
+    /// Get as close as possible: integers too big for a type become floats or `None`,
+    /// floats too big become infinity, or `null` in JSON
+    #[default]
+    Lenient,
+    /// Fail with a [`NumberError`]
+    Strict,
+}
+
+/// A number doesn't fit the type it's converted to with [`Strictness::Strict`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumberError {
+    /// Where the number is, empty if it's the converted value itself
+    pub path: crate::path::Path,
+    /// The number as it's spelled
+    pub num: String,
+    pub target: &'static str,
+}
+
+impl std::fmt::Display for NumberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.path.0.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{} doesn't fit into {}", self.num, self.target)
+    }
+}
+
+impl std::error::Error for NumberError {}
+
+/// Getting a field of an [`Object`] failed, see [`Object::require`]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error("field '{key}': expected {expected}, found {found}")]
+pub struct FieldError {
+    pub key: String,
+    pub expected: &'static str,
+    /// The type of the field's value, `nothing` if the key is missing
+    pub found: &'static str,
+}
+
+/// Whether an optional field of an [`Object`] is there, see [`Object::presence`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Presence<T> {
+    /// The key isn't in the object
+    Missing,
+    /// The key is there with a [nullish](Value::is_nullish) value
+    None,
+    Some(T),
+}
+
+impl<T> Presence<T> {
+    /// The value, `None` if it's missing or `None`
+    pub fn into_option(self) -> Option<T> {
+        match self {
+            Self::Some(t) => Some(t),
+            Self::Missing | Self::None => None,
+        }
+    }
+}
+
 /// Configures how a `Value` should be [Value::spell]ed
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[allow(unpredictable_function_pointer_comparisons)]
 pub struct SpellConfig {
     pub indent_amount: usize,
     pub indent_char: char,
     pub trailing_commas: bool,
-    /// Max width of string literals before they get wrapped.
+    /// Max width of string literals before they get wrapped, 0 (the default) disables
+    /// wrapping.
+    pub max_width: usize,
+    /// Wrap strings losslessly into literals joined with `+` (the default) instead of
+    /// squashing their whitespace and breaking the lines within the literal. Without the
+    /// `wrap` feature strings are always wrapped losslessly.
+    pub preserve_strings: bool,
+    /// When to spell lists without objects or lists in them on a single line.
+    pub inline_lists: InlineMode,
+    /// Spell objects within these limits on a single line, like `{x: 1, y: 2}`. Objects
+    /// containing objects or lists are never inlined.
+    pub inline_small_objects: Option<InlineLimits>,
+    /// Spell chains of objects with a single entry as dotted keys, like `server.port: 80`,
+    /// which only parse back with [`ParseOptions::dotted_keys`](crate::parser::ParseOptions::dotted_keys).
+    /// Chains stop at keys that aren't plain identifiers.
+    pub dotted_keys: bool,
+    /// Spell numbers canonically: no `_` separators, `+` signs or leading zeros, a
+    /// lowercase `e` and no trailing zeros in the fraction beyond the first. The output is
+    /// accepted by strict JSON parsers and doesn't depend on how the numbers were authored.
+    pub normalize_numbers: bool,
+    /// Leave out the entries of objects whose value is [nullish](Value::is_nullish)
+    pub skip_none: bool,
+    /// Spell every key in quotes, `None` as `null`, raw strings as ordinary ones, strings
+    /// with JSON's escapes and numbers normalized, or `null` if JSON can't spell them.
+    /// Without trailing commas the output is JSON, see
+    /// [`Value::min_spell_json_compat`] for the minimal spelling.
+    pub json_compat: bool,
+    /// Where [`Document::format`](crate::document::Document::format) puts the comments
+    /// that belong to entries and elements
+    pub comment_placement: CommentPlacement,
+    /// Break `//` comments that reach past [`max_width`](Self::max_width) into several
+    /// lines. Comments after a value that would reach past it go on the lines before it.
+    /// Without the `wrap` feature they're only moved, not broken.
+    pub wrap_comments: bool,
+    /// Line up the comments after the entries or elements of consecutive lines
+    pub align_comments: bool,
+    /// Stop spelling with an error when the output gets too long or the value too deep,
+    /// see [`Value::spell_limited`]. Unlimited by default.
+    pub output_limits: OutputLimits,
+    /// Gets the first shot at spelling every (sub) value, see [`SpellConfig::custom_renderer`].
+    /// Two configs only compare equal if they use the very same function.
+    pub custom_renderer: Option<CustomRenderer>,
+}
+
+/// When an object or list is small enough to be spelled on a single line
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InlineLimits {
+    /// At most this many entries or elements
+    pub max_entries: usize,
+    /// At most this many characters from the opening to the closing bracket
     pub max_width: usize,
 }
 
+/// How much output spelling a value may produce, see [`Value::spell_limited`] and
+/// [`Value::min_spell_limited`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct OutputLimits {
+    /// At most this many bytes of output
+    pub max_bytes: usize,
+    /// At most this many objects and lists nested in each other
+    pub max_depth: usize,
+}
+
+/// Spelling a value would exceed its [`OutputLimits`]
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum LimitError {
+    /// The output would be longer than this many bytes
+    #[error("output exceeds {0} bytes")]
+    TooLong(usize),
+    /// Objects and lists are nested deeper than this
+    #[error("value is nested deeper than {0} levels")]
+    TooDeep(usize),
+}
+
+/// The text given to [`unescape_str`] isn't the inside of a string literal
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+#[error(
+    "invalid string contents '{0}'\n\tExpected text with escape sequences like \\n, \\\" or \\\\"
+)]
+pub struct UnescapeError(pub String);
+
+/// A formatting profile couldn't be turned into a [`SpellConfig`]
+#[derive(Debug, Error)]
+pub enum SpellConfigError {
+    /// The profile couldn't be read
+    #[error("couldn't read format profile '{0}': {1}")]
+    Io(String, std::io::Error),
+    /// The profile isn't valid gon
+    #[error("couldn't parse format profile '{0}': {1}")]
+    Parse(String, GonError),
+    /// An option has a value it can't have
+    #[error("invalid format option '{0}': {1}")]
+    Invalid(String, String),
+}
+
+/// When lists are spelled on a single line, see [`SpellConfig::inline_lists`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum InlineMode {
+    /// Always one element per line
+    Never,
+    /// On one line if there are no objects or lists in it
+    Always,
+    /// On one line if there are no objects or lists in it and it's within the limits
+    Limits(InlineLimits),
+}
+
+/// Where comments go, see [`SpellConfig::comment_placement`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CommentPlacement {
+    /// Where they are written
+    #[default]
+    Keep,
+    /// On the lines before the entry or element
+    Above,
+    /// After the entry or element on the same line. Entries with more than one comment
+    /// before them, or that span several lines, keep them above.
+    SameLine,
+}
+
+/// Spells a value into the given buffer, or declines with `None` to leave it to the
+/// default speller. See [`SpellConfig::custom_renderer`].
+pub type CustomRenderer = fn(&Value, &mut String) -> Option<()>;
+
 impl Value {
+    /// Tags `value` with `tag`
+    pub fn tagged(tag: impl Into<String>, value: Value) -> Self {
+        Self::Tagged {
+            tag: tag.into(),
+            value: Box::new(value),
+        }
+    }
+
     pub fn as_f64(&self) -> Option<f64> {
         let Self::Num(num) = self else {
             return None;
@@ -67,117 +424,523 @@ impl Value {
         num.parse().ok()
     }
 
+    /// Like [`as_f64`](Self::as_f64), but numbers too big for an `f64` are an error
+    /// instead of infinity with [`Strictness::Strict`].
+    /// # Usage example
+    /// ```rust
+    /// use gon::{Value, value::Strictness};
+    /// let big = Value::Num("1e400".into());
+    /// assert_eq!(big.as_f64_with(Strictness::Lenient), Ok(Some(f64::INFINITY)));
+    /// assert!(big.as_f64_with(Strictness::Strict).is_err());
+    /// ```
+    pub fn as_f64_with(&self, strictness: Strictness) -> Result<Option<f64>, NumberError> {
+        match self.as_f64() {
+            Some(f) if f.is_infinite() && strictness == Strictness::Strict => {
+                Err(self.number_error("f64"))
+            }
+            f => Ok(f),
+        }
+    }
+
+    /// Like [`as_i128`](Self::as_i128), but integers too big for an `i128` are an error
+    /// instead of `None` with [`Strictness::Strict`]. Numbers with a fraction or an
+    /// exponent are `None` either way.
+    pub fn as_i128_with(&self, strictness: Strictness) -> Result<Option<i128>, NumberError> {
+        match self {
+            Self::Num(num) if strictness == Strictness::Strict && is_int_literal(num) => {
+                num.parse().map(Some).map_err(|_| self.number_error("i128"))
+            }
+            _ => Ok(self.as_i128()),
+        }
+    }
+
+    pub(crate) fn number_error(&self, target: &'static str) -> NumberError {
+        NumberError {
+            path: crate::path::Path::default(),
+            num: match self {
+                Self::Num(num) => num.clone(),
+                other => other.type_name().to_string(),
+            },
+            target,
+        }
+    }
+
+    /// Borrows the entries if this is an object
+    pub fn as_obj(&self) -> Option<&Object> {
+        match self {
+            Self::Obj(map) => Some(Object::from_map_ref(map)),
+            _ => None,
+        }
+    }
+
+    /// Borrows the entries mutably if this is an object
+    pub fn as_obj_mut(&mut self) -> Option<&mut Object> {
+        match self {
+            Self::Obj(map) => Some(Object::from_map_mut(map)),
+            _ => None,
+        }
+    }
+
+    /// Borrows the elements if this is a list
+    pub fn as_list(&self) -> Option<&List> {
+        match self {
+            Self::List(xs) => Some(List::from_vec_ref(xs)),
+            _ => None,
+        }
+    }
+
+    /// The kind of this value. Tagged values are of the kind of the value they tag, raw text
+    /// of the kind of what it parses into.
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::None => Kind::None,
+            Self::Str { .. } => Kind::Str,
+            Self::Num(_) => Kind::Num,
+            Self::Bool(_) => Kind::Bool,
+            Self::Obj(_) => Kind::Obj,
+            Self::List(_) => Kind::List,
+            Self::Tagged { value, .. } => value.kind(),
+            Self::Raw(text) => text.kind(),
+        }
+    }
+
+    /// Whether this is `None`, also when it's tagged or raw text
+    pub fn is_nullish(&self) -> bool {
+        self.kind() == Kind::None
+    }
+
+    /// Checks that this is a list of values of `kind` only, before loading it into a typed
+    /// collection. The error points at the first element that isn't.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, Kind};
+    /// let speeds = parse_str(r#"[1.5, 2, "3"]"#).unwrap();
+    /// assert_eq!(speeds.as_list().unwrap().element_kinds(), [Kind::Num, Kind::Str]);
+    /// let error = speeds.assert_homogeneous(Kind::Num).unwrap_err();
+    /// assert_eq!(error.to_string(), "[2]: expected num, found str");
+    /// ```
+    pub fn assert_homogeneous(&self, kind: Kind) -> Result<(), KindError> {
+        let Self::List(xs) = crate::path::untagged(self) else {
+            return Err(KindError {
+                path: crate::path::Path::default(),
+                expected: Kind::List,
+                found: self.kind(),
+            });
+        };
+        match xs.iter().position(|x| x.kind() != kind) {
+            Some(i) => Err(KindError {
+                path: crate::path::Path::default().index(i),
+                expected: kind,
+                found: xs[i].kind(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// The name of this value's type as used in error messages: the [`name`](Kind::name)
+    /// of its kind, but `tagged` and `raw` for tagged values and raw text, which conversions
+    /// don't see through
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Tagged { .. } => "tagged",
+            Self::Raw(_) => "raw",
+            other => other.kind().name(),
+        }
+    }
+
+    /// Deeply merges `other` into this value: objects are merged key by key, everything
+    /// else (including lists) is replaced by `other`.
+    /// # Usage example
+    /// ```rust
+    /// use gon::parse_str;
+    /// let mut base = parse_str("{server: {host: \"localhost\", port: 80}}").unwrap();
+    /// base.merge(parse_str("{server: {port: 8080}}").unwrap());
+    /// assert_eq!(base, parse_str("{server: {host: \"localhost\", port: 8080}}").unwrap());
+    /// ```
+    pub fn merge(&mut self, other: Value) {
+        match (self, other) {
+            (Self::Obj(base), Self::Obj(overlay)) => {
+                for (k, v) in overlay {
+                    match base.get_mut(&k) {
+                        Some(existing) => existing.merge(v),
+                        None => {
+                            base.insert(k, v);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+
+    /// The value at `path`, `None` if there is nothing. See [`path::get`](crate::path::get).
+    pub fn get_path(&self, path: &crate::path::Path) -> Option<&Value> {
+        crate::path::get(self, path)
+    }
+
+    /// The value at `path` to change it, `None` if there is nothing
+    pub fn get_path_mut(&mut self, path: &crate::path::Path) -> Option<&mut Value> {
+        crate::path::get_mut(self, path)
+    }
+
+    /// Puts `new` at `path`, creating objects for missing keys on the way, and returns what
+    /// was there. See [`path::set`](crate::path::set).
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, path::Path};
+    /// let mut value = parse_str("{servers: [{port: 80}]}").unwrap();
+    /// let port = Path::default().key("servers").index(0).key("port");
+    /// let old = value.set_path(&port, parse_str("443").unwrap()).unwrap();
+    /// assert_eq!(old, Some(parse_str("80").unwrap()));
+    /// let max_players = "limits.\"max.players\"".parse().unwrap();
+    /// value.set_path(&max_players, parse_str("8").unwrap()).unwrap();
+    /// assert_eq!(
+    ///     value,
+    ///     parse_str(r#"{servers: [{port: 443}], limits: {"max.players": 8}}"#).unwrap()
+    /// );
+    /// ```
+    pub fn set_path(
+        &mut self,
+        path: &crate::path::Path,
+        new: Value,
+    ) -> Result<Option<Value>, crate::path::SetError> {
+        crate::path::set(self, path, new)
+    }
+
+    /// Removes the value at `path` and returns it without copying it, `None` if there is
+    /// nothing. Taking the empty path leaves `None` behind.
+    /// # Usage example
+    /// ```rust
+    /// use gon::parse_str;
+    /// let mut value = parse_str("{systems: {audio: {volume: 5}, input: [1, 2]}}").unwrap();
+    /// let audio = value.take_path(&"systems.audio".parse().unwrap());
+    /// assert_eq!(audio, Some(parse_str("{volume: 5}").unwrap()));
+    /// assert_eq!(value, parse_str("{systems: {input: [1, 2]}}").unwrap());
+    /// ```
+    pub fn take_path(&mut self, path: &crate::path::Path) -> Option<Value> {
+        use crate::path::{self, Segment};
+        let Some((last, parent)) = path.0.split_last() else {
+            return Some(std::mem::replace(self, Self::None));
+        };
+        let parent = path::get_mut(self, &path::Path(parent.to_vec()))?;
+        match (path::untagged_mut(parent), last) {
+            (Self::Obj(obj), Segment::Key(key)) => remove_key(obj, key),
+            (Self::List(xs), Segment::Index(i)) if *i < xs.len() => Some(xs.remove(*i)),
+            _ => None,
+        }
+    }
+
     /// Minimally spells this value
     pub fn min_spell(&self) -> String {
+        trace_span!(spelling, "gon::min_spell");
+        let mut buf = String::new();
+        // nothing is longer than usize::MAX bytes
+        let _ = self.min_spell0(&mut buf, usize::MAX);
+        trace_done!(spelling, bytes = buf.len(), "spelled");
+        buf
+    }
+
+    /// Minimally spells this value so that it's JSON as well as gon, like
+    /// [`SpellConfig::json_compat`] does: keys are quoted, `None` is `null`, numbers are
+    /// [normalized](SpellConfig::normalize_numbers) and strings only use the escapes JSON
+    /// knows. Numbers JSON can't spell become `null`, tags are left out and raw text is
+    /// parsed and spelled again, or spelled as a string if it doesn't parse.
+    /// # Usage example
+    /// ```rust
+    /// use gon::parse_str;
+    /// let value = parse_str(r#"{id: 1_000, loot: [None, 2.50], name: r"slime"}"#).unwrap();
+    /// assert_eq!(
+    ///     value.min_spell_json_compat(),
+    ///     r#"{"id":1000,"loot":[null,2.5],"name":"slime"}"#
+    /// );
+    /// ```
+    pub fn min_spell_json_compat(&self) -> String {
+        let mut buf = String::new();
+        self.min_spell_json0(&mut buf);
+        buf
+    }
+
+    fn min_spell_json0(&self, buf: &mut String) {
         match self {
-            Self::None => "None".into(),
-            Self::Str { s, raw } => {
-                if *raw {
-                    format!("r{}", klex::Token::Str(s.into()).spelling())
-                } else {
-                    klex::Token::Str(s.into()).spelling()
+            Self::None => buf.push_str("null"),
+            Self::Str { s, .. } => push_json_str(buf, s),
+            Self::Num(s) => push_json_number(buf, s),
+            Self::Bool(b) => buf.push_str(if *b { "true" } else { "false" }),
+            Self::Obj(m) => {
+                buf.push('{');
+                for (i, (k, v)) in spelling_order(m).enumerate() {
+                    if i != 0 {
+                        buf.push(',');
+                    }
+                    push_json_str(buf, k);
+                    buf.push(':');
+                    v.min_spell_json0(buf);
                 }
+                buf.push('}');
             }
-            Self::Num(s) => s.into(),
-            Self::Bool(b) => {
-                if *b {
-                    "true".into()
-                } else {
-                    "false".into()
+            Self::List(xs) => {
+                buf.push('[');
+                for (i, v) in xs.iter().enumerate() {
+                    if i != 0 {
+                        buf.push(',');
+                    }
+                    v.min_spell_json0(buf);
+                }
+                buf.push(']');
+            }
+            Self::Tagged { value, .. } => value.min_spell_json0(buf),
+            Self::Raw(text) => match crate::parse_str(text) {
+                Ok(value) => value.min_spell_json0(buf),
+                Err(_) => push_json_str(buf, text),
+            },
+        }
+    }
+
+    /// Minimally spells this value, or fails as soon as it's clear that the output would
+    /// exceed `limits`, before allocating much more than `max_bytes`
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, value::{LimitError, OutputLimits}};
+    /// let value = parse_str("{a: [[1]], b: \"hello\"}").unwrap();
+    /// let limits = OutputLimits { max_bytes: 16, max_depth: 2 };
+    /// assert_eq!(value.min_spell_limited(limits), Err(LimitError::TooDeep(2)));
+    /// let limits = OutputLimits { max_bytes: 16, ..Default::default() };
+    /// assert_eq!(value.min_spell_limited(limits), Err(LimitError::TooLong(16)));
+    /// ```
+    pub fn min_spell_limited(&self, limits: OutputLimits) -> Result<String, LimitError> {
+        self.check_depth(limits.max_depth)?;
+        let mut buf = String::new();
+        self.min_spell0(&mut buf, limits.max_bytes)
+            .map_err(|_| LimitError::TooLong(limits.max_bytes))?;
+        Ok(buf)
+    }
+
+    /// Pretty spells this value like [`Value::spell`], but tells why spelling failed when
+    /// it would exceed the [`output_limits`](SpellConfig::output_limits) of `config`
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, SpellConfig, value::{LimitError, OutputLimits}};
+    /// let value = parse_str("[1, 2, 3]").unwrap();
+    /// let config = SpellConfig {
+    ///     output_limits: OutputLimits { max_bytes: 4, ..Default::default() },
+    ///     ..Default::default()
+    /// };
+    /// assert_eq!(value.spell_limited(config), Err(LimitError::TooLong(4)));
+    /// assert!(value.spell(config).is_err());
+    /// ```
+    pub fn spell_limited(&self, config: SpellConfig) -> Result<String, LimitError> {
+        let limits = config.output_limits;
+        self.check_depth(limits.max_depth)?;
+        let mut buf = String::new();
+        self.spell0(&mut buf, 0, &config)
+            .map_err(|_| LimitError::TooLong(limits.max_bytes))?;
+        Ok(buf)
+    }
+
+    /// Fails if objects and lists are nested deeper than `max_depth`, without recursing so
+    /// that arbitrarily deep values don't overflow the stack
+    fn check_depth(&self, max_depth: usize) -> Result<(), LimitError> {
+        if max_depth == usize::MAX {
+            return Ok(());
+        }
+        let mut stack = vec![(self, 0)];
+        while let Some((value, depth)) = stack.pop() {
+            match value {
+                Self::Obj(_) | Self::List(_) if depth == max_depth => {
+                    return Err(LimitError::TooDeep(max_depth));
                 }
+                Self::Obj(obj) => stack.extend(obj.values().map(|v| (v, depth + 1))),
+                Self::List(xs) => stack.extend(xs.iter().map(|x| (x, depth + 1))),
+                Self::Tagged { value, .. } => stack.push((value, depth)),
+                _ => {}
             }
+        }
+        Ok(())
+    }
+
+    /// Fails once `buf` is longer than `max_bytes`
+    fn min_spell0(&self, buf: &mut String, max_bytes: usize) -> std::fmt::Result {
+        match self {
+            Self::None => buf.push_str("None"),
+            Self::Str { s, raw } => {
+                if *raw {
+                    buf.push('r');
+                }
+                buf.push_str(&klex::Token::Str(s.into()).spelling());
+            }
+            Self::Num(s) => buf.push_str(s),
+            Self::Bool(b) => buf.push_str(if *b { "true" } else { "false" }),
             Self::Obj(m) => {
-                let mut spelling = String::from("{");
-                for (i, (k, v)) in m.iter().enumerate() {
-                    let key_needs_quotes = key_needs_quoting(k);
-                    if key_needs_quotes {
-                        spelling.push('"');
-                    }
-                    spelling.push_str(k);
-                    if key_needs_quotes {
-                        spelling.push('"');
-                    }
-                    spelling.push(':');
-                    spelling.push_str(&v.min_spell());
-                    if i != m.len() - 1 {
-                        spelling.push(',');
+                buf.push('{');
+                for (i, (k, v)) in spelling_order(m).enumerate() {
+                    if i != 0 {
+                        buf.push(',');
                     }
+                    write_key(buf, k);
+                    buf.push(':');
+                    v.min_spell0(buf, max_bytes)?;
                 }
-                spelling.push('}');
-                spelling
+                buf.push('}');
             }
             Self::List(xs) => {
-                let mut spelling = String::from("[");
+                buf.push('[');
                 for (i, v) in xs.iter().enumerate() {
-                    spelling.push_str(&v.min_spell());
-                    if i != xs.len() - 1 {
-                        spelling.push(',');
+                    if i != 0 {
+                        buf.push(',');
                     }
+                    v.min_spell0(buf, max_bytes)?;
+                }
+                buf.push(']');
+            }
+            Self::Tagged { value, .. } => value.min_spell0(buf, max_bytes)?,
+            Self::Raw(text) => {
+                buf.push_str(text);
+                if text.ends_in_line_comment() {
+                    buf.push('\n');
                 }
-                spelling.push(']');
-                spelling
             }
         }
+        if buf.len() > max_bytes {
+            return Err(std::fmt::Error);
+        }
+        Ok(())
     }
 
+    /// Pretty spells this value. The result is a fixpoint: parsing and spelling it again with
+    /// the same config yields the very same text. Unless strings are wrapped or a custom
+    /// renderer is used, it also parses back into an equal value. Fails if the
+    /// [`output_limits`](SpellConfig::output_limits) are exceeded.
     pub fn spell(&self, config: SpellConfig) -> Result<String, std::fmt::Error> {
+        trace_span!(spelling, "gon::spell");
+        self.check_depth(config.output_limits.max_depth)
+            .map_err(|_| std::fmt::Error)?;
         let mut buf = String::new();
         self.spell0(&mut buf, 0, &config)?;
+        trace_done!(spelling, bytes = buf.len(), "spelled");
         Ok(buf)
     }
 
-    fn spell0(
+    /// Pretty spells this value as if it started `indent` chars deep into a line, for
+    /// replacing part of a document. The first line isn't indented, it goes where the old
+    /// value started; the lines after it are indented relative to `indent`.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, SpellConfig};
+    /// let value = parse_str("{hp: 10}").unwrap();
+    /// let fragment = value.spell_fragment(4, SpellConfig::default()).unwrap();
+    /// assert_eq!(fragment, "{\n        hp: 10\n    }");
+    /// ```
+    pub fn spell_fragment(
+        &self,
+        indent: usize,
+        config: SpellConfig,
+    ) -> Result<String, std::fmt::Error> {
+        self.check_depth(config.output_limits.max_depth)
+            .map_err(|_| std::fmt::Error)?;
+        let mut buf = String::new();
+        self.spell0(&mut buf, indent, &config)?;
+        Ok(buf)
+    }
+
+    /// Pretty spells the value at `path` exactly like it's spelled as part of this whole
+    /// value, `None` if there is nothing. See [`Value::spell_fragment`].
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, SpellConfig};
+    /// let value = parse_str("{enemies: [{hp: 10, name: \"Goblin\"}]}").unwrap();
+    /// let config = SpellConfig::default();
+    /// let goblin = value.spell_at(&"enemies[0]".parse().unwrap(), config).unwrap().unwrap();
+    /// assert_eq!(goblin, "{\n            hp: 10,\n            name: \"Goblin\"\n        }");
+    /// assert!(value.spell(config).unwrap().contains(&goblin));
+    /// ```
+    pub fn spell_at(
+        &self,
+        path: &crate::path::Path,
+        config: SpellConfig,
+    ) -> Result<Option<String>, std::fmt::Error> {
+        use crate::path::{self, Segment};
+        let mut value = self;
+        let mut indent = 0;
+        for segment in &path.0 {
+            let parent = path::untagged(value);
+            let child = match (parent, segment) {
+                (Self::Obj(obj), Segment::Key(key)) => obj.get(key.as_str()),
+                (Self::List(xs), Segment::Index(i)) => xs.get(*i),
+                _ => None,
+            };
+            let Some(child) = child else {
+                return Ok(None);
+            };
+            // the elements of inline objects and lists are spelled as if they weren't indented
+            let inline = match parent {
+                Self::Obj(obj) => write_inline_obj(&mut String::new(), obj, &config)?,
+                Self::List(xs) => write_inline_list(&mut String::new(), xs, &config)?,
+                _ => false,
+            };
+            indent = if inline {
+                0
+            } else {
+                indent + config.indent_amount
+            };
+            value = child;
+        }
+        value.spell_fragment(indent, config).map(Some)
+    }
+
+    pub(crate) fn spell0(
         &self,
         buf: &mut String,
         current_indent: usize,
         config: &SpellConfig,
     ) -> std::fmt::Result {
+        if let Some(render) = config.custom_renderer {
+            let start = buf.len();
+            if render(self, buf).is_some() {
+                return Ok(());
+            }
+            buf.truncate(start);
+        }
         match self {
+            Self::None if config.json_compat => write!(buf, "null")?,
             Self::None => write!(buf, "None")?,
+            Self::Str { s, .. } if config.json_compat => push_json_str(buf, s),
             Self::Str { s, raw } => {
-                if config.max_width == 0 {
-                    write!(buf, "{}", klex::Token::Str(s.clone()).spelling())?;
-                } else if *raw {
+                if *raw {
                     write!(buf, "r{}", klex::Token::Str(s.clone()).spelling())?;
+                } else if config.max_width == 0 {
+                    write!(buf, "{}", klex::Token::Str(s.clone()).spelling())?;
+                } else if config.preserve_strings || cfg!(not(feature = "wrap")) {
+                    write_str_pieces(buf, s, current_indent + config.indent_amount, config)?;
                 } else {
-                    let mut raw_str = format!("{}", klex::Token::Str(s.clone()).spelling());
-                    raw_str = squash_whitespace(&raw_str);
-                    let wrapped_lines = textwrap::wrap(
-                        &raw_str,
-                        textwrap::Options::new(config.max_width).subsequent_indent(&gen_indent(
-                            current_indent + config.indent_amount,
-                            config,
-                        )),
-                    );
-                    for (i, line) in wrapped_lines.iter().enumerate() {
-                        if i == wrapped_lines.len() - 1 {
-                            write!(buf, "{line}")?;
-                        } else {
-                            writeln!(buf, "{line}")?;
-                        }
-                    }
+                    #[cfg(feature = "wrap")]
+                    write_wrapped_str(buf, s, current_indent + config.indent_amount, config)?;
                 }
             }
+            Self::Num(s) if config.json_compat => push_json_number(buf, s),
+            Self::Num(s) if config.normalize_numbers => buf.push_str(&normalize_number(s)),
             Self::Num(s) => write!(buf, "{s}")?,
             Self::Bool(b) => write!(buf, "{b}")?,
-            Self::Obj(obj) => {
+            Self::Obj(obj) => 'match_arm: {
+                if write_inline_obj(buf, obj, config)? {
+                    break 'match_arm;
+                }
                 writeln!(buf, "{{")?;
                 let new_indent = current_indent + config.indent_amount;
-                for (i, (k, v)) in obj.iter().enumerate() {
-                    apply_indent(buf, new_indent, config)?;
-                    if key_needs_quoting(k) {
-                        write!(buf, "\"{k}\": ")?;
-                    } else {
-                        write!(buf, "{k}: ")?;
-                    }
+                let entries: Vec<_> = spelled_entries(obj, config).collect();
+                for (i, (k, v)) in entries.iter().enumerate() {
+                    apply_indent(buf, new_indent, config);
+                    let v = write_entry_key(buf, k, v, config);
+                    write!(buf, ": ")?;
                     v.spell0(buf, new_indent, config)?;
-                    if !config.trailing_commas && i == obj.len() - 1 {
-                        writeln!(buf, "")?;
+                    let last = i == entries.len() - 1;
+                    // commas are optional, one after a line comment would be commented out
+                    if ends_in_line_comment(v) || (last && !config.trailing_commas) {
+                        writeln!(buf)?;
                     } else {
                         writeln!(buf, ",")?;
                     }
                 }
-                apply_indent(buf, current_indent, config)?;
+                apply_indent(buf, current_indent, config);
                 write!(buf, "}}")?;
             }
             Self::List(xs) => 'match_arm: {
@@ -185,75 +948,1120 @@ impl Value {
                     write!(buf, "[]")?;
                     break 'match_arm;
                 }
-                let oneline = xs.len() <= 5
-                    && xs
-                        .iter()
-                        .find(|v| matches!(v, Self::List(_) | Self::Obj(_)))
-                        .is_none();
-                if oneline {
-                    write!(buf, "[")?;
-                } else {
-                    writeln!(buf, "[")?;
+                if write_inline_list(buf, xs, config)? {
+                    break 'match_arm;
                 }
+                writeln!(buf, "[")?;
+                let new_indent = current_indent + config.indent_amount;
                 for (i, x) in xs.iter().enumerate() {
-                    if oneline {
-                        x.spell0(buf, 0, config)?;
-                    } else {
-                        let new_indent = current_indent + config.indent_amount;
-                        apply_indent(buf, new_indent, config)?;
-                        x.spell0(buf, new_indent, config)?;
+                    apply_indent(buf, new_indent, config);
+                    x.spell0(buf, new_indent, config)?;
+                    if !ends_in_line_comment(x) && (config.trailing_commas || i != xs.len() - 1) {
+                        write!(buf, ",")?;
                     }
-                    if oneline {
-                        if i != xs.len() - 1 {
-                            write!(buf, ", ")?;
-                        }
-                    } else {
-                        if config.trailing_commas || i != xs.len() - 1 {
-                            write!(buf, ",")?;
+                    writeln!(buf)?;
+                }
+                apply_indent(buf, current_indent, config);
+                write!(buf, "]")?;
+            }
+            Self::Tagged { value, .. } => value.spell0(buf, current_indent, config)?,
+            Self::Raw(text) if config.json_compat => match crate::parse_str(text) {
+                Ok(value) => value.spell0(buf, current_indent, config)?,
+                Err(_) => push_json_str(buf, text),
+            },
+            Self::Raw(text) => {
+                for (i, line) in text.lines().enumerate() {
+                    if i != 0 {
+                        writeln!(buf)?;
+                        if !line.is_empty() {
+                            apply_indent(buf, current_indent, config);
                         }
-                        writeln!(buf, "")?;
                     }
+                    buf.push_str(line);
                 }
-                if !oneline {
-                    apply_indent(buf, current_indent, config)?;
-                }
-                write!(buf, "]")?;
             }
         }
+        if buf.len() > config.output_limits.max_bytes {
+            return Err(std::fmt::Error);
+        }
         Ok(())
     }
 }
 
-fn squash_whitespace(input: &str) -> String {
-    let re = regex::Regex::new(r"[ \t\r\n]{2,}").unwrap();
-    re.replace_all(input, " ").into_owned()
+/// Removes `key` from `map`, keeping the order of the other entries with `preserve_order`
+pub(crate) fn remove_key(map: &mut crate::MapT, key: &str) -> Option<Value> {
+    remove_entry(map, key).map(|(_, v)| v)
+}
+
+#[cfg(feature = "preserve_order")]
+fn remove_entry(map: &mut crate::MapT, key: &str) -> Option<(crate::KeyT, Value)> {
+    map.shift_remove_entry(key)
 }
 
-fn apply_indent(buf: &mut String, amount: usize, config: &SpellConfig) -> std::fmt::Result {
-    write!(buf, "{}", gen_indent(amount, config))
+#[cfg(not(feature = "preserve_order"))]
+fn remove_entry(map: &mut crate::MapT, key: &str) -> Option<(crate::KeyT, Value)> {
+    map.remove_entry(key)
 }
 
-fn gen_indent(amount: usize, config: &SpellConfig) -> String {
-    std::iter::repeat(config.indent_char)
-        .take(amount)
-        .collect::<String>()
+/// Whether `num` is spelled like an integer, without a fraction or an exponent
+pub(crate) fn is_int_literal(num: &str) -> bool {
+    let digits = num.strip_prefix(['-', '+']).unwrap_or(num);
+    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// The canonical spelling of a number, see [`SpellConfig::normalize_numbers`]. Anything
+/// that isn't a number literal is returned as is.
+pub(crate) fn normalize_number(num: &str) -> String {
+    let (negative, digits) = match num.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, num.strip_prefix('+').unwrap_or(num)),
+    };
+    if !crate::parser::is_number_literal(digits) {
+        return num.to_string();
+    }
+    let digits = digits.replace('_', "").to_ascii_lowercase();
+    let (mantissa, exponent) = match digits.split_once('e') {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (&*digits, None),
+    };
+    let (int, fraction) = match mantissa.split_once('.') {
+        Some((int, fraction)) => (int, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let mut out = String::with_capacity(digits.len() + 1);
+    if negative {
+        out.push('-');
+    }
+    out.push_str(strip_leading_zeros(int));
+    if let Some(fraction) = fraction {
+        let fraction = fraction.trim_end_matches('0');
+        out.push('.');
+        out.push_str(if fraction.is_empty() { "0" } else { fraction });
+    }
+    if let Some(exponent) = exponent {
+        out.push('e');
+        let exponent = match exponent.strip_prefix('-') {
+            Some(exponent) => {
+                out.push('-');
+                exponent
+            }
+            None => exponent.strip_prefix('+').unwrap_or(exponent),
+        };
+        out.push_str(strip_leading_zeros(exponent));
+    }
+    out
+}
+
+/// `digits` without leading zeros, but at least `"0"`
+fn strip_leading_zeros(digits: &str) -> &str {
+    let stripped = digits.trim_start_matches('0');
+    if stripped.is_empty() { "0" } else { stripped }
+}
+
+/// What raw text stands for when it's converted into something other than gon, a string
+/// if it doesn't parse
+pub(crate) fn parse_raw(text: &str) -> Value {
+    crate::parse_str(text).unwrap_or_else(|_| Value::Str {
+        s: text.to_string(),
+        raw: false,
+    })
+}
+
+impl RawText {
+    /// The kind of value the text parses into, or [`Kind::Str`] if it doesn't parse. It's
+    /// only parsed the first time.
+    pub fn kind(&self) -> Kind {
+        *self.kind.get_or_init(|| parse_raw(&self.text).kind())
+    }
+
+    /// Whether the last token of the text is a line comment, which would comment out
+    /// whatever is spelled after it on the same line
+    pub fn ends_in_line_comment(&self) -> bool {
+        *self.line_comment.get_or_init(|| {
+            let mut pos = 0;
+            let last =
+                std::iter::from_fn(|| crate::parser::next_token(&self.text, &mut pos)).last();
+            last.is_some_and(|token| {
+                token.kind == crate::parser::TokenKind::Comment
+                    && !token.text(&self.text).starts_with("/*")
+            })
+        })
+    }
+}
+
+impl From<&str> for RawText {
+    fn from(text: &str) -> Self {
+        Self::from(text.to_string())
+    }
+}
+
+impl From<String> for RawText {
+    fn from(text: String) -> Self {
+        Self {
+            text,
+            ..Default::default()
+        }
+    }
+}
+
+impl std::ops::Deref for RawText {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl PartialEq for RawText {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for RawText {}
+
+impl std::fmt::Debug for RawText {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.text, f)
+    }
+}
+
+fn invalid_option(key: &str, message: impl Into<String>) -> SpellConfigError {
+    SpellConfigError::Invalid(key.into(), message.into())
+}
+
+fn convert_option<'a, T>(key: &str, value: &'a Value) -> Result<T, SpellConfigError>
+where
+    T: TryFrom<&'a Value, Error = TypeError>,
+{
+    T::try_from(value).map_err(|e| invalid_option(key, e.to_string()))
+}
+
+/// Reads `{max_entries, max_width}`, missing keys keep their default
+fn inline_limits(key: &str, value: &Value) -> Result<InlineLimits, SpellConfigError> {
+    let obj = value
+        .as_obj()
+        .ok_or_else(|| invalid_option(key, "expected {max_entries, max_width}"))?;
+    let mut limits = InlineLimits::default();
+    for (k, v) in obj.iter() {
+        let path = format!("{key}.{k}");
+        match &**k {
+            "max_entries" => limits.max_entries = convert_option(&path, v)?,
+            "max_width" => limits.max_width = convert_option(&path, v)?,
+            _ => return Err(invalid_option(&path, "unknown option")),
+        }
+    }
+    Ok(limits)
+}
+
+/// Reads `{max_bytes, max_depth}`, missing keys stay unlimited
+fn output_limits(key: &str, value: &Value) -> Result<OutputLimits, SpellConfigError> {
+    let obj = value
+        .as_obj()
+        .ok_or_else(|| invalid_option(key, "expected {max_bytes, max_depth}"))?;
+    let mut limits = OutputLimits::default();
+    for (k, v) in obj.iter() {
+        let path = format!("{key}.{k}");
+        match &**k {
+            "max_bytes" => limits.max_bytes = convert_option(&path, v)?,
+            "max_depth" => limits.max_depth = convert_option(&path, v)?,
+            _ => return Err(invalid_option(&path, "unknown option")),
+        }
+    }
+    Ok(limits)
+}
+
+/// The entries of `obj` in the order they're spelled in: insertion order with the
+/// `preserve_order` feature, sorted by key without it, so spelling is deterministic
+#[cfg(feature = "preserve_order")]
+pub(crate) fn spelling_order(obj: &crate::MapT) -> impl Iterator<Item = (&crate::KeyT, &Value)> {
+    obj.iter()
+}
+
+/// The entries of `obj` in the order they're spelled in: insertion order with the
+/// `preserve_order` feature, sorted by key without it, so spelling is deterministic
+#[cfg(not(feature = "preserve_order"))]
+pub(crate) fn spelling_order(obj: &crate::MapT) -> impl Iterator<Item = (&crate::KeyT, &Value)> {
+    let mut entries: Vec<_> = obj.iter().collect();
+    entries.sort_by_key(|(k, _)| *k);
+    entries.into_iter()
+}
+
+/// The entries of `obj` that are spelled with `config`, in [`spelling_order`]
+fn spelled_entries<'a>(
+    obj: &'a crate::MapT,
+    config: &SpellConfig,
+) -> impl Iterator<Item = (&'a crate::KeyT, &'a Value)> {
+    let skip_none = config.skip_none;
+    spelling_order(obj).filter(move |(_, v)| !(skip_none && v.is_nullish()))
+}
+
+/// Spells `obj` on a single line if it's within [`SpellConfig::inline_small_objects`],
+/// returns whether it did
+fn write_inline_obj(
+    buf: &mut String,
+    obj: &crate::MapT,
+    config: &SpellConfig,
+) -> Result<bool, std::fmt::Error> {
+    let Some(limits) = config.inline_small_objects else {
+        return Ok(false);
+    };
+    let entries: Vec<_> = spelled_entries(obj, config).collect();
+    if entries.is_empty()
+        || entries.len() > limits.max_entries
+        || entries.iter().any(|(_, v)| is_container(v))
+    {
+        return Ok(false);
+    }
+    let start = buf.len();
+    write!(buf, "{{")?;
+    for (i, (k, v)) in entries.into_iter().enumerate() {
+        if i != 0 {
+            write!(buf, ", ")?;
+        }
+        write_entry_key(buf, k, v, config);
+        write!(buf, ": ")?;
+        v.spell0(buf, 0, config)?;
+    }
+    write!(buf, "}}")?;
+    let line = &buf[start..];
+    let fits = !line.contains('\n') && line.chars().count() <= limits.max_width;
+    if !fits {
+        buf.truncate(start);
+    }
+    Ok(fits)
+}
+
+/// Whether `value` keeps its parent from being spelled on one line. Raw text does if it
+/// spans lines, ends in a line comment or parses into an object or list.
+fn is_container(value: &Value) -> bool {
+    let container = match value {
+        Value::List(_) | Value::Obj(_) => true,
+        Value::Raw(text) => text.contains('\n') || matches!(text.kind(), Kind::List | Kind::Obj),
+        _ => false,
+    };
+    container || ends_in_line_comment(value)
+}
+
+/// Whether what's spelled after `value` on the same line would be commented out
+fn ends_in_line_comment(value: &Value) -> bool {
+    match value {
+        Value::Tagged { value, .. } => ends_in_line_comment(value),
+        Value::Raw(text) => text.ends_in_line_comment(),
+        _ => false,
+    }
+}
+
+/// Spells `xs` on a single line if [`SpellConfig::inline_lists`] allows it, returns
+/// whether it did
+fn write_inline_list(
+    buf: &mut String,
+    xs: &[Value],
+    config: &SpellConfig,
+) -> Result<bool, std::fmt::Error> {
+    let limits = match config.inline_lists {
+        InlineMode::Never => return Ok(false),
+        InlineMode::Always => None,
+        InlineMode::Limits(limits) if xs.len() > limits.max_entries => return Ok(false),
+        InlineMode::Limits(limits) => Some(limits),
+    };
+    if xs.iter().any(is_container) {
+        return Ok(false);
+    }
+    let start = buf.len();
+    write!(buf, "[")?;
+    for (i, x) in xs.iter().enumerate() {
+        if i != 0 {
+            write!(buf, ", ")?;
+        }
+        x.spell0(buf, 0, config)?;
+    }
+    write!(buf, "]")?;
+    let fits = limits.is_none_or(|l| buf[start..].chars().count() <= l.max_width);
+    if !fits {
+        buf.truncate(start);
+    }
+    Ok(fits)
+}
+
+/// Spells `s` as literals of at most `config.max_width` characters joined with `+`, each
+/// on its own line. It's only split after spaces and always after newlines, so the literals
+/// concatenate back to exactly `s`.
+fn write_str_pieces(
+    buf: &mut String,
+    s: &str,
+    indent: usize,
+    config: &SpellConfig,
+) -> std::fmt::Result {
+    let mut piece_start = 0;
+    let mut piece_width = 0;
+    let mut end = 0;
+    for chunk in s.split_inclusive([' ', '\n']) {
+        let chunk_width = chunk.chars().count();
+        let too_long = piece_width + chunk_width > config.max_width;
+        if end != piece_start && (too_long || s[..end].ends_with('\n')) {
+            write_str_piece(buf, &s[piece_start..end], piece_start == 0, indent, config)?;
+            piece_start = end;
+            piece_width = 0;
+        }
+        piece_width += chunk_width;
+        end += chunk.len();
+    }
+    write_str_piece(buf, &s[piece_start..], piece_start == 0, indent, config)
+}
+
+fn write_str_piece(
+    buf: &mut String,
+    piece: &str,
+    first: bool,
+    indent: usize,
+    config: &SpellConfig,
+) -> std::fmt::Result {
+    if !first {
+        writeln!(buf, " +")?;
+        apply_indent(buf, indent, config);
+    }
+    write!(buf, "{}", klex::Token::Str(piece.into()).spelling())
+}
+
+/// Spells `s` with its whitespace squashed and wrapped at `config.max_width`. The line
+/// breaks and the indentation after them become part of the string, spelling it again
+/// squashes them back into single spaces and wraps it the same way.
+#[cfg(feature = "wrap")]
+fn write_wrapped_str(
+    buf: &mut String,
+    s: &str,
+    indent: usize,
+    config: &SpellConfig,
+) -> std::fmt::Result {
+    let squashed = squash_whitespace(s);
+    let options = textwrap::Options::new(config.max_width).break_words(false);
+    write!(buf, "\"")?;
+    for (i, line) in textwrap::wrap(squashed.trim(), options).iter().enumerate() {
+        if i != 0 {
+            writeln!(buf)?;
+            apply_indent(buf, indent, config);
+        }
+        write!(buf, "{}", escape_str(line))?;
+    }
+    write!(buf, "\"")
+}
+
+/// `input` with every run of spaces, tabs and line breaks replaced by a single space
+#[cfg(feature = "wrap")]
+fn squash_whitespace(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        if !matches!(c, ' ' | '\t' | '\r' | '\n') {
+            out.push(c);
+        } else if !out.ends_with(' ') {
+            out.push(' ');
+        }
+    }
+    out
+}
+
+pub(crate) fn apply_indent(buf: &mut String, amount: usize, config: &SpellConfig) {
+    buf.extend(std::iter::repeat_n(config.indent_char, amount));
 }
 
 fn key_needs_quoting(key: &str) -> bool {
-    let lexer_result = klex::Lexer::new(key, 0).lex();
-    match lexer_result {
-        Ok(tokens) => tokens.len() > 1,
+    let mut chars = key.chars();
+    let is_ident = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if is_ident {
+        return false;
+    }
+    match klex::Lexer::new(key, 0).lex().as_deref() {
+        Ok([rt]) => !matches!(&rt.inner, klex::Token::Sym(s) | klex::Token::Num(s) if s == key),
         _ => true,
     }
 }
 
+/// Spells an object key, in quotes if it has to be
+pub(crate) fn spell_key(key: &str) -> String {
+    let mut buf = String::new();
+    write_key(&mut buf, key);
+    buf
+}
+
+/// Appends `s` as a JSON string literal, escaped like serde_json does. Gon reads the
+/// escapes of quotes, backslashes, line breaks and tabs too.
+pub(crate) fn push_json_str(buf: &mut String, s: &str) {
+    buf.push('"');
+    for c in s.chars() {
+        match json_escape(c) {
+            Some(escaped) => buf.push_str(&escaped),
+            None => buf.push(c),
+        }
+    }
+    buf.push('"');
+}
+
+/// How `c` is escaped in a JSON string, if it has to be
+pub(crate) fn json_escape(c: char) -> Option<Cow<'static, str>> {
+    Some(Cow::Borrowed(match c {
+        '"' => "\\\"",
+        '\\' => "\\\\",
+        '\n' => "\\n",
+        '\t' => "\\t",
+        '\r' => "\\r",
+        '\u{8}' => "\\b",
+        '\u{c}' => "\\f",
+        c if (c as u32) < 0x20 => return Some(Cow::Owned(format!("\\u{:04x}", c as u32))),
+        _ => return None,
+    }))
+}
+
+/// Appends `num` [normalized](normalize_number), or `null` if it isn't a number literal
+fn push_json_number(buf: &mut String, num: &str) {
+    let digits = num.strip_prefix(['-', '+']).unwrap_or(num);
+    if crate::parser::is_number_literal(digits) {
+        buf.push_str(&normalize_number(num));
+    } else {
+        buf.push_str("null");
+    }
+}
+
+/// Escapes `s` so it can be put between the double quotes of a string literal, for
+/// building gon text without going through a [`Value`]. [`unescape_str`] reverses it.
+/// # Usage example
+/// ```rust
+/// use gon::{escape_str, parse_str, unescape_str, Value};
+/// let name = "say \"hi\"\n";
+/// let src = format!("{{name: \"{}\"}}", escape_str(name));
+/// assert_eq!(src, r#"{name: "say \"hi\"\n"}"#);
+/// let value = parse_str(&format!("\"{}\"", escape_str(name))).unwrap();
+/// assert_eq!(value, Value::Str { s: name.into(), raw: false });
+/// assert_eq!(unescape_str(&escape_str(name)).unwrap(), name);
+/// assert!(unescape_str("unquoted \" quote").is_err());
+/// ```
+pub fn escape_str(s: &str) -> String {
+    let spelling = klex::Token::Str(s.into()).spelling();
+    spelling[1..spelling.len() - 1].to_string()
+}
+
+/// The string that `escaped`, the inside of a string literal without its quotes, spells.
+/// Escape sequences are resolved like the parser does, see [`escape_str`].
+pub fn unescape_str(escaped: &str) -> Result<String, UnescapeError> {
+    let literal = format!("\"{escaped}\"");
+    match klex::Lexer::new(&literal, 0).lex().as_deref() {
+        Ok(
+            [
+                klex::RichToken {
+                    inner: klex::Token::Str(s),
+                    ..
+                },
+            ],
+        ) => Ok(s.clone()),
+        _ => Err(UnescapeError(escaped.to_string())),
+    }
+}
+
+/// Writes `key` and, with [`SpellConfig::dotted_keys`], the keys of the objects with a
+/// single entry that `value` starts a chain of. Returns the value after the last key.
+fn write_entry_key<'v>(
+    buf: &mut String,
+    key: &str,
+    mut value: &'v Value,
+    config: &SpellConfig,
+) -> &'v Value {
+    if config.json_compat {
+        push_json_str(buf, key);
+        return value;
+    }
+    write_key(buf, key);
+    let is_plain = |key: &str| !key.contains('.') && !key_needs_quoting(key);
+    if !config.dotted_keys || !is_plain(key) {
+        return value;
+    }
+    while let Value::Obj(obj) = value {
+        let mut entries = obj.iter();
+        match (entries.next(), entries.next()) {
+            (Some((k, v)), None) if is_plain(k) => {
+                buf.push('.');
+                buf.push_str(k);
+                value = v;
+            }
+            _ => break,
+        }
+    }
+    value
+}
+
+pub(crate) fn write_key(buf: &mut String, key: &str) {
+    if key_needs_quoting(key) {
+        buf.push_str(&klex::Token::Str(key.into()).spelling());
+    } else {
+        buf.push_str(key);
+    }
+}
+
+impl Object {
+    /// Views a map as an `Object` without copying it
+    pub fn from_map_ref(map: &crate::MapT) -> &Self {
+        // SAFETY: `Object` is a `repr(transparent)` wrapper around `MapT`
+        unsafe { &*(map as *const crate::MapT as *const Self) }
+    }
+
+    /// Views a map as a mutable `Object` without copying it
+    pub fn from_map_mut(map: &mut crate::MapT) -> &mut Self {
+        // SAFETY: `Object` is a `repr(transparent)` wrapper around `MapT`
+        unsafe { &mut *(map as *mut crate::MapT as *mut Self) }
+    }
+
+    /// Removes the entries with the given keys and returns them as a new object, without
+    /// copying their values. Keys that aren't there are ignored.
+    /// # Usage example
+    /// ```rust
+    /// use gon::parse_str;
+    /// let mut value = parse_str("{audio: {}, input: {}, render: {}}").unwrap();
+    /// let split = value.as_obj_mut().unwrap().split_off(&["audio", "render", "net"]);
+    /// assert_eq!(split.len(), 2);
+    /// assert_eq!(value, parse_str("{input: {}}").unwrap());
+    /// ```
+    pub fn split_off(&mut self, keys: &[&str]) -> Object {
+        let mut split = crate::MapT::new();
+        for key in keys {
+            if let Some((k, v)) = remove_entry(&mut self.inner, key) {
+                split.insert(k, v);
+            }
+        }
+        split.into()
+    }
+
+    /// Converts the value of `key` with `T::try_from`, failing with the key, the expected
+    /// and the found type if it's missing or of another type.
+    /// # Usage example
+    /// ```rust
+    /// use gon::parse_str;
+    /// let value = parse_str(r#"{name: "slime", hp: "many"}"#).unwrap();
+    /// let enemy = value.as_obj().unwrap();
+    /// assert_eq!(enemy.require_str("name"), Ok("slime"));
+    /// assert_eq!(
+    ///     enemy.require_i64("hp").unwrap_err().to_string(),
+    ///     "field 'hp': expected i64, found str"
+    /// );
+    /// assert_eq!(
+    ///     enemy.require::<bool>("boss").unwrap_err().to_string(),
+    ///     "field 'boss': expected bool, found nothing"
+    /// );
+    /// ```
+    pub fn require<'a, T>(&'a self, key: &str) -> Result<T, FieldError>
+    where
+        T: TryFrom<&'a Value, Error = TypeError>,
+    {
+        match self.inner.get(key) {
+            Some(value) => T::try_from(value).map_err(|e| field_error(key, e)),
+            None => Err(FieldError {
+                key: key.into(),
+                expected: expected_type::<T>(),
+                found: "nothing",
+            }),
+        }
+    }
+
+    /// The value of `key` unless it's missing or [nullish](Value::is_nullish)
+    pub fn get_non_none(&self, key: &str) -> Option<&Value> {
+        self.inner.get(key).filter(|v| !v.is_nullish())
+    }
+
+    /// Like [`optional`](Self::optional), but tells a missing key and `None` apart.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, value::Presence};
+    /// let value = parse_str("{volume: None, speed: 2}").unwrap();
+    /// let settings = value.as_obj().unwrap();
+    /// assert_eq!(settings.presence::<u8>("volume"), Ok(Presence::None));
+    /// assert_eq!(settings.presence::<u8>("speed"), Ok(Presence::Some(2)));
+    /// assert_eq!(settings.presence::<u8>("mode"), Ok(Presence::Missing));
+    /// assert_eq!(settings.get_non_none("volume"), None);
+    /// ```
+    pub fn presence<'a, T>(&'a self, key: &str) -> Result<Presence<T>, FieldError>
+    where
+        T: TryFrom<&'a Value, Error = TypeError>,
+    {
+        match self.inner.get(key) {
+            None => Ok(Presence::Missing),
+            Some(value) if value.is_nullish() => Ok(Presence::None),
+            Some(value) => T::try_from(value)
+                .map(Presence::Some)
+                .map_err(|e| field_error(key, e)),
+        }
+    }
+
+    /// Like [`require`](Self::require), but a missing key or `None` is `Ok(None)`
+    pub fn optional<'a, T>(&'a self, key: &str) -> Result<Option<T>, FieldError>
+    where
+        T: TryFrom<&'a Value, Error = TypeError>,
+    {
+        match self.inner.get(key) {
+            None | Some(Value::None) => Ok(None),
+            Some(value) => T::try_from(value)
+                .map(Some)
+                .map_err(|e| field_error(key, e)),
+        }
+    }
+
+    pub fn require_str(&self, key: &str) -> Result<&str, FieldError> {
+        self.require(key)
+    }
+
+    pub fn require_i64(&self, key: &str) -> Result<i64, FieldError> {
+        self.require(key)
+    }
+
+    pub fn require_u64(&self, key: &str) -> Result<u64, FieldError> {
+        self.require(key)
+    }
+
+    pub fn require_f64(&self, key: &str) -> Result<f64, FieldError> {
+        self.require(key)
+    }
+
+    pub fn require_bool(&self, key: &str) -> Result<bool, FieldError> {
+        self.require(key)
+    }
+
+    pub fn require_obj(&self, key: &str) -> Result<&Object, FieldError> {
+        self.require(key)
+    }
+
+    pub fn require_list(&self, key: &str) -> Result<&List, FieldError> {
+        self.require(key)
+    }
+
+    pub fn optional_str(&self, key: &str) -> Result<Option<&str>, FieldError> {
+        self.optional(key)
+    }
+
+    pub fn optional_i64(&self, key: &str) -> Result<Option<i64>, FieldError> {
+        self.optional(key)
+    }
+
+    pub fn optional_u64(&self, key: &str) -> Result<Option<u64>, FieldError> {
+        self.optional(key)
+    }
+
+    pub fn optional_f64(&self, key: &str) -> Result<Option<f64>, FieldError> {
+        self.optional(key)
+    }
+
+    pub fn optional_bool(&self, key: &str) -> Result<Option<bool>, FieldError> {
+        self.optional(key)
+    }
+
+    pub fn optional_obj(&self, key: &str) -> Result<Option<&Object>, FieldError> {
+        self.optional(key)
+    }
+
+    pub fn optional_list(&self, key: &str) -> Result<Option<&List>, FieldError> {
+        self.optional(key)
+    }
+}
+
+fn field_error(key: &str, e: TypeError) -> FieldError {
+    FieldError {
+        key: key.into(),
+        expected: e.expected,
+        found: e.found,
+    }
+}
+
+/// The name of the type `T` converts values into, taken from the error of converting `None`
+pub(crate) fn expected_type<'a, T>() -> &'static str
+where
+    T: TryFrom<&'a Value, Error = TypeError>,
+{
+    const NONE: &Value = &Value::None;
+    T::try_from(NONE).err().map_or("a value", |e| e.expected)
+}
+
+impl List {
+    /// Views a vector of values as a `List` without copying it
+    #[allow(clippy::ptr_arg)]
+    pub fn from_vec_ref(xs: &Vec<Value>) -> &Self {
+        // SAFETY: `List` is a `repr(transparent)` wrapper around `Vec<Value>`
+        unsafe { &*(xs as *const Vec<Value> as *const Self) }
+    }
+
+    /// The kinds of the elements, each once, in the order they first appear. A list of more
+    /// than one kind is heterogeneous, see [`Value::assert_homogeneous`].
+    pub fn element_kinds(&self) -> Vec<Kind> {
+        let mut kinds = Vec::new();
+        for kind in self.inner.iter().map(Value::kind) {
+            if !kinds.contains(&kind) {
+                kinds.push(kind);
+            }
+        }
+        kinds
+    }
+
+    /// Borrows all elements as strings
+    pub fn as_vec_of_str(&self) -> Result<Vec<&str>, ElementError> {
+        self.try_map()
+    }
+
+    /// Converts all elements to floats
+    pub fn as_vec_of_f64(&self) -> Result<Vec<f64>, ElementError> {
+        self.try_map()
+    }
+
+    /// Borrows all elements as objects
+    pub fn as_vec_of_obj(&self) -> Result<Vec<&Object>, ElementError> {
+        self.try_map()
+    }
+
+    /// Converts every element with `T::try_from`, failing with the index of the first
+    /// element that couldn't be converted.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, value::ElementError};
+    /// let value = parse_str("[1, 2, \"three\"]").unwrap();
+    /// let list = value.as_list().unwrap();
+    /// assert_eq!(list.try_map::<u8>().unwrap_err().index, 2);
+    /// ```
+    pub fn try_map<'a, T>(&'a self) -> Result<Vec<T>, ElementError<T::Error>>
+    where
+        T: TryFrom<&'a Value>,
+    {
+        self.inner
+            .iter()
+            .enumerate()
+            .map(|(index, x)| T::try_from(x).map_err(|error| ElementError { index, error }))
+            .collect()
+    }
+}
+
+impl std::ops::Deref for Object {
+    type Target = crate::MapT;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl std::ops::Deref for List {
+    type Target = Vec<Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl From<crate::MapT> for Object {
+    fn from(inner: crate::MapT) -> Self {
+        Self { inner }
+    }
+}
+
+impl From<Object> for Value {
+    fn from(object: Object) -> Self {
+        Self::Obj(object.inner)
+    }
+}
+
+impl From<Vec<Value>> for List {
+    fn from(inner: Vec<Value>) -> Self {
+        Self { inner }
+    }
+}
+
+/// Spells the value minimally, or pretty with the default [`SpellConfig`] when formatted
+/// with `{:#}`.
+/// # Usage example
+/// ```rust
+/// use gon::Value;
+/// let value: Value = "{xs: [1, 2]}".parse().unwrap();
+/// assert_eq!(format!("{value}"), "{xs:[1,2]}");
+/// assert_eq!(format!("{value:#}"), "{\n    xs: [1, 2]\n}");
+/// ```
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if f.alternate() {
+            f.write_str(&self.spell(SpellConfig::default())?)
+        } else {
+            f.write_str(&self.min_spell())
+        }
+    }
+}
+
+/// Parses a value, see [`crate::parse_str`]
+impl std::str::FromStr for Value {
+    type Err = GonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        crate::parse_str(s)
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for ElementError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "element {}: {}", self.index, self.error)
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for ElementError<E> {}
+
 impl Default for SpellConfig {
     fn default() -> Self {
         Self {
             indent_amount: 4,
             indent_char: ' ',
             trailing_commas: false,
-            max_width: 100,
+            max_width: 0,
+            preserve_strings: true,
+            inline_lists: InlineMode::default(),
+            inline_small_objects: None,
+            dotted_keys: false,
+            normalize_numbers: false,
+            skip_none: false,
+            json_compat: false,
+            comment_placement: CommentPlacement::default(),
+            wrap_comments: false,
+            align_comments: false,
+            output_limits: OutputLimits::default(),
+            custom_renderer: None,
         }
     }
 }
+
+impl Default for InlineMode {
+    /// Up to five elements, regardless of their width
+    fn default() -> Self {
+        Self::Limits(InlineLimits {
+            max_entries: 5,
+            max_width: usize::MAX,
+        })
+    }
+}
+
+impl Default for OutputLimits {
+    /// No limits at all
+    fn default() -> Self {
+        Self {
+            max_bytes: usize::MAX,
+            max_depth: usize::MAX,
+        }
+    }
+}
+
+impl Default for InlineLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 4,
+            max_width: 80,
+        }
+    }
+}
+
+impl SpellConfig {
+    /// The file [`SpellConfig::from_file`] is usually called on. `gon fmt` uses the first
+    /// one it finds in the directory of the input or any of its parents.
+    pub const FILE_NAME: &'static str = ".gonfmt.gon";
+
+    /// Reads a formatting profile, see [`SpellConfig::from_value`]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SpellConfigError> {
+        let path = path.as_ref();
+        let src = std::fs::read_to_string(path)
+            .map_err(|e| SpellConfigError::Io(path.display().to_string(), e))?;
+        let value = crate::parse_str(&src)
+            .map_err(|e| SpellConfigError::Parse(path.display().to_string(), e))?;
+        Self::from_value(&value)
+    }
+
+    /// Builds a config out of an object with the same keys as the fields of this struct,
+    /// missing keys keep their default. `inline_lists` is `"never"`, `"always"` or an
+    /// object like `inline_small_objects`, which is `None` or `{max_entries, max_width}`.
+    /// `comment_placement` is `"keep"`, `"above"` or `"same_line"`.
+    /// A custom renderer can't be set this way. `key_order` is left to
+    /// [`KeyOrderPolicy`](crate::key_order::KeyOrderPolicy) and `lint` to
+    /// [`Linter`](crate::lint::Linter).
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, InlineMode, SpellConfig};
+    /// let profile = parse_str(r#"{indent_amount: 2, inline_lists: "always"}"#).unwrap();
+    /// let config = SpellConfig::from_value(&profile).unwrap();
+    /// assert_eq!(config.indent_amount, 2);
+    /// assert_eq!(config.inline_lists, InlineMode::Always);
+    /// ```
+    pub fn from_value(value: &Value) -> Result<Self, SpellConfigError> {
+        let obj = value
+            .as_obj()
+            .ok_or_else(|| invalid_option("(root)", "expected obj"))?;
+        let mut config = Self::default();
+        for (key, v) in obj.iter() {
+            match &**key {
+                "indent_amount" => config.indent_amount = convert_option(key, v)?,
+                "indent_char" => {
+                    let s: &str = convert_option(key, v)?;
+                    let mut chars = s.chars();
+                    config.indent_char = match (chars.next(), chars.next()) {
+                        (Some(c), None) => c,
+                        _ => return Err(invalid_option(key, "expected a single character")),
+                    };
+                }
+                "trailing_commas" => config.trailing_commas = convert_option(key, v)?,
+                "max_width" => config.max_width = convert_option(key, v)?,
+                "preserve_strings" => config.preserve_strings = convert_option(key, v)?,
+                "inline_lists" => {
+                    config.inline_lists = match v {
+                        Value::Str { s, .. } if s == "never" => InlineMode::Never,
+                        Value::Str { s, .. } if s == "always" => InlineMode::Always,
+                        Value::Obj(_) => InlineMode::Limits(inline_limits(key, v)?),
+                        _ => {
+                            return Err(invalid_option(
+                                key,
+                                "expected \"never\", \"always\" or {max_entries, max_width}",
+                            ));
+                        }
+                    }
+                }
+                "dotted_keys" => config.dotted_keys = convert_option(key, v)?,
+                "normalize_numbers" => config.normalize_numbers = convert_option(key, v)?,
+                "skip_none" => config.skip_none = convert_option(key, v)?,
+                "json_compat" => config.json_compat = convert_option(key, v)?,
+                "comment_placement" => {
+                    config.comment_placement = match convert_option::<&str>(key, v)? {
+                        "keep" => CommentPlacement::Keep,
+                        "above" => CommentPlacement::Above,
+                        "same_line" => CommentPlacement::SameLine,
+                        _ => {
+                            return Err(invalid_option(
+                                key,
+                                "expected \"keep\", \"above\" or \"same_line\"",
+                            ));
+                        }
+                    }
+                }
+                "wrap_comments" => config.wrap_comments = convert_option(key, v)?,
+                "align_comments" => config.align_comments = convert_option(key, v)?,
+                "output_limits" => config.output_limits = output_limits(key, v)?,
+                "inline_small_objects" => {
+                    config.inline_small_objects = match v {
+                        Value::None => None,
+                        _ => Some(inline_limits(key, v)?),
+                    }
+                }
+                crate::key_order::KEY | crate::lint::KEY => {}
+                _ => return Err(invalid_option(key, "unknown option")),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Lets `renderer` spell values before the default speller does. It's called for the
+    /// value being spelled and every value nested in it; whatever it writes is used as is
+    /// if it returns `Some(())` and is discarded otherwise.
+    /// # Usage example
+    /// ```rust
+    /// use gon::{parse_str, SpellConfig, Value};
+    /// fn vec(value: &Value, buf: &mut String) -> Option<()> {
+    ///     let Value::Tagged { tag, value } = value else { return None };
+    ///     (tag == "vec").then(|| buf.push_str(&format!("vec{}", value.min_spell())))
+    /// }
+    /// let value = Value::tagged("vec", parse_str("[1, 2]").unwrap());
+    /// let config = SpellConfig::default().custom_renderer(vec);
+    /// assert_eq!(value.spell(config).unwrap(), "vec[1,2]");
+    /// ```
+    pub fn custom_renderer(mut self, renderer: CustomRenderer) -> Self {
+        self.custom_renderer = Some(renderer);
+        self
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a str {
+    type Error = TypeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Str { s, .. } => Ok(s),
+            other => Err(TypeError {
+                expected: Kind::Str.name(),
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a Object {
+    type Error = TypeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        value.as_obj().ok_or(TypeError {
+            expected: Kind::Obj.name(),
+            found: value.type_name(),
+        })
+    }
+}
+
+impl<'a> TryFrom<&'a Value> for &'a List {
+    type Error = TypeError;
+
+    fn try_from(value: &'a Value) -> Result<Self, Self::Error> {
+        value.as_list().ok_or(TypeError {
+            expected: Kind::List.name(),
+            found: value.type_name(),
+        })
+    }
+}
+
+impl TryFrom<&Value> for String {
+    type Error = TypeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        <&str>::try_from(value).map(String::from)
+    }
+}
+
+impl TryFrom<&Value> for bool {
+    type Error = TypeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Bool(b) => Ok(*b),
+            other => Err(TypeError {
+                expected: Kind::Bool.name(),
+                found: other.type_name(),
+            }),
+        }
+    }
+}
+
+macro_rules! impl_try_from_num {
+    ($as:ident => $($t:ty),*) => {
+        $(
+            impl TryFrom<&Value> for $t {
+                type Error = TypeError;
+
+                fn try_from(value: &Value) -> Result<Self, Self::Error> {
+                    value
+                        .$as()
+                        .and_then(|n| <$t>::try_from(n).ok())
+                        .ok_or(TypeError {
+                            expected: stringify!($t),
+                            found: value.type_name(),
+                        })
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_num!(as_i128 => i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_try_from_num!(as_f64 => f64);
+
+impl TryFrom<&Value> for f32 {
+    type Error = TypeError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        f64::try_from(value)
+            .map(|f| f as f32)
+            .map_err(|e| TypeError {
+                expected: "f32",
+                ..e
+            })
+    }
+}
@@ -0,0 +1,892 @@
+//! Converting between [`Value`]s and Rust types with serde, see [`to_value`] and
+//! [`from_value`]
+
+use std::fmt;
+
+use ::serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, MapAccess, SeqAccess, Unexpected,
+    VariantAccess, Visitor,
+};
+use ::serde::ser::{self, Serialize};
+use ::serde::{Deserialize, Deserializer, Serializer, forward_to_deserialize_any};
+
+use super::{Value, is_int_literal, normalize_number, parse_raw};
+use crate::MapT;
+use crate::path::{Path, Segment};
+
+/// A Rust value couldn't be converted to a [`Value`] with [`to_value`] or the other way
+/// around with [`from_value`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerdeError {
+    /// Where the value that couldn't be converted is, empty if it's the converted value itself
+    pub path: Path,
+    pub message: String,
+}
+
+impl SerdeError {
+    /// The error of a value that is at `segment` of the one the error is returned for
+    fn within(mut self, segment: Segment) -> Self {
+        self.path.0.insert(0, segment);
+        self
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.0.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Self {
+            path: Path::default(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        <Self as ser::Error>::custom(message)
+    }
+}
+
+/// Converts anything that implements [`Serialize`] to a [`Value`], like serde_json's
+/// `to_value`. Structs and maps become objects, sequences and tuples lists, `None` and
+/// `()` become `None`. Unit variants of enums become strings of their names, the others
+/// objects with the name as the only key. Floats that aren't finite become `None` and the
+/// keys of maps have to be strings, numbers, booleans or chars.
+/// # Usage example
+/// ```rust
+/// use std::collections::BTreeMap;
+/// use gon::{parse_str, value::to_value};
+/// let ports = BTreeMap::from([("http", vec![80, 8080]), ("https", vec![443])]);
+/// assert_eq!(
+///     to_value(&ports).unwrap(),
+///     parse_str("{http: [80, 8080], https: [443]}").unwrap()
+/// );
+/// ```
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, SerdeError> {
+    value.serialize(ValueSerializer)
+}
+
+/// Converts a [`Value`] to anything that implements [`Deserialize`], like serde_json's
+/// `from_value`. Take a part of a value with [`Value::get_path`] or [`path::get`](crate::path::get)
+/// to only convert that. Tags are looked through and raw text is parsed. The error says
+/// where the value that didn't fit is.
+/// # Usage example
+/// ```rust
+/// use std::collections::HashMap;
+/// use gon::{parse_str, value::from_value};
+/// let config = parse_str("{limits: {cpu: 2, memory: 512}, name: \"worker\"}").unwrap();
+/// let subtree = config.get_path(&"limits".parse().unwrap()).unwrap().clone();
+/// let limits: HashMap<String, u32> = from_value(subtree.clone()).unwrap();
+/// assert_eq!(limits["memory"], 512);
+/// let err = from_value::<HashMap<String, u8>>(subtree).unwrap_err();
+/// assert_eq!(err.path.to_string(), "memory");
+/// ```
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, SerdeError> {
+    T::deserialize(value)
+}
+
+struct ValueSerializer;
+
+fn str_value(s: impl Into<String>) -> Value {
+    Value::Str {
+        s: s.into(),
+        raw: false,
+    }
+}
+
+fn float_value(f: f64) -> Value {
+    if f.is_finite() {
+        Value::Num(format!("{f:?}"))
+    } else {
+        Value::None
+    }
+}
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = VariantSerializer<ListSerializer>;
+    type SerializeMap = ObjSerializer;
+    type SerializeStruct = ObjSerializer;
+    type SerializeStructVariant = VariantSerializer<ObjSerializer>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, SerdeError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, SerdeError> {
+        Ok(Value::Num(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, SerdeError> {
+        Ok(float_value(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, SerdeError> {
+        Ok(float_value(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, SerdeError> {
+        Ok(str_value(v))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, SerdeError> {
+        Ok(str_value(v))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, SerdeError> {
+        Ok(Value::List(
+            v.iter().map(|b| Value::Num(b.to_string())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Value, SerdeError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, SerdeError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, SerdeError> {
+        Ok(Value::None)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, SerdeError> {
+        Ok(str_value(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, SerdeError> {
+        let value = to_value(value).map_err(|e| e.within(Segment::Key(variant.into())))?;
+        Ok(variant_value(variant, value))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, SerdeError> {
+        Ok(ListSerializer(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ListSerializer, SerdeError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, SerdeError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<ObjSerializer, SerdeError> {
+        Ok(ObjSerializer::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<ObjSerializer, SerdeError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, SerdeError> {
+        Ok(VariantSerializer {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+/// An object with `variant` as its only key
+// keys only need converting with `shared_keys` or `compact_keys`
+#[allow(clippy::useless_conversion)]
+fn variant_value(variant: &str, value: Value) -> Value {
+    let mut obj = MapT::new();
+    obj.insert(variant.into(), value);
+    Value::Obj(obj)
+}
+
+struct ListSerializer(Vec<Value>);
+
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let index = self.0.len();
+        self.0
+            .push(to_value(value).map_err(|e| e.within(Segment::Index(index)))?);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::List(self.0))
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::List(self.0))
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::List(self.0))
+    }
+}
+
+#[derive(Default)]
+struct ObjSerializer {
+    obj: MapT,
+    /// The key of the value [`SerializeMap::serialize_value`](ser::SerializeMap) gets next
+    key: Option<String>,
+}
+
+impl ObjSerializer {
+    // keys only need converting with `shared_keys` or `compact_keys`
+    #[allow(clippy::useless_conversion)]
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), SerdeError> {
+        let value = to_value(value).map_err(|e| e.within(Segment::Key(key.clone())))?;
+        self.obj.insert(key.into(), value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for ObjSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), SerdeError> {
+        self.key = Some(match to_value(key)? {
+            Value::Str { s, .. } => s,
+            Value::Num(num) => num,
+            Value::Bool(b) => b.to_string(),
+            other => {
+                return Err(<SerdeError as ser::Error>::custom(format!(
+                    "keys have to be strings, numbers or booleans, not {}",
+                    other.type_name()
+                )));
+            }
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <SerdeError as ser::Error>::custom("a value without a key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Obj(self.obj))
+    }
+}
+
+impl ser::SerializeStruct for ObjSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(Value::Obj(self.obj))
+    }
+}
+
+/// Serializes the fields of an enum variant with `inner` and wraps them in an object with
+/// the name of the variant as the only key
+struct VariantSerializer<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer<ListSerializer> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), SerdeError> {
+        self.inner
+            .push(value)
+            .map_err(|e| e.within(Segment::Key(self.variant.into())))
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(variant_value(self.variant, Value::List(self.inner.0)))
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer<ObjSerializer> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), SerdeError> {
+        self.inner
+            .insert(key.to_string(), value)
+            .map_err(|e| e.within(Segment::Key(self.variant.into())))
+    }
+
+    fn end(self) -> Result<Value, SerdeError> {
+        Ok(variant_value(self.variant, Value::Obj(self.inner.obj)))
+    }
+}
+
+/// `value` without tags around it and with raw text parsed
+fn resolved(mut value: Value) -> Value {
+    loop {
+        value = match value {
+            Value::Tagged { value, .. } => *value,
+            Value::Raw(text) => parse_raw(&text),
+            value => return value,
+        }
+    }
+}
+
+/// Visits the number `num` as the smallest of `i64`, `u64`, `i128` and `u128` it fits into
+/// if it's an integer, as an `f64` otherwise
+fn visit_number<'de, V: Visitor<'de>>(num: &str, visitor: V) -> Result<V::Value, SerdeError> {
+    let num = normalize_number(num);
+    if is_int_literal(&num) {
+        if let Ok(i) = num.parse::<i64>() {
+            return visitor.visit_i64(i);
+        }
+        if let Ok(u) = num.parse::<u64>() {
+            return visitor.visit_u64(u);
+        }
+        if let Ok(i) = num.parse::<i128>() {
+            return visitor.visit_i128(i);
+        }
+        if let Ok(u) = num.parse::<u128>() {
+            return visitor.visit_u128(u);
+        }
+    }
+    match num.parse::<f64>() {
+        Ok(f) => visitor.visit_f64(f),
+        Err(_) => Err(de::Error::invalid_value(Unexpected::Other(&num), &visitor)),
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match resolved(self) {
+            Value::None => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::Num(num) => visit_number(&num, visitor),
+            Value::Str { s, .. } => visitor.visit_string(s),
+            Value::List(xs) => {
+                let len = xs.len();
+                let mut seq = SeqDeserializer {
+                    elements: xs.into_iter(),
+                    index: 0,
+                };
+                let value = visitor.visit_seq(&mut seq)?;
+                match seq.elements.len() {
+                    0 => Ok(value),
+                    _ => Err(de::Error::invalid_length(
+                        len,
+                        &"fewer elements in the list",
+                    )),
+                }
+            }
+            Value::Obj(obj) => visitor.visit_map(MapDeserializer {
+                entries: obj.into_iter(),
+                value: None,
+            }),
+            Value::Tagged { .. } | Value::Raw(_) => unreachable!("resolved"),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match resolved(self) {
+            Value::None => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        match resolved(self) {
+            Value::Str { s, .. } => visitor.visit_enum(EnumDeserializer {
+                variant: s,
+                value: None,
+            }),
+            Value::Obj(obj) if obj.len() == 1 => {
+                let (variant, value) = obj.into_iter().next().expect("one entry");
+                visitor.visit_enum(EnumDeserializer {
+                    variant: variant.to_string(),
+                    value: Some(value),
+                })
+            }
+            other => Err(de::Error::invalid_type(
+                unexpected(&other),
+                &"a string or an object with one entry",
+            )),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// What `value` is called in type errors
+fn unexpected(value: &Value) -> Unexpected<'_> {
+    match value {
+        Value::None => Unexpected::Unit,
+        Value::Bool(b) => Unexpected::Bool(*b),
+        Value::Num(num) => Unexpected::Other(num),
+        Value::Str { s, .. } => Unexpected::Str(s),
+        Value::List(_) => Unexpected::Seq,
+        Value::Obj(_) => Unexpected::Map,
+        Value::Tagged { tag, .. } => Unexpected::Other(tag),
+        Value::Raw(_) => Unexpected::Other("raw text"),
+    }
+}
+
+struct SeqDeserializer {
+    elements: std::vec::IntoIter<Value>,
+    index: usize,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, SerdeError> {
+        let Some(element) = self.elements.next() else {
+            return Ok(None);
+        };
+        let index = self.index;
+        self.index += 1;
+        seed.deserialize(element)
+            .map(Some)
+            .map_err(|e| e.within(Segment::Index(index)))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapDeserializer {
+    entries: <MapT as IntoIterator>::IntoIter,
+    /// The value of the key [`next_key_seed`](MapAccess::next_key_seed) returned last
+    value: Option<(String, Value)>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, SerdeError> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        let key = key.to_string();
+        let deserialized = seed
+            .deserialize(KeyDeserializer(key.clone()))
+            .map_err(|e| e.within(Segment::Key(key.clone())))?;
+        self.value = Some((key, value));
+        Ok(Some(deserialized))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, SerdeError> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| <SerdeError as de::Error>::custom("a value without a key"))?;
+        seed.deserialize(value)
+            .map_err(|e| e.within(Segment::Key(key)))
+    }
+}
+
+/// Deserializes the key of an object, which can also stand for a number or a boolean
+struct KeyDeserializer(String);
+
+impl KeyDeserializer {
+    fn number<'de, V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match crate::parser::is_number_literal(self.0.trim_start_matches('-')) {
+            true => visit_number(&self.0, visitor),
+            false => visitor.visit_string(self.0),
+        }
+    }
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_string(self.0)
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        match self.0.parse() {
+            Ok(b) => visitor.visit_bool(b),
+            Err(_) => visitor.visit_string(self.0),
+        }
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        self.number(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, SerdeError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        str_value(self.0).deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+/// An enum variant, spelled as a string or as an object with the name of the variant as
+/// its only key
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = SerdeError;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self), SerdeError> {
+        let variant = seed.deserialize(KeyDeserializer(self.variant.clone()))?;
+        Ok((variant, self))
+    }
+}
+
+impl EnumDeserializer {
+    /// The value of a variant with fields, `expected` describes them for the error if there
+    /// is none
+    fn fields(self, expected: &str) -> Result<(Value, Segment), SerdeError> {
+        match self.value {
+            Some(value) => Ok((value, Segment::Key(self.variant))),
+            None => Err(de::Error::invalid_type(Unexpected::UnitVariant, &expected)),
+        }
+    }
+}
+
+impl<'de> VariantAccess<'de> for EnumDeserializer {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> Result<(), SerdeError> {
+        match self.value {
+            None => Ok(()),
+            Some(value) => {
+                <()>::deserialize(value).map_err(|e| e.within(Segment::Key(self.variant)))
+            }
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, SerdeError> {
+        let (value, segment) = self.fields("a newtype variant")?;
+        seed.deserialize(value).map_err(|e| e.within(segment))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let (value, segment) = self.fields("a tuple variant")?;
+        value
+            .deserialize_seq(visitor)
+            .map_err(|e| e.within(segment))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, SerdeError> {
+        let (value, segment) = self.fields("a struct variant")?;
+        value
+            .deserialize_map(visitor)
+            .map_err(|e| e.within(segment))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn round_trips() {
+        let nested = BTreeMap::from([
+            (
+                "a".to_string(),
+                vec![(1u8, Some("x".to_string())), (2, None)],
+            ),
+            ("b".to_string(), Vec::new()),
+        ]);
+        let value = to_value(&nested).unwrap();
+        assert_eq!(
+            value,
+            parse_str("{a: [[1, \"x\"], [2, None]], b: []}").unwrap()
+        );
+        assert_eq!(from_value::<BTreeMap<_, _>>(value), Ok(nested));
+        let ids = HashMap::from([(7u32, true), (-1i32 as u32, false)]);
+        assert_eq!(from_value(to_value(&ids).unwrap()), Ok(ids));
+        let results: Vec<Result<u32, String>> = vec![Ok(3), Err("no".into())];
+        let value = to_value(&results).unwrap();
+        assert_eq!(value, parse_str("[{Ok: 3}, {Err: \"no\"}]").unwrap());
+        assert_eq!(from_value(value), Ok(results));
+    }
+
+    #[test]
+    fn converts_numbers() {
+        assert_eq!(to_value(&1.0), Ok(Value::Num("1.0".into())));
+        assert_eq!(to_value(&f64::NAN), Ok(Value::None));
+        assert_eq!(to_value(&u128::MAX), Ok(Value::Num(u128::MAX.to_string())));
+        let big = Value::Num("18_446_744_073_709_551_615".into());
+        assert_eq!(from_value::<u64>(big.clone()), Ok(u64::MAX));
+        assert_eq!(from_value::<f64>(Value::Num("2".into())), Ok(2.0));
+        assert!(from_value::<i64>(big).is_err());
+        assert!(from_value::<u8>(Value::Num("2.5".into())).is_err());
+    }
+
+    #[test]
+    fn looks_through_tags_and_raw_text() {
+        let tagged = Value::tagged("bytes", Value::Raw("[1, 2]".into()));
+        assert_eq!(from_value::<Vec<u8>>(tagged), Ok(vec![1, 2]));
+        assert_eq!(
+            from_value::<Option<String>>(Value::tagged("maybe", Value::None)),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn errors_say_where() {
+        let value = parse_str("{a: [1, 300]}").unwrap();
+        let err = from_value::<BTreeMap<String, Vec<u8>>>(value).unwrap_err();
+        assert_eq!(err.path.to_string(), "a[1]");
+        assert!(err.to_string().starts_with("a[1]: "), "{err}");
+        let err = from_value::<(u8, u8)>(parse_str("[1, 2, 3]").unwrap()).unwrap_err();
+        assert!(err.path.is_root());
+        let err = from_value::<Result<u8, u8>>(parse_str("{Ok: true}").unwrap()).unwrap_err();
+        assert_eq!(err.path.to_string(), "Ok");
+        let list_key = BTreeMap::from([(vec![1], 2)]);
+        assert!(to_value(&list_key).is_err());
+    }
+}
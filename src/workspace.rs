@@ -0,0 +1,576 @@
+//! Validation of a whole directory tree of gon files against schemas.
+//!
+//! A manifest maps globs of file paths to the schemas those files have to match, like
+//! `{"enemies/*.gon": "schemas/enemy.gon", "items/**/*.gon": "schemas/item.gon"}`.
+//! Globs and schemas are relative to the directory of the manifest. In globs, `*` and `?`
+//! match within a directory (see [`glob_match`]) and `**` matches any number of
+//! directories. A file that several globs match has to match all their schemas, files that
+//! no glob matches aren't checked, and neither are hidden files and directories. Schemas
+//! are read with [`Schema::from_file`], so they can refer to each others' definitions.
+//!
+//! [`Workspace::validate`] checks the files on several threads and collects the outcome of
+//! every file into a [`Report`]. [`Workspace::dependency_graph`] tells which files the
+//! outcome for a file depends on, so build systems know what to check again after a change.
+//!
+//! # Usage example
+//! ```rust,no_run
+//! use gon::workspace::Workspace;
+//! let report = Workspace::from_file("config/workspace.gon")
+//!     .unwrap()
+//!     .validate()
+//!     .unwrap();
+//! print!("{report}");
+//! if !report.is_ok() {
+//!     std::process::exit(3);
+//! }
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use thiserror::Error;
+
+use crate::cache::ParseCache;
+use crate::encoding::ReadError;
+use crate::path::glob_match;
+use crate::schema::{Schema, SchemaError, ValidationErrors, normalize};
+use crate::{GonError, Value, parse_str};
+
+/// A manifest and the schemas it names, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// The directory of the manifest, which globs and schemas are relative to
+    dir: PathBuf,
+    /// The manifest itself, which isn't checked even if a glob matches it
+    manifest: Option<PathBuf>,
+    entries: Vec<Entry>,
+    threads: usize,
+    cache: Option<Arc<ParseCache>>,
+}
+
+/// A glob of the manifest and the schema of the files it matches
+#[derive(Debug, Clone)]
+struct Entry {
+    glob: String,
+    /// Where the schema is, relative to the directory of the manifest
+    file: String,
+    schema: Schema,
+    /// Which schema files refer to which, starting with this one, like the files of the
+    /// graph
+    references: Vec<(String, String)>,
+}
+
+/// Which files of a [`Workspace`] depend on which others, see
+/// [`Workspace::dependency_graph`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DependencyGraph {
+    /// Every file and the files it depends on, relative to the directory of the manifest,
+    /// separated by `/` and without `.` and `..`. A file that globs match depends on the
+    /// manifest and their schemas, a schema on the schema files whose definitions it
+    /// refers to.
+    pub edges: BTreeMap<String, BTreeSet<String>>,
+}
+
+/// The outcome of [`Workspace::validate`], one [`FileReport`] per checked file
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Ordered by file
+    pub files: Vec<FileReport>,
+}
+
+/// The outcome of checking one file
+#[derive(Debug)]
+pub struct FileReport {
+    /// The file, relative to the directory of the manifest and separated by `/`
+    pub file: String,
+    /// Everything that's wrong with the file, empty if it's valid
+    pub errors: Vec<FileError>,
+}
+
+/// Something that's wrong with a file of a [`Workspace`]
+#[derive(Debug, Error)]
+pub enum FileError {
+    #[error("couldn't read file: {0}")]
+    Io(std::io::Error),
+    #[error("couldn't parse file: {0}")]
+    Parse(GonError),
+    /// The file doesn't match the schema with this path
+    #[error("doesn't match {0}:\n{1}")]
+    Invalid(String, ValidationErrors),
+}
+
+/// A manifest couldn't be loaded, or its directory couldn't be searched for files
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("couldn't read '{0}': {1}")]
+    Io(String, std::io::Error),
+    #[error("couldn't parse manifest '{0}': {1}")]
+    Parse(String, GonError),
+    #[error("invalid manifest: {0}")]
+    Manifest(String),
+    #[error("invalid schema '{0}': {1}")]
+    Schema(String, SchemaError),
+}
+
+impl Workspace {
+    /// Reads the manifest at `path` and the schemas it names
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, WorkspaceError> {
+        let path = path.as_ref();
+        let name = || path.display().to_string();
+        let src = std::fs::read_to_string(path).map_err(|e| WorkspaceError::Io(name(), e))?;
+        let value = parse_str(&src).map_err(|e| WorkspaceError::Parse(name(), e))?;
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut workspace = Self::from_value(&value, dir)?;
+        workspace.manifest = path.file_name().map(PathBuf::from);
+        Ok(workspace)
+    }
+
+    /// Reads a manifest whose globs and schemas are relative to `dir`
+    pub fn from_value(value: &Value, dir: impl Into<PathBuf>) -> Result<Self, WorkspaceError> {
+        let dir = dir.into();
+        let obj = value.as_obj().ok_or_else(|| {
+            WorkspaceError::Manifest("expected an obj of globs and schemas".into())
+        })?;
+        let mut entries = Vec::with_capacity(obj.len());
+        for (glob, file) in crate::value::spelling_order(obj) {
+            let Value::Str { s: file, .. } = file else {
+                return Err(WorkspaceError::Manifest(format!(
+                    "{glob}: expected the path of a schema, found {}",
+                    file.type_name()
+                )));
+            };
+            let (schema, references) = Schema::from_file_with_references(dir.join(file))
+                .map_err(|e| WorkspaceError::Schema(file.clone(), e))?;
+            entries.push(Entry {
+                glob: glob.to_string(),
+                file: file.clone(),
+                schema,
+                references: (references.iter())
+                    .map(|(from, to)| (relative(&dir, from), relative(&dir, to)))
+                    .collect(),
+            });
+        }
+        Ok(Self {
+            dir,
+            manifest: None,
+            entries,
+            threads: std::thread::available_parallelism().map_or(1, usize::from),
+            cache: None,
+        })
+    }
+
+    /// Checks files on up to `threads` threads instead of one per core
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Parses the files through `cache`, so validating again only parses the files that
+    /// changed since, see the docs of [`cache`](crate::cache)
+    pub fn cache(mut self, cache: Arc<ParseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Checks every file that a glob matches against its schemas
+    pub fn validate(&self) -> Result<Report, WorkspaceError> {
+        let jobs = self.jobs()?;
+        let next = AtomicUsize::new(0);
+        let mut reports: Vec<(usize, FileReport)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads.min(jobs.len()))
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut reports = Vec::new();
+                        loop {
+                            let i = next.fetch_add(1, Ordering::Relaxed);
+                            let Some((file, entries)) = jobs.get(i) else {
+                                return reports;
+                            };
+                            reports.push((i, self.check(file, entries)));
+                        }
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("checking a file panicked"))
+                .collect()
+        });
+        reports.sort_by_key(|(i, _)| *i);
+        Ok(Report {
+            files: reports.into_iter().map(|(_, report)| report).collect(),
+        })
+    }
+
+    /// Which files the outcome of [`validate`](Self::validate) depends on: the files that
+    /// globs match, the manifest and the schemas, see [`DependencyGraph`]
+    pub fn dependency_graph(&self) -> Result<DependencyGraph, WorkspaceError> {
+        let mut graph = DependencyGraph::default();
+        let manifest = (self.manifest.as_ref()).map(|manifest| relative(Path::new(""), manifest));
+        for (file, entries) in self.jobs()? {
+            let dependencies = (entries.iter())
+                .map(|entry| relative(Path::new(""), Path::new(&entry.file)))
+                .chain(manifest.clone());
+            graph.edges.entry(file).or_default().extend(dependencies);
+        }
+        for entry in &self.entries {
+            let file = relative(Path::new(""), Path::new(&entry.file));
+            graph.edges.entry(file).or_default();
+            for (from, to) in &entry.references {
+                graph
+                    .edges
+                    .entry(from.clone())
+                    .or_default()
+                    .insert(to.clone());
+            }
+        }
+        let files: Vec<_> = graph.edges.values().flatten().cloned().collect();
+        for file in files {
+            graph.edges.entry(file).or_default();
+        }
+        Ok(graph)
+    }
+
+    /// The files that globs match, except for the manifest and the schemas, and the
+    /// entries whose globs match them
+    fn jobs(&self) -> Result<Vec<(String, Vec<&Entry>)>, WorkspaceError> {
+        let mut files = Vec::new();
+        list_files(&self.dir, "", &mut files)?;
+        // every schema file, including the ones that schemas refer to
+        let skipped: BTreeSet<_> = (self.manifest.iter().map(PathBuf::as_path))
+            .chain(self.entries.iter().map(|entry| Path::new(&entry.file)))
+            .map(|file| relative(Path::new(""), file))
+            .chain(
+                (self.entries.iter())
+                    .flat_map(|entry| &entry.references)
+                    .flat_map(|(from, to)| [from.clone(), to.clone()]),
+            )
+            .collect();
+        Ok(files
+            .into_iter()
+            .filter(|file| !skipped.contains(file))
+            .map(|file| {
+                let entries = (self.entries.iter())
+                    .filter(|entry| path_matches(&entry.glob, &file))
+                    .collect();
+                (file, entries)
+            })
+            .filter(|(_, entries): &(_, Vec<_>)| !entries.is_empty())
+            .collect())
+    }
+
+    fn check(&self, file: &str, entries: &[&Entry]) -> FileReport {
+        let mut errors = Vec::new();
+        match self.parse(&self.dir.join(file)) {
+            Ok(value) => {
+                for entry in entries {
+                    if let Err(e) = entry.schema.validate(&value) {
+                        errors.push(FileError::Invalid(entry.file.clone(), e));
+                    }
+                }
+            }
+            Err(e) => errors.push(e),
+        }
+        FileReport {
+            file: file.to_string(),
+            errors,
+        }
+    }
+
+    fn parse(&self, path: &Path) -> Result<Arc<Value>, FileError> {
+        let Some(cache) = &self.cache else {
+            let src = std::fs::read_to_string(path).map_err(FileError::Io)?;
+            return parse_str(&src).map(Arc::new).map_err(FileError::Parse);
+        };
+        cache.parse_file(path).map_err(|e| match e {
+            ReadError::Io(e) => FileError::Io(e),
+            ReadError::Parse(e) => FileError::Parse(e),
+            e => FileError::Io(std::io::Error::other(e)),
+        })
+    }
+}
+
+impl DependencyGraph {
+    /// The files that `file` depends on, directly or through others. `file` may be spelled
+    /// with `.` and `..`, files that aren't in the graph have no dependencies.
+    pub fn dependencies(&self, file: &str) -> BTreeSet<&str> {
+        let file = relative(Path::new(""), Path::new(file));
+        let mut found = BTreeSet::new();
+        let mut todo = vec![file.as_str()];
+        while let Some(from) = todo.pop() {
+            for to in self.edges.get(from).into_iter().flatten() {
+                if found.insert(to.as_str()) {
+                    todo.push(to);
+                }
+            }
+        }
+        found.remove(file.as_str());
+        found
+    }
+
+    /// The files that depend on `file`, directly or through others, so whatever was made
+    /// of them is out of date when `file` changes. This reverses the graph first, so call
+    /// [`reversed`](Self::reversed) once and ask it for the dependencies of every file
+    /// instead when asking for many.
+    pub fn dependents(&self, file: &str) -> BTreeSet<&str> {
+        let reversed = self.reversed();
+        let dependents = reversed.dependencies(file);
+        (self.edges.keys())
+            .map(String::as_str)
+            .filter(|dependent| dependents.contains(dependent))
+            .collect()
+    }
+
+    /// The graph with every edge turned around, in which the dependencies of a file are
+    /// the files that depend on it
+    pub fn reversed(&self) -> DependencyGraph {
+        let mut reversed = DependencyGraph::default();
+        for (from, dependencies) in &self.edges {
+            reversed.edges.entry(from.clone()).or_default();
+            for to in dependencies {
+                let dependents = reversed.edges.entry(to.clone()).or_default();
+                dependents.insert(from.clone());
+            }
+        }
+        reversed
+    }
+}
+
+impl Report {
+    /// Whether every file is valid
+    pub fn is_ok(&self) -> bool {
+        self.files.iter().all(|file| file.errors.is_empty())
+    }
+
+    /// The files that aren't valid
+    pub fn invalid(&self) -> impl Iterator<Item = &FileReport> {
+        self.files.iter().filter(|file| !file.errors.is_empty())
+    }
+}
+
+impl fmt::Display for Report {
+    /// Every problem of the invalid files and a summary line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for report in self.invalid() {
+            for e in &report.errors {
+                writeln!(
+                    f,
+                    "{}: {}",
+                    report.file,
+                    e.to_string().replace('\n', "\n    ")
+                )?;
+            }
+        }
+        writeln!(
+            f,
+            "checked {} files, {} invalid",
+            self.files.len(),
+            self.invalid().count()
+        )
+    }
+}
+
+/// Adds the files in `dir` and its subdirectories to `files`, as paths relative to the
+/// directory the search started at and separated by `/`. Hidden files are left out.
+fn list_files(dir: &Path, prefix: &str, files: &mut Vec<String>) -> Result<(), WorkspaceError> {
+    let io = |e| WorkspaceError::Io(dir.display().to_string(), e);
+    let mut entries = std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(io)?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let path = format!("{prefix}{name}");
+        if entry.file_type().map_err(io)?.is_dir() {
+            list_files(&entry.path(), &format!("{path}/"), files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// `path` relative to `dir` if it's in there, separated by `/` and with `.` and `..`
+/// resolved without looking at the file system
+fn relative(dir: &Path, path: &Path) -> String {
+    let parts = |path: &Path| -> Vec<String> {
+        (normalize(path).components())
+            .map(|component| match component {
+                // so that joining them starts with a `/`
+                Component::RootDir => String::new(),
+                _ => component.as_os_str().to_string_lossy().into_owned(),
+            })
+            .collect()
+    };
+    let (dir, path) = (parts(dir), parts(path));
+    path.strip_prefix(&dir[..]).unwrap_or(&path).join("/")
+}
+
+/// Whether the `/`-separated `file` matches `glob`, see the [module docs](self)
+fn path_matches(glob: &str, file: &str) -> bool {
+    let glob: Vec<_> = glob.split('/').collect();
+    let file: Vec<_> = file.split('/').collect();
+    path_matches0(&glob, &file)
+}
+
+fn path_matches0(glob: &[&str], file: &[&str]) -> bool {
+    match glob.split_first() {
+        None => file.is_empty(),
+        Some((&"**", rest)) => (0..=file.len()).any(|i| path_matches0(rest, &file[i..])),
+        Some((segment, rest)) => file
+            .split_first()
+            .is_some_and(|(name, file)| glob_match(segment, name) && path_matches0(rest, file)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn matches_paths() {
+        assert!(path_matches("items/*.gon", "items/axe.gon"));
+        assert!(!path_matches("items/*.gon", "items/tools/axe.gon"));
+        assert!(path_matches("items/**/*.gon", "items/axe.gon"));
+        assert!(path_matches("items/**/*.gon", "items/tools/old/axe.gon"));
+        assert!(path_matches("**", "a/b"));
+        assert!(!path_matches("**/*.gon", "a/b.json"));
+    }
+
+    #[test]
+    fn validates_workspaces() {
+        let dir = TempDir::new("workspace");
+        std::fs::create_dir_all(dir.join("items/tools")).unwrap();
+        std::fs::create_dir_all(dir.join("schemas")).unwrap();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        std::fs::write(
+            dir.join("workspace.gon"),
+            r#"{"**/*.gon": "./schemas/any.gon", "items/**/*.gon": "schemas/item.gon"}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("schemas/any.gon"), r#""obj""#).unwrap();
+        let item = r#"{name: {"@ref": "names.gon#name"}}"#;
+        std::fs::write(dir.join("schemas/item.gon"), item).unwrap();
+        let names = r#"{"@definitions": {name: "str"}}"#;
+        std::fs::write(dir.join("schemas/names.gon"), names).unwrap();
+        std::fs::write(dir.join("items/axe.gon"), r#"{name: "axe"}"#).unwrap();
+        std::fs::write(dir.join("items/tools/saw.gon"), "{name: 2}").unwrap();
+        std::fs::write(dir.join("items/broken.gon"), "{name:").unwrap();
+        std::fs::write(dir.join("level.gon"), "{}").unwrap();
+        std::fs::write(dir.join(".git/config.gon"), "[]").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let report = Workspace::from_file(dir.join("workspace.gon"))
+            .unwrap()
+            .threads(2)
+            .validate()
+            .unwrap();
+        let files: Vec<_> = report.files.iter().map(|f| f.file.as_str()).collect();
+        assert_eq!(
+            files,
+            [
+                "items/axe.gon",
+                "items/broken.gon",
+                "items/tools/saw.gon",
+                "level.gon"
+            ]
+        );
+        assert!(!report.is_ok());
+        let invalid: Vec<_> = report.invalid().map(|f| f.file.as_str()).collect();
+        assert_eq!(invalid, ["items/broken.gon", "items/tools/saw.gon"]);
+        assert!(report.to_string().ends_with(
+            "items/tools/saw.gon: doesn't match schemas/item.gon:\n    name: expected str, found num\nchecked 4 files, 2 invalid\n"
+        ));
+
+        let cache = Arc::new(ParseCache::new());
+        let workspace = Workspace::from_file(dir.join("workspace.gon"))
+            .unwrap()
+            .cache(Arc::clone(&cache));
+        for _ in 0..2 {
+            assert_eq!(
+                workspace.validate().unwrap().to_string(),
+                report.to_string()
+            );
+        }
+        // the valid files and the one that doesn't match its schema
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn builds_dependency_graphs() {
+        let dir = TempDir::new("deps");
+        std::fs::create_dir_all(dir.join("items")).unwrap();
+        std::fs::create_dir_all(dir.join("schemas/common")).unwrap();
+        std::fs::write(
+            dir.join("workspace.gon"),
+            r#"{"items/*.gon": "./schemas/item.gon"}"#,
+        )
+        .unwrap();
+        let item = r#"{name: "str", stats: {"@ref": "common/stats.gon#stats"}}"#;
+        std::fs::write(dir.join("schemas/item.gon"), item).unwrap();
+        let stats = r#"{"@definitions": {stats: {power: {"@ref": "../power.gon#power"}}}}"#;
+        std::fs::write(dir.join("schemas/common/stats.gon"), stats).unwrap();
+        let power = r#"{"@definitions": {power: "num"}}"#;
+        std::fs::write(dir.join("schemas/power.gon"), power).unwrap();
+        std::fs::write(dir.join("items/axe.gon"), r#"{name: "axe"}"#).unwrap();
+
+        let workspace = Workspace::from_file(dir.join("workspace.gon")).unwrap();
+        let graph = workspace.dependency_graph().unwrap();
+        let files: Vec<_> = graph.edges.keys().map(String::as_str).collect();
+        assert_eq!(
+            files,
+            [
+                "items/axe.gon",
+                "schemas/common/stats.gon",
+                "schemas/item.gon",
+                "schemas/power.gon",
+                "workspace.gon"
+            ]
+        );
+        assert_eq!(
+            graph.dependencies("items/axe.gon"),
+            BTreeSet::from([
+                "schemas/common/stats.gon",
+                "schemas/item.gon",
+                "schemas/power.gon",
+                "workspace.gon"
+            ])
+        );
+        assert_eq!(
+            graph.dependents("schemas/power.gon"),
+            BTreeSet::from([
+                "items/axe.gon",
+                "schemas/common/stats.gon",
+                "schemas/item.gon"
+            ])
+        );
+        assert_eq!(
+            graph.reversed().dependencies("schemas/power.gon"),
+            graph.dependents("schemas/power.gon")
+        );
+        assert_eq!(
+            graph.dependencies("./items/../items/axe.gon"),
+            graph.dependencies("items/axe.gon")
+        );
+        assert!(graph.dependencies("items/sword.gon").is_empty());
+        assert!(graph.dependents("items/sword.gon").is_empty());
+
+        // the schema files are spelled the same way however they are referred to
+        let (_, dependencies) =
+            Schema::from_file_with_dependencies(dir.join("schemas/./item.gon")).unwrap();
+        assert_eq!(
+            dependencies,
+            [
+                dir.join("schemas/common/stats.gon"),
+                dir.join("schemas/power.gon")
+            ]
+        );
+    }
+}
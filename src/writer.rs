@@ -0,0 +1,344 @@
+//! Push-style writing of gon source without building a [`Value`].
+//!
+//! A [`GonWriter`] is the write side of the [`Cursor`](crate::cursor::Cursor): objects
+//! and lists are opened and closed with calls, keys and scalars are written as they come.
+//! Calls that would produce malformed gon, like a key in a list or a second root value,
+//! fail with a [`WriterError`] and write nothing. Pretty output looks like
+//! [`Value::spell`] with [`InlineMode::Never`](crate::InlineMode::Never), minified output
+//! like [`Value::min_spell`].
+//!
+//! # Usage example
+//! ```rust
+//! use gon::{writer::GonWriter, SpellConfig};
+//! let mut writer = GonWriter::pretty(Vec::new(), SpellConfig::default());
+//! writer.begin_obj()?;
+//! writer.key("name")?.str("Goblin")?;
+//! writer.key("drops")?.begin_list()?.num(1)?.num(-2.5)?.end()?;
+//! writer.end()?;
+//! let out = writer.finish()?;
+//! assert_eq!(
+//!     String::from_utf8(out).unwrap(),
+//!     "{\n    name: \"Goblin\",\n    drops: [\n        1,\n        -2.5\n    ]\n}"
+//! );
+//! # Ok::<(), gon::writer::WriterError>(())
+//! ```
+
+use std::fmt;
+use std::io::{self, Write};
+
+use thiserror::Error;
+
+use crate::parser::is_number_literal;
+use crate::value::write_key;
+use crate::{SpellConfig, Value};
+
+/// A [`GonWriter`] couldn't write what it was asked to
+#[derive(Debug, Error)]
+pub enum WriterError {
+    /// Writing to the output failed
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// The call doesn't fit where the writer is, like a key in a list
+    #[error("{found} where {expected} was expected")]
+    Unexpected {
+        found: &'static str,
+        expected: &'static str,
+    },
+    /// [`GonWriter::num`] was given something that isn't spelled like a number
+    #[error("malformed number '{0}'")]
+    MalformedNumber(String),
+}
+
+/// Writes gon to `out` call by call, see the [module docs](self)
+#[derive(Debug)]
+pub struct GonWriter<W> {
+    out: W,
+    /// `None` for minified output
+    config: Option<SpellConfig>,
+    /// The objects and lists that are open, innermost last
+    open: Vec<Frame>,
+    /// Whether the root value was started
+    started: bool,
+    /// Reused for spelling keys and line breaks
+    scratch: String,
+}
+
+#[derive(Debug)]
+struct Frame {
+    obj: bool,
+    entries: usize,
+    /// In an object, whether a key was written whose value is missing
+    has_key: bool,
+}
+
+impl<W: Write> GonWriter<W> {
+    /// Writes every entry and element on its own line, indented like `config` says.
+    /// Trailing commas are written if `config` asks for them.
+    pub fn pretty(out: W, config: SpellConfig) -> Self {
+        Self::new(out, Some(config))
+    }
+
+    /// Writes without any whitespace
+    pub fn minified(out: W) -> Self {
+        Self::new(out, None)
+    }
+
+    fn new(out: W, config: Option<SpellConfig>) -> Self {
+        Self {
+            out,
+            config,
+            open: Vec::new(),
+            started: false,
+            scratch: String::new(),
+        }
+    }
+
+    /// Opens an object, write its entries with [`GonWriter::key`] and a value each
+    pub fn begin_obj(&mut self) -> Result<&mut Self, WriterError> {
+        self.begin_value("an obj")?;
+        self.out.write_all(b"{")?;
+        self.open.push(Frame {
+            obj: true,
+            entries: 0,
+            has_key: false,
+        });
+        Ok(self)
+    }
+
+    /// Opens a list, every value written until the matching [`GonWriter::end`] is an
+    /// element of it
+    pub fn begin_list(&mut self) -> Result<&mut Self, WriterError> {
+        self.begin_value("a list")?;
+        self.out.write_all(b"[")?;
+        self.open.push(Frame {
+            obj: false,
+            entries: 0,
+            has_key: false,
+        });
+        Ok(self)
+    }
+
+    /// Writes the key of the next entry of the innermost object, quoted if it has to be
+    pub fn key(&mut self, key: &str) -> Result<&mut Self, WriterError> {
+        match self.open.last() {
+            Some(frame) if frame.obj && !frame.has_key => {}
+            _ => return Err(self.unexpected("a key")),
+        }
+        self.write_separator()?;
+        self.scratch.clear();
+        write_key(&mut self.scratch, key);
+        self.out.write_all(self.scratch.as_bytes())?;
+        let colon: &[u8] = if self.config.is_some() { b": " } else { b":" };
+        self.out.write_all(colon)?;
+        if let Some(frame) = self.open.last_mut() {
+            frame.has_key = true;
+        }
+        Ok(self)
+    }
+
+    /// Closes the innermost object or list
+    pub fn end(&mut self) -> Result<&mut Self, WriterError> {
+        let frame = match self.open.last() {
+            Some(frame) if !frame.has_key => frame,
+            _ => return Err(self.unexpected("an end")),
+        };
+        let (obj, entries) = (frame.obj, frame.entries);
+        self.open.pop();
+        if let Some(config) = self.config.filter(|_| obj || entries > 0) {
+            if config.trailing_commas && entries > 0 {
+                self.out.write_all(b",")?;
+            }
+            self.write_line_break(config)?;
+        }
+        self.out.write_all(if obj { b"}" } else { b"]" })?;
+        Ok(self)
+    }
+
+    /// Writes a string literal
+    pub fn str(&mut self, s: &str) -> Result<&mut Self, WriterError> {
+        self.begin_value("a str")?;
+        let spelling = klex::Token::Str(s.into()).spelling();
+        self.out.write_all(spelling.as_bytes())?;
+        Ok(self)
+    }
+
+    /// Writes a number, anything whose [`Display`](fmt::Display) is spelled like one:
+    /// integers, finite floats or number literals like `"1_000"`
+    pub fn num(&mut self, num: impl fmt::Display) -> Result<&mut Self, WriterError> {
+        let num = num.to_string();
+        if !is_number_literal(num.strip_prefix('-').unwrap_or(&num)) {
+            return Err(WriterError::MalformedNumber(num));
+        }
+        self.begin_value("a num")?;
+        self.out.write_all(num.as_bytes())?;
+        Ok(self)
+    }
+
+    /// Writes `true` or `false`
+    pub fn bool(&mut self, b: bool) -> Result<&mut Self, WriterError> {
+        self.begin_value("a bool")?;
+        self.out.write_all(if b { b"true" } else { b"false" })?;
+        Ok(self)
+    }
+
+    /// Writes `None`
+    pub fn none(&mut self) -> Result<&mut Self, WriterError> {
+        self.begin_value("None")?;
+        self.out.write_all(b"None")?;
+        Ok(self)
+    }
+
+    /// Writes a whole value, spelled with the config of a pretty writer
+    pub fn value(&mut self, value: &Value) -> Result<&mut Self, WriterError> {
+        self.begin_value("a value")?;
+        let spelling = match self.config {
+            Some(config) => {
+                let indent = self.open.len() * config.indent_amount;
+                value
+                    .spell_fragment(indent, config)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+            }
+            None => value.min_spell(),
+        };
+        self.out.write_all(spelling.as_bytes())?;
+        Ok(self)
+    }
+
+    /// Checks that a root value was written and everything is closed, flushes the output
+    /// and hands it back
+    pub fn finish(mut self) -> Result<W, WriterError> {
+        if !self.started || !self.open.is_empty() {
+            return Err(self.unexpected("the end"));
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    /// Makes sure a value can go where the writer is and writes what goes before it
+    fn begin_value(&mut self, found: &'static str) -> Result<(), WriterError> {
+        match self.open.last_mut() {
+            None if self.started => return Err(self.unexpected(found)),
+            None => self.started = true,
+            Some(frame) if frame.obj && !frame.has_key => return Err(self.unexpected(found)),
+            Some(frame) if frame.obj => frame.has_key = false,
+            Some(_) => self.write_separator()?,
+        }
+        Ok(())
+    }
+
+    /// The comma before all but the first entry or element, and the line break and
+    /// indentation in front of it when pretty
+    fn write_separator(&mut self) -> io::Result<()> {
+        let Some(frame) = self.open.last_mut() else {
+            return Ok(());
+        };
+        frame.entries += 1;
+        if frame.entries > 1 {
+            self.out.write_all(b",")?;
+        }
+        match self.config {
+            Some(config) => self.write_line_break(config),
+            None => Ok(()),
+        }
+    }
+
+    /// A line break and the indentation of the innermost open object or list
+    fn write_line_break(&mut self, config: SpellConfig) -> io::Result<()> {
+        self.scratch.clear();
+        self.scratch.push('\n');
+        let indent = self.open.len() * config.indent_amount;
+        self.scratch
+            .extend(std::iter::repeat_n(config.indent_char, indent));
+        self.out.write_all(self.scratch.as_bytes())
+    }
+
+    fn unexpected(&self, found: &'static str) -> WriterError {
+        let expected = match self.open.last() {
+            None if self.started => "nothing",
+            None => "a value",
+            Some(frame) if frame.obj && frame.has_key => "a value",
+            Some(frame) if frame.obj => "a key or an end",
+            Some(_) => "a value or an end",
+        };
+        WriterError::Unexpected { found, expected }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{InlineMode, parse_str};
+
+    fn write(writer: &mut GonWriter<Vec<u8>>) -> Result<(), WriterError> {
+        writer.begin_obj()?;
+        writer.key("a b")?.begin_list()?;
+        writer
+            .num("1_000")?
+            .str("x\n")?
+            .begin_obj()?
+            .end()?
+            .begin_list()?
+            .end()?;
+        writer.end()?;
+        writer.key("empty")?.begin_obj()?.end()?;
+        writer
+            .key("nested")?
+            .value(&parse_str("{x: [true, None]}").unwrap())?;
+        writer.key("z")?.bool(false)?.key("zz")?.none()?;
+        writer.end()?;
+        Ok(())
+    }
+
+    #[test]
+    fn writes_like_the_spellers() {
+        let expected = parse_str(
+            r#"{"a b": [1_000, "x\n", {}, []], empty: {}, nested: {x: [true, None]}, z: false, zz: None}"#,
+        )
+        .unwrap();
+        for trailing_commas in [false, true] {
+            let config = SpellConfig {
+                trailing_commas,
+                inline_lists: InlineMode::Never,
+                ..Default::default()
+            };
+            let mut writer = GonWriter::pretty(Vec::new(), config);
+            write(&mut writer).unwrap();
+            let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+            assert_eq!(out, expected.spell(config).unwrap());
+        }
+        let mut writer = GonWriter::minified(Vec::new());
+        write(&mut writer).unwrap();
+        let out = String::from_utf8(writer.finish().unwrap()).unwrap();
+        assert_eq!(out, expected.min_spell());
+    }
+
+    #[test]
+    fn rejects_malformed_output() {
+        let unexpected = |e| match e {
+            Err(WriterError::Unexpected { found, expected }) => (found, expected),
+            other => panic!("{other:?}"),
+        };
+        let mut writer = GonWriter::minified(Vec::new());
+        assert_eq!(unexpected(writer.key("a").map(drop)), ("a key", "a value"));
+        writer.begin_list().unwrap();
+        assert_eq!(
+            unexpected(writer.key("a").map(drop)),
+            ("a key", "a value or an end")
+        );
+        writer.end().unwrap();
+        assert_eq!(unexpected(writer.num(1).map(drop)), ("a num", "nothing"));
+        assert!(matches!(
+            writer.num(f64::NAN),
+            Err(WriterError::MalformedNumber(n)) if n == "NaN"
+        ));
+        assert_eq!(writer.finish().unwrap(), b"[]");
+
+        let mut writer = GonWriter::minified(Vec::new());
+        writer.begin_obj().unwrap().key("a").unwrap();
+        assert_eq!(unexpected(writer.end().map(drop)), ("an end", "a value"));
+        assert_eq!(
+            unexpected(writer.finish().map(drop)),
+            ("the end", "a value")
+        );
+    }
+}